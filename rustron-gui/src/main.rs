@@ -0,0 +1,277 @@
+//! The graphical frontend, meant to replace the terminal one: the settings, the preset library and
+//! a MIDI monitor, drawn with egui on top of `rustron::core::AppCore`.
+use std::error;
+use std::time::{Duration, Instant};
+
+use eframe::egui;
+use log::{error, info};
+
+use rustron::app::MENU_MAPPINGS;
+use rustron::core::AppCore;
+use rustron::history::{HistoryEntry, Message};
+use rustron::library::PresetLibrary;
+use rustron::locale::Locale;
+use rustron::midi::Source;
+use rustron::options::Options;
+use rustron_lib::protocol::NeutronMessage::SetGlobalSetting;
+
+/// How often the core runs while nothing is drawn, so the rate limiter and polls keep going.
+const TICK_RATE: Duration = Duration::from_millis(50);
+
+/// The messages shown in each half of the monitor.
+const MONITOR_ROWS: usize = 200;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Tab {
+    Settings,
+    Presets,
+    Monitor,
+    Logs,
+}
+
+impl Tab {
+    const ALL: [Tab; 4] = [Tab::Settings, Tab::Presets, Tab::Monitor, Tab::Logs];
+
+    fn title(self) -> &'static str {
+        match self {
+            Tab::Settings => "title.neutron_state",
+            Tab::Presets => "title.presets",
+            Tab::Monitor => "title.midi_stream",
+            Tab::Logs => "title.logs",
+        }
+    }
+}
+
+struct Gui {
+    core: AppCore,
+    library: PresetLibrary,
+    locale: Locale,
+    tab: Tab,
+    /// The name the current settings are saved under in the presets tab
+    preset_name: String,
+}
+
+impl Gui {
+    fn new(options: &Options) -> Gui {
+        let (mut core, connected) = AppCore::new(options);
+        core.connection_changed(connected);
+        Gui {
+            library: PresetLibrary::new(core.config.presets.directory.clone()),
+            locale: Locale::select(core.config.ui.locale),
+            core,
+            tab: Tab::Settings,
+            preset_name: String::new(),
+        }
+    }
+
+    fn text(&self, key: &'static str) -> &'static str {
+        self.locale.text(key)
+    }
+
+    /// Hands what was received to the core and lets it send what is due, like a tick of the
+    /// terminal frontend.
+    fn tick(&mut self) {
+        while let Some((source, bytes)) = self.core.try_receive_midi() {
+            match (source, Message::parse(&bytes)) {
+                (Source::Port(port), _) => self.core.receive_from_port(&port, bytes),
+                (Source::Neutron, Message::Parsed(msg)) => self.core.receive(msg),
+                (Source::Neutron, Message::Raw(_)) => self.core.receive_raw(bytes),
+            }
+        }
+        while let Some(line) = self.core.try_receive_log() {
+            self.core.log.push(line);
+        }
+        self.core.tick(Instant::now());
+    }
+
+    fn settings(&mut self, ui: &mut egui::Ui) {
+        let state = &self.core.neutron_state;
+        ui.label(format!(
+            "{}: {}",
+            self.text("label.software_version"),
+            state
+                .software_version
+                .as_deref()
+                .unwrap_or(self.text("value.unknown"))
+        ));
+        let expected = state.expected();
+        let mut chosen = None;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("settings").striped(true).show(ui, |ui| {
+                for (i, (name, setting)) in MENU_MAPPINGS.iter().enumerate() {
+                    let current = expected.settings.values().any(|s| s == setting);
+                    if ui.selectable_label(current, *name).clicked() {
+                        chosen = Some(*setting);
+                    }
+                    if i % 2 == 1 {
+                        ui.end_row();
+                    }
+                }
+            });
+        });
+        if let Some(setting) = chosen {
+            self.core
+                .command_message(&SetGlobalSetting(self.core.target, setting));
+        }
+    }
+
+    fn presets(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.preset_name);
+            if ui.button(self.text("button.save")).clicked() {
+                self.save_preset();
+            }
+            if ui.button(self.text("button.send")).clicked() {
+                if let Some(preset) = self.library.preview.clone() {
+                    self.core.send_preset(&preset);
+                }
+            }
+        });
+        ui.separator();
+        let selected = self.library.selected_title();
+        let mut chosen = None;
+        ui.columns(2, |columns| {
+            egui::ScrollArea::vertical()
+                .id_salt("presets")
+                .show(&mut columns[0], |ui| {
+                    for title in self.library.titles() {
+                        let current = selected.as_deref() == Some(title.as_str());
+                        if ui.selectable_label(current, &title).clicked() {
+                            chosen = Some(title);
+                        }
+                    }
+                });
+            egui::ScrollArea::vertical()
+                .id_salt("preview")
+                .show(&mut columns[1], |ui| {
+                    for setting in self.library.preview.iter().flat_map(|p| &p.settings) {
+                        ui.label(format!("{}: {}", setting.id().id(), setting.value_text()));
+                    }
+                });
+        });
+        if let Some(title) = chosen {
+            self.library.select(&title);
+        }
+    }
+
+    fn save_preset(&mut self) {
+        let name = self.preset_name.trim().to_string();
+        let mut preset = self.core.neutron_state.preset();
+        // Saving over a preset keeps its patch sheet
+        if let Ok(existing) = self.library.load(&name) {
+            preset.sheet = existing.sheet;
+        }
+        match self.library.save(&name, &preset) {
+            Ok(()) => info!("Saved preset {}", name),
+            Err(error) => error!("Could not save preset {}: {}", name, error),
+        }
+    }
+
+    fn monitor(&self, ui: &mut egui::Ui) {
+        ui.columns(2, |columns| {
+            history_column(
+                &mut columns[0],
+                self.text("title.command_history"),
+                &self.core.command_history,
+            );
+            history_column(
+                &mut columns[1],
+                self.text("title.midi_stream"),
+                &self.core.midi_in_messages,
+            );
+        });
+    }
+
+    fn logs(&self, ui: &mut egui::Ui) {
+        egui::ScrollArea::vertical()
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+                for line in &self.core.log {
+                    ui.label(line);
+                }
+            });
+    }
+
+    /// Asks to confirm the command held back by `[safety] confirm`.
+    fn confirmation(&mut self, ctx: &egui::Context) {
+        let question = match &self.core.awaiting_confirmation {
+            Some(bytes) => self.locale.format("prompt.send", &[&Message::parse(bytes)]),
+            None => return,
+        };
+        let mut answer = None;
+        egui::Window::new(self.text("title.confirm"))
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(question);
+                ui.horizontal(|ui| {
+                    if ui.button(self.text("button.send")).clicked() {
+                        answer = Some(true);
+                    }
+                    if ui.button(self.text("button.cancel")).clicked() {
+                        answer = Some(false);
+                    }
+                });
+            });
+        match answer {
+            Some(true) => self.core.confirm(),
+            Some(false) => {
+                self.core.cancel_confirmation();
+            }
+            None => {}
+        }
+    }
+}
+
+/// The last messages of `entries` under `title`, following new ones.
+fn history_column(ui: &mut egui::Ui, title: &str, entries: &[HistoryEntry]) {
+    ui.heading(title);
+    egui::ScrollArea::vertical()
+        .id_salt(title)
+        .stick_to_bottom(true)
+        .show(ui, |ui| {
+            let start = entries.len().saturating_sub(MONITOR_ROWS);
+            for entry in &entries[start..] {
+                ui.monospace(&entry.text);
+            }
+        });
+}
+
+impl eframe::App for Gui {
+    fn update(&mut self, ctx: &egui::Context, _: &mut eframe::Frame) {
+        self.tick();
+        egui::TopBottomPanel::top("tabs").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                for tab in Tab::ALL {
+                    let title = self.text(tab.title());
+                    ui.selectable_value(&mut self.tab, tab, title);
+                }
+                ui.separator();
+                ui.label(self.text(if self.core.connected {
+                    "value.connected"
+                } else {
+                    "value.not_connected"
+                }));
+            });
+        });
+        egui::CentralPanel::default().show(ctx, |ui| match self.tab {
+            Tab::Settings => self.settings(ui),
+            Tab::Presets => self.presets(ui),
+            Tab::Monitor => self.monitor(ui),
+            Tab::Logs => self.logs(ui),
+        });
+        self.confirmation(ctx);
+        ctx.request_repaint_after(TICK_RATE);
+    }
+}
+
+fn main() -> Result<(), Box<dyn error::Error>> {
+    let options = Options::parse(std::env::args().skip(1))?;
+    let gui = Gui::new(&options);
+    eframe::run_native(
+        "rustron",
+        eframe::NativeOptions::default(),
+        Box::new(|_| Ok(Box::new(gui))),
+    )?;
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use rustron_lib::preset;
+use rustron_lib::preset::Preset;
+use rustron_lib::protocol::DeviceId::Multicast;
+use rustron_lib::protocol::GlobalSetting::{
+    LfoShapeOrder, LfoShapePhase, Osc1Autoglide, Osc2Autoglide, OscSync, ParaphonicMode,
+};
+use rustron_lib::protocol::NeutronMessage::SetGlobalSetting;
+use rustron_lib::protocol::{
+    AutoglideSemitones, LfoIndex, LfoPhaseOffset, LfoShape, NeutronMessage, ToggleOption,
+};
+
+fn preset() -> Preset {
+    let mut preset = Preset::new();
+    preset.set(ParaphonicMode(ToggleOption::On));
+    preset.set(OscSync(ToggleOption::Off));
+    preset.set(Osc1Autoglide(AutoglideSemitones::PlusSeven));
+    preset.set(Osc2Autoglide(AutoglideSemitones::MinusFive));
+    for index in [
+        LfoIndex::One,
+        LfoIndex::Two,
+        LfoIndex::Three,
+        LfoIndex::Four,
+        LfoIndex::Five,
+    ]
+    .iter()
+    .cloned()
+    {
+        preset.set(LfoShapeOrder(index, LfoShape::Sine));
+        preset.set(LfoShapePhase(index, LfoPhaseOffset::Ninety));
+    }
+    preset
+}
+
+fn batch_preset_application(c: &mut Criterion) {
+    let preset = preset();
+    c.bench_function("as_bytes per setting", |b| {
+        b.iter(|| {
+            preset
+                .settings
+                .iter()
+                .map(|s| SetGlobalSetting(Multicast, *s).as_bytes().len())
+                .sum::<usize>()
+        })
+    });
+
+    let mut scratch = Vec::with_capacity(NeutronMessage::MAX_LEN);
+    c.bench_function("write_to a reused buffer per setting", |b| {
+        b.iter(|| {
+            preset
+                .settings
+                .iter()
+                .map(|s| {
+                    scratch.clear();
+                    SetGlobalSetting(Multicast, *s).write_to(&mut scratch);
+                    scratch.len()
+                })
+                .sum::<usize>()
+        })
+    });
+
+    c.bench_function("to_syx", |b| b.iter(|| preset::to_syx(&preset)));
+}
+
+criterion_group!(benches, batch_preset_application);
+criterion_main!(benches);
@@ -0,0 +1,55 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use rustron_lib::parser::describe;
+use rustron_lib::protocol::DeviceId::Multicast;
+use rustron_lib::protocol::GlobalSetting::{OscSync, ParaphonicMode};
+use rustron_lib::protocol::NeutronMessage::{GlobalSettingUpdate, SetGlobalSetting};
+use rustron_lib::protocol::ToggleOption::{Off, On};
+
+const HISTORY_LENGTH: usize = 10_000;
+/// Roughly the number of rows visible in a message pane
+const VISIBLE_ROWS: usize = 50;
+
+fn history() -> Vec<Vec<u8>> {
+    (0..HISTORY_LENGTH)
+        .map(|i| match i % 4 {
+            0 => SetGlobalSetting(Multicast, ParaphonicMode(On)).as_bytes(),
+            1 => GlobalSettingUpdate(Multicast, ParaphonicMode(On)).as_bytes(),
+            2 => SetGlobalSetting(Multicast, OscSync(Off)).as_bytes(),
+            _ => vec![0xf0, 0x7e, 0x7f, 0x06, 0x01, 0xf7],
+        })
+        .collect()
+}
+
+fn visible<T>(history: &[T]) -> &[T] {
+    &history[history.len() - VISIBLE_ROWS..]
+}
+
+fn render_prep(c: &mut Criterion) {
+    let history = history();
+    c.bench_function("describe visible rows every frame", |b| {
+        b.iter(|| {
+            visible(&history)
+                .iter()
+                .map(|message| describe(message))
+                .collect::<Vec<String>>()
+        })
+    });
+
+    let described: Vec<String> = history.iter().map(|message| describe(message)).collect();
+    c.bench_function("borrow visible rows described on receipt", |b| {
+        b.iter(|| {
+            visible(&described)
+                .iter()
+                .map(String::as_str)
+                .collect::<Vec<&str>>()
+        })
+    });
+
+    c.bench_function("describe on receipt", |b| {
+        b.iter(|| describe(black_box(&history[0])))
+    });
+}
+
+criterion_group!(benches, render_prep);
+criterion_main!(benches);
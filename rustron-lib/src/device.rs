@@ -0,0 +1,190 @@
+use std::ops::RangeInclusive;
+
+use crate::device::crave::Crave;
+use crate::protocol::{
+    is_behringer_packet, BEHRINGER_MANUFACTURER, COMMS_PROTOCOL_V1, NEUTRON_DEVICE,
+    SYSEX_MESSAGE_START,
+};
+
+pub mod crave;
+
+/// The commands of the Behringer SysEx protocol, each with an opcode per device.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Command {
+    SetSetting,
+    RestoreSettings,
+    CalibrationMode,
+    VersionRequest,
+    VersionResponse,
+    SettingUpdate,
+    StateRequest,
+    StateDump,
+}
+
+impl Command {
+    pub const ALL: [Command; 8] = [
+        Command::SetSetting,
+        Command::RestoreSettings,
+        Command::CalibrationMode,
+        Command::VersionRequest,
+        Command::VersionResponse,
+        Command::SettingUpdate,
+        Command::StateRequest,
+        Command::StateDump,
+    ];
+}
+
+/// A Behringer device speaking the SysEx protocol of the Neutron: every message is
+/// `F0 00 20 32 <device> <device id> <opcode> ... F7`. Implementations give what differs between
+/// devices, so that the wrapper and the parsers are shared.
+///
+/// # Example
+///
+/// ```rust
+/// use rustron_lib::device::{BehringerDevice, Command, Neutron};
+///
+/// assert_eq!(Neutron::header(), [0xf0, 0x00, 0x20, 0x32, 0x28]);
+/// assert_eq!(Neutron::opcode(Command::SettingUpdate), &[0x5a, 0x01]);
+/// ```
+pub trait BehringerDevice {
+    /// The name of the device, shown when describing messages
+    const NAME: &'static str;
+    /// The byte identifying the device, following the manufacturer id
+    const DEVICE: u8;
+
+    /// The bytes following the device id for `command`, including any protocol version.
+    fn opcode(command: Command) -> &'static [u8];
+
+    /// The lengths in bytes, from SysEx start to end, a message of `command` can have.
+    fn length(command: Command) -> RangeInclusive<usize>;
+
+    /// The command of a message for the device, going by its opcode. `None` if the message is for
+    /// another device or the opcode is unknown.
+    fn command(message: &[u8]) -> Option<Command> {
+        if !message.starts_with(&Self::header()) {
+            return None;
+        }
+        let opcode = message.get(Self::header().len() + 1..)?;
+        Command::ALL
+            .iter()
+            .copied()
+            .find(|command| opcode.starts_with(Self::opcode(*command)))
+    }
+
+    /// The bytes every message for the device starts with.
+    fn header() -> [u8; 5] {
+        [
+            SYSEX_MESSAGE_START,
+            BEHRINGER_MANUFACTURER[0],
+            BEHRINGER_MANUFACTURER[1],
+            BEHRINGER_MANUFACTURER[2],
+            Self::DEVICE,
+        ]
+    }
+}
+
+pub struct Neutron;
+
+impl BehringerDevice for Neutron {
+    const NAME: &'static str = "Neutron";
+    const DEVICE: u8 = NEUTRON_DEVICE;
+
+    fn opcode(command: Command) -> &'static [u8] {
+        match command {
+            Command::SetSetting => &[0x0a],
+            Command::RestoreSettings => &[0x0b],
+            Command::CalibrationMode => &[0x10],
+            Command::VersionRequest => &[0x73],
+            Command::VersionResponse => &[0x74, COMMS_PROTOCOL_V1],
+            Command::SettingUpdate => &[0x5a, COMMS_PROTOCOL_V1],
+            Command::StateRequest => &[0x05],
+            Command::StateDump => &[0x06, COMMS_PROTOCOL_V1],
+        }
+    }
+
+    /// Header, device id and opcode, then the payload of the command and the SysEx end. Settings
+    /// take two or three bytes, versions and state dumps at least one.
+    fn length(command: Command) -> RangeInclusive<usize> {
+        match command {
+            Command::SetSetting => 10..=11,
+            Command::SettingUpdate => 11..=12,
+            Command::VersionResponse | Command::StateDump => 10..=usize::MAX,
+            Command::RestoreSettings
+            | Command::CalibrationMode
+            | Command::VersionRequest
+            | Command::StateRequest => 8..=8,
+        }
+    }
+}
+
+/// The name of the known device a Behringer message is for or from, going by its device byte.
+///
+/// # Example
+///
+/// ```rust
+/// use rustron_lib::device::detect;
+///
+/// assert_eq!(detect(&[0xf0, 0x00, 0x20, 0x32, 0x28, 0x7f, 0x73, 0xf7]), Some("Neutron"));
+/// assert_eq!(detect(&[0xf0, 0x00, 0x20, 0x32, 0x01, 0x7f, 0x73, 0xf7]), None);
+/// assert_eq!(detect(&[0x90, 0x3c, 0x40]), None);
+/// ```
+pub fn detect(bytes: &[u8]) -> Option<&'static str> {
+    if !is_behringer_packet(bytes) {
+        return None;
+    }
+    match bytes[4] {
+        Neutron::DEVICE => Some(Neutron::NAME),
+        Crave::DEVICE => Some(Crave::NAME),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::ops::RangeInclusive;
+
+    use crate::device::{BehringerDevice, Command, Neutron};
+    use crate::parser::device_message;
+    use crate::protocol::DeviceId::Multicast;
+    use crate::protocol::GlobalSetting::OscSync;
+    use crate::protocol::NeutronMessage::{SetGlobalSetting, StateRequest};
+    use crate::protocol::ToggleOption::On;
+
+    struct Other;
+
+    impl BehringerDevice for Other {
+        const NAME: &'static str = "Other";
+        const DEVICE: u8 = 0x42;
+
+        fn opcode(command: Command) -> &'static [u8] {
+            match command {
+                Command::SetSetting => &[0x1a],
+                command => Neutron::opcode(command),
+            }
+        }
+
+        fn length(command: Command) -> RangeInclusive<usize> {
+            Neutron::length(command)
+        }
+    }
+
+    #[test]
+    fn messages_use_the_header_and_opcodes_of_the_device() {
+        let message = SetGlobalSetting(Multicast, OscSync(On));
+        let mut bytes = Vec::new();
+        message.write_for::<Other>(&mut bytes);
+        assert_eq!(bytes[4], 0x42);
+        assert_eq!(bytes[6], 0x1a);
+        assert_eq!(
+            device_message::<Other>(&bytes),
+            Ok((&[][..], message.clone()))
+        );
+        assert!(device_message::<Neutron>(&bytes).is_err());
+
+        let mut bytes = Vec::new();
+        StateRequest(Multicast).write_for::<Other>(&mut bytes);
+        assert_eq!(bytes[6], 0x05);
+        assert_eq!(Other::command(&bytes), Some(Command::StateRequest));
+        assert_eq!(Neutron::command(&bytes), None);
+    }
+}
@@ -0,0 +1,79 @@
+//! Experimental support for the Behringer Crave, which appears to share the SysEx scheme of the
+//! Neutron with its own device byte. Only the commands and settings listed here are expected to
+//! work, none of this has been verified against a Crave yet.
+
+use std::ops::RangeInclusive;
+
+use crate::device::{BehringerDevice, Command, Neutron};
+use crate::protocol::{GlobalSetting, NeutronMessage};
+use crate::setting::SettingId;
+
+// TODO verify this
+pub const CRAVE_DEVICE: u8 = 0x20;
+
+/// The settings the Crave is expected to share with the Neutron.
+pub const SETTINGS: [SettingId; 1] = [SettingId::MidiChannel];
+
+pub struct Crave;
+
+impl BehringerDevice for Crave {
+    const NAME: &'static str = "Crave";
+    const DEVICE: u8 = CRAVE_DEVICE;
+
+    fn opcode(command: Command) -> &'static [u8] {
+        Neutron::opcode(command)
+    }
+
+    fn length(command: Command) -> RangeInclusive<usize> {
+        Neutron::length(command)
+    }
+}
+
+/// Whether `message` only uses what the Crave is expected to understand.
+///
+/// # Example
+///
+/// ```rust
+/// use rustron_lib::device::crave::is_supported;
+/// use rustron_lib::protocol::DeviceId::Multicast;
+/// use rustron_lib::protocol::GlobalSetting::{MidiChannel, OscSync};
+/// use rustron_lib::protocol::NeutronMessage::{SetGlobalSetting, StateRequest};
+/// use rustron_lib::protocol::{Channel, ToggleOption};
+///
+/// assert!(is_supported(&SetGlobalSetting(Multicast, MidiChannel(Channel::Two))));
+/// assert!(!is_supported(&SetGlobalSetting(Multicast, OscSync(ToggleOption::On))));
+/// assert!(is_supported(&StateRequest(Multicast)));
+/// ```
+pub fn is_supported(message: &NeutronMessage) -> bool {
+    let supported = |setting: &GlobalSetting| SETTINGS.contains(&setting.id());
+    match message {
+        NeutronMessage::SetGlobalSetting(_, setting)
+        | NeutronMessage::GlobalSettingUpdate(_, setting) => supported(setting),
+        NeutronMessage::CalibrationModeCommand(_) | NeutronMessage::StateDump(..) => false,
+        NeutronMessage::RestoreGlobalSetting(_)
+        | NeutronMessage::SoftwareVersionRequest(_)
+        | NeutronMessage::SoftwareVersionResponse(..)
+        | NeutronMessage::StateRequest(_) => true,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::device::crave::Crave;
+    use crate::device::{detect, BehringerDevice};
+    use crate::parser::device_message;
+    use crate::protocol::DeviceId::Multicast;
+    use crate::protocol::GlobalSetting::MidiChannel;
+    use crate::protocol::NeutronMessage::SetGlobalSetting;
+    use crate::protocol::{classify, Channel, DeviceClass};
+
+    #[test]
+    fn crave_messages_are_detected_and_parsed() {
+        let message = SetGlobalSetting(Multicast, MidiChannel(Channel::Three));
+        let mut bytes = Vec::new();
+        message.write_for::<Crave>(&mut bytes);
+        assert_eq!(classify(&bytes), DeviceClass::OtherBehringer);
+        assert_eq!(detect(&bytes), Some(Crave::NAME));
+        assert_eq!(device_message::<Crave>(&bytes), Ok((&[][..], message)));
+    }
+}
@@ -0,0 +1,174 @@
+use std::error;
+use std::fmt::{Display, Formatter};
+
+use crate::device::detect;
+use crate::preset::{sysex_messages, SyxError};
+use crate::protocol::is_behringer_packet;
+
+/// A Behringer firmware update file: the SysEx messages the official updater sends, one block
+/// each, all addressed to the same device. Only the framing is known, the blocks are sent as they
+/// are.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Firmware {
+    /// The device byte following the manufacturer id, 0x28 for the Neutron
+    pub device: u8,
+    pub blocks: Vec<Vec<u8>>,
+    /// The firmware version, if known, see [`version_from_name`]
+    pub version: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FirmwareError {
+    Syx(SyxError),
+    /// The file holds no SysEx messages
+    Empty,
+    /// The block at the index isn't a Behringer message
+    NotBehringer(usize),
+    /// The block at the index is for another device than the first one
+    MixedDevices(usize),
+}
+
+impl Display for FirmwareError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FirmwareError::Syx(error) => write!(f, "{}", error),
+            FirmwareError::Empty => write!(f, "no SysEx messages"),
+            FirmwareError::NotBehringer(index) => {
+                write!(f, "block {} isn't a Behringer message", index)
+            }
+            FirmwareError::MixedDevices(index) => {
+                write!(f, "block {} is for another device", index)
+            }
+        }
+    }
+}
+
+impl error::Error for FirmwareError {}
+
+impl From<SyxError> for FirmwareError {
+    fn from(error: SyxError) -> FirmwareError {
+        FirmwareError::Syx(error)
+    }
+}
+
+impl Firmware {
+    /// Reads the blocks of a firmware `.syx` file, checking they are all for one Behringer
+    /// device.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustron_lib::firmware::Firmware;
+    ///
+    /// let block = [0xf0, 0x00, 0x20, 0x32, 0x28, 0x7f, 0x01, 0x02, 0xf7];
+    /// let firmware = Firmware::parse(&[block, block].concat()).unwrap();
+    /// assert_eq!(firmware.device_name(), Some("Neutron"));
+    /// assert_eq!(firmware.blocks.len(), 2);
+    /// ```
+    pub fn parse(bytes: &[u8]) -> Result<Firmware, FirmwareError> {
+        let messages = sysex_messages(bytes)?;
+        let device = match messages.first() {
+            Some(first) if is_behringer_packet(first) && first.len() > 5 => first[4],
+            Some(_) => return Err(FirmwareError::NotBehringer(0)),
+            None => return Err(FirmwareError::Empty),
+        };
+        for (index, message) in messages.iter().enumerate() {
+            if !is_behringer_packet(message) || message.len() <= 5 {
+                return Err(FirmwareError::NotBehringer(index));
+            }
+            if message[4] != device {
+                return Err(FirmwareError::MixedDevices(index));
+            }
+        }
+        Ok(Firmware {
+            device,
+            blocks: messages.into_iter().map(<[u8]>::to_vec).collect(),
+            version: None,
+        })
+    }
+
+    /// The name of the device the firmware is for, if it is a known one.
+    pub fn device_name(&self) -> Option<&'static str> {
+        detect(self.blocks.first()?)
+    }
+
+    /// The size in bytes of all blocks.
+    pub fn size(&self) -> usize {
+        self.blocks.iter().map(Vec::len).sum()
+    }
+
+    /// The CRC-32 of all blocks, as computed by common `crc32` tools over the file.
+    pub fn checksum(&self) -> u32 {
+        let mut crc = !0u32;
+        for byte in self.blocks.iter().flatten() {
+            crc ^= u32::from(*byte);
+            for _ in 0..8 {
+                crc = if crc & 1 == 1 {
+                    (crc >> 1) ^ 0xedb8_8320
+                } else {
+                    crc >> 1
+                };
+            }
+        }
+        !crc
+    }
+}
+
+/// The version in the name of a firmware file, as the updater files are named after it.
+///
+/// # Example
+///
+/// ```rust
+/// use rustron_lib::firmware::version_from_name;
+///
+/// assert_eq!(version_from_name("NEUTRON_FW_V2.0.1.syx"), Some(String::from("2.0.1")));
+/// assert_eq!(version_from_name("neutron.syx"), None);
+/// ```
+pub fn version_from_name(name: &str) -> Option<String> {
+    let name = name.strip_suffix(".syx").unwrap_or(name);
+    name.split(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .map(|part| part.trim_matches('.'))
+        .find(|part| part.contains('.') && !part.is_empty())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::firmware::{Firmware, FirmwareError};
+    use crate::preset::SyxError;
+
+    const BLOCK: [u8; 9] = [0xf0, 0x00, 0x20, 0x32, 0x28, 0x7f, 0x01, 0x02, 0xf7];
+
+    #[test]
+    fn firmware_blocks_are_checked() {
+        assert_eq!(Firmware::parse(&[]), Err(FirmwareError::Empty));
+        assert_eq!(
+            Firmware::parse(&[0xf0, 0x00]),
+            Err(FirmwareError::Syx(SyxError::Unterminated(0)))
+        );
+        let mut other = BLOCK;
+        other[4] = 0x42;
+        assert_eq!(
+            Firmware::parse(&[BLOCK, other].concat()),
+            Err(FirmwareError::MixedDevices(1))
+        );
+        assert_eq!(
+            Firmware::parse(&[&BLOCK[..], &[0xf0, 0x7e, 0x00, 0xf7][..]].concat()),
+            Err(FirmwareError::NotBehringer(1))
+        );
+
+        let firmware = Firmware::parse(&[BLOCK, BLOCK].concat()).unwrap();
+        assert_eq!(firmware.device, 0x28);
+        assert_eq!(firmware.size(), 18);
+        // crc32 of the file
+        assert_eq!(
+            Firmware {
+                device: 0,
+                blocks: vec![b"123456789".to_vec()],
+                version: None
+            }
+            .checksum(),
+            0xcbf4_3926
+        );
+    }
+}
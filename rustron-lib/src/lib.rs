@@ -1,2 +1,6 @@
+pub mod device;
+pub mod firmware;
 pub mod parser;
+pub mod preset;
 pub mod protocol;
+pub mod setting;
@@ -1,11 +1,18 @@
+use std::fmt::{Display, Formatter};
+
 use nom::{
     branch::alt,
     bytes::complete::{is_not, tag, take},
     combinator::{cut, map},
+    error::{context, ErrorKind, ParseError as NomError},
     sequence::{delimited, pair, preceded, separated_pair, terminated},
     IResult,
 };
 
+#[cfg(feature = "trace")]
+use nom::error::{VerboseError, VerboseErrorKind};
+
+use crate::device::{BehringerDevice, Command, Neutron};
 use crate::protocol::GlobalSetting::{
     AssignOut, DisableMidiDips, EnvRetriggerMode, KeyRangeMute, KeyRangeReset, LfoBlendMode,
     LfoDepth, LfoKeySync, LfoMidiSync, LfoOneShot, LfoResetOrder, LfoRetrigger, LfoShapeOrder,
@@ -15,191 +22,372 @@ use crate::protocol::GlobalSetting::{
 };
 use crate::protocol::NeutronMessage::{
     GlobalSettingUpdate, RestoreGlobalSetting, SetGlobalSetting, SoftwareVersionRequest,
-    SoftwareVersionResponse,
+    SoftwareVersionResponse, StateDump, StateRequest,
 };
 use crate::protocol::{
     AssignOutOption, AutoglideSemitones, BlendMode, Channel, DeviceId, GlobalSetting, KeyTrackMode,
     LfoIndex, LfoPhaseOffset, LfoShape, ModSource, NeutronMessage, OscRange, Percent,
-    RetriggerMode, ToggleOption, COMMS_PROTOCOL_V1, NEUTRON_MESSAGE_HEADER, SYSEX_EOX,
+    RetriggerMode, ToggleOption, NEUTRON_MESSAGE_HEADER, SYSEX_EOX,
 };
+use crate::setting::SettingId;
+
+/// Why a message couldn't be parsed: the offset of the first byte that didn't fit and what was
+/// expected there.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub offset: usize,
+    pub expected: &'static str,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "parse failed at byte {}: expected {}",
+            self.offset, self.expected
+        )
+    }
+}
+
+/// A message with a known opcode but a length that doesn't fit it, told apart from messages with
+/// unknown opcodes since it hints at a truncated or corrupted message.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LengthError {
+    TooShort { command: Command, length: usize },
+    TooLong { command: Command, length: usize },
+}
+
+/// Checks the length of a message for device `D` against the lengths of its opcode, returning the
+/// command if it fits. `Ok(None)` if the message isn't for `D` or has an unknown opcode.
+///
+/// ```
+/// use rustron_lib::device::{Command, Neutron};
+/// use rustron_lib::parser::{validate_length, LengthError};
+///
+/// let restore = [0xf0, 0x00, 0x20, 0x32, 0x28, 0x7f, 0x0b, 0xf7];
+/// assert_eq!(validate_length::<Neutron>(&restore), Ok(Some(Command::RestoreSettings)));
+/// let truncated = [0xf0, 0x00, 0x20, 0x32, 0x28, 0x7f, 0x0a, 0x0e, 0xf7];
+/// assert_eq!(
+///     validate_length::<Neutron>(&truncated),
+///     Err(LengthError::TooShort { command: Command::SetSetting, length: 9 })
+/// );
+/// let unknown = [0xf0, 0x00, 0x20, 0x32, 0x28, 0x7f, 0x42, 0xf7];
+/// assert_eq!(validate_length::<Neutron>(&unknown), Ok(None));
+/// ```
+pub fn validate_length<D: BehringerDevice>(message: &[u8]) -> Result<Option<Command>, LengthError> {
+    let command = match D::command(message) {
+        Some(command) => command,
+        None => return Ok(None),
+    };
+    let length = message.len();
+    let expected = D::length(command);
+    if length < *expected.start() {
+        Err(LengthError::TooShort { command, length })
+    } else if length > *expected.end() {
+        Err(LengthError::TooLong { command, length })
+    } else {
+        Ok(Some(command))
+    }
+}
 
-fn toggle_option(input: &[u8]) -> IResult<&[u8], ToggleOption> {
-    alt((
-        map(tag(&[0x01]), |_| ToggleOption::On),
-        map(tag(&[0x00]), |_| ToggleOption::Off),
-    ))(input)
+/// The error the parsers below build while running. Offsets are only known relative to the end of
+/// the input, so it counts the bytes that were left when a parser gave up.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Expected {
+    remaining: usize,
+    expected: Option<&'static str>,
 }
 
-fn take1(input: &[u8]) -> IResult<&[u8], &[u8]> {
+impl<'a> NomError<&'a [u8]> for Expected {
+    fn from_error_kind(input: &'a [u8], _: ErrorKind) -> Self {
+        Expected {
+            remaining: input.len(),
+            expected: None,
+        }
+    }
+
+    fn append(_: &'a [u8], _: ErrorKind, other: Self) -> Self {
+        other
+    }
+
+    /// Of two failed alternatives, the one that got furthest explains the message best.
+    fn or(self, other: Self) -> Self {
+        if other.remaining < self.remaining
+            || (other.remaining == self.remaining && self.expected.is_none())
+        {
+            other
+        } else {
+            self
+        }
+    }
+
+    /// The innermost context describes the failing byte best, e.g. "on/off value" rather than
+    /// "setting" or the kind of message.
+    fn add_context(_: &'a [u8], context: &'static str, mut other: Self) -> Self {
+        other.expected.get_or_insert(context);
+        other
+    }
+}
+
+/// A `VerboseError` that keeps what every failed alternative ran into rather than only the last
+/// one, so a trace shows how far each of them got.
+#[cfg(feature = "trace")]
+struct Trace<'a>(VerboseError<&'a [u8]>);
+
+#[cfg(feature = "trace")]
+impl<'a> NomError<&'a [u8]> for Trace<'a> {
+    fn from_error_kind(input: &'a [u8], kind: ErrorKind) -> Self {
+        Trace(VerboseError::from_error_kind(input, kind))
+    }
+
+    fn append(input: &'a [u8], kind: ErrorKind, other: Self) -> Self {
+        Trace(VerboseError::append(input, kind, other.0))
+    }
+
+    fn or(mut self, other: Self) -> Self {
+        self.0.errors.extend(other.0.errors);
+        self
+    }
+
+    fn add_context(input: &'a [u8], context: &'static str, other: Self) -> Self {
+        Trace(VerboseError::add_context(input, context, other.0))
+    }
+}
+
+fn opcode<'a, E: NomError<&'a [u8]>>(
+    bytes: &'static [u8],
+) -> impl Fn(&'a [u8]) -> IResult<&'a [u8], &'a [u8], E> {
+    context("opcode", tag(bytes))
+}
+
+fn toggle_option<'a, E: NomError<&'a [u8]>>(input: &'a [u8]) -> IResult<&'a [u8], ToggleOption, E> {
+    context(
+        "on/off value",
+        alt((
+            map(tag(&[0x01]), |_| ToggleOption::On),
+            map(tag(&[0x00]), |_| ToggleOption::Off),
+        )),
+    )(input)
+}
+
+fn take1<'a, E: NomError<&'a [u8]>>(input: &'a [u8]) -> IResult<&'a [u8], &'a [u8], E> {
     take(1usize)(input)
 }
 
-fn percent(input: &[u8]) -> IResult<&[u8], Percent> {
-    map(take1, |p| Percent::from_byte(p[0]))(input)
+fn percent<'a, E: NomError<&'a [u8]>>(input: &'a [u8]) -> IResult<&'a [u8], Percent, E> {
+    context("percentage", map(take1, |p| Percent::from_byte(p[0])))(input)
 }
 
-fn blend_mode(input: &[u8]) -> IResult<&[u8], BlendMode> {
-    alt((
-        map(tag(&[0x01]), |_| BlendMode::Switch),
-        map(tag(&[0x00]), |_| BlendMode::Blend),
-    ))(input)
+fn blend_mode<'a, E: NomError<&'a [u8]>>(input: &'a [u8]) -> IResult<&'a [u8], BlendMode, E> {
+    context(
+        "blend mode",
+        alt((
+            map(tag(&[0x01]), |_| BlendMode::Switch),
+            map(tag(&[0x00]), |_| BlendMode::Blend),
+        )),
+    )(input)
 }
 
-fn retrigger_mode(input: &[u8]) -> IResult<&[u8], RetriggerMode> {
-    alt((
-        map(tag(&[0x01]), |_| RetriggerMode::Legato),
-        map(tag(&[0x00]), |_| RetriggerMode::Staccato),
-    ))(input)
+fn retrigger_mode<'a, E: NomError<&'a [u8]>>(
+    input: &'a [u8],
+) -> IResult<&'a [u8], RetriggerMode, E> {
+    context(
+        "retrigger mode",
+        alt((
+            map(tag(&[0x01]), |_| RetriggerMode::Legato),
+            map(tag(&[0x00]), |_| RetriggerMode::Staccato),
+        )),
+    )(input)
 }
 
-fn osc_range(input: &[u8]) -> IResult<&[u8], OscRange> {
-    alt((
-        map(tag(&[0x00]), |_| OscRange::ThirtyTwo),
-        map(tag(&[0x01]), |_| OscRange::Sixteen),
-        map(tag(&[0x02]), |_| OscRange::Eight),
-        map(tag(&[0x03]), |_| OscRange::PlusMinusTen),
-    ))(input)
+fn osc_range<'a, E: NomError<&'a [u8]>>(input: &'a [u8]) -> IResult<&'a [u8], OscRange, E> {
+    context(
+        "oscillator range",
+        alt((
+            map(tag(&[0x00]), |_| OscRange::ThirtyTwo),
+            map(tag(&[0x01]), |_| OscRange::Sixteen),
+            map(tag(&[0x02]), |_| OscRange::Eight),
+            map(tag(&[0x03]), |_| OscRange::PlusMinusTen),
+        )),
+    )(input)
 }
 
-fn autoglide_semitones(input: &[u8]) -> IResult<&[u8], AutoglideSemitones> {
-    alt((
+pub(crate) fn autoglide_semitones<'a, E: NomError<&'a [u8]>>(
+    input: &'a [u8],
+) -> IResult<&'a [u8], AutoglideSemitones, E> {
+    context(
+        "autoglide semitones",
         alt((
-            map(tag(&[0x00]), |_| AutoglideSemitones::MinusTwelve),
-            map(tag(&[0x01]), |_| AutoglideSemitones::MinusEleven),
-            map(tag(&[0x02]), |_| AutoglideSemitones::MinusTen),
-            map(tag(&[0x03]), |_| AutoglideSemitones::MinusNine),
-            map(tag(&[0x04]), |_| AutoglideSemitones::MinusEight),
-            map(tag(&[0x05]), |_| AutoglideSemitones::MinusSeven),
-            map(tag(&[0x06]), |_| AutoglideSemitones::MinusSix),
-            map(tag(&[0x07]), |_| AutoglideSemitones::MinusFive),
-            map(tag(&[0x08]), |_| AutoglideSemitones::MinusFour),
-            map(tag(&[0x09]), |_| AutoglideSemitones::MinusThree),
-            map(tag(&[0x0a]), |_| AutoglideSemitones::MinusTwo),
-            map(tag(&[0x0b]), |_| AutoglideSemitones::MinusOne),
+            alt((
+                map(tag(&[0x00]), |_| AutoglideSemitones::MinusTwelve),
+                map(tag(&[0x01]), |_| AutoglideSemitones::MinusEleven),
+                map(tag(&[0x02]), |_| AutoglideSemitones::MinusTen),
+                map(tag(&[0x03]), |_| AutoglideSemitones::MinusNine),
+                map(tag(&[0x04]), |_| AutoglideSemitones::MinusEight),
+                map(tag(&[0x05]), |_| AutoglideSemitones::MinusSeven),
+                map(tag(&[0x06]), |_| AutoglideSemitones::MinusSix),
+                map(tag(&[0x07]), |_| AutoglideSemitones::MinusFive),
+                map(tag(&[0x08]), |_| AutoglideSemitones::MinusFour),
+                map(tag(&[0x09]), |_| AutoglideSemitones::MinusThree),
+                map(tag(&[0x0a]), |_| AutoglideSemitones::MinusTwo),
+                map(tag(&[0x0b]), |_| AutoglideSemitones::MinusOne),
+            )),
+            alt((
+                map(tag(&[0x0c]), |_| AutoglideSemitones::Zero),
+                map(tag(&[0x0d]), |_| AutoglideSemitones::PlusOne),
+                map(tag(&[0x0e]), |_| AutoglideSemitones::PlusTwo),
+                map(tag(&[0x0f]), |_| AutoglideSemitones::PlusThree),
+                map(tag(&[0x10]), |_| AutoglideSemitones::PlusFour),
+                map(tag(&[0x11]), |_| AutoglideSemitones::PlusFive),
+                map(tag(&[0x12]), |_| AutoglideSemitones::PlusSix),
+                map(tag(&[0x13]), |_| AutoglideSemitones::PlusSeven),
+                map(tag(&[0x14]), |_| AutoglideSemitones::PlusEight),
+                map(tag(&[0x15]), |_| AutoglideSemitones::PlusNine),
+                map(tag(&[0x16]), |_| AutoglideSemitones::PlusTen),
+                map(tag(&[0x17]), |_| AutoglideSemitones::PlusEleven),
+                map(tag(&[0x18]), |_| AutoglideSemitones::PlusTwelve),
+            )),
         )),
+    )(input)
+}
+
+fn key_track_mode<'a, E: NomError<&'a [u8]>>(
+    input: &'a [u8],
+) -> IResult<&'a [u8], KeyTrackMode, E> {
+    context(
+        "key track mode",
         alt((
-            map(tag(&[0x0c]), |_| AutoglideSemitones::Zero),
-            map(tag(&[0x0d]), |_| AutoglideSemitones::PlusOne),
-            map(tag(&[0x0e]), |_| AutoglideSemitones::PlusTwo),
-            map(tag(&[0x0f]), |_| AutoglideSemitones::PlusThree),
-            map(tag(&[0x10]), |_| AutoglideSemitones::PlusFour),
-            map(tag(&[0x11]), |_| AutoglideSemitones::PlusFive),
-            map(tag(&[0x12]), |_| AutoglideSemitones::PlusSix),
-            map(tag(&[0x13]), |_| AutoglideSemitones::PlusSeven),
-            map(tag(&[0x14]), |_| AutoglideSemitones::PlusEight),
-            map(tag(&[0x15]), |_| AutoglideSemitones::PlusNine),
-            map(tag(&[0x16]), |_| AutoglideSemitones::PlusTen),
-            map(tag(&[0x17]), |_| AutoglideSemitones::PlusEleven),
-            map(tag(&[0x18]), |_| AutoglideSemitones::PlusTwelve),
+            map(tag(&[0x01]), |_| KeyTrackMode::Hold),
+            map(tag(&[0x00]), |_| KeyTrackMode::Track),
         )),
-    ))(input)
-}
-
-fn key_track_mode(input: &[u8]) -> IResult<&[u8], KeyTrackMode> {
-    alt((
-        map(tag(&[0x01]), |_| KeyTrackMode::Hold),
-        map(tag(&[0x00]), |_| KeyTrackMode::Track),
-    ))(input)
-}
-
-fn lfo_index(input: &[u8]) -> IResult<&[u8], LfoIndex> {
-    alt((
-        map(tag(&[0x00]), |_| LfoIndex::One),
-        map(tag(&[0x01]), |_| LfoIndex::Two),
-        map(tag(&[0x02]), |_| LfoIndex::Three),
-        map(tag(&[0x03]), |_| LfoIndex::Four),
-        map(tag(&[0x04]), |_| LfoIndex::Five),
-    ))(input)
-}
-
-fn lfo_shape(input: &[u8]) -> IResult<&[u8], LfoShape> {
-    alt((
-        map(tag(&[0x00]), |_| LfoShape::Sine),
-        map(tag(&[0x01]), |_| LfoShape::Triangle),
-        map(tag(&[0x02]), |_| LfoShape::FallingSaw),
-        map(tag(&[0x03]), |_| LfoShape::Square),
-        map(tag(&[0x04]), |_| LfoShape::RisingSaw),
-    ))(input)
-}
-
-fn lfo_phase_offset(input: &[u8]) -> IResult<&[u8], LfoPhaseOffset> {
-    alt((
-        map(tag(&[0x00]), |_| LfoPhaseOffset::Zero),
-        map(tag(&[0x01]), |_| LfoPhaseOffset::FourtyFive),
-        map(tag(&[0x02]), |_| LfoPhaseOffset::Ninety),
-        map(tag(&[0x03]), |_| LfoPhaseOffset::HundredThirtyFive),
-        map(tag(&[0x04]), |_| LfoPhaseOffset::HundredEighty),
-        map(tag(&[0x05]), |_| LfoPhaseOffset::TwoHundredTwentyFive),
-        map(tag(&[0x06]), |_| LfoPhaseOffset::TwoHundredSeventy),
-        map(tag(&[0x07]), |_| LfoPhaseOffset::ThreeHundredFifteen),
-    ))(input)
-}
-
-fn mod_source(input: &[u8]) -> IResult<&[u8], ModSource> {
-    alt((
-        map(tag(&[0x00]), |_| ModSource::Off),
-        map(tag(&[0x01]), |_| ModSource::AfterTouch),
-        map(tag(&[0x02]), |_| ModSource::ModWheel),
-        map(tag(&[0x03]), |_| ModSource::Velocity),
-    ))(input)
-}
-
-fn assign_out_option(input: &[u8]) -> IResult<&[u8], AssignOutOption> {
-    alt((
-        map(tag(&[0x00]), |_| AssignOutOption::Osc1),
-        map(tag(&[0x01]), |_| AssignOutOption::Osc2),
-        map(tag(&[0x02]), |_| AssignOutOption::Velocity),
-        map(tag(&[0x03]), |_| AssignOutOption::ModWheel),
-        map(tag(&[0x04]), |_| AssignOutOption::AfterTouch),
-    ))(input)
-}
-
-fn global_setting(input: &[u8]) -> IResult<&[u8], GlobalSetting> {
-    alt((
+    )(input)
+}
+
+fn lfo_index<'a, E: NomError<&'a [u8]>>(input: &'a [u8]) -> IResult<&'a [u8], LfoIndex, E> {
+    context(
+        "LFO index",
         alt((
-            map(preceded(tag(&[0x0f]), toggle_option), ParaphonicMode),
-            map(preceded(tag(&[0x0e]), toggle_option), OscSync),
-            map(preceded(tag(&[0x20]), blend_mode), Osc1BlendMode),
-            map(preceded(tag(&[0x21]), blend_mode), Osc2BlendMode),
-            map(preceded(tag(&[0x22]), toggle_option), Osc1TunePotBypass),
-            map(preceded(tag(&[0x23]), toggle_option), Osc2TunePotBypass),
-            map(preceded(tag(&[0x26]), osc_range), Osc1Range),
-            map(preceded(tag(&[0x27]), osc_range), Osc2Range),
-            map(preceded(tag(&[0x2a]), key_track_mode), Osc2KeyTrack),
-            map(preceded(tag(&[0x30]), blend_mode), LfoBlendMode),
-            map(preceded(tag(&[0x37]), toggle_option), LfoKeySync),
-            map(preceded(tag(&[0x31]), toggle_option), LfoOneShot),
-            map(preceded(tag(&[0x3b]), toggle_option), LfoRetrigger),
-            map(preceded(tag(&[0x35]), toggle_option), LfoMidiSync),
-            map(preceded(tag(&[0x34]), percent), LfoDepth),
-            map(tag(&[0x39, 0x00]), |_| LfoResetOrder),
-            map(preceded(tag(&[0x11]), toggle_option), VcfKeyTracking),
-            map(preceded(tag(&[0x14]), percent), VcfModDepth),
-            map(preceded(tag(&[0x00]), channel), MidiChannel),
-            map(preceded(tag(&[0x0a]), toggle_option), DisableMidiDips),
-            map(preceded(tag(&[0x08]), toggle_option), PolyChainMode),
+            map(tag(&[0x00]), |_| LfoIndex::One),
+            map(tag(&[0x01]), |_| LfoIndex::Two),
+            map(tag(&[0x02]), |_| LfoIndex::Three),
+            map(tag(&[0x03]), |_| LfoIndex::Four),
+            map(tag(&[0x04]), |_| LfoIndex::Five),
         )),
+    )(input)
+}
+
+fn lfo_shape<'a, E: NomError<&'a [u8]>>(input: &'a [u8]) -> IResult<&'a [u8], LfoShape, E> {
+    context(
+        "LFO shape",
         alt((
-            map(preceded(tag(&[0x24]), autoglide_semitones), Osc1Autoglide),
-            map(preceded(tag(&[0x25]), autoglide_semitones), Osc2Autoglide),
-            map(preceded(tag(&[0x0b]), toggle_option), KeyRangeMute),
-            map(tag(&[0x06, 0x00]), |_| KeyRangeReset),
-            map(
-                preceded(tag(&[0x38]), pair(lfo_index, lfo_shape)),
-                |(i, s)| LfoShapeOrder(i, s),
-            ),
-            map(
-                preceded(tag(&[0x3a]), pair(lfo_index, lfo_phase_offset)),
-                |(i, o)| LfoShapePhase(i, o),
-            ),
-            map(preceded(tag(&[0x12]), mod_source), VcfModSource),
-            map(preceded(tag(&[0x04]), assign_out_option), AssignOut),
-            map(preceded(tag(&[0x05]), retrigger_mode), EnvRetriggerMode),
+            map(tag(&[0x00]), |_| LfoShape::Sine),
+            map(tag(&[0x01]), |_| LfoShape::Triangle),
+            map(tag(&[0x02]), |_| LfoShape::FallingSaw),
+            map(tag(&[0x03]), |_| LfoShape::Square),
+            map(tag(&[0x04]), |_| LfoShape::RisingSaw),
+        )),
+    )(input)
+}
+
+fn lfo_phase_offset<'a, E: NomError<&'a [u8]>>(
+    input: &'a [u8],
+) -> IResult<&'a [u8], LfoPhaseOffset, E> {
+    context(
+        "LFO phase offset",
+        alt((
+            map(tag(&[0x00]), |_| LfoPhaseOffset::Zero),
+            map(tag(&[0x01]), |_| LfoPhaseOffset::FourtyFive),
+            map(tag(&[0x02]), |_| LfoPhaseOffset::Ninety),
+            map(tag(&[0x03]), |_| LfoPhaseOffset::HundredThirtyFive),
+            map(tag(&[0x04]), |_| LfoPhaseOffset::HundredEighty),
+            map(tag(&[0x05]), |_| LfoPhaseOffset::TwoHundredTwentyFive),
+            map(tag(&[0x06]), |_| LfoPhaseOffset::TwoHundredSeventy),
+            map(tag(&[0x07]), |_| LfoPhaseOffset::ThreeHundredFifteen),
+        )),
+    )(input)
+}
+
+fn mod_source<'a, E: NomError<&'a [u8]>>(input: &'a [u8]) -> IResult<&'a [u8], ModSource, E> {
+    context(
+        "mod source",
+        alt((
+            map(tag(&[0x00]), |_| ModSource::Off),
+            map(tag(&[0x01]), |_| ModSource::AfterTouch),
+            map(tag(&[0x02]), |_| ModSource::ModWheel),
+            map(tag(&[0x03]), |_| ModSource::Velocity),
+        )),
+    )(input)
+}
+
+fn assign_out_option<'a, E: NomError<&'a [u8]>>(
+    input: &'a [u8],
+) -> IResult<&'a [u8], AssignOutOption, E> {
+    context(
+        "assign out option",
+        alt((
+            map(tag(&[0x00]), |_| AssignOutOption::Osc1),
+            map(tag(&[0x01]), |_| AssignOutOption::Osc2),
+            map(tag(&[0x02]), |_| AssignOutOption::Velocity),
+            map(tag(&[0x03]), |_| AssignOutOption::ModWheel),
+            map(tag(&[0x04]), |_| AssignOutOption::AfterTouch),
+        )),
+    )(input)
+}
+
+fn global_setting<'a, E: NomError<&'a [u8]>>(
+    input: &'a [u8],
+) -> IResult<&'a [u8], GlobalSetting, E> {
+    context(
+        "setting",
+        alt((
+            alt((
+                map(preceded(tag(&[0x0f]), toggle_option), ParaphonicMode),
+                map(preceded(tag(&[0x0e]), toggle_option), OscSync),
+                map(preceded(tag(&[0x20]), blend_mode), Osc1BlendMode),
+                map(preceded(tag(&[0x21]), blend_mode), Osc2BlendMode),
+                map(preceded(tag(&[0x22]), toggle_option), Osc1TunePotBypass),
+                map(preceded(tag(&[0x23]), toggle_option), Osc2TunePotBypass),
+                map(preceded(tag(&[0x26]), osc_range), Osc1Range),
+                map(preceded(tag(&[0x27]), osc_range), Osc2Range),
+                map(preceded(tag(&[0x2a]), key_track_mode), Osc2KeyTrack),
+                map(preceded(tag(&[0x30]), blend_mode), LfoBlendMode),
+                map(preceded(tag(&[0x37]), toggle_option), LfoKeySync),
+                map(preceded(tag(&[0x31]), toggle_option), LfoOneShot),
+                map(preceded(tag(&[0x3b]), toggle_option), LfoRetrigger),
+                map(preceded(tag(&[0x35]), toggle_option), LfoMidiSync),
+                map(preceded(tag(&[0x34]), percent), LfoDepth),
+                map(tag(&[0x39, 0x00]), |_| LfoResetOrder),
+                map(preceded(tag(&[0x11]), toggle_option), VcfKeyTracking),
+                map(preceded(tag(&[0x14]), percent), VcfModDepth),
+                map(
+                    preceded(tag(&[0x00]), context("MIDI channel", channel)),
+                    MidiChannel,
+                ),
+                map(preceded(tag(&[0x0a]), toggle_option), DisableMidiDips),
+                map(preceded(tag(&[0x08]), toggle_option), PolyChainMode),
+            )),
+            alt((
+                map(preceded(tag(&[0x24]), autoglide_semitones), Osc1Autoglide),
+                map(preceded(tag(&[0x25]), autoglide_semitones), Osc2Autoglide),
+                map(preceded(tag(&[0x0b]), toggle_option), KeyRangeMute),
+                map(tag(&[0x06, 0x00]), |_| KeyRangeReset),
+                map(
+                    preceded(tag(&[0x38]), pair(lfo_index, lfo_shape)),
+                    |(i, s)| LfoShapeOrder(i, s),
+                ),
+                map(
+                    preceded(tag(&[0x3a]), pair(lfo_index, lfo_phase_offset)),
+                    |(i, o)| LfoShapePhase(i, o),
+                ),
+                map(preceded(tag(&[0x12]), mod_source), VcfModSource),
+                map(preceded(tag(&[0x04]), assign_out_option), AssignOut),
+                map(preceded(tag(&[0x05]), retrigger_mode), EnvRetriggerMode),
+            )),
         )),
-    ))(input)
+    )(input)
 }
 
-fn channel(input: &[u8]) -> IResult<&[u8], Channel> {
+pub(crate) fn channel<'a, E: NomError<&'a [u8]>>(input: &'a [u8]) -> IResult<&'a [u8], Channel, E> {
     cut(alt((
         map(tag(&[0x00]), |_| Channel::One),
         map(tag(&[0x01]), |_| Channel::Two),
@@ -220,46 +408,333 @@ fn channel(input: &[u8]) -> IResult<&[u8], Channel> {
     )))(input)
 }
 
-fn device_id(input: &[u8]) -> IResult<&[u8], DeviceId> {
-    alt((
-        map(tag(&[0x7f]), |_| DeviceId::Multicast),
-        map(channel, DeviceId::Channel),
-    ))(input)
+fn device_id<'a, E: NomError<&'a [u8]>>(input: &'a [u8]) -> IResult<&'a [u8], DeviceId, E> {
+    context(
+        "device id",
+        alt((
+            map(tag(&[0x7f]), |_| DeviceId::Multicast),
+            map(channel, DeviceId::Channel),
+        )),
+    )(input)
 }
 
-fn version(input: &[u8]) -> IResult<&[u8], String> {
-    map(is_not([SYSEX_EOX]), |v| {
-        String::from_utf8_lossy(v).into_owned()
-    })(input)
+fn version<'a, E: NomError<&'a [u8]>>(input: &'a [u8]) -> IResult<&'a [u8], String, E> {
+    context(
+        "version",
+        map(is_not([SYSEX_EOX]), |v| {
+            String::from_utf8_lossy(v).into_owned()
+        }),
+    )(input)
 }
 
-pub fn neutron_message(input: &[u8]) -> IResult<&[u8], NeutronMessage> {
+/// Parses a message in the protocol of the Neutron, with the header and opcodes of `D`.
+fn message<'a, D: BehringerDevice, E: NomError<&'a [u8]>>(
+    input: &'a [u8],
+) -> IResult<&'a [u8], NeutronMessage, E> {
     delimited(
-        tag(NEUTRON_MESSAGE_HEADER),
+        context("SysEx header", tag(D::header())),
         alt((
-            map(
-                separated_pair(device_id, tag(&[0x0a]), global_setting),
-                |(id, gs)| SetGlobalSetting(id, gs),
+            context(
+                "set setting",
+                map(
+                    separated_pair(
+                        device_id,
+                        opcode(D::opcode(Command::SetSetting)),
+                        global_setting,
+                    ),
+                    |(id, gs)| SetGlobalSetting(id, gs),
+                ),
+            ),
+            context(
+                "restore settings",
+                map(
+                    terminated(device_id, opcode(D::opcode(Command::RestoreSettings))),
+                    RestoreGlobalSetting,
+                ),
+            ),
+            context(
+                "software version request",
+                map(
+                    terminated(device_id, opcode(D::opcode(Command::VersionRequest))),
+                    SoftwareVersionRequest,
+                ),
             ),
-            map(terminated(device_id, tag(&[0x0b])), |id| {
-                RestoreGlobalSetting(id)
-            }),
-            map(terminated(device_id, tag(&[0x73])), |id| {
-                SoftwareVersionRequest(id)
-            }),
-            map(
-                separated_pair(device_id, tag(&[0x74, COMMS_PROTOCOL_V1]), version),
-                |(id, version)| SoftwareVersionResponse(id, version),
+            context(
+                "software version response",
+                map(
+                    separated_pair(
+                        device_id,
+                        opcode(D::opcode(Command::VersionResponse)),
+                        version,
+                    ),
+                    |(id, version)| SoftwareVersionResponse(id, version),
+                ),
             ),
-            map(
-                separated_pair(device_id, tag(&[0x5a, COMMS_PROTOCOL_V1]), global_setting),
-                |(id, gs)| GlobalSettingUpdate(id, gs),
+            context(
+                "setting update",
+                map(
+                    separated_pair(
+                        device_id,
+                        opcode(D::opcode(Command::SettingUpdate)),
+                        global_setting,
+                    ),
+                    |(id, gs)| GlobalSettingUpdate(id, gs),
+                ),
+            ),
+            context(
+                "state request",
+                map(
+                    terminated(device_id, opcode(D::opcode(Command::StateRequest))),
+                    StateRequest,
+                ),
+            ),
+            context(
+                "state dump",
+                map(
+                    separated_pair(
+                        device_id,
+                        opcode(D::opcode(Command::StateDump)),
+                        context("state", is_not([SYSEX_EOX])),
+                    ),
+                    |(id, payload): (DeviceId, &[u8])| StateDump(id, payload.to_vec()),
+                ),
             ),
         )),
-        tag(&[SYSEX_EOX]),
+        context("SysEx end", tag(&[SYSEX_EOX])),
     )(input)
 }
 
+/// Parses a complete Neutron message, returning what follows it.
+///
+/// ```
+/// use rustron_lib::parser::{neutron_message, ParseError};
+///
+/// let unknown_opcode = [0xf0, 0x00, 0x20, 0x32, 0x28, 0x7f, 0x42, 0xf7];
+/// assert_eq!(
+///     neutron_message(&unknown_opcode),
+///     Err(nom::Err::Error(ParseError { offset: 6, expected: "opcode" }))
+/// );
+/// ```
+pub fn neutron_message(input: &[u8]) -> IResult<&[u8], NeutronMessage, ParseError> {
+    device_message::<Neutron>(input)
+}
+
+/// Parses a complete message for device `D`, returning what follows it.
+pub fn device_message<D: BehringerDevice>(
+    input: &[u8],
+) -> IResult<&[u8], NeutronMessage, ParseError> {
+    message::<D, Expected>(input).map_err(|e| {
+        e.map(|e| ParseError {
+            offset: input.len() - e.remaining,
+            expected: e.expected.unwrap_or("end of message"),
+        })
+    })
+}
+
+/// Parses a message, explaining where and why it failed otherwise. A message with a known opcode
+/// but the wrong length is reported as too short or too long, see `validate_length`, unless it is
+/// missing its SysEx end. Bytes left over after a message are an error as well.
+///
+/// ```
+/// use rustron_lib::parser::parse;
+///
+/// let bad_value = [0xf0, 0x00, 0x20, 0x32, 0x28, 0x7f, 0x0a, 0x0e, 0x05, 0xf7];
+/// assert_eq!(
+///     parse(&bad_value).unwrap_err().to_string(),
+///     "parse failed at byte 8: expected on/off value"
+/// );
+/// let too_long = [0xf0, 0x00, 0x20, 0x32, 0x28, 0x7f, 0x73, 0x00, 0xf7];
+/// assert_eq!(
+///     parse(&too_long).unwrap_err().to_string(),
+///     "parse failed at byte 7: expected fewer bytes for the opcode"
+/// );
+/// ```
+pub fn parse(message: &[u8]) -> Result<NeutronMessage, ParseError> {
+    match neutron_message(message) {
+        Ok(([], msg)) => Ok(msg),
+        Ok((rest, _)) => Err(ParseError {
+            offset: message.len() - rest.len(),
+            expected: "end of message",
+        }),
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e))
+            if message.last() != Some(&SYSEX_EOX) =>
+        {
+            Err(e)
+        }
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+            Err(match validate_length::<Neutron>(message) {
+                Err(LengthError::TooShort { length, .. }) => ParseError {
+                    offset: length.saturating_sub(1),
+                    expected: "more bytes for the opcode",
+                },
+                Err(LengthError::TooLong { command, .. }) => ParseError {
+                    offset: *Neutron::length(command).end() - 1,
+                    expected: "fewer bytes for the opcode",
+                },
+                Ok(_) => e,
+            })
+        }
+        Err(nom::Err::Incomplete(_)) => Err(ParseError {
+            offset: message.len(),
+            expected: "more bytes",
+        }),
+    }
+}
+
+/// Parses a message like `parse`, but explains a failure with a trace of everything that failed,
+/// innermost first and including every alternative that was tried. Bytes left over after a
+/// message are an error, like in `parse`.
+///
+/// ```
+/// use rustron_lib::parser::trace;
+///
+/// let bad_value = [0xf0, 0x00, 0x20, 0x32, 0x28, 0x7f, 0x0a, 0x0e, 0x05, 0xf7];
+/// let trace = trace(&bad_value).unwrap_err();
+/// assert!(trace.contains(&String::from("byte 8: in on/off value")));
+/// assert!(trace.contains(&String::from("byte 6: in opcode")));
+/// ```
+#[cfg(feature = "trace")]
+pub fn trace(message: &[u8]) -> Result<NeutronMessage, Vec<String>> {
+    let errors = match self::message::<Neutron, Trace>(message) {
+        Ok(([], msg)) => return Ok(msg),
+        Ok((rest, _)) => {
+            return Err(vec![format!(
+                "byte {}: trailing bytes after the end of the message",
+                message.len() - rest.len()
+            )])
+        }
+        Err(nom::Err::Error(Trace(e))) | Err(nom::Err::Failure(Trace(e))) => e.errors,
+        Err(nom::Err::Incomplete(_)) => return Err(vec![String::from("incomplete")]),
+    };
+    Err(errors
+        .into_iter()
+        .map(|(input, kind)| {
+            let offset = message.len() - input.len();
+            match kind {
+                VerboseErrorKind::Context(context) => format!("byte {}: in {}", offset, context),
+                VerboseErrorKind::Char(c) => format!("byte {}: expected '{}'", offset, c),
+                VerboseErrorKind::Nom(kind) => format!("byte {}: {:?} failed", offset, kind),
+            }
+        })
+        .collect())
+}
+
+/// A human readable description of a raw message: the decoded message if it can be parsed,
+/// otherwise its bytes as hex.
+///
+/// ```
+/// use rustron_lib::parser::describe;
+///
+/// assert_eq!(describe(&[0xf0, 0x01, 0xf7]), "f001f7");
+/// ```
+#[cfg(feature = "hex")]
+pub fn describe(message: &[u8]) -> String {
+    match neutron_message(message) {
+        Ok((_, msg)) => msg.to_string(),
+        Err(_) => hex::encode(message),
+    }
+}
+
+/// Explains every byte of a message as far as it can be parsed, e.g. for showing the raw bytes of a
+/// received message next to what they mean. Bytes of messages that can't be parsed are only
+/// labelled where the framing is recognised.
+///
+/// ```
+/// use rustron_lib::parser::annotate;
+/// use rustron_lib::protocol::DeviceId::Multicast;
+/// use rustron_lib::protocol::GlobalSetting::OscSync;
+/// use rustron_lib::protocol::NeutronMessage::SetGlobalSetting;
+/// use rustron_lib::protocol::ToggleOption::On;
+///
+/// let bytes = SetGlobalSetting(Multicast, OscSync(On)).as_bytes();
+/// let labels: Vec<String> = annotate(&bytes).into_iter().map(|(_, label)| label).collect();
+/// assert_eq!(labels[5], "device id: all");
+/// assert_eq!(labels[7], "setting: osc.sync");
+/// assert_eq!(labels[8], "value: on");
+/// ```
+pub fn annotate(message: &[u8]) -> Vec<(u8, String)> {
+    let mut labels = vec![String::from("?"); message.len()];
+    let mut label = |index: usize, text: String| {
+        if let Some(label) = labels.get_mut(index) {
+            *label = text;
+        }
+    };
+    if message.first() == Some(&NEUTRON_MESSAGE_HEADER[0]) {
+        label(0, String::from("SysEx start"));
+    }
+    if message.starts_with(&NEUTRON_MESSAGE_HEADER) {
+        for index in 1..4 {
+            label(index, String::from("manufacturer: Behringer"));
+        }
+        label(4, format!("device: {}", Neutron::NAME));
+    }
+    if message.len() > 1 && message.last() == Some(&SYSEX_EOX) {
+        label(message.len() - 1, String::from("SysEx end"));
+    }
+    let msg = match parse(message) {
+        Ok(msg) => msg,
+        Err(error) => {
+            // Framing bytes keep their labels even when they are where parsing stopped
+            if let Some(unknown) = labels.get_mut(error.offset).filter(|l| *l == "?") {
+                *unknown = format!("expected {}", error.expected);
+            }
+            return message.iter().copied().zip(labels).collect();
+        }
+    };
+
+    let (id, command) = match &msg {
+        SetGlobalSetting(id, _) => (id, "set setting"),
+        RestoreGlobalSetting(id) => (id, "restore settings"),
+        NeutronMessage::CalibrationModeCommand(id) => (id, "calibration mode"),
+        SoftwareVersionRequest(id) => (id, "software version request"),
+        SoftwareVersionResponse(id, _) => (id, "software version response"),
+        GlobalSettingUpdate(id, _) => (id, "setting update"),
+        StateRequest(id) => (id, "state request"),
+        StateDump(id, _) => (id, "state dump"),
+    };
+    label(
+        5,
+        match id {
+            DeviceId::Channel(channel) => format!("device id: channel {}", channel.number()),
+            DeviceId::Multicast => String::from("device id: all"),
+        },
+    );
+    label(6, format!("command: {}", command));
+
+    let mut index = 7;
+    if let SoftwareVersionResponse(..) | GlobalSettingUpdate(..) | StateDump(..) = msg {
+        label(index, String::from("protocol version"));
+        index += 1;
+    }
+    let value_end = message.len() - 1;
+    match &msg {
+        SetGlobalSetting(_, setting) | GlobalSettingUpdate(_, setting) => {
+            let id = setting.id();
+            label(index, format!("setting: {}", id.id()));
+            index += 1;
+            if let SettingId::LfoShapeOrder(lfo_index) | SettingId::LfoShapePhase(lfo_index) = id {
+                label(index, format!("LFO index: {}", lfo_index.as_byte() + 1));
+                index += 1;
+            }
+            for value_index in index..value_end {
+                label(value_index, format!("value: {}", setting.value_text()));
+            }
+        }
+        SoftwareVersionResponse(_, version) => {
+            for value_index in index..value_end {
+                label(value_index, format!("version: {}", version));
+            }
+        }
+        StateDump(..) => {
+            for value_index in index..value_end {
+                label(value_index, format!("state byte {}", value_index - index));
+            }
+        }
+        _ => {}
+    }
+    message.iter().copied().zip(labels).collect()
+}
+
 #[cfg(test)]
 mod test {
     use nom::error::ErrorKind;
@@ -267,10 +742,9 @@ mod test {
     use nom::IResult;
 
     use crate::parser::{
-        blend_mode, device_id, global_setting, key_track_mode, neutron_message, osc_range,
-        toggle_option,
+        blend_mode, device_id, key_track_mode, neutron_message, osc_range, parse, toggle_option,
+        Expected, ParseError,
     };
-    use crate::protocol::BlendMode::{Blend, Switch};
     use crate::protocol::GlobalSetting::{
         AssignOut, DisableMidiDips, EnvRetriggerMode, KeyRangeMute, KeyRangeReset, LfoBlendMode,
         LfoDepth, LfoKeySync, LfoMidiSync, LfoOneShot, LfoResetOrder, LfoRetrigger, LfoShapeOrder,
@@ -278,29 +752,28 @@ mod test {
         Osc2Autoglide, Osc2BlendMode, Osc2KeyTrack, Osc2Range, Osc2TunePotBypass, OscSync,
         ParaphonicMode, PolyChainMode, VcfKeyTracking, VcfModDepth, VcfModSource,
     };
-    use crate::protocol::KeyTrackMode::Track;
     use crate::protocol::NeutronMessage::{
         GlobalSettingUpdate, RestoreGlobalSetting, SetGlobalSetting, SoftwareVersionRequest,
-        SoftwareVersionResponse,
+        SoftwareVersionResponse, StateDump, StateRequest,
     };
-    use crate::protocol::OscRange::{PlusMinusTen, ThirtyTwo};
-    use crate::protocol::ToggleOption::{Off, On};
+    use crate::protocol::ToggleOption::On;
     use crate::protocol::{
-        AssignOutOption, AutoglideSemitones, BlendMode, ByteBuilder, Channel, DeviceId,
-        GlobalSetting, KeyTrackMode, LfoIndex, LfoPhaseOffset, LfoShape, ModSource, OscRange,
-        Percent, RetriggerMode, ToggleOption, BEHRINGER_MANUFACTURER, NEUTRON_DEVICE, SYSEX_EOX,
-        SYSEX_MESSAGE_START,
+        maybe_request_state, AssignOutOption, AutoglideSemitones, BlendMode, Channel, DeviceId,
+        GlobalSetting, KeyTrackMode, LfoIndex, LfoPhaseOffset, LfoShape, ModSource, NeutronMessage,
+        OscRange, Percent, RetriggerMode, ToggleOption, BEHRINGER_MANUFACTURER, NEUTRON_DEVICE,
+        SYSEX_EOX, SYSEX_MESSAGE_START,
     };
+    #[cfg(feature = "iter")]
     use strum::IntoEnumIterator;
 
     #[test]
     fn test_toggle_option() {
         assert_eq!(
-            toggle_option(&[ToggleOption::On.as_byte()]),
+            toggle_option::<Expected>(&[ToggleOption::On.as_byte()]),
             Ok((&[][..], ToggleOption::On))
         );
         assert_eq!(
-            toggle_option(&[ToggleOption::Off.as_byte()]),
+            toggle_option::<Expected>(&[ToggleOption::Off.as_byte()]),
             Ok((&[][..], ToggleOption::Off))
         );
     }
@@ -308,11 +781,11 @@ mod test {
     #[test]
     fn test_blend_mode() {
         assert_eq!(
-            blend_mode(&[BlendMode::Switch.as_byte()]),
+            blend_mode::<Expected>(&[BlendMode::Switch.as_byte()]),
             Ok((&[][..], BlendMode::Switch))
         );
         assert_eq!(
-            blend_mode(&[BlendMode::Blend.as_byte()]),
+            blend_mode::<Expected>(&[BlendMode::Blend.as_byte()]),
             Ok((&[][..], BlendMode::Blend))
         );
     }
@@ -320,108 +793,152 @@ mod test {
     #[test]
     fn test_osc_range() {
         assert_eq!(
-            osc_range(&[OscRange::ThirtyTwo.as_byte()]),
+            osc_range::<Expected>(&[OscRange::ThirtyTwo.as_byte()]),
             Ok((&[][..], OscRange::ThirtyTwo))
         );
         assert_eq!(
-            osc_range(&[OscRange::Sixteen.as_byte()]),
+            osc_range::<Expected>(&[OscRange::Sixteen.as_byte()]),
             Ok((&[][..], OscRange::Sixteen))
         );
         assert_eq!(
-            osc_range(&[OscRange::Eight.as_byte()]),
+            osc_range::<Expected>(&[OscRange::Eight.as_byte()]),
             Ok((&[][..], OscRange::Eight))
         );
         assert_eq!(
-            osc_range(&[OscRange::PlusMinusTen.as_byte()]),
+            osc_range::<Expected>(&[OscRange::PlusMinusTen.as_byte()]),
             Ok((&[][..], OscRange::PlusMinusTen))
         );
     }
 
+    #[test]
+    #[cfg(feature = "iter")]
+    fn test_autoglide_interval_names() {
+        for semitones in AutoglideSemitones::iter() {
+            assert_eq!(semitones.to_string().parse(), Ok(semitones));
+        }
+        assert_eq!(AutoglideSemitones::Zero.to_string(), "unison");
+        assert_eq!(AutoglideSemitones::MinusSeven.to_string(), "-P5");
+        assert_eq!(AutoglideSemitones::PlusThree.to_string(), "+m3");
+        assert!("+P9".parse::<AutoglideSemitones>().is_err());
+        assert!("13".parse::<AutoglideSemitones>().is_err());
+    }
+
     #[test]
     fn test_key_track_mode() {
         assert_eq!(
-            key_track_mode(&[KeyTrackMode::Hold.as_byte()]),
+            key_track_mode::<Expected>(&[KeyTrackMode::Hold.as_byte()]),
             Ok((&[][..], KeyTrackMode::Hold))
         );
         assert_eq!(
-            key_track_mode(&[KeyTrackMode::Track.as_byte()]),
+            key_track_mode::<Expected>(&[KeyTrackMode::Track.as_byte()]),
             Ok((&[][..], KeyTrackMode::Track))
         );
     }
 
-    // Test helper
-    fn to_vec(gs: GlobalSetting) -> Vec<u8> {
-        let mut buf = Vec::new();
-        gs.append_to(&mut buf);
-        buf
-    }
-
-    macro_rules! verify_global_setting {
-        ($($setting:tt)*) => {
-            assert_eq!(
-                global_setting(to_vec($($setting)*).as_slice()),
-                Ok((&[][..], $($setting)*))
-            );
+    /// Every value of every setting listed, enumerating the values of each field with `EnumIter`.
+    /// The generated match makes the test stop compiling when a setting is added but not listed.
+    #[cfg(feature = "iter")]
+    macro_rules! every_global_setting {
+        ($($setting:ident $(($($field:ident),+))?),* $(,)?) => {{
+            #[allow(dead_code)]
+            fn listed(setting: GlobalSetting) {
+                match setting {
+                    $($setting { .. } => (),)*
+                }
+            }
+            let mut settings: Vec<GlobalSetting> = Vec::new();
+            $(every_global_setting!(@values settings, $setting $(, $($field),+)?);)*
+            settings
+        }};
+        (@values $settings:ident, $setting:ident) => {
+            $settings.push($setting);
         };
-    }
-
-    macro_rules! verify_global_setting_variants {
-        ($container:ident, $enum:ident) => {
-            for x in $enum::iter() {
-                assert_eq!(
-                    global_setting(to_vec($container(x)).as_slice()),
-                    Ok((&[][..], $container(x)))
-                );
+        (@values $settings:ident, $setting:ident, $field:ident) => {
+            for value in every_global_setting!(@iter $field) {
+                $settings.push($setting(value));
             }
         };
+        (@values $settings:ident, $setting:ident, $first:ident, $second:ident) => {
+            for first in every_global_setting!(@iter $first) {
+                for second in every_global_setting!(@iter $second) {
+                    $settings.push($setting(first, second));
+                }
+            }
+        };
+        // Percent is a byte rather than an enum
+        (@iter Percent) => {
+            (0..=63).map(Percent::from_byte)
+        };
+        (@iter $field:ident) => {
+            $field::iter()
+        };
     }
 
     #[test]
-    fn test_global_setting() {
-        verify_global_setting!(ParaphonicMode(On));
-        verify_global_setting!(OscSync(Off));
-        verify_global_setting!(Osc1BlendMode(Blend));
-        verify_global_setting!(Osc2BlendMode(Switch));
-        verify_global_setting!(Osc1TunePotBypass(On));
-        verify_global_setting!(Osc2TunePotBypass(On));
-        verify_global_setting!(Osc1Range(ThirtyTwo));
-        verify_global_setting!(Osc1Range(PlusMinusTen));
-        verify_global_setting!(Osc2KeyTrack(Track));
-        verify_global_setting_variants!(Osc1Autoglide, AutoglideSemitones);
-        verify_global_setting_variants!(Osc2Autoglide, AutoglideSemitones);
-        verify_global_setting!(LfoBlendMode(Blend));
-        verify_global_setting!(LfoKeySync(On));
-        verify_global_setting!(LfoOneShot(On));
-        verify_global_setting!(LfoRetrigger(On));
-        verify_global_setting!(LfoMidiSync(On));
-        verify_global_setting!(LfoResetOrder);
-        verify_global_setting!(VcfKeyTracking(On));
-        verify_global_setting_variants!(VcfModSource, ModSource);
-        verify_global_setting_variants!(MidiChannel, Channel);
-        verify_global_setting!(DisableMidiDips(On));
-        verify_global_setting!(PolyChainMode(On));
-        verify_global_setting!(KeyRangeMute(On));
-        verify_global_setting!(KeyRangeReset);
-        verify_global_setting_variants!(AssignOut, AssignOutOption);
-        verify_global_setting!(EnvRetriggerMode(RetriggerMode::Legato));
-        assert_eq!(
-            global_setting(to_vec(LfoDepth(Percent::from_percentage(50))).as_slice()),
-            Ok((&[][..], LfoDepth(Percent::from_byte(31))))
-        );
-        assert_eq!(
-            global_setting(to_vec(VcfModDepth(Percent::from_percentage(50))).as_slice()),
-            Ok((&[][..], VcfModDepth(Percent::from_byte(31))))
+    #[cfg(feature = "iter")]
+    fn test_global_setting_round_trip() {
+        let settings = every_global_setting!(
+            ParaphonicMode(ToggleOption),
+            OscSync(ToggleOption),
+            Osc1BlendMode(BlendMode),
+            Osc2BlendMode(BlendMode),
+            Osc1TunePotBypass(ToggleOption),
+            Osc2TunePotBypass(ToggleOption),
+            Osc1Range(OscRange),
+            Osc2Range(OscRange),
+            Osc2KeyTrack(KeyTrackMode),
+            Osc1Autoglide(AutoglideSemitones),
+            Osc2Autoglide(AutoglideSemitones),
+            LfoBlendMode(BlendMode),
+            LfoKeySync(ToggleOption),
+            LfoOneShot(ToggleOption),
+            LfoRetrigger(ToggleOption),
+            LfoMidiSync(ToggleOption),
+            LfoDepth(Percent),
+            LfoShapeOrder(LfoIndex, LfoShape),
+            LfoShapePhase(LfoIndex, LfoPhaseOffset),
+            LfoResetOrder,
+            VcfKeyTracking(ToggleOption),
+            VcfModDepth(Percent),
+            VcfModSource(ModSource),
+            MidiChannel(Channel),
+            DisableMidiDips(ToggleOption),
+            PolyChainMode(ToggleOption),
+            KeyRangeMute(ToggleOption),
+            KeyRangeReset,
+            AssignOut(AssignOutOption),
+            EnvRetriggerMode(RetriggerMode),
         );
-        for shape in LfoShape::iter() {
-            assert_eq!(
-                global_setting(to_vec(LfoShapeOrder(LfoIndex::Two, shape)).as_slice()),
-                Ok((&[][..], LfoShapeOrder(LfoIndex::Two, shape)))
-            );
+        for setting in settings {
+            for message in [
+                SetGlobalSetting(DeviceId::Multicast, setting),
+                GlobalSettingUpdate(DeviceId::Channel(Channel::Two), setting),
+            ] {
+                assert_eq!(
+                    neutron_message(message.as_bytes().as_slice()),
+                    Ok((&[][..], message))
+                );
+            }
         }
-        for phase in LfoPhaseOffset::iter() {
+
+        // Percentages are encoded on the 0-63 scale of the device
+        for (sent, received) in [
+            (
+                LfoDepth(Percent::from_percentage(50)),
+                LfoDepth(Percent::from_byte(31)),
+            ),
+            (
+                VcfModDepth(Percent::from_percentage(50)),
+                VcfModDepth(Percent::from_byte(31)),
+            ),
+        ] {
             assert_eq!(
-                global_setting(to_vec(LfoShapePhase(LfoIndex::Three, phase)).as_slice()),
-                Ok((&[][..], LfoShapePhase(LfoIndex::Three, phase)))
+                neutron_message(
+                    SetGlobalSetting(DeviceId::Multicast, sent)
+                        .as_bytes()
+                        .as_slice()
+                ),
+                Ok((&[][..], SetGlobalSetting(DeviceId::Multicast, received)))
             );
         }
     }
@@ -429,20 +946,32 @@ mod test {
     #[test]
     fn test_device_id() {
         assert_eq!(
-            device_id(&[0x00]),
+            device_id::<Expected>(&[0x00]),
             Ok((&[][..], DeviceId::Channel(Channel::One)))
         );
         assert_eq!(
-            device_id(&[0x0f]),
+            device_id::<Expected>(&[0x0f]),
             Ok((&[][..], DeviceId::Channel(Channel::Sixteen)))
         );
-        assert_eq!(device_id(&[0x7f]), Ok((&[][..], DeviceId::Multicast)));
-        match device_id(&[0x10]) {
+        assert_eq!(
+            device_id::<Expected>(&[0x7f]),
+            Ok((&[][..], DeviceId::Multicast))
+        );
+        match device_id::<Expected>(&[0x10]) {
             Ok(_) => panic!("Invalid DeviceId should fail"),
             _ => (),
         }
     }
 
+    #[test]
+    fn test_max_len() {
+        let longest = GlobalSettingUpdate(
+            DeviceId::Multicast,
+            LfoShapePhase(LfoIndex::One, LfoPhaseOffset::Zero),
+        );
+        assert_eq!(longest.as_bytes().len(), NeutronMessage::MAX_LEN);
+    }
+
     #[test]
     fn test_neutron_message() {
         assert_eq!(
@@ -502,6 +1031,112 @@ mod test {
                 GlobalSettingUpdate(DeviceId::Multicast, ParaphonicMode(On))
             ))
         );
+        assert_eq!(
+            neutron_message(StateRequest(DeviceId::Multicast).as_bytes().as_slice()),
+            Ok((&[][..], StateRequest(DeviceId::Multicast)))
+        );
+        assert_eq!(
+            neutron_message(maybe_request_state().as_slice()),
+            Ok((&[][..], StateRequest(DeviceId::Multicast)))
+        );
+        assert_eq!(
+            neutron_message(
+                StateDump(DeviceId::Channel(Channel::One), vec![0x6b, 0x02, 0x00])
+                    .as_bytes()
+                    .as_slice()
+            ),
+            Ok((
+                &[][..],
+                StateDump(DeviceId::Channel(Channel::One), vec![0x6b, 0x02, 0x00])
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |bytes: &[u8]| parse(bytes).unwrap_err();
+        assert_eq!(
+            error(&[0xf0, 0x01, 0xf7]),
+            ParseError {
+                offset: 0,
+                expected: "SysEx header"
+            }
+        );
+        let mut bytes = SetGlobalSetting(DeviceId::Multicast, OscSync(On)).as_bytes();
+        bytes[5] = 0x20;
+        assert_eq!(
+            error(&bytes),
+            ParseError {
+                offset: 5,
+                expected: "device id"
+            }
+        );
+        bytes[5] = 0x7f;
+        bytes[7] = 0x7e;
+        assert_eq!(
+            error(&bytes),
+            ParseError {
+                offset: 7,
+                expected: "setting"
+            }
+        );
+        bytes[7] = 0x00;
+        bytes[8] = 0x10;
+        assert_eq!(
+            error(&bytes),
+            ParseError {
+                offset: 8,
+                expected: "MIDI channel"
+            }
+        );
+        bytes[8] = 0x00;
+        bytes.insert(9, 0x00);
+        assert_eq!(
+            error(&bytes),
+            ParseError {
+                offset: 9,
+                expected: "SysEx end"
+            }
+        );
+        assert_eq!(
+            error(&bytes[..9]).to_string(),
+            "parse failed at byte 9: expected SysEx end"
+        );
+        let mut bytes = SetGlobalSetting(DeviceId::Multicast, OscSync(On)).as_bytes();
+        bytes.extend_from_slice(&[0x90, 0x3c]);
+        assert_eq!(
+            error(&bytes),
+            ParseError {
+                offset: 10,
+                expected: "end of message"
+            }
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "trace")]
+    fn trace_rejects_trailing_bytes_like_parse() {
+        let mut bytes = SetGlobalSetting(DeviceId::Multicast, OscSync(On)).as_bytes();
+        bytes.extend_from_slice(&[0x90, 0x3c]);
+        assert!(parse(&bytes).is_err());
+        assert_eq!(
+            crate::parser::trace(&bytes),
+            Err(vec![String::from(
+                "byte 10: trailing bytes after the end of the message"
+            )])
+        );
+    }
+
+    #[test]
+    fn wrong_lengths_are_told_apart_from_unknown_opcodes() {
+        let error = |bytes: &[u8]| parse(bytes).unwrap_err().expected;
+        let mut bytes = StateRequest(DeviceId::Multicast).as_bytes();
+        bytes.insert(7, 0x00);
+        assert_eq!(error(&bytes), "fewer bytes for the opcode");
+        let bytes = [0xf0, 0x00, 0x20, 0x32, 0x28, 0x7f, 0x5a, 0x01, 0x0e, 0xf7];
+        assert_eq!(error(&bytes), "more bytes for the opcode");
+        let bytes = [0xf0, 0x00, 0x20, 0x32, 0x28, 0x7f, 0x5b, 0x01, 0x0e, 0xf7];
+        assert_eq!(error(&bytes), "opcode");
     }
 
     #[test]
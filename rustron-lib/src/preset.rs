@@ -0,0 +1,643 @@
+use std::error;
+use std::fmt::{Display, Formatter};
+
+use crate::parser::neutron_message;
+use crate::protocol::DeviceId::Multicast;
+use crate::protocol::GlobalSetting::{
+    EnvRetriggerMode, LfoBlendMode, LfoKeySync, LfoMidiSync, LfoOneShot, LfoRetrigger,
+    Osc1Autoglide, Osc1BlendMode, Osc1Range, Osc1TunePotBypass, Osc2Autoglide, Osc2BlendMode,
+    Osc2KeyTrack, Osc2Range, Osc2TunePotBypass, OscSync, ParaphonicMode, VcfKeyTracking,
+    VcfModSource,
+};
+use crate::protocol::NeutronMessage::{GlobalSettingUpdate, SetGlobalSetting, StateDump};
+use crate::protocol::{
+    AutoglideSemitones, BlendMode, GlobalSetting, KeyTrackMode, ModSource, NeutronMessage,
+    OscRange, RetriggerMode, ToggleOption, SYSEX_EOX, SYSEX_MESSAGE_START,
+};
+use crate::setting::{SettingId, ValueType};
+
+/// A set of global settings, at most one value per setting.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Preset {
+    pub settings: Vec<GlobalSetting>,
+    pub sheet: PatchSheet,
+}
+
+/// The manufacturer id for non-commercial use followed by a tag, starting the messages that store
+/// a patch sheet in a `.syx` file. Devices ignore them, as they aren't theirs.
+const SHEET_HEADER: [u8; 5] = [SYSEX_MESSAGE_START, 0x7d, b'R', b'S', b'T'];
+const SHEET_NOTE: u8 = 0x01;
+const SHEET_CABLE: u8 = 0x02;
+
+/// What is needed to recreate a patch besides its settings: notes, e.g. about the front panel
+/// knobs, and the patch cables.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PatchSheet {
+    pub notes: Vec<String>,
+    pub cables: Vec<Cable>,
+}
+
+/// A patch cable between two jacks, by their names on the front panel.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cable {
+    pub from: String,
+    pub to: String,
+}
+
+impl Cable {
+    /// Parses `from -> to`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustron_lib::preset::Cable;
+    ///
+    /// let cable = Cable::parse("LFO -> VCF cutoff").unwrap();
+    /// assert_eq!(cable.to, "VCF cutoff");
+    /// assert_eq!(Cable::parse("LFO"), None);
+    /// ```
+    pub fn parse(text: &str) -> Option<Cable> {
+        let (from, to) = text.split_once("->")?;
+        let (from, to) = (from.trim(), to.trim());
+        if from.is_empty() || to.is_empty() {
+            return None;
+        }
+        Some(Cable {
+            from: from.to_string(),
+            to: to.to_string(),
+        })
+    }
+}
+
+impl Display for Cable {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} -> {}", self.from, self.to)
+    }
+}
+
+impl PatchSheet {
+    pub fn is_empty(&self) -> bool {
+        self.notes.is_empty() && self.cables.is_empty()
+    }
+
+    /// Appends one SysEx message per note and cable. Text is stored as 7 bit ASCII, other
+    /// characters become `?`.
+    fn write_to(&self, bytes: &mut Vec<u8>) {
+        let entries = self
+            .notes
+            .iter()
+            .map(|note| (SHEET_NOTE, note.clone()))
+            .chain(
+                self.cables
+                    .iter()
+                    .map(|cable| (SHEET_CABLE, cable.to_string())),
+            );
+        for (kind, text) in entries {
+            bytes.extend_from_slice(&SHEET_HEADER);
+            bytes.push(kind);
+            bytes.extend(text.chars().map(|c| {
+                if c.is_ascii() && !c.is_ascii_control() {
+                    c as u8
+                } else {
+                    b'?'
+                }
+            }));
+            bytes.push(SYSEX_EOX);
+        }
+    }
+
+    /// Adds the note or cable in `message`, if it is one.
+    fn read(&mut self, message: &[u8]) {
+        let body = match message.strip_prefix(&SHEET_HEADER[..]) {
+            Some(body) => body,
+            None => return,
+        };
+        let text = match body.get(1..body.len().saturating_sub(1)) {
+            Some(text) => String::from_utf8_lossy(text).into_owned(),
+            None => return,
+        };
+        match body[0] {
+            SHEET_NOTE => self.notes.push(text),
+            SHEET_CABLE => self.cables.extend(Cable::parse(&text)),
+            _ => {}
+        }
+    }
+}
+
+impl Preset {
+    pub fn new() -> Preset {
+        Default::default()
+    }
+
+    /// A preset of `settings`, later values replacing earlier ones of the same setting.
+    pub fn from_settings(settings: &[GlobalSetting]) -> Preset {
+        let mut preset = Preset::new();
+        for global_setting in settings {
+            preset.set(*global_setting);
+        }
+        preset
+    }
+
+    /// Adds the setting, replacing any previous value of the same setting.
+    pub fn set(&mut self, global_setting: GlobalSetting) {
+        let key = global_setting.key();
+        match self.settings.iter_mut().find(|s| s.key() == key) {
+            Some(existing) => *existing = global_setting,
+            None => self.settings.push(global_setting),
+        }
+    }
+
+    /// The value of the setting with `key`, if it's part of the preset.
+    pub fn get(&self, key: &[u8]) -> Option<GlobalSetting> {
+        self.settings.iter().find(|s| s.key() == key).cloned()
+    }
+
+    /// The settings that differ between the presets, as pairs of the value in `self` and the
+    /// value in `other`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustron_lib::preset::Preset;
+    /// use rustron_lib::protocol::GlobalSetting::{OscSync, ParaphonicMode};
+    /// use rustron_lib::protocol::ToggleOption::{Off, On};
+    ///
+    /// let mut a = Preset::new();
+    /// a.set(ParaphonicMode(On));
+    /// a.set(OscSync(On));
+    /// let mut b = Preset::new();
+    /// b.set(ParaphonicMode(Off));
+    /// b.set(OscSync(On));
+    /// assert_eq!(a.diff(&b), vec![(Some(ParaphonicMode(On)), Some(ParaphonicMode(Off)))]);
+    /// ```
+    pub fn diff(&self, other: &Preset) -> Vec<(Option<GlobalSetting>, Option<GlobalSetting>)> {
+        let mut differences: Vec<(Option<GlobalSetting>, Option<GlobalSetting>)> = self
+            .settings
+            .iter()
+            .map(|s| (Some(*s), other.get(&s.key())))
+            .filter(|(a, b)| a != b)
+            .collect();
+        differences.extend(
+            other
+                .settings
+                .iter()
+                .filter(|s| self.get(&s.key()).is_none())
+                .map(|s| (None, Some(*s))),
+        );
+        differences
+    }
+}
+
+/// The oscillator, LFO and envelope settings as the Neutron ships. MIDI channel, poly chain and
+/// other setup related settings are left out so loading it doesn't break a working setup.
+pub fn factory_defaults() -> Preset {
+    Preset::from_settings(&[
+        ParaphonicMode(ToggleOption::Off),
+        OscSync(ToggleOption::Off),
+        Osc1BlendMode(BlendMode::Switch),
+        Osc2BlendMode(BlendMode::Switch),
+        Osc1TunePotBypass(ToggleOption::Off),
+        Osc2TunePotBypass(ToggleOption::Off),
+        Osc1Range(OscRange::Eight),
+        Osc2Range(OscRange::Eight),
+        Osc2KeyTrack(KeyTrackMode::Track),
+        Osc1Autoglide(AutoglideSemitones::Zero),
+        Osc2Autoglide(AutoglideSemitones::Zero),
+        LfoBlendMode(BlendMode::Switch),
+        LfoKeySync(ToggleOption::Off),
+        LfoOneShot(ToggleOption::Off),
+        LfoRetrigger(ToggleOption::Off),
+        LfoMidiSync(ToggleOption::Off),
+        VcfKeyTracking(ToggleOption::Off),
+        VcfModSource(ModSource::Off),
+        EnvRetriggerMode(RetriggerMode::Staccato),
+    ])
+}
+
+/// The built in templates by name, each a complete starting point based on the factory
+/// defaults.
+///
+/// # Example
+///
+/// ```rust
+/// use rustron_lib::preset::templates;
+/// use rustron_lib::protocol::GlobalSetting::ParaphonicMode;
+/// use rustron_lib::protocol::ToggleOption::On;
+///
+/// let (_, duo) = templates().into_iter().find(|(name, _)| *name == "paraphonic duo").unwrap();
+/// assert_eq!(duo.get(&ParaphonicMode(On).key()), Some(ParaphonicMode(On)));
+/// ```
+pub fn templates() -> Vec<(&'static str, Preset)> {
+    let mut duo = factory_defaults();
+    for global_setting in &[
+        ParaphonicMode(ToggleOption::On),
+        Osc2Range(OscRange::Sixteen),
+        EnvRetriggerMode(RetriggerMode::Legato),
+    ] {
+        duo.set(*global_setting);
+    }
+
+    let mut bass = factory_defaults();
+    for global_setting in &[
+        Osc1Range(OscRange::Sixteen),
+        Osc2Range(OscRange::ThirtyTwo),
+        VcfKeyTracking(ToggleOption::On),
+    ] {
+        bass.set(*global_setting);
+    }
+
+    vec![
+        ("factory defaults", factory_defaults()),
+        ("paraphonic duo", duo),
+        ("mono bass", bass),
+    ]
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SyxError {
+    /// A SysEx message starting at the offset is missing its end byte
+    Unterminated(usize),
+    /// Data outside of a SysEx message at the offset
+    UnexpectedByte(usize),
+}
+
+impl Display for SyxError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SyxError::Unterminated(offset) => {
+                write!(f, "unterminated SysEx message at offset {}", offset)
+            }
+            SyxError::UnexpectedByte(offset) => write!(f, "unexpected byte at offset {}", offset),
+        }
+    }
+}
+
+impl error::Error for SyxError {}
+
+/// Creates a standard `.syx` file, i.e. the concatenated SysEx messages, with one
+/// `SetGlobalSetting` message per setting of the preset, followed by the patch sheet.
+///
+/// # Example
+///
+/// ```rust
+/// use rustron_lib::preset::{from_syx, to_syx, Preset};
+/// use rustron_lib::protocol::GlobalSetting::ParaphonicMode;
+/// use rustron_lib::protocol::ToggleOption::On;
+///
+/// let mut preset = Preset::new();
+/// preset.set(ParaphonicMode(On));
+/// assert_eq!(from_syx(to_syx(&preset).as_slice()), Ok(preset));
+/// ```
+pub fn to_syx(preset: &Preset) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(preset.settings.len() * NeutronMessage::MAX_LEN);
+    for global_setting in &preset.settings {
+        SetGlobalSetting(Multicast, *global_setting).write_to(&mut bytes);
+    }
+    preset.sheet.write_to(&mut bytes);
+    bytes
+}
+
+/// Reads the global settings and the patch sheet from a `.syx` file. Both `SetGlobalSetting` and
+/// `GlobalSettingUpdate` messages are accepted, any other SysEx messages are skipped.
+pub fn from_syx(bytes: &[u8]) -> Result<Preset, SyxError> {
+    let mut preset = Preset::new();
+    for message in sysex_messages(bytes)? {
+        match neutron_message(message) {
+            Ok((_, SetGlobalSetting(_, s))) | Ok((_, GlobalSettingUpdate(_, s))) => preset.set(s),
+            _ => preset.sheet.read(message),
+        }
+    }
+    Ok(preset)
+}
+
+/// Reads the global settings from SysEx dumps saved by the official Neutron app. In addition to
+/// what [`from_syx`] accepts, the state dumps the Neutron sends the app are decoded, as far as
+/// their layout is known.
+pub fn from_app_dump(bytes: &[u8]) -> Result<Preset, SyxError> {
+    let mut preset = Preset::new();
+    for message in sysex_messages(bytes)? {
+        match neutron_message(message) {
+            Ok((_, SetGlobalSetting(_, s))) | Ok((_, GlobalSettingUpdate(_, s))) => preset.set(s),
+            Ok((_, StateDump(_, payload))) => {
+                for s in from_state_dump(&payload) {
+                    preset.set(s)
+                }
+            }
+            _ => preset.sheet.read(message),
+        }
+    }
+    Ok(preset)
+}
+
+/// Decodes the known settings of a state dump payload, as laid out in [`STATE_DUMP_BITS`].
+pub fn from_state_dump(payload: &[u8]) -> Vec<GlobalSetting> {
+    STATE_DUMP_BITS
+        .iter()
+        .filter_map(|bit| {
+            let byte = payload.get(bit.byte)?;
+            bit.setting.with_raw_value(i32::from(byte & bit.mask != 0))
+        })
+        .collect()
+}
+
+/// The offset of the payload in a state dump message, after the header, device id, opcode and
+/// protocol version.
+pub const STATE_DUMP_OFFSET: usize = 8;
+
+/// An on/off setting stored in one bit of the state dump payload.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct StateBit {
+    /// Index into the payload
+    pub byte: usize,
+    pub mask: u8,
+    pub setting: SettingId,
+}
+
+/// The known bits of the state dump payload, found by toggling settings and comparing dumps, see
+/// the captures in `tests/fixtures`. Decoding, encoding and diffing dumps all go by this table.
+pub const STATE_DUMP_BITS: [StateBit; 2] = [
+    StateBit {
+        byte: 0,
+        mask: 0x10,
+        setting: SettingId::OscSync,
+    },
+    StateBit {
+        byte: 7,
+        mask: 0x01,
+        setting: SettingId::ParaphonicMode,
+    },
+];
+
+/// A byte that differs between two state dump messages.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DumpDifference {
+    /// Offset into the messages
+    pub offset: usize,
+    /// The byte in the first message, `None` if it is shorter
+    pub before: Option<u8>,
+    pub after: Option<u8>,
+    /// The known settings stored in the changed bits
+    pub settings: Vec<SettingId>,
+}
+
+impl DumpDifference {
+    /// The bits that changed, all of them if one message is shorter.
+    pub fn changed_bits(&self) -> u8 {
+        match (self.before, self.after) {
+            (Some(before), Some(after)) => before ^ after,
+            _ => 0xff,
+        }
+    }
+}
+
+impl Display for DumpDifference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let byte = |byte: Option<u8>| byte.map_or(String::from("--"), |b| format!("{:02x}", b));
+        write!(f, "byte {}", self.offset)?;
+        if let Some(index) = self.offset.checked_sub(STATE_DUMP_OFFSET) {
+            write!(f, " (state byte {})", index)?;
+        }
+        write!(
+            f,
+            ": {} -> {}, bits {:08b}",
+            byte(self.before),
+            byte(self.after),
+            self.changed_bits()
+        )?;
+        for (i, setting) in self.settings.iter().enumerate() {
+            write!(f, "{}{}", if i == 0 { ": " } else { ", " }, setting.id())?;
+        }
+        Ok(())
+    }
+}
+
+/// The bytes that differ between two state dump messages, with the known settings they hold.
+///
+/// # Example
+///
+/// ```rust
+/// use rustron_lib::preset::diff_state_dumps;
+/// use rustron_lib::setting::SettingId;
+///
+/// let before = [0xf0, 0x00, 0x20, 0x32, 0x28, 0x00, 0x06, 0x01, 0x6b, 0x02, 0xf7];
+/// let after = [0xf0, 0x00, 0x20, 0x32, 0x28, 0x00, 0x06, 0x01, 0x7b, 0x03, 0xf7];
+/// let differences = diff_state_dumps(&before, &after);
+/// assert_eq!(differences.len(), 2);
+/// assert_eq!(differences[0].settings, vec![SettingId::OscSync]);
+/// assert_eq!(
+///     differences[0].to_string(),
+///     "byte 8 (state byte 0): 6b -> 7b, bits 00010000: osc.sync"
+/// );
+/// assert_eq!(differences[1].to_string(), "byte 9 (state byte 1): 02 -> 03, bits 00000001");
+/// ```
+pub fn diff_state_dumps(before: &[u8], after: &[u8]) -> Vec<DumpDifference> {
+    (0..before.len().max(after.len()))
+        .filter_map(|offset| {
+            let (b, a) = (before.get(offset).copied(), after.get(offset).copied());
+            if a == b {
+                return None;
+            }
+            let mut difference = DumpDifference {
+                offset,
+                before: b,
+                after: a,
+                settings: Vec::new(),
+            };
+            let changed = difference.changed_bits();
+            difference.settings = STATE_DUMP_BITS
+                .iter()
+                .filter(|bit| {
+                    offset.checked_sub(STATE_DUMP_OFFSET) == Some(bit.byte)
+                        && changed & bit.mask != 0
+                })
+                .map(|bit| bit.setting)
+                .collect();
+            Some(difference)
+        })
+        .collect()
+}
+
+/// Writes the known settings of `preset` into a copy of the state dump payload `base`, the
+/// inverse of [`from_state_dump`]. Unknown bits are kept from `base`.
+///
+/// # Example
+///
+/// ```rust
+/// use rustron_lib::preset::{from_state_dump, to_state_dump, Preset};
+/// use rustron_lib::protocol::GlobalSetting::{OscSync, ParaphonicMode};
+/// use rustron_lib::protocol::ToggleOption::{Off, On};
+///
+/// let preset = Preset::from_settings(&[OscSync(On), ParaphonicMode(Off)]);
+/// let payload = to_state_dump(&preset, &[0x00; 8]);
+/// assert_eq!(from_state_dump(&payload), preset.settings);
+/// ```
+pub fn to_state_dump(preset: &Preset, base: &[u8]) -> Vec<u8> {
+    let mut payload = base.to_vec();
+    let mut set_bit = |index: usize, mask: u8, on: bool| {
+        if let Some(byte) = payload.get_mut(index) {
+            if on {
+                *byte |= mask;
+            } else {
+                *byte &= !mask;
+            }
+        }
+    };
+    for global_setting in &preset.settings {
+        if let Some(bit) = STATE_DUMP_BITS
+            .iter()
+            .find(|bit| bit.setting == global_setting.id())
+        {
+            set_bit(bit.byte, bit.mask, global_setting.raw_value() == Some(1));
+        }
+    }
+    payload
+}
+
+/// The steps of gradually changing `from` into `to`, each holding the settings that change in it.
+/// Settings with a continuous value, the depths and autoglide, move towards their target in every
+/// step, all others switch in the last one. Settings `to` doesn't have are left alone.
+///
+/// ```
+/// use rustron_lib::preset::{morph, Preset};
+/// use rustron_lib::protocol::GlobalSetting::{LfoDepth, OscSync};
+/// use rustron_lib::protocol::Percent;
+/// use rustron_lib::protocol::ToggleOption::{Off, On};
+///
+/// let from = Preset::from_settings(&[LfoDepth(Percent::from_byte(0)), OscSync(Off)]);
+/// let to = Preset::from_settings(&[LfoDepth(Percent::from_byte(63)), OscSync(On)]);
+/// assert_eq!(
+///     morph(&from, &to, 3),
+///     vec![
+///         vec![LfoDepth(Percent::from_byte(21))],
+///         vec![LfoDepth(Percent::from_byte(42))],
+///         vec![LfoDepth(Percent::from_byte(63)), OscSync(On)],
+///     ]
+/// );
+/// ```
+pub fn morph(from: &Preset, to: &Preset, steps: u32) -> Vec<Vec<GlobalSetting>> {
+    let steps = steps.max(1) as i32;
+    let mut morphed = vec![Vec::new(); steps as usize];
+    for (start, end) in from.diff(to) {
+        let end = match end {
+            Some(end) => end,
+            None => continue,
+        };
+        let id = end.id();
+        let continuous = matches!(id.value_type(), ValueType::Percent | ValueType::Autoglide);
+        match (start.and_then(|s| s.raw_value()), end.raw_value()) {
+            (Some(start), Some(end)) if continuous => {
+                let mut previous = start;
+                for step in 1..=steps {
+                    let raw = start + (end - start) * step / steps;
+                    if raw != previous {
+                        morphed[step as usize - 1].extend(id.with_raw_value(raw));
+                        previous = raw;
+                    }
+                }
+            }
+            _ => morphed[steps as usize - 1].push(end),
+        }
+    }
+    morphed
+}
+
+pub(crate) fn sysex_messages(bytes: &[u8]) -> Result<Vec<&[u8]>, SyxError> {
+    let mut messages = Vec::new();
+    let mut offset = 0;
+    while offset < bytes.len() {
+        if bytes[offset] != SYSEX_MESSAGE_START {
+            return Err(SyxError::UnexpectedByte(offset));
+        }
+        let length = bytes[offset..]
+            .iter()
+            .position(|b| *b == SYSEX_EOX)
+            .ok_or(SyxError::Unterminated(offset))?
+            + 1;
+        messages.push(&bytes[offset..offset + length]);
+        offset += length;
+    }
+    Ok(messages)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::preset::{
+        from_app_dump, from_syx, templates, to_syx, Cable, PatchSheet, Preset, SyxError,
+    };
+    use crate::protocol::Channel::One;
+    use crate::protocol::DeviceId::Channel;
+    use crate::protocol::GlobalSetting::{OscSync, ParaphonicMode};
+    use crate::protocol::NeutronMessage::StateDump;
+    use crate::protocol::ToggleOption::{Off, On};
+
+    #[test]
+    fn set_replaces_previous_value() {
+        let mut preset = Preset::new();
+        preset.set(ParaphonicMode(On));
+        preset.set(OscSync(On));
+        preset.set(ParaphonicMode(Off));
+        assert_eq!(preset.settings, vec![ParaphonicMode(Off), OscSync(On)]);
+    }
+
+    #[test]
+    fn templates_survive_a_syx_round_trip() {
+        for (name, template) in templates() {
+            assert_eq!(
+                from_syx(&to_syx(&template)).as_ref(),
+                Ok(&template),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn patch_sheets_are_stored_in_the_syx() {
+        let mut preset = Preset::new();
+        preset.set(OscSync(On));
+        preset.sheet = PatchSheet {
+            notes: vec![String::from("Cutoff at noon")],
+            cables: vec![Cable::parse("LFO -> VCF").unwrap()],
+        };
+        let syx = to_syx(&preset);
+        assert_eq!(from_syx(&syx), Ok(preset.clone()));
+        assert_eq!(from_app_dump(&syx), Ok(preset));
+
+        let mut preset = Preset::new();
+        preset.sheet.notes.push(String::from("Résumé\n"));
+        assert_eq!(from_syx(&to_syx(&preset)).unwrap().sheet.notes, ["R?sum??"]);
+    }
+
+    #[test]
+    fn syx_skips_foreign_messages() {
+        let mut preset = Preset::new();
+        preset.set(OscSync(Off));
+        let mut syx = vec![0xf0, 0x7e, 0x00, 0x06, 0x01, 0xf7];
+        syx.extend(to_syx(&preset));
+        assert_eq!(from_syx(syx.as_slice()), Ok(preset));
+    }
+
+    #[test]
+    fn malformed_syx_is_rejected() {
+        assert_eq!(from_syx(&[0xf0, 0x00]), Err(SyxError::Unterminated(0)));
+        assert_eq!(
+            from_syx(&[0xf0, 0xf7, 0x01]),
+            Err(SyxError::UnexpectedByte(2))
+        );
+    }
+
+    #[test]
+    fn app_dump_decodes_state() {
+        let dump = StateDump(
+            Channel(One),
+            vec![
+                0x7b, 0x02, 0x00, 0x00, 0x02, 0x31, 0x08, 0x58, 0x46, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00, 0x7f, 0x0f, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
+            ],
+        );
+        assert_eq!(
+            from_app_dump(dump.as_bytes().as_slice()).unwrap().settings,
+            vec![OscSync(On), ParaphonicMode(Off)]
+        );
+    }
+}
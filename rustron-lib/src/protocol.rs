@@ -1,7 +1,13 @@
+use std::convert::TryFrom;
 use std::fmt::{Debug, Display};
+use std::str::FromStr;
 
+#[cfg(feature = "iter")]
 use strum_macros::EnumIter;
 
+use crate::device::{BehringerDevice, Command, Neutron};
+use crate::parser;
+
 pub const SYSEX_MESSAGE_START: u8 = 0xf0;
 pub const SYSEX_EOX: u8 = 0xf7;
 pub const BEHRINGER_MANUFACTURER: [u8; 3] = [0x00, 0x20, 0x32];
@@ -17,6 +23,64 @@ pub const MAYBE_STATIC: [u8; 3] = [0x28, 0x7f, 0x0a];
 
 pub const COMMS_PROTOCOL_V1: u8 = 0x01;
 
+/// Which kind of device a message is for or from, going by its SysEx header.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DeviceClass {
+    Neutron,
+    /// A Behringer device with another device id than the Neutron's
+    OtherBehringer,
+    /// SysEx of another manufacturer
+    OtherSysEx,
+    /// Anything but SysEx, e.g. notes and controllers
+    NotSysEx,
+}
+
+impl Display for DeviceClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeviceClass::Neutron => write!(f, "Neutron"),
+            DeviceClass::OtherBehringer => write!(f, "other Behringer"),
+            DeviceClass::OtherSysEx => write!(f, "other SysEx"),
+            DeviceClass::NotSysEx => write!(f, "not SysEx"),
+        }
+    }
+}
+
+/// Whether `bytes` is a Behringer SysEx message with a device id.
+pub fn is_behringer_packet(bytes: &[u8]) -> bool {
+    bytes.len() > NEUTRON_MESSAGE_HEADER.len() - 1
+        && bytes[0] == SYSEX_MESSAGE_START
+        && bytes[1..4] == BEHRINGER_MANUFACTURER
+}
+
+/// # Example
+///
+/// ```rust
+/// use rustron_lib::protocol::{classify, DeviceClass};
+///
+/// assert_eq!(classify(&[0xf0, 0x00, 0x20, 0x32, 0x28, 0x00, 0xf7]), DeviceClass::Neutron);
+/// // A Crave
+/// assert_eq!(classify(&[0xf0, 0x00, 0x20, 0x32, 0x20, 0x00, 0xf7]), DeviceClass::OtherBehringer);
+/// assert_eq!(classify(&[0xf0, 0x41, 0x10, 0xf7]), DeviceClass::OtherSysEx);
+/// assert_eq!(classify(&[0x90, 0x3c, 0x40]), DeviceClass::NotSysEx);
+/// ```
+pub fn classify(bytes: &[u8]) -> DeviceClass {
+    if is_behringer_packet(bytes) {
+        if bytes[4] == NEUTRON_DEVICE {
+            DeviceClass::Neutron
+        } else {
+            DeviceClass::OtherBehringer
+        }
+    } else if bytes.first() == Some(&SYSEX_MESSAGE_START) {
+        DeviceClass::OtherSysEx
+    } else {
+        DeviceClass::NotSysEx
+    }
+}
+
+/// Shows a Behringer message as its device id, or `N` for the Neutron, and the rest in hex.
+/// `bytes` has to be a Behringer packet, see `is_behringer_packet`.
+#[cfg(feature = "hex")]
 pub fn format_behringer_packet(bytes: &[u8]) -> String {
     let device = bytes[4];
     let mut buffer = String::new();
@@ -51,6 +115,7 @@ pub trait ByteBuilder {
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "iter", derive(EnumIter))]
 pub enum ToggleOption {
     On,
     Off,
@@ -128,7 +193,8 @@ impl Percent {
     }
 }
 
-#[derive(Copy, Clone, Debug, EnumIter, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "iter", derive(EnumIter))]
 pub enum AutoglideSemitones {
     MinusTwelve,
     MinusEleven,
@@ -187,9 +253,94 @@ impl AutoglideSemitones {
             AutoglideSemitones::PlusTwelve => 0x18,
         }
     }
+
+    /// The glide in semitones, -12 - 12
+    pub fn semitones(self) -> i8 {
+        self.as_byte() as i8 - 12
+    }
+
+    pub fn from_semitones(semitones: i8) -> Option<AutoglideSemitones> {
+        let byte = u8::try_from(semitones.checked_add(12)?).ok()?;
+        parser::autoglide_semitones::<parser::Expected>(&[byte])
+            .ok()
+            .map(|(_, s)| s)
+    }
+}
+
+const INTERVAL_NAMES: [&str; 13] = [
+    "unison", "m2", "M2", "m3", "M3", "P4", "TT", "P5", "m6", "M6", "m7", "M7", "octave",
+];
+
+/// Formats the glide as a musical interval, e.g. `-P5`, `+m3` or `+octave`.
+impl Display for AutoglideSemitones {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let semitones = self.semitones();
+        let sign = match semitones {
+            0 => "",
+            s if s < 0 => "-",
+            _ => "+",
+        };
+        write!(
+            f,
+            "{}{}",
+            sign,
+            INTERVAL_NAMES[usize::from(semitones.unsigned_abs())]
+        )
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ParseAutoglideError(String);
+
+impl Display for ParseAutoglideError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid autoglide interval: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseAutoglideError {}
+
+/// Parses an interval as formatted by `Display`, or a number of semitones.
+///
+/// # Example
+///
+/// ```rust
+/// use rustron_lib::protocol::AutoglideSemitones;
+///
+/// assert_eq!("-P5".parse(), Ok(AutoglideSemitones::MinusSeven));
+/// assert_eq!("+7".parse(), Ok(AutoglideSemitones::PlusSeven));
+/// assert_eq!(AutoglideSemitones::PlusTwelve.to_string(), "+octave");
+/// ```
+impl FromStr for AutoglideSemitones {
+    type Err = ParseAutoglideError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || ParseAutoglideError(s.to_string());
+        let semitones = match s.parse::<i8>() {
+            Ok(semitones) => semitones,
+            Err(_) => {
+                let (negative, name) = match s.chars().next() {
+                    Some('-') => (true, &s[1..]),
+                    Some('+') => (false, &s[1..]),
+                    _ => (false, s),
+                };
+                let magnitude = INTERVAL_NAMES
+                    .iter()
+                    .position(|n| *n == name)
+                    .ok_or_else(error)? as i8;
+                if negative {
+                    -magnitude
+                } else {
+                    magnitude
+                }
+            }
+        };
+        AutoglideSemitones::from_semitones(semitones).ok_or_else(error)
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "iter", derive(EnumIter))]
 pub enum BlendMode {
     Switch,
     Blend,
@@ -205,6 +356,7 @@ impl BlendMode {
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "iter", derive(EnumIter))]
 pub enum OscRange {
     // Oscillator Pipe Lengths
     ThirtyTwo,
@@ -226,6 +378,7 @@ impl OscRange {
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "iter", derive(EnumIter))]
 pub enum KeyTrackMode {
     Track,
     Hold,
@@ -241,6 +394,7 @@ impl KeyTrackMode {
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "iter", derive(EnumIter))]
 pub enum LfoIndex {
     One,
     Two,
@@ -261,7 +415,8 @@ impl LfoIndex {
     }
 }
 
-#[derive(Copy, Clone, Debug, EnumIter, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "iter", derive(EnumIter))]
 pub enum LfoShape {
     Sine,
     Triangle,
@@ -283,7 +438,8 @@ impl LfoShape {
 }
 
 /// Lfo phase offset in degrees
-#[derive(Copy, Clone, Debug, EnumIter, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "iter", derive(EnumIter))]
 pub enum LfoPhaseOffset {
     Zero,
     FourtyFive,
@@ -308,9 +464,14 @@ impl LfoPhaseOffset {
             LfoPhaseOffset::ThreeHundredFifteen => 0x07,
         }
     }
+
+    pub fn as_degrees(self) -> u16 {
+        self.as_byte() as u16 * 45
+    }
 }
 
-#[derive(Copy, Clone, Debug, EnumIter, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "iter", derive(EnumIter))]
 pub enum ModSource {
     Off,
     AfterTouch,
@@ -329,7 +490,8 @@ impl ModSource {
     }
 }
 
-#[derive(Copy, Clone, Debug, EnumIter, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "iter", derive(EnumIter))]
 pub enum AssignOutOption {
     Osc1,
     Osc2,
@@ -351,6 +513,7 @@ impl AssignOutOption {
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "iter", derive(EnumIter))]
 pub enum RetriggerMode {
     Staccato,
     Legato,
@@ -399,6 +562,31 @@ pub enum GlobalSetting {
     EnvRetriggerMode(RetriggerMode),
 }
 
+impl GlobalSetting {
+    /// Identifies which setting this is, independent of its value.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustron_lib::protocol::GlobalSetting::Osc1Range;
+    /// use rustron_lib::protocol::OscRange::{Eight, Sixteen};
+    ///
+    /// assert_eq!(Osc1Range(Eight).key(), Osc1Range(Sixteen).key());
+    /// ```
+    pub fn key(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.append_to(&mut bytes);
+        // The value is always the last byte
+        bytes.pop();
+        bytes
+    }
+
+    /// The opcode of the setting, i.e. the first byte of the setting in a message.
+    pub fn opcode(&self) -> u8 {
+        self.key()[0]
+    }
+}
+
 impl ByteBuilder for GlobalSetting {
     fn append_to(&self, buffer: &mut Vec<u8>) {
         match self {
@@ -528,7 +716,8 @@ impl ByteBuilder for GlobalSetting {
     }
 }
 
-#[derive(Copy, Clone, Debug, EnumIter, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "iter", derive(EnumIter))]
 pub enum Channel {
     One,
     Two,
@@ -549,6 +738,25 @@ pub enum Channel {
 }
 
 impl Channel {
+    /// The channel number as shown on the device, 1-16.
+    pub fn number(self) -> u8 {
+        self.as_byte() + 1
+    }
+
+    /// The channel with the given number (1-16), if any.
+    ///
+    /// ```
+    /// use rustron_lib::protocol::Channel;
+    ///
+    /// assert_eq!(Channel::from_number(5), Some(Channel::Five));
+    /// assert_eq!(Channel::from_number(17), None);
+    /// ```
+    pub fn from_number(number: u8) -> Option<Channel> {
+        parser::channel::<parser::Expected>(&[number.checked_sub(1)?])
+            .ok()
+            .map(|(_, c)| c)
+    }
+
     fn as_byte(self) -> u8 {
         match self {
             Channel::One => 0x00,
@@ -586,7 +794,7 @@ impl DeviceId {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum NeutronMessage {
     SetGlobalSetting(DeviceId, GlobalSetting),
     RestoreGlobalSetting(DeviceId),
@@ -594,6 +802,11 @@ pub enum NeutronMessage {
     SoftwareVersionRequest(DeviceId),
     SoftwareVersionResponse(DeviceId, String),
     GlobalSettingUpdate(DeviceId, GlobalSetting),
+    /// Not in the documentation, sent periodically by the official app
+    StateRequest(DeviceId),
+    /// Not in the documentation, the response to a `StateRequest`. The payload seems to contain
+    /// the configuration state as a bitfield
+    StateDump(DeviceId, Vec<u8>),
 }
 
 impl Display for NeutronMessage {
@@ -603,45 +816,86 @@ impl Display for NeutronMessage {
 }
 
 impl NeutronMessage {
+    /// The length of the longest message with a fixed size, a `GlobalSettingUpdate` with a three
+    /// byte setting. `SoftwareVersionResponse` and `StateDump` add their payload on top of this.
+    pub const MAX_LEN: usize = NEUTRON_MESSAGE_HEADER.len() + 3 + 3 + 1;
+
     pub fn as_bytes(&self) -> Vec<u8> {
-        let mut bytes: Vec<u8> = Vec::new();
-        bytes.push(SYSEX_MESSAGE_START);
-        bytes.extend_from_slice(&BEHRINGER_MANUFACTURER);
-        bytes.push(NEUTRON_DEVICE);
+        let mut bytes = Vec::with_capacity(NeutronMessage::MAX_LEN);
+        self.write_to(&mut bytes);
+        bytes
+    }
+
+    /// Appends the message to `bytes`, so that one buffer can be reused for many messages.
+    ///
+    /// ```
+    /// use rustron_lib::protocol::DeviceId::Multicast;
+    /// use rustron_lib::protocol::NeutronMessage::{SoftwareVersionRequest, StateRequest};
+    ///
+    /// let mut buffer = Vec::new();
+    /// SoftwareVersionRequest(Multicast).write_to(&mut buffer);
+    /// StateRequest(Multicast).write_to(&mut buffer);
+    /// assert_eq!(
+    ///     buffer,
+    ///     [
+    ///         SoftwareVersionRequest(Multicast).as_bytes(),
+    ///         StateRequest(Multicast).as_bytes()
+    ///     ]
+    ///     .concat()
+    /// );
+    /// ```
+    pub fn write_to(&self, bytes: &mut Vec<u8>) {
+        self.write_for::<Neutron>(bytes)
+    }
+
+    /// Appends the message to `bytes` with the header and opcodes of device `D`.
+    pub fn write_for<D: BehringerDevice>(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&D::header());
+        bytes.push(self.device_id().as_byte());
+        bytes.extend_from_slice(D::opcode(self.command()));
         match self {
-            NeutronMessage::SetGlobalSetting(id, c) => {
-                bytes.push(id.as_byte());
-                bytes.push(0x0a);
-                c.append_to(&mut bytes);
-            }
-            NeutronMessage::RestoreGlobalSetting(id) => {
-                bytes.push(id.as_byte());
-                bytes.push(0x0b)
-            }
-            NeutronMessage::CalibrationModeCommand(id) => {
-                bytes.push(id.as_byte());
-                bytes.push(0x10);
-                // TODO
-            }
-            NeutronMessage::SoftwareVersionRequest(id) => {
-                bytes.push(id.as_byte());
-                bytes.push(0x73)
-            }
-            NeutronMessage::SoftwareVersionResponse(id, v) => {
-                bytes.push(id.as_byte());
-                bytes.push(0x74);
-                bytes.push(COMMS_PROTOCOL_V1);
-                bytes.extend_from_slice(v.as_bytes()); // TODO verify this
+            NeutronMessage::SetGlobalSetting(_, c) | NeutronMessage::GlobalSettingUpdate(_, c) => {
+                c.append_to(bytes)
             }
-            NeutronMessage::GlobalSettingUpdate(id, c) => {
-                bytes.push(id.as_byte());
-                bytes.push(0x5a);
-                bytes.push(COMMS_PROTOCOL_V1);
-                c.append_to(&mut bytes);
+            NeutronMessage::SoftwareVersionResponse(_, v) => {
+                bytes.extend_from_slice(v.as_bytes()); // TODO verify this
             }
+            NeutronMessage::StateDump(_, payload) => bytes.extend_from_slice(payload),
+            // TODO calibration mode arguments
+            NeutronMessage::RestoreGlobalSetting(_)
+            | NeutronMessage::CalibrationModeCommand(_)
+            | NeutronMessage::SoftwareVersionRequest(_)
+            | NeutronMessage::StateRequest(_) => {}
         }
         bytes.push(SYSEX_EOX);
-        bytes
+    }
+
+    /// The device id the message is addressed to or sent from.
+    pub fn device_id(&self) -> DeviceId {
+        match self {
+            NeutronMessage::SetGlobalSetting(id, _)
+            | NeutronMessage::RestoreGlobalSetting(id)
+            | NeutronMessage::CalibrationModeCommand(id)
+            | NeutronMessage::SoftwareVersionRequest(id)
+            | NeutronMessage::SoftwareVersionResponse(id, _)
+            | NeutronMessage::GlobalSettingUpdate(id, _)
+            | NeutronMessage::StateRequest(id)
+            | NeutronMessage::StateDump(id, _) => *id,
+        }
+    }
+
+    /// The command of the message, which decides its opcode.
+    pub fn command(&self) -> Command {
+        match self {
+            NeutronMessage::SetGlobalSetting(..) => Command::SetSetting,
+            NeutronMessage::RestoreGlobalSetting(_) => Command::RestoreSettings,
+            NeutronMessage::CalibrationModeCommand(_) => Command::CalibrationMode,
+            NeutronMessage::SoftwareVersionRequest(_) => Command::VersionRequest,
+            NeutronMessage::SoftwareVersionResponse(..) => Command::VersionResponse,
+            NeutronMessage::GlobalSettingUpdate(..) => Command::SettingUpdate,
+            NeutronMessage::StateRequest(_) => Command::StateRequest,
+            NeutronMessage::StateDump(..) => Command::StateDump,
+        }
     }
 }
 
@@ -755,15 +1009,8 @@ pub fn maybe_request_state() -> Vec<u8> {
     wrapped_message
 }
 
-// F0 00 20 32 28 00 06 01  6B 02 00 00 02 31 08 59  46 00 00 00 00 00 00 00  7F 0F 00 00 00 00 00 01  F7
-
-// TEST
-// OSC SYNC OFF, PARAPHONIC MODE OFF
-// F0 00 20 32 28 00 06 01  6B 02 00 00 02 31 08 58  46 00 00 00 00 00 00 00  7F 0F 00 00 00 00 00 01  F7
-// OSC SYNC ON              |
-// F0 00 20 32 28 00 06 01  7B 02 00 00 02 31 08 58  46 00 00 00 00 00 00 00  7F 0F 00 00 00 00 00 01  F7
-// PARAPHONIC MODE ON                             |
-// F0 00 20 32 28 00 06 01  7B 02 00 00 02 31 08 59  46 00 00 00 00 00 00 00  7F 0F 00 00 00 00 00 01  F7
+// Captured answers, including dumps taken while toggling osc sync and paraphonic mode, are in
+// tests/fixtures/state_dump*.hex
 
 // Maybe firmware version?
 // Only sent once when first connecting to the neutron
@@ -780,7 +1027,7 @@ pub fn maybe_request_state2() -> Vec<u8> {
     wrapped_message.push(SYSEX_EOX);
     wrapped_message
 }
-// Sample response: F0 00 20 32 28 00 74 01  32 2E 30 2E 32 F7
+// Sample response: tests/fixtures/software_version_response.hex
 
 // Possibly tuner values plus other stuff:
 // Header:
@@ -793,16 +1040,6 @@ pub fn maybe_request_state2() -> Vec<u8> {
 // within the payloads, the first hex changes often,
 // the second sometimes, and the last one sometimes
 
-// unknown stuff
-// possibly settings state?
-// 28 00 06 01
-// 00 01 00 00 02 31 08 58
-// 46 00 00 00 00 00 00 00
-// 7f 2f 00 00 00 00 00 01
-//
-// Seems like this is an answer to the app sending a message with '28 7f 05'
+// A state dump with bytes that aren't understood yet: tests/fixtures/state_dump_unknown.hex
 
-// Probably confirmation that OSC 1 Blend mode was set to SWITCH (28 7f 0a 20 01)
-// 28 00 5a 01 20 01
-// Probably confirmation that OSC 1 Blend mode was set to BLEND (28 7f 0a 20 00)
-// 28 00 5a 01 20 00
+// Confirmations of setting OSC 1 Blend mode: tests/fixtures/setting_update_osc1_blend_*.hex
@@ -0,0 +1,827 @@
+use std::error;
+use std::fmt::{Display, Formatter};
+
+use crate::protocol::{
+    AssignOutOption, AutoglideSemitones, BlendMode, Channel, GlobalSetting, KeyTrackMode, LfoIndex,
+    LfoPhaseOffset, LfoShape, ModSource, OscRange, Percent, RetriggerMode, ToggleOption,
+};
+
+#[derive(Debug, PartialEq)]
+pub enum SettingError {
+    /// No setting has the name
+    UnknownSetting(String),
+    /// The value isn't valid for the setting
+    InvalidValue {
+        setting: String,
+        value: String,
+        expected: String,
+    },
+}
+
+impl Display for SettingError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SettingError::UnknownSetting(name) => write!(f, "unknown setting {}", name),
+            SettingError::InvalidValue {
+                setting,
+                value,
+                expected,
+            } => write!(
+                f,
+                "invalid value {} for {}, expected {}",
+                value, setting, expected
+            ),
+        }
+    }
+}
+
+impl error::Error for SettingError {}
+
+const TOGGLE: [(&str, ToggleOption); 6] = [
+    ("on", ToggleOption::On),
+    ("off", ToggleOption::Off),
+    ("true", ToggleOption::On),
+    ("false", ToggleOption::Off),
+    ("1", ToggleOption::On),
+    ("0", ToggleOption::Off),
+];
+const BLEND: [(&str, BlendMode); 2] = [("switch", BlendMode::Switch), ("blend", BlendMode::Blend)];
+const RANGE: [(&str, OscRange); 4] = [
+    ("32", OscRange::ThirtyTwo),
+    ("16", OscRange::Sixteen),
+    ("8", OscRange::Eight),
+    ("10oct", OscRange::PlusMinusTen),
+];
+const KEY_TRACK: [(&str, KeyTrackMode); 2] =
+    [("track", KeyTrackMode::Track), ("hold", KeyTrackMode::Hold)];
+const LFO_INDEX: [(&str, LfoIndex); 5] = [
+    ("1", LfoIndex::One),
+    ("2", LfoIndex::Two),
+    ("3", LfoIndex::Three),
+    ("4", LfoIndex::Four),
+    ("5", LfoIndex::Five),
+];
+const SHAPE: [(&str, LfoShape); 5] = [
+    ("sine", LfoShape::Sine),
+    ("triangle", LfoShape::Triangle),
+    ("falling_saw", LfoShape::FallingSaw),
+    ("square", LfoShape::Square),
+    ("rising_saw", LfoShape::RisingSaw),
+];
+const PHASE: [(&str, LfoPhaseOffset); 8] = [
+    ("0", LfoPhaseOffset::Zero),
+    ("45", LfoPhaseOffset::FourtyFive),
+    ("90", LfoPhaseOffset::Ninety),
+    ("135", LfoPhaseOffset::HundredThirtyFive),
+    ("180", LfoPhaseOffset::HundredEighty),
+    ("225", LfoPhaseOffset::TwoHundredTwentyFive),
+    ("270", LfoPhaseOffset::TwoHundredSeventy),
+    ("315", LfoPhaseOffset::ThreeHundredFifteen),
+];
+const MOD_SOURCE: [(&str, ModSource); 4] = [
+    ("off", ModSource::Off),
+    ("aftertouch", ModSource::AfterTouch),
+    ("mod_wheel", ModSource::ModWheel),
+    ("velocity", ModSource::Velocity),
+];
+const ASSIGN_OUT: [(&str, AssignOutOption); 5] = [
+    ("osc1", AssignOutOption::Osc1),
+    ("osc2", AssignOutOption::Osc2),
+    ("velocity", AssignOutOption::Velocity),
+    ("mod_wheel", AssignOutOption::ModWheel),
+    ("aftertouch", AssignOutOption::AfterTouch),
+];
+const RETRIGGER: [(&str, RetriggerMode); 2] = [
+    ("staccato", RetriggerMode::Staccato),
+    ("legato", RetriggerMode::Legato),
+];
+
+/// Identifies a setting independent of its value, with a stable string id used by all text
+/// based interfaces.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SettingId {
+    ParaphonicMode,
+    OscSync,
+    Osc1BlendMode,
+    Osc2BlendMode,
+    Osc1TunePotBypass,
+    Osc2TunePotBypass,
+    Osc1Range,
+    Osc2Range,
+    Osc2KeyTrack,
+    Osc1Autoglide,
+    Osc2Autoglide,
+    LfoBlendMode,
+    LfoKeySync,
+    LfoOneShot,
+    LfoRetrigger,
+    LfoMidiSync,
+    LfoDepth,
+    LfoShapeOrder(LfoIndex),
+    LfoShapePhase(LfoIndex),
+    LfoResetOrder,
+    VcfKeyTracking,
+    VcfModDepth,
+    VcfModSource,
+    MidiChannel,
+    DisableMidiDips,
+    PolyChainMode,
+    KeyRangeMute,
+    KeyRangeReset,
+    AssignOut,
+    EnvRetriggerMode,
+}
+
+/// The kind of value a setting takes.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValueType {
+    Toggle,
+    /// One of the named options
+    Choice(Vec<&'static str>),
+    /// A percentage 0-100
+    Percent,
+    /// An interval, see `AutoglideSemitones`
+    Autoglide,
+    /// A MIDI channel 1-16
+    Channel,
+    /// No value, sending the setting triggers an action
+    Action,
+}
+
+/// The raw values a numeric view of a setting can take, from `min` to `max` inclusive in steps of
+/// `step`, or `coarse_step` for bigger jumps. Choices and toggles are numbered by their position.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ValueRange {
+    pub min: i16,
+    pub max: i16,
+    pub step: i16,
+    pub coarse_step: i16,
+}
+
+impl ValueRange {
+    pub fn clamp(self, value: i32) -> i16 {
+        value.clamp(i32::from(self.min), i32::from(self.max)) as i16
+    }
+
+    /// Maps `value` of `0..=max_in`, e.g. a 7 bit controller value, linearly onto the range.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustron_lib::setting::SettingId;
+    ///
+    /// let range = SettingId::LfoDepth.range().unwrap();
+    /// assert_eq!(range.scale(0, 127), 0);
+    /// assert_eq!(range.scale(64, 127), 32);
+    /// assert_eq!(range.scale(127, 127), 63);
+    /// ```
+    pub fn scale(self, value: u32, max_in: u32) -> i16 {
+        if max_in == 0 {
+            return self.min;
+        }
+        let span = i64::from(self.max) - i64::from(self.min);
+        let value = i64::from(value.min(max_in));
+        let scaled = (value * span + i64::from(max_in) / 2) / i64::from(max_in);
+        self.clamp((i64::from(self.min) + scaled) as i32)
+    }
+}
+
+/// The ids of the settings without an LFO index, in the order of the registry.
+const UNINDEXED: [(&str, SettingId); 28] = [
+    ("paraphonic", SettingId::ParaphonicMode),
+    ("osc.sync", SettingId::OscSync),
+    ("osc1.blend", SettingId::Osc1BlendMode),
+    ("osc2.blend", SettingId::Osc2BlendMode),
+    ("osc1.tune_pot_bypass", SettingId::Osc1TunePotBypass),
+    ("osc2.tune_pot_bypass", SettingId::Osc2TunePotBypass),
+    ("osc1.range", SettingId::Osc1Range),
+    ("osc2.range", SettingId::Osc2Range),
+    ("osc2.key_track", SettingId::Osc2KeyTrack),
+    ("osc1.autoglide", SettingId::Osc1Autoglide),
+    ("osc2.autoglide", SettingId::Osc2Autoglide),
+    ("lfo.blend", SettingId::LfoBlendMode),
+    ("lfo.key_sync", SettingId::LfoKeySync),
+    ("lfo.one_shot", SettingId::LfoOneShot),
+    ("lfo.retrigger", SettingId::LfoRetrigger),
+    ("lfo.midi_sync", SettingId::LfoMidiSync),
+    ("lfo.depth", SettingId::LfoDepth),
+    ("lfo.reset_order", SettingId::LfoResetOrder),
+    ("vcf.key_tracking", SettingId::VcfKeyTracking),
+    ("vcf.mod_depth", SettingId::VcfModDepth),
+    ("vcf.mod_source", SettingId::VcfModSource),
+    ("midi.channel", SettingId::MidiChannel),
+    ("midi.disable_dips", SettingId::DisableMidiDips),
+    ("poly_chain", SettingId::PolyChainMode),
+    ("key_range.mute", SettingId::KeyRangeMute),
+    ("key_range.reset", SettingId::KeyRangeReset),
+    ("assign_out", SettingId::AssignOut),
+    ("env.retrigger", SettingId::EnvRetriggerMode),
+];
+
+fn names<T>(options: &[(&'static str, T)]) -> Vec<&'static str> {
+    options.iter().map(|(name, _)| *name).collect()
+}
+
+fn name_of<T: PartialEq>(options: &[(&'static str, T)], value: T) -> &'static str {
+    // Every value of the option enums has a name
+    options.iter().find(|(_, v)| *v == value).unwrap().0
+}
+
+impl SettingId {
+    /// Every setting, including one per LFO index for the shape order and phase.
+    pub fn all() -> Vec<SettingId> {
+        let mut all: Vec<SettingId> = UNINDEXED.iter().map(|(_, id)| *id).collect();
+        for (_, index) in LFO_INDEX.iter() {
+            all.push(SettingId::LfoShapeOrder(*index));
+            all.push(SettingId::LfoShapePhase(*index));
+        }
+        all
+    }
+
+    /// The stable string id of the setting, e.g. `osc1.range` or `lfo.shape.2`.
+    pub fn id(self) -> String {
+        match self {
+            SettingId::LfoShapeOrder(index) => format!("lfo.shape.{}", name_of(&LFO_INDEX, index)),
+            SettingId::LfoShapePhase(index) => format!("lfo.phase.{}", name_of(&LFO_INDEX, index)),
+            _ => name_of(&UNINDEXED, self).to_string(),
+        }
+    }
+
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustron_lib::protocol::LfoIndex;
+    /// use rustron_lib::setting::SettingId;
+    ///
+    /// assert_eq!(SettingId::from_id("lfo.phase.3"), Some(SettingId::LfoShapePhase(LfoIndex::Three)));
+    /// assert_eq!(SettingId::from_id("osc1.range").map(SettingId::opcode), Some(0x26));
+    /// ```
+    pub fn from_id(id: &str) -> Option<SettingId> {
+        let id = id.trim();
+        let indexed = |prefix: &str| {
+            let index = id.strip_prefix(prefix)?;
+            LFO_INDEX
+                .iter()
+                .find(|(name, _)| *name == index)
+                .map(|(_, i)| *i)
+        };
+        if let Some(index) = indexed("lfo.shape.") {
+            return Some(SettingId::LfoShapeOrder(index));
+        }
+        if let Some(index) = indexed("lfo.phase.") {
+            return Some(SettingId::LfoShapePhase(index));
+        }
+        UNINDEXED
+            .iter()
+            .find(|(name, _)| *name == id)
+            .map(|(_, setting_id)| *setting_id)
+    }
+
+    pub fn value_type(self) -> ValueType {
+        match self {
+            SettingId::Osc1BlendMode | SettingId::Osc2BlendMode | SettingId::LfoBlendMode => {
+                ValueType::Choice(names(&BLEND))
+            }
+            SettingId::Osc1Range | SettingId::Osc2Range => ValueType::Choice(names(&RANGE)),
+            SettingId::Osc2KeyTrack => ValueType::Choice(names(&KEY_TRACK)),
+            SettingId::Osc1Autoglide | SettingId::Osc2Autoglide => ValueType::Autoglide,
+            SettingId::LfoDepth | SettingId::VcfModDepth => ValueType::Percent,
+            SettingId::LfoShapeOrder(_) => ValueType::Choice(names(&SHAPE)),
+            SettingId::LfoShapePhase(_) => ValueType::Choice(names(&PHASE)),
+            SettingId::LfoResetOrder | SettingId::KeyRangeReset => ValueType::Action,
+            SettingId::VcfModSource => ValueType::Choice(names(&MOD_SOURCE)),
+            SettingId::MidiChannel => ValueType::Channel,
+            SettingId::AssignOut => ValueType::Choice(names(&ASSIGN_OUT)),
+            SettingId::EnvRetriggerMode => ValueType::Choice(names(&RETRIGGER)),
+            _ => ValueType::Toggle,
+        }
+    }
+
+    /// What the setting does on the hardware, shown as help text.
+    pub fn description(self) -> &'static str {
+        match self {
+            SettingId::ParaphonicMode => {
+                "Plays the two oscillators as separate voices: the lowest held note on oscillator \
+                 1, the highest on oscillator 2. Both share the filter and envelopes."
+            }
+            SettingId::OscSync => {
+                "Hard syncs oscillator 2 to oscillator 1, restarting its cycle on every cycle of \
+                 oscillator 1."
+            }
+            SettingId::Osc1BlendMode | SettingId::Osc2BlendMode => {
+                "Switch selects one of the five waveforms with the shape knob, blend crossfades \
+                 smoothly between neighbouring waveforms."
+            }
+            SettingId::Osc1TunePotBypass | SettingId::Osc2TunePotBypass => {
+                "Ignores the tune knob so the oscillator stays in tune regardless of its \
+                 position."
+            }
+            SettingId::Osc1Range | SettingId::Osc2Range => {
+                "The octave range of the oscillator as pipe length (32', 16', 8'), or ±10 octaves \
+                 for the range knob to sweep the full span."
+            }
+            SettingId::Osc2KeyTrack => {
+                "Track follows the keyboard, hold keeps oscillator 2 at a fixed pitch as a drone."
+            }
+            SettingId::Osc1Autoglide | SettingId::Osc2Autoglide => {
+                "Glides into every note from this many semitones above or below, independent of \
+                 the portamento knob."
+            }
+            SettingId::LfoBlendMode => {
+                "Switch steps through the LFO shapes with the shape knob, blend morphs between \
+                 them."
+            }
+            SettingId::LfoKeySync => "Restarts the LFO cycle on every new note.",
+            SettingId::LfoOneShot => {
+                "Runs the LFO for a single cycle after it is restarted, like an extra envelope."
+            }
+            SettingId::LfoRetrigger => "Restarts the LFO when its trigger input receives a pulse.",
+            SettingId::LfoMidiSync => {
+                "Syncs the LFO rate to MIDI clock, the rate knob then picks the clock division."
+            }
+            SettingId::LfoDepth => "The amount of LFO reaching its normalled destinations.",
+            SettingId::LfoShapeOrder(_) => {
+                "The shape at this position of the five the shape knob moves through."
+            }
+            SettingId::LfoShapePhase(_) => {
+                "Offsets the start of the shape at this position, in steps of 45 degrees."
+            }
+            SettingId::LfoResetOrder => {
+                "Restores the default shape order sine, triangle, falling saw, square, rising saw."
+            }
+            SettingId::VcfKeyTracking => {
+                "Opens the filter further for higher notes so the timbre stays even across the \
+                 keyboard."
+            }
+            SettingId::VcfModDepth => "How much the modulation source moves the filter cutoff.",
+            SettingId::VcfModSource => {
+                "The MIDI source modulating the filter cutoff: aftertouch, mod wheel or velocity."
+            }
+            SettingId::MidiChannel => {
+                "The channel the Neutron listens on when the DIP switches are disabled. SysEx \
+                 sent to a single device uses this channel as device id."
+            }
+            SettingId::DisableMidiDips => {
+                "Ignores the MIDI channel DIP switches on the back and uses the MIDI channel \
+                 setting instead."
+            }
+            SettingId::PolyChainMode => {
+                "Passes notes the Neutron can't play on to MIDI out, for chaining several units \
+                 into a polyphonic setup."
+            }
+            SettingId::KeyRangeMute => {
+                "Mutes notes outside the key range set by holding the outermost keys."
+            }
+            SettingId::KeyRangeReset => "Resets the key range to the full keyboard.",
+            SettingId::AssignOut => {
+                "What the assignable output on the patch bay sends: one of the oscillators, or a \
+                 control voltage from velocity, mod wheel or aftertouch."
+            }
+            SettingId::EnvRetriggerMode => {
+                "Staccato restarts the envelopes on every note, legato only when no other key is \
+                 held."
+            }
+        }
+    }
+
+    /// The range of raw values, `None` for actions.
+    pub fn range(self) -> Option<ValueRange> {
+        let (min, max, coarse_step) = match self.value_type() {
+            ValueType::Toggle => (0, 1, 1),
+            ValueType::Choice(options) => (0, options.len() as i16 - 1, 1),
+            ValueType::Percent => (0, 63, 8),
+            // An octave
+            ValueType::Autoglide => (-12, 12, 12),
+            ValueType::Channel => (1, 16, 4),
+            ValueType::Action => return None,
+        };
+        Some(ValueRange {
+            min,
+            max,
+            step: 1,
+            coarse_step,
+        })
+    }
+
+    /// Builds the setting with a raw value, see `GlobalSetting::raw_value`. Values outside the
+    /// range are clamped, `None` for actions.
+    pub fn with_raw_value(self, raw: i32) -> Option<GlobalSetting> {
+        let raw = self.range()?.clamp(raw);
+        let setting = match self {
+            SettingId::LfoDepth => GlobalSetting::LfoDepth(Percent::from_byte(raw as u8)),
+            SettingId::VcfModDepth => GlobalSetting::VcfModDepth(Percent::from_byte(raw as u8)),
+            SettingId::Osc1Autoglide => {
+                GlobalSetting::Osc1Autoglide(AutoglideSemitones::from_semitones(raw as i8)?)
+            }
+            SettingId::Osc2Autoglide => {
+                GlobalSetting::Osc2Autoglide(AutoglideSemitones::from_semitones(raw as i8)?)
+            }
+            SettingId::MidiChannel => GlobalSetting::MidiChannel(Channel::from_number(raw as u8)?),
+            _ => match self.value_type() {
+                ValueType::Choice(options) => self.parse_value(options[raw as usize]).ok()?,
+                _ => self.parse_value(if raw == 1 { "on" } else { "off" }).ok()?,
+            },
+        };
+        Some(setting)
+    }
+
+    /// Whether the setting has exactly two values, like toggles and blend modes, see
+    /// `GlobalSetting::toggled`.
+    pub fn is_two_valued(self) -> bool {
+        self.range().is_some_and(|range| range.max - range.min == 1)
+    }
+
+    /// The opcode of the setting, see `GlobalSetting::opcode`.
+    pub fn opcode(self) -> u8 {
+        let value = match self.value_type() {
+            ValueType::Choice(options) => options[0],
+            ValueType::Toggle | ValueType::Percent | ValueType::Autoglide | ValueType::Action => {
+                "0"
+            }
+            ValueType::Channel => "1",
+        };
+        // The first value of every value type is valid
+        self.parse_value(value).unwrap().opcode()
+    }
+
+    /// Builds the setting with a textual value, values are case insensitive. The value of
+    /// actions is ignored.
+    pub fn parse_value(self, value: &str) -> Result<GlobalSetting, SettingError> {
+        let id = self.id();
+        let v = Value {
+            setting: &id,
+            value,
+        };
+        let setting = match self {
+            SettingId::ParaphonicMode => GlobalSetting::ParaphonicMode(v.toggle()?),
+            SettingId::OscSync => GlobalSetting::OscSync(v.toggle()?),
+            SettingId::Osc1BlendMode => GlobalSetting::Osc1BlendMode(v.choice(&BLEND)?),
+            SettingId::Osc2BlendMode => GlobalSetting::Osc2BlendMode(v.choice(&BLEND)?),
+            SettingId::Osc1TunePotBypass => GlobalSetting::Osc1TunePotBypass(v.toggle()?),
+            SettingId::Osc2TunePotBypass => GlobalSetting::Osc2TunePotBypass(v.toggle()?),
+            SettingId::Osc1Range => GlobalSetting::Osc1Range(v.choice(&RANGE)?),
+            SettingId::Osc2Range => GlobalSetting::Osc2Range(v.choice(&RANGE)?),
+            SettingId::Osc2KeyTrack => GlobalSetting::Osc2KeyTrack(v.choice(&KEY_TRACK)?),
+            SettingId::Osc1Autoglide => GlobalSetting::Osc1Autoglide(v.autoglide()?),
+            SettingId::Osc2Autoglide => GlobalSetting::Osc2Autoglide(v.autoglide()?),
+            SettingId::LfoBlendMode => GlobalSetting::LfoBlendMode(v.choice(&BLEND)?),
+            SettingId::LfoKeySync => GlobalSetting::LfoKeySync(v.toggle()?),
+            SettingId::LfoOneShot => GlobalSetting::LfoOneShot(v.toggle()?),
+            SettingId::LfoRetrigger => GlobalSetting::LfoRetrigger(v.toggle()?),
+            SettingId::LfoMidiSync => GlobalSetting::LfoMidiSync(v.toggle()?),
+            SettingId::LfoDepth => GlobalSetting::LfoDepth(v.percent()?),
+            SettingId::LfoShapeOrder(index) => {
+                GlobalSetting::LfoShapeOrder(index, v.choice(&SHAPE)?)
+            }
+            SettingId::LfoShapePhase(index) => {
+                GlobalSetting::LfoShapePhase(index, v.choice(&PHASE)?)
+            }
+            SettingId::LfoResetOrder => GlobalSetting::LfoResetOrder,
+            SettingId::VcfKeyTracking => GlobalSetting::VcfKeyTracking(v.toggle()?),
+            SettingId::VcfModDepth => GlobalSetting::VcfModDepth(v.percent()?),
+            SettingId::VcfModSource => GlobalSetting::VcfModSource(v.choice(&MOD_SOURCE)?),
+            SettingId::MidiChannel => GlobalSetting::MidiChannel(v.channel()?),
+            SettingId::DisableMidiDips => GlobalSetting::DisableMidiDips(v.toggle()?),
+            SettingId::PolyChainMode => GlobalSetting::PolyChainMode(v.toggle()?),
+            SettingId::KeyRangeMute => GlobalSetting::KeyRangeMute(v.toggle()?),
+            SettingId::KeyRangeReset => GlobalSetting::KeyRangeReset,
+            SettingId::AssignOut => GlobalSetting::AssignOut(v.choice(&ASSIGN_OUT)?),
+            SettingId::EnvRetriggerMode => GlobalSetting::EnvRetriggerMode(v.choice(&RETRIGGER)?),
+        };
+        Ok(setting)
+    }
+}
+
+/// Parses values of one setting, producing the errors for it.
+struct Value<'a> {
+    setting: &'a str,
+    value: &'a str,
+}
+
+impl<'a> Value<'a> {
+    fn invalid(&self, expected: String) -> SettingError {
+        SettingError::InvalidValue {
+            setting: self.setting.to_string(),
+            value: self.value.to_string(),
+            expected,
+        }
+    }
+
+    fn choice<T: Copy>(&self, options: &[(&str, T)]) -> Result<T, SettingError> {
+        let value = self.value.trim().to_lowercase();
+        options
+            .iter()
+            .find(|(name, _)| *name == value)
+            .map(|(_, option)| *option)
+            .ok_or_else(|| {
+                let names: Vec<&str> = options.iter().map(|(name, _)| *name).collect();
+                self.invalid(names.join("|"))
+            })
+    }
+
+    fn toggle(&self) -> Result<ToggleOption, SettingError> {
+        self.choice(&TOGGLE)
+    }
+
+    fn percent(&self) -> Result<Percent, SettingError> {
+        match self.value.trim().parse::<u8>() {
+            Ok(percentage) if percentage <= 100 => Ok(Percent::from_percentage(percentage)),
+            _ => Err(self.invalid(String::from("a percentage 0-100"))),
+        }
+    }
+
+    fn autoglide(&self) -> Result<AutoglideSemitones, SettingError> {
+        self.value
+            .trim()
+            .parse()
+            .map_err(|_| self.invalid(String::from("an interval like -P5 or semitones -12-12")))
+    }
+
+    fn channel(&self) -> Result<Channel, SettingError> {
+        self.value
+            .trim()
+            .parse()
+            .ok()
+            .and_then(Channel::from_number)
+            .ok_or_else(|| self.invalid(String::from("a channel 1-16")))
+    }
+}
+
+impl GlobalSetting {
+    /// Builds a setting from its id and a textual value, the parsing rules shared by all text
+    /// based interfaces. See `SettingId` for the ids.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustron_lib::protocol::GlobalSetting;
+    /// use rustron_lib::protocol::GlobalSetting::{LfoShapeOrder, Osc1Range};
+    /// use rustron_lib::protocol::{LfoIndex, LfoShape, OscRange};
+    ///
+    /// assert_eq!(
+    ///     GlobalSetting::from_name_value("osc1.range", "16"),
+    ///     Ok(Osc1Range(OscRange::Sixteen))
+    /// );
+    /// assert_eq!(
+    ///     GlobalSetting::from_name_value("lfo.shape.2", "Square"),
+    ///     Ok(LfoShapeOrder(LfoIndex::Two, LfoShape::Square))
+    /// );
+    /// assert!(GlobalSetting::from_name_value("osc1.range", "64").is_err());
+    /// ```
+    pub fn from_name_value(name: &str, value: &str) -> Result<GlobalSetting, SettingError> {
+        SettingId::from_id(name)
+            .ok_or_else(|| SettingError::UnknownSetting(name.trim().to_string()))?
+            .parse_value(value)
+    }
+
+    /// Which setting this is.
+    pub fn id(&self) -> SettingId {
+        match *self {
+            GlobalSetting::ParaphonicMode(_) => SettingId::ParaphonicMode,
+            GlobalSetting::OscSync(_) => SettingId::OscSync,
+            GlobalSetting::Osc1BlendMode(_) => SettingId::Osc1BlendMode,
+            GlobalSetting::Osc2BlendMode(_) => SettingId::Osc2BlendMode,
+            GlobalSetting::Osc1TunePotBypass(_) => SettingId::Osc1TunePotBypass,
+            GlobalSetting::Osc2TunePotBypass(_) => SettingId::Osc2TunePotBypass,
+            GlobalSetting::Osc1Range(_) => SettingId::Osc1Range,
+            GlobalSetting::Osc2Range(_) => SettingId::Osc2Range,
+            GlobalSetting::Osc2KeyTrack(_) => SettingId::Osc2KeyTrack,
+            GlobalSetting::Osc1Autoglide(_) => SettingId::Osc1Autoglide,
+            GlobalSetting::Osc2Autoglide(_) => SettingId::Osc2Autoglide,
+            GlobalSetting::LfoBlendMode(_) => SettingId::LfoBlendMode,
+            GlobalSetting::LfoKeySync(_) => SettingId::LfoKeySync,
+            GlobalSetting::LfoOneShot(_) => SettingId::LfoOneShot,
+            GlobalSetting::LfoRetrigger(_) => SettingId::LfoRetrigger,
+            GlobalSetting::LfoMidiSync(_) => SettingId::LfoMidiSync,
+            GlobalSetting::LfoDepth(_) => SettingId::LfoDepth,
+            GlobalSetting::LfoShapeOrder(index, _) => SettingId::LfoShapeOrder(index),
+            GlobalSetting::LfoShapePhase(index, _) => SettingId::LfoShapePhase(index),
+            GlobalSetting::LfoResetOrder => SettingId::LfoResetOrder,
+            GlobalSetting::VcfKeyTracking(_) => SettingId::VcfKeyTracking,
+            GlobalSetting::VcfModDepth(_) => SettingId::VcfModDepth,
+            GlobalSetting::VcfModSource(_) => SettingId::VcfModSource,
+            GlobalSetting::MidiChannel(_) => SettingId::MidiChannel,
+            GlobalSetting::DisableMidiDips(_) => SettingId::DisableMidiDips,
+            GlobalSetting::PolyChainMode(_) => SettingId::PolyChainMode,
+            GlobalSetting::KeyRangeMute(_) => SettingId::KeyRangeMute,
+            GlobalSetting::KeyRangeReset => SettingId::KeyRangeReset,
+            GlobalSetting::AssignOut(_) => SettingId::AssignOut,
+            GlobalSetting::EnvRetriggerMode(_) => SettingId::EnvRetriggerMode,
+        }
+    }
+
+    /// The value as a number within `SettingId::range`: the byte of percentages, semitones of
+    /// autoglide, the channel number and the position of choices. `None` for actions.
+    pub fn raw_value(&self) -> Option<i32> {
+        let raw = match *self {
+            GlobalSetting::LfoDepth(p) | GlobalSetting::VcfModDepth(p) => i32::from(p.as_byte()),
+            GlobalSetting::Osc1Autoglide(s) | GlobalSetting::Osc2Autoglide(s) => {
+                i32::from(s.semitones())
+            }
+            GlobalSetting::MidiChannel(c) => i32::from(c.number()),
+            _ => {
+                let text = self.value_text();
+                match self.id().value_type() {
+                    ValueType::Toggle => i32::from(text == "on"),
+                    ValueType::Choice(options) => {
+                        options.iter().position(|option| *option == text)? as i32
+                    }
+                    _ => return None,
+                }
+            }
+        };
+        Some(raw)
+    }
+
+    /// The setting with the other of its two values, `None` for settings with more values.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustron_lib::protocol::GlobalSetting::{LfoDepth, OscSync};
+    /// use rustron_lib::protocol::{Percent, ToggleOption};
+    ///
+    /// assert_eq!(OscSync(ToggleOption::On).toggled(), Some(OscSync(ToggleOption::Off)));
+    /// assert_eq!(LfoDepth(Percent::from_byte(10)).toggled(), None);
+    /// ```
+    pub fn toggled(&self) -> Option<GlobalSetting> {
+        let id = self.id();
+        if !id.is_two_valued() {
+            return None;
+        }
+        id.with_raw_value(1 - self.raw_value()?)
+    }
+
+    /// The value as text, as accepted by `SettingId::parse_value`. Empty for actions.
+    pub fn value_text(&self) -> String {
+        match *self {
+            GlobalSetting::ParaphonicMode(t)
+            | GlobalSetting::OscSync(t)
+            | GlobalSetting::Osc1TunePotBypass(t)
+            | GlobalSetting::Osc2TunePotBypass(t)
+            | GlobalSetting::LfoKeySync(t)
+            | GlobalSetting::LfoOneShot(t)
+            | GlobalSetting::LfoRetrigger(t)
+            | GlobalSetting::LfoMidiSync(t)
+            | GlobalSetting::VcfKeyTracking(t)
+            | GlobalSetting::DisableMidiDips(t)
+            | GlobalSetting::PolyChainMode(t)
+            | GlobalSetting::KeyRangeMute(t) => name_of(&TOGGLE, t).to_string(),
+            GlobalSetting::Osc1BlendMode(b)
+            | GlobalSetting::Osc2BlendMode(b)
+            | GlobalSetting::LfoBlendMode(b) => name_of(&BLEND, b).to_string(),
+            GlobalSetting::Osc1Range(r) | GlobalSetting::Osc2Range(r) => {
+                name_of(&RANGE, r).to_string()
+            }
+            GlobalSetting::Osc2KeyTrack(k) => name_of(&KEY_TRACK, k).to_string(),
+            GlobalSetting::Osc1Autoglide(s) | GlobalSetting::Osc2Autoglide(s) => s.to_string(),
+            // Rounded up so that parsing gives the same value back
+            GlobalSetting::LfoDepth(p) | GlobalSetting::VcfModDepth(p) => {
+                format!("{}", p.as_percentage().ceil())
+            }
+            GlobalSetting::LfoShapeOrder(_, s) => name_of(&SHAPE, s).to_string(),
+            GlobalSetting::LfoShapePhase(_, o) => name_of(&PHASE, o).to_string(),
+            GlobalSetting::LfoResetOrder | GlobalSetting::KeyRangeReset => String::new(),
+            GlobalSetting::VcfModSource(m) => name_of(&MOD_SOURCE, m).to_string(),
+            GlobalSetting::MidiChannel(c) => c.number().to_string(),
+            GlobalSetting::AssignOut(o) => name_of(&ASSIGN_OUT, o).to_string(),
+            GlobalSetting::EnvRetriggerMode(m) => name_of(&RETRIGGER, m).to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::protocol::GlobalSetting;
+    use crate::protocol::GlobalSetting::{
+        KeyRangeReset, LfoDepth, LfoShapePhase, MidiChannel, Osc2Autoglide, ParaphonicMode,
+    };
+    use crate::protocol::ToggleOption::On;
+    use crate::protocol::{AutoglideSemitones, Channel, LfoIndex, LfoPhaseOffset, Percent};
+    use crate::setting::{SettingError, SettingId, ValueType};
+
+    #[test]
+    fn valid_values_are_parsed() {
+        assert_eq!(
+            GlobalSetting::from_name_value("paraphonic", "ON"),
+            Ok(ParaphonicMode(On))
+        );
+        assert_eq!(
+            GlobalSetting::from_name_value("osc2.autoglide", "-P5"),
+            Ok(Osc2Autoglide(AutoglideSemitones::MinusSeven))
+        );
+        assert_eq!(
+            GlobalSetting::from_name_value("lfo.depth", "100"),
+            Ok(LfoDepth(Percent::from_byte(63)))
+        );
+        assert_eq!(
+            GlobalSetting::from_name_value("lfo.phase.5", "270"),
+            Ok(LfoShapePhase(
+                LfoIndex::Five,
+                LfoPhaseOffset::TwoHundredSeventy
+            ))
+        );
+        assert_eq!(
+            GlobalSetting::from_name_value("midi.channel", "16"),
+            Ok(MidiChannel(Channel::Sixteen))
+        );
+        assert_eq!(
+            GlobalSetting::from_name_value("key_range.reset", ""),
+            Ok(KeyRangeReset)
+        );
+    }
+
+    #[test]
+    fn registry_round_trips() {
+        let all = SettingId::all();
+        assert_eq!(all.len(), 38);
+        for id in all {
+            assert_eq!(SettingId::from_id(&id.id()), Some(id));
+            assert!(!id.description().is_empty());
+            let first = match id.value_type() {
+                ValueType::Choice(options) => options[0].to_string(),
+                _ => String::from("1"),
+            };
+            let setting = id.parse_value(&first).unwrap();
+            assert_eq!(setting.id(), id);
+            assert_eq!(setting.opcode(), id.opcode());
+            if id.value_type() != ValueType::Action {
+                assert_eq!(id.parse_value(&setting.value_text()), Ok(setting));
+            }
+        }
+        for byte in 0..=63 {
+            let setting = LfoDepth(Percent::from_byte(byte));
+            assert_eq!(
+                SettingId::LfoDepth.parse_value(&setting.value_text()),
+                Ok(setting)
+            );
+        }
+    }
+
+    #[test]
+    fn raw_values_are_clamped_to_range() {
+        for id in SettingId::all() {
+            let range = match id.range() {
+                Some(range) => range,
+                None => {
+                    assert_eq!(id.with_raw_value(0), None);
+                    continue;
+                }
+            };
+            for raw in range.min..=range.max {
+                let setting = id.with_raw_value(i32::from(raw)).unwrap();
+                assert_eq!(setting.raw_value(), Some(i32::from(raw)));
+            }
+            let below = id.with_raw_value(i32::from(range.min) - 1).unwrap();
+            assert_eq!(below.raw_value(), Some(i32::from(range.min)));
+            let above = id.with_raw_value(i32::from(range.max) + 100).unwrap();
+            assert_eq!(above.raw_value(), Some(i32::from(range.max)));
+        }
+        assert_eq!(
+            SettingId::MidiChannel.with_raw_value(0),
+            Some(MidiChannel(Channel::One))
+        );
+        assert_eq!(
+            SettingId::Osc2Autoglide.with_raw_value(13),
+            Some(Osc2Autoglide(AutoglideSemitones::PlusTwelve))
+        );
+    }
+
+    #[test]
+    fn controller_values_are_scaled_to_range() {
+        let channel = SettingId::MidiChannel.range().unwrap();
+        assert_eq!(channel.scale(0, 127), 1);
+        assert_eq!(channel.scale(127, 127), 16);
+        assert_eq!(channel.scale(500, 127), 16);
+        let autoglide = SettingId::Osc1Autoglide.range().unwrap();
+        assert_eq!(autoglide.scale(0, 127), -12);
+        assert_eq!(autoglide.scale(64, 127), 0);
+        assert_eq!(autoglide.scale(127, 127), 12);
+        let toggle = SettingId::OscSync.range().unwrap();
+        assert_eq!(toggle.scale(63, 127), 0);
+        assert_eq!(toggle.scale(64, 127), 1);
+        assert_eq!(toggle.scale(1, 0), 0);
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        assert_eq!(
+            GlobalSetting::from_name_value("osc.sync", "maybe"),
+            Err(SettingError::InvalidValue {
+                setting: String::from("osc.sync"),
+                value: String::from("maybe"),
+                expected: String::from("on|off|true|false|1|0"),
+            })
+        );
+        assert!(GlobalSetting::from_name_value("lfo.depth", "101").is_err());
+        assert!(GlobalSetting::from_name_value("midi.channel", "0").is_err());
+        assert_eq!(
+            GlobalSetting::from_name_value("lfo.shape.6", "sine"),
+            Err(SettingError::UnknownSetting(String::from("lfo.shape.6")))
+        );
+        assert_eq!(
+            GlobalSetting::from_name_value("osc3.range", "8"),
+            Err(SettingError::UnknownSetting(String::from("osc3.range")))
+        );
+    }
+}
@@ -0,0 +1,137 @@
+//! Messages captured from a real Neutron, in `tests/fixtures`. Each fixture is one message as hex
+//! bytes, with `#` lines describing where it came from.
+
+use rustron_lib::parser::neutron_message;
+use rustron_lib::preset::{diff_state_dumps, from_state_dump, STATE_DUMP_BITS, STATE_DUMP_OFFSET};
+use rustron_lib::protocol::BlendMode::{Blend, Switch};
+use rustron_lib::protocol::DeviceId::Channel;
+use rustron_lib::protocol::GlobalSetting::{Osc1BlendMode, OscSync, ParaphonicMode};
+use rustron_lib::protocol::NeutronMessage::{GlobalSettingUpdate, SoftwareVersionResponse};
+use rustron_lib::protocol::ToggleOption::{Off, On};
+use rustron_lib::protocol::{self, GlobalSetting, NeutronMessage};
+use rustron_lib::setting::SettingId;
+
+macro_rules! fixture {
+    ($name:literal) => {
+        ($name, include_str!(concat!("fixtures/", $name)))
+    };
+}
+
+fn bytes((name, fixture): (&str, &str)) -> Vec<u8> {
+    fixture
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .flat_map(str::split_whitespace)
+        .map(|byte| u8::from_str_radix(byte, 16))
+        .collect::<Result<_, _>>()
+        .unwrap_or_else(|e| panic!("{} is not hex: {}", name, e))
+}
+
+/// Parses a fixture, checking that the message encodes back to the same bytes.
+fn parse(fixture: (&str, &str)) -> NeutronMessage {
+    let bytes = bytes(fixture);
+    let (rest, message) =
+        neutron_message(&bytes).unwrap_or_else(|e| panic!("{} does not parse: {:?}", fixture.0, e));
+    assert!(rest.is_empty(), "{} has trailing bytes", fixture.0);
+    assert_eq!(
+        message.as_bytes(),
+        bytes,
+        "{} encodes differently",
+        fixture.0
+    );
+    message
+}
+
+#[test]
+fn messages() {
+    let fixtures = vec![
+        (
+            fixture!("software_version_response.hex"),
+            SoftwareVersionResponse(Channel(protocol::Channel::One), String::from("2.0.2")),
+        ),
+        (
+            fixture!("setting_update_osc1_blend_switch.hex"),
+            GlobalSettingUpdate(Channel(protocol::Channel::One), Osc1BlendMode(Switch)),
+        ),
+        (
+            fixture!("setting_update_osc1_blend_blend.hex"),
+            GlobalSettingUpdate(Channel(protocol::Channel::One), Osc1BlendMode(Blend)),
+        ),
+    ];
+    for (fixture, expected) in fixtures {
+        assert_eq!(parse(fixture), expected, "{}", fixture.0);
+    }
+}
+
+#[test]
+fn state_dumps() {
+    let fixtures: Vec<(_, Vec<GlobalSetting>)> = vec![
+        (
+            fixture!("state_dump.hex"),
+            vec![OscSync(Off), ParaphonicMode(On)],
+        ),
+        (
+            fixture!("state_dump_sync_off_paraphonic_off.hex"),
+            vec![OscSync(Off), ParaphonicMode(Off)],
+        ),
+        (
+            fixture!("state_dump_sync_on.hex"),
+            vec![OscSync(On), ParaphonicMode(Off)],
+        ),
+        (
+            fixture!("state_dump_sync_on_paraphonic_on.hex"),
+            vec![OscSync(On), ParaphonicMode(On)],
+        ),
+        (
+            fixture!("state_dump_unknown.hex"),
+            vec![OscSync(Off), ParaphonicMode(Off)],
+        ),
+    ];
+    for (fixture, expected) in fixtures {
+        match parse(fixture) {
+            NeutronMessage::StateDump(Channel(protocol::Channel::One), payload) => {
+                assert_eq!(payload.len(), 24, "{}", fixture.0);
+                assert_eq!(from_state_dump(&payload), expected, "{}", fixture.0);
+            }
+            other => panic!("{} is not a state dump: {}", fixture.0, other),
+        }
+    }
+}
+
+#[test]
+fn state_dump_differences() {
+    let differences = diff_state_dumps(
+        &bytes(fixture!("state_dump_sync_off_paraphonic_off.hex")),
+        &bytes(fixture!("state_dump_sync_on_paraphonic_on.hex")),
+    );
+    let settings: Vec<SettingId> = differences
+        .iter()
+        .flat_map(|difference| difference.settings.clone())
+        .collect();
+    assert_eq!(
+        settings,
+        vec![SettingId::OscSync, SettingId::ParaphonicMode]
+    );
+}
+
+/// Each bit of the table is the one the captures of toggling its setting differ in.
+#[test]
+fn state_dump_bits_match_the_captures() {
+    let captures = [
+        (
+            bytes(fixture!("state_dump_sync_off_paraphonic_off.hex")),
+            bytes(fixture!("state_dump_sync_on.hex")),
+        ),
+        (
+            bytes(fixture!("state_dump_sync_on.hex")),
+            bytes(fixture!("state_dump_sync_on_paraphonic_on.hex")),
+        ),
+    ];
+    for (bit, (off, on)) in STATE_DUMP_BITS.iter().zip(captures.iter()) {
+        let differences = diff_state_dumps(off, on);
+        assert_eq!(differences.len(), 1, "{:?}", bit.setting);
+        assert_eq!(differences[0].offset, STATE_DUMP_OFFSET + bit.byte);
+        assert_eq!(differences[0].changed_bits(), bit.mask);
+        assert_eq!(off[STATE_DUMP_OFFSET + bit.byte] & bit.mask, 0);
+    }
+}
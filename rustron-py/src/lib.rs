@@ -0,0 +1,220 @@
+//! Python bindings for rustron-lib, for scripting the Neutron from Python.
+//!
+//! ```python
+//! import rustron
+//!
+//! client = rustron.Client()
+//! client.send(rustron.Message.paraphonic_mode(True))
+//! client.send(rustron.Message.set("osc1.range", "16", device=2))
+//! print(client.receive(timeout=1.0))
+//! ```
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use midir::{MidiInput, MidiInputConnection, MidiOutput, MidiOutputConnection};
+use pyo3::exceptions::{PyIOError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use rustron_lib::parser;
+use rustron_lib::protocol::GlobalSetting::{OscSync, ParaphonicMode};
+use rustron_lib::protocol::NeutronMessage::{
+    SetGlobalSetting, SoftwareVersionRequest, StateRequest,
+};
+use rustron_lib::protocol::ToggleOption;
+use rustron_lib::protocol::{Channel, DeviceId, GlobalSetting, NeutronMessage};
+
+/// A message to or from the Neutron.
+#[pyclass(name = "Message")]
+struct PyMessage {
+    inner: NeutronMessage,
+}
+
+#[pymethods]
+impl PyMessage {
+    /// Parses a complete SysEx message, raising `ValueError` if it isn't a known Neutron message.
+    #[staticmethod]
+    fn parse(data: &[u8]) -> PyResult<PyMessage> {
+        match parser::neutron_message(data) {
+            Ok((_, inner)) => Ok(PyMessage { inner }),
+            Err(_) => Err(PyValueError::new_err(format!(
+                "not a Neutron message: {}",
+                parser::describe(data)
+            ))),
+        }
+    }
+
+    /// Asks for the firmware version, of the device on channel `device` (1-16) if given.
+    #[staticmethod]
+    #[pyo3(signature = (device=None))]
+    fn software_version_request(device: Option<u8>) -> PyResult<PyMessage> {
+        Ok(PyMessage {
+            inner: SoftwareVersionRequest(device_id(device)?),
+        })
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (device=None))]
+    fn state_request(device: Option<u8>) -> PyResult<PyMessage> {
+        Ok(PyMessage {
+            inner: StateRequest(device_id(device)?),
+        })
+    }
+
+    /// Sets a global setting by id and textual value, e.g. `Message.set("osc1.range", "16")`.
+    /// Raises `ValueError` for unknown settings or invalid values.
+    #[staticmethod]
+    #[pyo3(signature = (name, value, device=None))]
+    fn set(name: &str, value: &str, device: Option<u8>) -> PyResult<PyMessage> {
+        let setting = GlobalSetting::from_name_value(name, value)
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(PyMessage {
+            inner: SetGlobalSetting(device_id(device)?, setting),
+        })
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (on, device=None))]
+    fn paraphonic_mode(on: bool, device: Option<u8>) -> PyResult<PyMessage> {
+        Ok(PyMessage {
+            inner: SetGlobalSetting(device_id(device)?, ParaphonicMode(ToggleOption::from(on))),
+        })
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (on, device=None))]
+    fn osc_sync(on: bool, device: Option<u8>) -> PyResult<PyMessage> {
+        Ok(PyMessage {
+            inner: SetGlobalSetting(device_id(device)?, OscSync(ToggleOption::from(on))),
+        })
+    }
+
+    fn to_bytes<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.inner.as_bytes())
+    }
+
+    fn __str__(&self) -> String {
+        self.inner.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Message({})", self.inner)
+    }
+}
+
+/// The device on the given channel (1-16), or every device when `None`.
+fn device_id(device: Option<u8>) -> PyResult<DeviceId> {
+    match device {
+        Some(number) => Channel::from_number(number)
+            .map(DeviceId::Channel)
+            .ok_or_else(|| {
+                PyValueError::new_err(format!("invalid device {}, expected 1-16", number))
+            }),
+        None => Ok(DeviceId::Multicast),
+    }
+}
+
+/// A human readable description of a raw message.
+#[pyfunction]
+fn describe(data: &[u8]) -> String {
+    parser::describe(data)
+}
+
+fn neutron_port(port_names: impl Iterator<Item = Option<String>>) -> PyResult<usize> {
+    port_names
+        .enumerate()
+        .find(|(_, name)| name.as_deref().is_some_and(|n| n.starts_with("Neutron")))
+        .map(|(i, _)| i)
+        .ok_or_else(|| PyIOError::new_err("Could not find Neutron."))
+}
+
+/// A MIDI connection to the Neutron.
+#[pyclass(unsendable)]
+struct Client {
+    midi_out: MidiOutputConnection,
+    _midi_in: MidiInputConnection<()>,
+    /// Locked so that `receive` can wait without holding the GIL
+    receiver: Mutex<Receiver<Vec<u8>>>,
+}
+
+#[pymethods]
+impl Client {
+    #[new]
+    fn new() -> PyResult<Client> {
+        let io_error = |error: &dyn std::fmt::Display| PyIOError::new_err(error.to_string());
+
+        let output = MidiOutput::new("Neutron").map_err(|e| io_error(&e))?;
+        let out_port = neutron_port((0..output.port_count()).map(|i| output.port_name(i).ok()))?;
+        let midi_out = output
+            .connect(out_port, "neutron")
+            .map_err(|e| io_error(&e))?;
+
+        let input = MidiInput::new("Neutron").map_err(|e| io_error(&e))?;
+        let in_port = neutron_port((0..input.port_count()).map(|i| input.port_name(i).ok()))?;
+        let (sender, receiver) = mpsc::channel();
+        let midi_in = input
+            .connect(
+                in_port,
+                "neutron",
+                move |_, msg, _| {
+                    // The client is gone if this fails, nothing left to deliver to
+                    let _ = sender.send(msg.to_vec());
+                },
+                (),
+            )
+            .map_err(|e| io_error(&e))?;
+
+        Ok(Client {
+            midi_out,
+            _midi_in: midi_in,
+            receiver: Mutex::new(receiver),
+        })
+    }
+
+    /// Sends a `Message` or raw bytes.
+    fn send(&mut self, message: &Bound<'_, PyAny>) -> PyResult<()> {
+        let bytes = match message.downcast::<PyMessage>() {
+            Ok(message) => message.borrow().inner.as_bytes(),
+            Err(_) => message.extract::<Vec<u8>>()?,
+        };
+        self.midi_out
+            .send(&bytes)
+            .map_err(|e| PyIOError::new_err(e.to_string()))
+    }
+
+    /// Waits for the next message from the Neutron, up to `timeout` seconds if given, letting
+    /// other Python threads run meanwhile. Returns a `Message` if it could be parsed, the raw
+    /// bytes otherwise, or `None` on timeout. A negative or NaN `timeout` raises `ValueError`.
+    #[pyo3(signature = (timeout=None))]
+    fn receive(&self, py: Python<'_>, timeout: Option<f64>) -> PyResult<Option<PyObject>> {
+        let timeout = timeout
+            .map(Duration::try_from_secs_f64)
+            .transpose()
+            .map_err(|e| PyValueError::new_err(format!("invalid timeout: {}", e)))?;
+        let receiver = &self.receiver;
+        let bytes = py.allow_threads(|| {
+            let receiver = receiver.lock().unwrap_or_else(|e| e.into_inner());
+            match timeout {
+                Some(timeout) => receiver.recv_timeout(timeout).ok(),
+                None => receiver.recv().ok(),
+            }
+        });
+        Ok(match bytes {
+            Some(bytes) => Some(match parser::neutron_message(&bytes) {
+                Ok((_, inner)) => Py::new(py, PyMessage { inner })?.into_any(),
+                Err(_) => PyBytes::new(py, &bytes).into_any().unbind(),
+            }),
+            None => None,
+        })
+    }
+}
+
+#[pymodule]
+fn rustron(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyMessage>()?;
+    m.add_class::<Client>()?;
+    m.add_function(wrap_pyfunction!(describe, m)?)?;
+    Ok(())
+}
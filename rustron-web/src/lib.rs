@@ -0,0 +1,182 @@
+//! Browser bindings for rustron-lib: the message parser and builders exposed to JavaScript, and
+//! SysEx I/O with the Neutron through the Web MIDI API.
+//!
+//! Build with `wasm-pack build rustron-web --target web`.
+use js_sys::{Function, Object, Reflect, Uint8Array};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{MidiAccess, MidiInput, MidiMessageEvent, MidiOptions, MidiOutput};
+
+use rustron_lib::parser;
+use rustron_lib::protocol::DeviceId::Multicast;
+use rustron_lib::protocol::GlobalSetting::{OscSync, ParaphonicMode};
+use rustron_lib::protocol::NeutronMessage::{
+    SetGlobalSetting, SoftwareVersionRequest, StateRequest,
+};
+use rustron_lib::protocol::{Channel, DeviceId, GlobalSetting, NeutronMessage, ToggleOption};
+
+/// A human readable description of a raw message.
+#[wasm_bindgen]
+pub fn describe(message: &[u8]) -> String {
+    parser::describe(message)
+}
+
+/// Whether `message` is a message from or to the Neutron that rustron-lib understands.
+#[wasm_bindgen]
+pub fn is_neutron_message(message: &[u8]) -> bool {
+    parser::neutron_message(message).is_ok()
+}
+
+/// Parses a complete SysEx message into an object with its `kind`, `deviceId` (the channel 1-16,
+/// or `null` for every device) and, for setting messages, the `setting` id and its `value` as
+/// text. Version responses carry the `version` and state dumps the `payload` bytes. Throws the
+/// parse error text if `message` isn't a Neutron message.
+#[wasm_bindgen]
+pub fn parse(message: &[u8]) -> Result<Object, JsValue> {
+    let message = parser::parse(message).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let object = Object::new();
+    let set = |key: &str, value: JsValue| Reflect::set(&object, &JsValue::from_str(key), &value);
+    let kind = match &message {
+        NeutronMessage::SetGlobalSetting(..) => "setGlobalSetting",
+        NeutronMessage::RestoreGlobalSetting(_) => "restoreGlobalSetting",
+        NeutronMessage::CalibrationModeCommand(_) => "calibrationModeCommand",
+        NeutronMessage::SoftwareVersionRequest(_) => "softwareVersionRequest",
+        NeutronMessage::SoftwareVersionResponse(..) => "softwareVersionResponse",
+        NeutronMessage::GlobalSettingUpdate(..) => "globalSettingUpdate",
+        NeutronMessage::StateRequest(_) => "stateRequest",
+        NeutronMessage::StateDump(..) => "stateDump",
+    };
+    set("kind", JsValue::from_str(kind))?;
+    let device_id = match message.device_id() {
+        DeviceId::Channel(channel) => JsValue::from(channel.number()),
+        DeviceId::Multicast => JsValue::NULL,
+    };
+    set("deviceId", device_id)?;
+    match &message {
+        NeutronMessage::SetGlobalSetting(_, setting)
+        | NeutronMessage::GlobalSettingUpdate(_, setting) => {
+            set("setting", JsValue::from_str(&setting.id().id()))?;
+            set("value", JsValue::from_str(&setting.value_text()))?;
+        }
+        NeutronMessage::SoftwareVersionResponse(_, version) => {
+            set("version", JsValue::from_str(version))?;
+        }
+        NeutronMessage::StateDump(_, payload) => {
+            set("payload", Uint8Array::from(payload.as_slice()).into())?;
+        }
+        _ => {}
+    }
+    Ok(object)
+}
+
+/// The device on the given channel (1-16), or every device when `undefined`.
+fn device_id(device_id: Option<u8>) -> Result<DeviceId, JsValue> {
+    match device_id {
+        Some(number) => Channel::from_number(number)
+            .map(DeviceId::Channel)
+            .ok_or_else(|| JsValue::from_str(&format!("invalid device {}, expected 1-16", number))),
+        None => Ok(DeviceId::Multicast),
+    }
+}
+
+/// Sets a global setting by id and textual value, e.g. `set_global_setting("osc1.range", "16")`,
+/// on the device on channel `device_id` or every device when it's `undefined`. Throws for unknown
+/// settings, invalid values or channels.
+#[wasm_bindgen]
+pub fn set_global_setting(
+    name: &str,
+    value: &str,
+    device_id: Option<u8>,
+) -> Result<Vec<u8>, JsValue> {
+    let setting = GlobalSetting::from_name_value(name, value)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    Ok(SetGlobalSetting(self::device_id(device_id)?, setting).as_bytes())
+}
+
+#[wasm_bindgen]
+pub fn software_version_request(device_id: Option<u8>) -> Result<Vec<u8>, JsValue> {
+    Ok(SoftwareVersionRequest(self::device_id(device_id)?).as_bytes())
+}
+
+#[wasm_bindgen]
+pub fn state_request(device_id: Option<u8>) -> Result<Vec<u8>, JsValue> {
+    Ok(StateRequest(self::device_id(device_id)?).as_bytes())
+}
+
+#[wasm_bindgen]
+pub fn paraphonic_mode(on: bool) -> Vec<u8> {
+    SetGlobalSetting(Multicast, ParaphonicMode(ToggleOption::from(on))).as_bytes()
+}
+
+#[wasm_bindgen]
+pub fn osc_sync(on: bool) -> Vec<u8> {
+    SetGlobalSetting(Multicast, OscSync(ToggleOption::from(on))).as_bytes()
+}
+
+/// The first port of a Web MIDI port map whose name starts with "Neutron", like the native
+/// frontend looks for.
+fn find_neutron_port<T: JsCast>(ports: &JsValue) -> Result<T, JsValue> {
+    let values = js_sys::Reflect::get(ports, &JsValue::from_str("values"))?
+        .dyn_into::<Function>()?
+        .call0(ports)?;
+    let iterator = js_sys::try_iter(&values)?.ok_or("MIDI ports are not iterable")?;
+    for port in iterator {
+        let port = port?;
+        let name = js_sys::Reflect::get(&port, &JsValue::from_str("name"))?;
+        if name.as_string().is_some_and(|n| n.starts_with("Neutron")) {
+            return port.dyn_into::<T>();
+        }
+    }
+    Err(JsValue::from_str("Could not find Neutron."))
+}
+
+/// A SysEx connection to the Neutron through Web MIDI.
+#[wasm_bindgen]
+pub struct WebMidiConnection {
+    output: MidiOutput,
+    input: MidiInput,
+    // Kept alive for as long as the connection, the browser calls it for every message
+    _on_message: Closure<dyn FnMut(MidiMessageEvent)>,
+}
+
+#[wasm_bindgen]
+impl WebMidiConnection {
+    /// Asks the browser for MIDI access with SysEx enabled and connects to the Neutron.
+    /// `on_message` is called with a `Uint8Array` for every message received.
+    pub async fn connect(on_message: Function) -> Result<WebMidiConnection, JsValue> {
+        let window = web_sys::window().ok_or("No window")?;
+        let options = MidiOptions::new();
+        options.set_sysex(true);
+        let access: MidiAccess = JsFuture::from(
+            window
+                .navigator()
+                .request_midi_access_with_options(&options)?,
+        )
+        .await?
+        .dyn_into()?;
+        let output: MidiOutput = find_neutron_port(&access.outputs())?;
+        let input: MidiInput = find_neutron_port(&access.inputs())?;
+        let on_message = Closure::wrap(Box::new(move |event: MidiMessageEvent| {
+            if let Ok(data) = event.data() {
+                let _ = on_message.call1(&JsValue::NULL, &Uint8Array::from(data.as_slice()));
+            }
+        }) as Box<dyn FnMut(MidiMessageEvent)>);
+        input.set_onmidimessage(Some(on_message.as_ref().unchecked_ref()));
+        Ok(WebMidiConnection {
+            output,
+            input,
+            _on_message: on_message,
+        })
+    }
+
+    pub fn send(&self, message: &[u8]) -> Result<(), JsValue> {
+        self.output.send(&Uint8Array::from(message))
+    }
+}
+
+impl Drop for WebMidiConnection {
+    fn drop(&mut self) {
+        self.input.set_onmidimessage(None);
+    }
+}
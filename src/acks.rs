@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use rustron_lib::protocol::GlobalSetting;
+
+/// How long to wait for a `GlobalSettingUpdate` after sending a `SetGlobalSetting`.
+pub const DEFAULT_ACK_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// Keeps track of sent settings that the Neutron hasn't acknowledged yet, keyed by opcode.
+pub struct AckTracker {
+    pending: HashMap<u8, (GlobalSetting, Instant)>,
+    timeout: Duration,
+    pub timed_out: usize,
+}
+
+impl AckTracker {
+    pub fn new(timeout: Duration) -> AckTracker {
+        AckTracker {
+            pending: HashMap::new(),
+            timeout,
+            timed_out: 0,
+        }
+    }
+
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn sent(&mut self, global_setting: GlobalSetting, now: Instant) {
+        self.pending
+            .insert(global_setting.opcode(), (global_setting, now));
+    }
+
+    /// Whether a command for the setting waits for an acknowledgment.
+    pub fn is_pending(&self, global_setting: &GlobalSetting) -> bool {
+        self.pending.contains_key(&global_setting.opcode())
+    }
+
+    /// Clears the pending acknowledgment for the setting, returns `false` if none was pending.
+    pub fn acknowledged(&mut self, global_setting: GlobalSetting) -> bool {
+        self.pending.remove(&global_setting.opcode()).is_some()
+    }
+
+    /// Removes and returns the settings that have waited longer than the timeout at `now`.
+    pub fn expire(&mut self, now: Instant) -> Vec<GlobalSetting> {
+        let timeout = self.timeout;
+        let expired: Vec<u8> = self
+            .pending
+            .iter()
+            .filter(|(_, (_, sent))| now.duration_since(*sent) > timeout)
+            .map(|(opcode, _)| *opcode)
+            .collect();
+        self.timed_out += expired.len();
+        expired
+            .iter()
+            .filter_map(|opcode| self.pending.remove(opcode))
+            .map(|(global_setting, _)| global_setting)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use rustron_lib::protocol::GlobalSetting::{OscSync, ParaphonicMode};
+    use rustron_lib::protocol::ToggleOption::{Off, On};
+
+    use crate::acks::AckTracker;
+
+    #[test]
+    fn unacknowledged_settings_time_out() {
+        let mut acks = AckTracker::new(Duration::from_millis(100));
+        let start = Instant::now();
+        acks.sent(ParaphonicMode(On), start);
+        acks.sent(OscSync(On), start);
+        assert_eq!(acks.pending(), 2);
+        assert!(acks.is_pending(&OscSync(Off)));
+        assert!(acks.acknowledged(OscSync(Off)));
+        assert!(!acks.is_pending(&OscSync(Off)));
+        assert!(acks.expire(start).is_empty());
+        assert_eq!(
+            acks.expire(start + Duration::from_millis(200)),
+            vec![ParaphonicMode(On)]
+        );
+        assert_eq!(acks.pending(), 0);
+        assert_eq!(acks.timed_out, 1);
+    }
+}
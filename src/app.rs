@@ -1,106 +1,66 @@
-use log::{error, info, warn, LevelFilter, Record};
+use arboard::Clipboard;
+use log::{error, info, warn};
 use termion::event::Key;
 
-use rustron_lib::parser::neutron_message;
+use rustron_lib::firmware::{version_from_name, Firmware};
+use rustron_lib::preset::{Cable, PatchSheet, Preset};
 use rustron_lib::protocol;
 use rustron_lib::protocol::{
     BlendMode::{Blend, Switch},
-    DeviceId::Multicast,
-    GlobalSetting,
+    Channel, DeviceClass, DeviceId, GlobalSetting,
     GlobalSetting::{
         LfoBlendMode, LfoKeySync, LfoMidiSync, LfoOneShot, LfoResetOrder, LfoRetrigger,
         Osc1BlendMode, Osc1Range, Osc1TunePotBypass, Osc2BlendMode, Osc2KeyTrack, Osc2Range,
         Osc2TunePotBypass, OscSync, ParaphonicMode, VcfKeyTracking,
     },
     KeyTrackMode::{Hold, Track},
-    NeutronMessage::SetGlobalSetting,
+    NeutronMessage,
+    NeutronMessage::{SetGlobalSetting, SoftwareVersionRequest, StateDump},
     OscRange::{Eight, PlusMinusTen, Sixteen, ThirtyTwo},
     ToggleOption::{Off, On},
+    NEUTRON_DEVICE,
 };
+use rustron_lib::setting::SettingId;
 
+use crate::autoglide::AutoglideEditor;
+use crate::calibration::CalibrationWizard;
+use crate::compare::AbCompare;
+use crate::config::Config;
+use crate::core::state::{ChangeSource, SettingChange};
+use crate::core::AppCore;
 use crate::events;
+use crate::events::{AdaptiveTick, KeyPress};
+use crate::handshake::Handshake;
+use crate::history;
+use crate::history::{Coalesce, HistoryEntry, Message, Pause};
+use crate::hotkeys::Hotkey;
+use crate::inspect;
+use crate::inspect::{Inspector, Stream};
+use crate::lfo::LfoEditor;
+use crate::librarian::Librarian;
+use crate::library::PresetLibrary;
+use crate::locale::Locale;
+use crate::macros;
 use crate::midi;
-use flexi_logger::DeferredNow;
-use std::io;
-use std::sync::mpsc;
+use crate::midi::Source;
+use crate::options::Options;
+use crate::poll::PollTracker;
+use crate::randomize::Randomizer;
+use crate::recall::PromptHistory;
+use crate::redraw::{Pane, Redraw};
+use crate::report;
+use crate::risk::Risk;
+use crate::session::{self, LoggedMessage, Session};
+use crate::setup::{SetupStep, SetupWizard};
+use crate::synccheck;
+use std::error;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 mod state {
-    use rustron_lib::protocol::GlobalSetting;
-    use rustron_lib::protocol::NeutronMessage;
-
-    #[derive(Default)]
-    pub struct GlobalSettingsState {
-        // TODO device_id stuff
-        device_id: u8,
-        paraphonic_mode: bool,
-        osc_sync: bool,
-    }
-
-    #[derive(Default)]
-    pub struct NeutronState {
-        global_settings: GlobalSettingsState,
-    }
-
-    impl NeutronState {
-        pub fn new() -> NeutronState {
-            // TODO device_id
-            Default::default()
-        }
-
-        fn global_setting_update(&mut self, global_setting: GlobalSetting) {
-            match global_setting {
-                GlobalSetting::ParaphonicMode(t) => self.global_settings.paraphonic_mode = t.into(),
-                GlobalSetting::OscSync(_) => {}
-                GlobalSetting::Osc1BlendMode(_) => {}
-                GlobalSetting::Osc2BlendMode(_) => {}
-                GlobalSetting::Osc1TunePotBypass(_) => {}
-                GlobalSetting::Osc2TunePotBypass(_) => {}
-                GlobalSetting::Osc1Range(_) => {}
-                GlobalSetting::Osc2Range(_) => {}
-                GlobalSetting::Osc2KeyTrack(_) => {}
-                GlobalSetting::Osc1Autoglide(_) => {}
-                GlobalSetting::Osc2Autoglide(_) => {}
-                GlobalSetting::LfoBlendMode(_) => {}
-                GlobalSetting::LfoKeySync(_) => {}
-                GlobalSetting::LfoOneShot(_) => {}
-                GlobalSetting::LfoRetrigger(_) => {}
-                GlobalSetting::LfoMidiSync(_) => {}
-                GlobalSetting::LfoDepth(_) => {}
-                GlobalSetting::LfoShapeOrder(_, _) => {}
-                GlobalSetting::LfoShapePhase(_, _) => {}
-                GlobalSetting::LfoResetOrder => {}
-                GlobalSetting::VcfKeyTracking(_) => {}
-                GlobalSetting::VcfModDepth(_) => {}
-                GlobalSetting::VcfModSource(_) => {}
-                GlobalSetting::MidiChannel(_) => {}
-                GlobalSetting::DisableMidiDips(_) => {}
-                GlobalSetting::PolyChainMode(_) => {}
-                GlobalSetting::KeyRangeMute(_) => {}
-                GlobalSetting::KeyRangeReset => {}
-                GlobalSetting::AssignOut(_) => {}
-                GlobalSetting::EnvRetriggerMode(_) => {}
-            }
-        }
-
-        pub fn update(&mut self, message: NeutronMessage) {
-            match message {
-                NeutronMessage::SetGlobalSetting(_, global_setting) => {
-                    // Messages sent to the Neutron
-                    self.global_setting_update(global_setting)
-                }
-                NeutronMessage::GlobalSettingUpdate(_, global_setting) => {
-                    // Messages sent from the Neutron
-                    self.global_setting_update(global_setting)
-                }
-                NeutronMessage::RestoreGlobalSetting(_) => {}
-                NeutronMessage::CalibrationModeCommand(_) => {}
-                NeutronMessage::SoftwareVersionRequest(_) => {}
-                NeutronMessage::SoftwareVersionResponse(_, _) => {}
-            }
-        }
-    }
-
     pub struct ListState<T> {
         pub items: Vec<T>,
         pub selection: usize,
@@ -127,6 +87,44 @@ mod state {
         }
     }
 
+    pub enum PromptAction {
+        SavePreset,
+        RenamePreset(String),
+        MidiChannel,
+        DipSwitches,
+        SetSetting,
+        Profile,
+        SaveDump,
+        RequestDump,
+        AddWorkspace,
+        ExportReport,
+        /// Adds a note to the patch sheet of the preset
+        AddNote(String),
+        AddCable(String),
+        InspectFirmware,
+        /// Confirms sending the inspected firmware by typing its checksum
+        SendFirmware,
+        /// Confirms the command held back by the core, see `AppCore::confirm`
+        Confirm,
+    }
+
+    /// A single line text input, shown in the status bar
+    pub struct Prompt {
+        pub label: String,
+        pub text: String,
+        pub action: PromptAction,
+    }
+
+    impl Prompt {
+        pub fn new(label: &str, text: &str, action: PromptAction) -> Prompt {
+            Prompt {
+                label: label.to_string(),
+                text: text.to_string(),
+                action,
+            }
+        }
+    }
+
     pub struct TabsState<'a> {
         pub titles: Vec<&'a str>,
         pub index: usize,
@@ -136,6 +134,9 @@ mod state {
         pub fn new(titles: Vec<&'a str>) -> TabsState {
             TabsState { titles, index: 0 }
         }
+        pub fn current(&self) -> &'a str {
+            self.titles[self.index]
+        }
         pub fn next(&mut self) {
             self.index = (self.index + 1) % self.titles.len();
         }
@@ -148,184 +149,1360 @@ mod state {
             }
         }
     }
-
-    #[cfg(test)]
-    mod test {
-        use crate::app::state::NeutronState;
-        use rustron_lib::protocol::Channel::One;
-        use rustron_lib::protocol::DeviceId::Channel;
-        use rustron_lib::protocol::GlobalSetting::ParaphonicMode;
-        use rustron_lib::protocol::NeutronMessage::{GlobalSettingUpdate, SetGlobalSetting};
-        use rustron_lib::protocol::ToggleOption::{Off, On};
-
-        #[test]
-        fn paraphonic_mode_is_updated() {
-            let mut ns = NeutronState::new();
-            assert!(!ns.global_settings.paraphonic_mode);
-            ns.update(SetGlobalSetting(Channel(One), ParaphonicMode(On)));
-            assert!(ns.global_settings.paraphonic_mode);
-            ns.update(GlobalSettingUpdate(Channel(One), ParaphonicMode(Off)));
-            assert!(!ns.global_settings.paraphonic_mode);
-        }
-    }
-}
-
-struct ApplicationLogger {
-    level: LevelFilter,
-    sender: mpsc::SyncSender<String>,
-}
-
-impl ApplicationLogger {
-    fn new(sender: mpsc::SyncSender<String>) -> Self {
-        ApplicationLogger {
-            level: LevelFilter::Trace,
-            sender,
-        }
-    }
 }
 
-impl flexi_logger::writers::LogWriter for ApplicationLogger {
-    fn write(&self, _now: &mut DeferredNow, record: &Record) -> io::Result<()> {
-        self.sender
-            .send(format!(
-                "{}:{} -- {}",
-                record.level(),
-                record.target(),
-                record.args()
-            ))
-            .unwrap();
-        Ok(())
-    }
-
-    fn flush(&self) -> io::Result<()> {
-        Ok(())
-    }
-
-    fn max_log_level(&self) -> LevelFilter {
-        self.level
-    }
+/// Everything that can happen to the application, handled by `App::dispatch` regardless of
+/// where it came from.
+pub enum AppEvent {
+    KeyInput(KeyPress),
+    MidiIn(NeutronMessage),
+    /// A received message that couldn't be parsed
+    MidiRaw(Vec<u8>),
+    /// A message received on another port than the Neutron's, by port name
+    PortIn(Arc<str>, Vec<u8>),
+    LogLine(String),
+    ConnectionChanged(bool),
+    Tick,
 }
 
+/// The terminal frontend: tabs, menus, editors and key bindings on top of an `AppCore`.
 pub struct App {
+    pub core: AppCore,
     pub tabs: state::TabsState<'static>,
-    pub neutron_state: state::NeutronState,
-    pub command_history: Vec<String>,
-    // TODO will grow indefinitely, does it matter?
-    pub midi_in_messages: Vec<Vec<u8>>,
     pub basic_menu: state::ListState<String>,
-    pub log: Vec<String>,
+    pub library: PresetLibrary,
+    pub librarian: Librarian,
+    /// The calibration in the calibrate tab, `None` until started
+    pub calibration: Option<CalibrationWizard>,
+    /// The language of the interface
+    pub locale: Locale,
+    /// The last inspected firmware file, with its path
+    pub firmware: Option<(PathBuf, Firmware)>,
+    pub prompt: Option<state::Prompt>,
+    pub compare: AbCompare,
+    pub lfo_editor: LfoEditor,
+    pub autoglide_editor: AutoglideEditor,
+    pub inspector: Inspector,
+    /// The selected output in the devices tab
+    pub device_selection: usize,
+    /// Shows sent and received messages in one stream on the app tab
+    pub merged_stream: bool,
+    /// Shows only the received messages of one kind of device
+    pub stream_filter: Option<DeviceClass>,
+    /// Shows runs of repeated received messages as one row, see `history::coalesce`
+    pub coalesce: Coalesce,
+    /// Freezes the stream views while set, messages are still received
+    pub paused: Option<Pause>,
+    /// Shows what the selected menu item does
+    pub show_help: bool,
+    /// Number keys toggle the settings bound in the configuration instead of switching
+    /// workspaces
+    pub hotkey_layer: bool,
+    /// The first run setup, shown instead of the tabs while running
+    pub setup: Option<SetupWizard>,
+    /// Name of the last preset loaded from the library
+    pub loaded_preset: Option<String>,
+    /// The settings of that preset, to mark the settings changed since it was loaded
+    pub loaded_settings: Option<Preset>,
+    pub randomizer: Randomizer,
+    /// The last randomized settings, until the state is saved as a preset
+    pub randomized: Option<Preset>,
     pub should_quit: bool,
-    connection: midi::MidiConnection,
-    midi_receiver: Receiver<Vec<u8>>,
-    log_receiver: Receiver<String>,
+    /// The panes changed since the last frame
+    pub redraw: Redraw,
+    /// The commands entered in the set prompt
+    pub prompt_history: PromptHistory,
+    tick_rate: AdaptiveTick,
     events: events::Events,
+    /// Changes of the tracked settings, to notice settings changed on the Neutron itself
+    setting_changes: Receiver<SettingChange>,
+    /// Opened on first use, and kept open since on some platforms the copied text is gone once
+    /// it is closed
+    clipboard: Option<Clipboard>,
 }
 
 impl App {
-    pub fn new() -> App {
-        // Wire up logging
-        let (app_log_sender, app_log_receiver) = mpsc::sync_channel(1000);
-        flexi_logger::Logger::with_env_or_str("info")
-            .log_target(flexi_logger::LogTarget::Writer(Box::new(
-                ApplicationLogger::new(app_log_sender),
-            )))
-            .start()
-            .unwrap();
-
-        let (midi_in_sender, midi_in_receiver) = mpsc::channel();
-        let mut midi_connection = midi::MidiConnection::new();
-        if let Err(error) = midi_connection.register_midi_in_channel(midi_in_sender) {
-            warn!("{}", error);
-        };
-
-        App {
-            tabs: state::TabsState::new(vec!["app", "logs"]),
-            connection: midi_connection,
-            neutron_state: state::NeutronState::new(),
-            command_history: Vec::new(),
-            midi_in_messages: Vec::new(),
-            midi_receiver: midi_in_receiver,
+    /// Sets up the core, see `AppCore::new`, and the terminal input events. The app is then driven
+    /// by `tick`, or by passing events to `dispatch` directly from another frontend.
+    pub fn new(options: &Options) -> App {
+        let (mut core, connected) = AppCore::new(options);
+        let setting_changes = core.neutron_state.subscribe();
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64);
+        let randomizer = Randomizer::new(
+            core.config.randomize.settings.clone(),
+            core.config.randomize.locked.clone(),
+            seed,
+        );
+        let mut app = App {
+            tabs: state::TabsState::new(vec![
+                "app",
+                "state",
+                "presets",
+                "lfo",
+                "glide",
+                "inspect",
+                "stats",
+                "devices",
+                "librarian",
+                "calibrate",
+                "logs",
+            ]),
             basic_menu: state::ListState::new(
                 MENU_MAPPINGS
                     .iter()
                     .map(|(name, _)| name.to_string())
                     .collect(),
             ),
-            log: Vec::new(),
-            log_receiver: app_log_receiver,
+            library: PresetLibrary::new(core.config.presets.directory.clone()),
+            librarian: Librarian::new(core.config.librarian.directory.clone()),
+            calibration: None,
+            locale: Locale::select(core.config.ui.locale),
+            firmware: None,
+            prompt: None,
+            compare: AbCompare::new(),
+            lfo_editor: LfoEditor::new(),
+            autoglide_editor: AutoglideEditor::new(),
+            inspector: Inspector::new(),
+            device_selection: 0,
+            merged_stream: false,
+            stream_filter: None,
+            coalesce: Coalesce::Off,
+            paused: None,
+            show_help: false,
+            hotkey_layer: false,
+            setup: None,
+            loaded_preset: None,
+            loaded_settings: None,
+            randomizer,
+            randomized: None,
             should_quit: false,
             events: events::Events::new(),
+            setting_changes,
+            clipboard: None,
+            redraw: Redraw::new(Instant::now()),
+            prompt_history: PromptHistory::load(PromptHistory::path()),
+            tick_rate: AdaptiveTick::new(
+                core.config.polling.fast_tick,
+                core.config.polling.idle_tick,
+            ),
+            core,
+        };
+        if !Config::exists() && !options.simulate {
+            app.setup = Some(SetupWizard::new(midi::output_port_names()));
         }
+        if app.core.config.session.restore {
+            app.restore_session();
+        }
+        app.dispatch(AppEvent::ConnectionChanged(connected));
+        app
     }
 
-    pub fn command(&mut self, message: &[u8]) {
-        match neutron_message(message) {
-            Ok((_, msg)) => {
-                self.command_history.push(msg.to_string());
+    fn restore_session(&mut self) {
+        let session = match Session::load() {
+            Ok(Some(session)) => session,
+            Ok(None) => return,
+            Err(e) => {
+                warn!("Could not restore session: {}", e);
+                return;
             }
-            Err(_) => self.command_history.push(hex::encode(message)),
+        };
+        self.core.target = session.target;
+        if let Some(index) = self.tabs.titles.iter().position(|t| *t == session.tab) {
+            self.tabs.index = index;
         }
-        if let Err(error) = self.connection.send_message(message) {
-            error!("{}", error);
+        if let Some(name) = &session.preset {
+            self.library.select(name);
+            self.loaded_settings = self.library.load(name).ok();
+        }
+        self.loaded_preset = session.preset;
+        let now = (Instant::now(), SystemTime::now());
+        let restore = |messages: Vec<LoggedMessage>| {
+            messages
+                .iter()
+                .map(|(time, bytes)| HistoryEntry {
+                    time: time.map_or(now.0, |time| session::instant(time, now)),
+                    ..HistoryEntry::new(Message::parse(bytes))
+                })
+                .collect()
         };
+        self.core.command_history = restore(session.sent);
+        self.core.midi_in_messages = restore(session.received);
+        self.core.polls = PollTracker::new();
+        info!(
+            "Restored session with {} sent and {} received messages",
+            self.core.command_history.len(),
+            self.core.midi_in_messages.len()
+        );
     }
 
+    /// Saves the session for the next start, if restoring it is enabled.
+    pub fn save_session(&self) {
+        if !self.core.config.session.restore {
+            return;
+        }
+        let now = (Instant::now(), SystemTime::now());
+        let save = |entries: &[HistoryEntry]| {
+            entries
+                .iter()
+                .map(|entry| {
+                    let time = session::wall_time(entry.time, now);
+                    (Some(time), entry.message.to_bytes())
+                })
+                .collect()
+        };
+        let session = Session {
+            target: self.core.target,
+            tab: self.tabs.current().to_string(),
+            preset: self.loaded_preset.clone(),
+            sent: save(&self.core.command_history),
+            received: save(&self.core.midi_in_messages),
+        };
+        if let Err(e) = session.save() {
+            error!("Could not save session: {}", e);
+        }
+    }
+
+    /// The setting of the selected menu item.
+    pub fn selected_menu_setting(&self) -> GlobalSetting {
+        MENU_MAPPINGS[self.basic_menu.selection].1
+    }
+
+    fn switch_compare_slot(&mut self) {
+        let settings = self.compare.switch(self.core.neutron_state.preset());
+        info!(
+            "Switched to {}, sending {} settings",
+            self.compare.active,
+            settings.len()
+        );
+        let target = self.core.target;
+        self.core.macros.play(
+            settings
+                .iter()
+                .map(|s| SetGlobalSetting(target, *s).as_bytes())
+                .collect(),
+        );
+    }
+
+    /// Like `switch_compare_slot`, but morphs to the other slot over the configured duration.
+    fn morph_compare_slot(&mut self) {
+        let duration = self.core.config.morph.duration;
+        let steps = (duration.as_millis() / macros::DEFAULT_PACING.as_millis()).max(1) as u32;
+        let morph = self.compare.morph(self.core.neutron_state.preset(), steps);
+        info!(
+            "Morphing to {} in {} ms",
+            self.compare.active,
+            duration.as_millis()
+        );
+        let target = self.core.target;
+        self.core.macros.play_timed(
+            morph
+                .iter()
+                .enumerate()
+                .flat_map(|(step, settings)| {
+                    let time = duration * (step as u32 + 1) / steps;
+                    settings
+                        .iter()
+                        .map(move |s| (time, SetGlobalSetting(target, *s).as_bytes()))
+                })
+                .collect(),
+        );
+    }
+
+    /// Sends random values for the unlocked settings selected for randomizing.
+    fn randomize(&mut self) {
+        let preset = Preset::from_settings(&self.randomizer.randomize());
+        info!(
+            "Randomized {} settings, {} locked",
+            preset.settings.len(),
+            self.randomizer.locked.len()
+        );
+        self.core.send_preset(&preset);
+        self.loaded_preset = None;
+        self.loaded_settings = None;
+        self.randomized = Some(preset);
+    }
+
+    /// Locks or unlocks the setting of the selected menu item for randomizing.
+    fn toggle_randomize_lock(&mut self) {
+        let id = self.selected_menu_setting().id();
+        let locked = self.randomizer.toggle_lock(id);
+        info!(
+            "{} {} for randomizing",
+            id.id(),
+            if locked { "locked" } else { "unlocked" }
+        );
+    }
+
+    fn toggle_automation(&mut self) {
+        let automation = &mut self.core.automation;
+        if automation.lanes.is_empty() {
+            warn!("No automation lanes configured");
+            return;
+        }
+        automation.toggle(Instant::now());
+        if automation.is_running() {
+            info!("Automating {} lanes", automation.lanes.len());
+        } else {
+            info!("Automation stopped");
+        }
+    }
+
+    /// Starts MIDI learn for the setting of the selected menu item, or cancels it.
+    fn toggle_midi_learn(&mut self) {
+        if self.core.cc_map.learning.take().is_some() {
+            info!("MIDI learn cancelled");
+            return;
+        }
+        let id = self.selected_menu_setting().id();
+        self.core.cc_map.learn(id);
+        info!("Move a controller to map it to {}", id.id());
+    }
+
+    fn load_selected_preset(&mut self) {
+        if let Some(preset) = self.library.preview.clone() {
+            self.core.send_preset(&preset);
+            self.loaded_preset = self.library.selected_title();
+            self.loaded_settings = Some(preset);
+        }
+    }
+
+    fn delete_selected_preset(&mut self) {
+        if let Some(name) = self.library.selected().map(str::to_string) {
+            match self.library.delete(&name) {
+                Ok(()) => info!("Deleted preset {}", name),
+                Err(error) => error!("Could not delete preset {}: {}", name, error),
+            }
+        }
+    }
+
+    fn prompt_input(&mut self, key: Key) {
+        let prompt = match &mut self.prompt {
+            Some(prompt) => prompt,
+            None => return,
+        };
+        match key {
+            Key::Char('\n') => {
+                if let Some(prompt) = self.prompt.take() {
+                    self.submit_prompt(prompt);
+                }
+            }
+            Key::Esc => {
+                if matches!(prompt.action, state::PromptAction::Confirm) {
+                    self.cancel_confirmation();
+                }
+                self.prompt = None;
+                self.prompt_history.reset();
+            }
+            Key::Backspace => {
+                prompt.text.pop();
+            }
+            Key::Ctrl('p') | Key::Up
+                if matches!(prompt.action, state::PromptAction::SetSetting) =>
+            {
+                if let Some(command) = self.prompt_history.older() {
+                    prompt.text = command.to_string();
+                }
+            }
+            Key::Ctrl('n') | Key::Down
+                if matches!(prompt.action, state::PromptAction::SetSetting) =>
+            {
+                prompt.text = self.prompt_history.newer().unwrap_or("").to_string();
+            }
+            Key::Char(c) => prompt.text.push(c),
+            _ => {}
+        }
+    }
+
+    fn submit_prompt(&mut self, prompt: state::Prompt) {
+        let name = prompt.text.trim();
+        if name.is_empty() {
+            return;
+        }
+        let result = match prompt.action {
+            state::PromptAction::SavePreset => {
+                let mut preset = self.core.neutron_state.preset();
+                // Saving over a preset keeps its patch sheet
+                if let Ok(existing) = self.library.load(name) {
+                    preset.sheet = existing.sheet;
+                }
+                let saved = self.library.save(name, &preset);
+                if saved.is_ok() {
+                    // Nothing is unsaved anymore
+                    self.randomized = None;
+                    self.loaded_preset = Some(name.to_string());
+                    self.loaded_settings = Some(preset);
+                }
+                saved
+            }
+            state::PromptAction::RenamePreset(old_name) => {
+                match self.library.rename(&old_name, name) {
+                    Ok(()) => info!("Renamed preset {} to {}", old_name, name),
+                    Err(error) => error!("Could not rename preset {}: {}", old_name, error),
+                }
+                return;
+            }
+            state::PromptAction::SaveDump => {
+                match self.librarian.save_received(name) {
+                    Ok(()) => info!("Saved dump {}", name),
+                    Err(error) => error!("Could not save dump {}: {}", name, error),
+                }
+                return;
+            }
+            state::PromptAction::RequestDump => {
+                match hex::decode(name.replace(' ', "")) {
+                    Ok(request) => {
+                        self.librarian.start_receiving();
+                        self.core.command(&request);
+                    }
+                    Err(error) => error!("Invalid request {}: {}", name, error),
+                }
+                return;
+            }
+            state::PromptAction::AddNote(preset) => {
+                self.edit_sheet(&preset, |sheet| {
+                    if name == "-" {
+                        *sheet = PatchSheet::default();
+                    } else {
+                        sheet.notes.push(name.to_string());
+                    }
+                    Ok(())
+                });
+                return;
+            }
+            state::PromptAction::AddCable(preset) => {
+                self.edit_sheet(&preset, |sheet| {
+                    let cable = Cable::parse(name)
+                        .ok_or_else(|| format!("Expected from -> to, got {}", name))?;
+                    sheet.cables.push(cable);
+                    Ok(())
+                });
+                return;
+            }
+            state::PromptAction::ExportReport => {
+                let sheet = self
+                    .loaded_preset
+                    .as_deref()
+                    .and_then(|name| self.library.load(name).ok())
+                    .map(|preset| preset.sheet);
+                let report = report::render(
+                    &self.core.neutron_state,
+                    self.loaded_preset.as_deref(),
+                    sheet.as_ref(),
+                );
+                if name == "-" {
+                    self.copy_to_clipboard(report);
+                    return;
+                }
+                match fs::write(name, report) {
+                    Ok(()) => info!("Wrote the settings report to {}", name),
+                    Err(error) => error!("Could not write the settings report: {}", error),
+                }
+                return;
+            }
+            state::PromptAction::InspectFirmware => {
+                self.inspect_firmware(Path::new(name));
+                return;
+            }
+            state::PromptAction::Confirm => {
+                if name == "y" || name == "yes" {
+                    self.core.confirm();
+                } else {
+                    self.cancel_confirmation();
+                }
+                return;
+            }
+            state::PromptAction::SendFirmware => {
+                match &self.firmware {
+                    Some((path, firmware)) if name == format!("{:08x}", firmware.checksum()) => {
+                        self.core.start_upload(firmware, path)
+                    }
+                    Some(_) => warn!("Firmware not sent, the checksum didn't match"),
+                    None => {}
+                }
+                return;
+            }
+            state::PromptAction::AddWorkspace => {
+                let target = match name {
+                    "all" => Some(DeviceId::Multicast),
+                    number => number
+                        .parse()
+                        .ok()
+                        .and_then(Channel::from_number)
+                        .map(DeviceId::Channel),
+                };
+                match target.map(|target| self.core.workspaces.add(target)) {
+                    Some(Some(index)) => self.switch_workspace(index),
+                    Some(None) => error!("There already is a workspace for {}", name),
+                    None => error!("Invalid MIDI channel {}, expected 1-16 or all", name),
+                }
+                return;
+            }
+            state::PromptAction::MidiChannel => {
+                match name.parse().ok().and_then(Channel::from_number) {
+                    Some(channel) => self.core.start_channel_wizard(channel),
+                    None => error!("Invalid MIDI channel {}, expected 1-16", name),
+                }
+                return;
+            }
+            state::PromptAction::DipSwitches => {
+                match name.parse() {
+                    Ok(dip_switches) => {
+                        info!("DIP switches set to {}", dip_switches);
+                        self.core.neutron_state.dip_switches = Some(dip_switches);
+                        for warning in self.core.neutron_state.midi_advice().warnings {
+                            warn!("{}", warning);
+                        }
+                    }
+                    Err(error) => error!("Invalid DIP switches {}: {}", name, error),
+                }
+                return;
+            }
+            state::PromptAction::Profile => {
+                let profile = Some(name).filter(|name| *name != "-");
+                match self.core.switch_profile(profile) {
+                    Ok(()) => {
+                        self.library =
+                            PresetLibrary::new(self.core.config.presets.directory.clone())
+                    }
+                    Err(error) => error!("Could not switch profile: {}", error),
+                }
+                return;
+            }
+            state::PromptAction::SetSetting => {
+                let mut parts = name.splitn(2, char::is_whitespace);
+                let setting = GlobalSetting::from_name_value(
+                    parts.next().unwrap_or(""),
+                    parts.next().unwrap_or(""),
+                );
+                match setting {
+                    Ok(setting) => {
+                        // Only settings that parse are worth recalling
+                        if let Err(error) = self.prompt_history.add(name) {
+                            warn!("Could not save the prompt history: {}", error);
+                        }
+                        self.core
+                            .command_message(&SetGlobalSetting(self.core.target, setting));
+                    }
+                    Err(error) => error!("{}", error),
+                }
+                return;
+            }
+        };
+        match result {
+            Ok(()) => info!("Saved preset {}", name),
+            Err(error) => error!("Could not save preset {}: {}", name, error),
+        }
+    }
+
+    /// Changes the patch sheet of the preset `name` and saves it.
+    fn edit_sheet<F>(&mut self, name: &str, edit: F)
+    where
+        F: FnOnce(&mut PatchSheet) -> Result<(), Box<dyn error::Error>>,
+    {
+        let result = self.library.load(name).and_then(|mut preset| {
+            edit(&mut preset.sheet)?;
+            self.library.save(name, &preset)
+        });
+        match result {
+            Ok(()) => {
+                self.library.select(name);
+                info!("Updated the patch sheet of {}", name)
+            }
+            Err(error) => error!("Could not update the patch sheet of {}: {}", name, error),
+        }
+    }
+
+    fn presets_input(&mut self, key: Key) {
+        match key {
+            Key::Char('\n') => self.load_selected_preset(),
+            Key::Char('S') => {
+                self.prompt = Some(state::Prompt::new(
+                    self.locale.text("prompt.save_preset"),
+                    "",
+                    state::PromptAction::SavePreset,
+                ))
+            }
+            Key::Char('R') => {
+                if let Some(name) = self.library.selected().map(str::to_string) {
+                    self.prompt = Some(state::Prompt::new(
+                        self.locale.text("prompt.rename_preset"),
+                        &name,
+                        state::PromptAction::RenamePreset(name.clone()),
+                    ))
+                }
+            }
+            Key::Char('D') => self.delete_selected_preset(),
+            Key::Char('n') => {
+                if let Some(name) = self.library.selected().map(str::to_string) {
+                    self.prompt = Some(state::Prompt::new(
+                        self.locale.text("prompt.note"),
+                        "",
+                        state::PromptAction::AddNote(name),
+                    ))
+                }
+            }
+            Key::Char('J') => {
+                if let Some(name) = self.library.selected().map(str::to_string) {
+                    self.prompt = Some(state::Prompt::new(
+                        self.locale.text("prompt.cable"),
+                        "",
+                        state::PromptAction::AddCable(name),
+                    ))
+                }
+            }
+            Key::Down => self.library.select_next(),
+            Key::Up => self.library.select_previous(),
+            _ => self.global_input(key),
+        }
+    }
+
+    fn lfo_input(&mut self, key: Key) {
+        match key {
+            Key::Char('\n') => {
+                for global_setting in self.lfo_editor.selected_settings().iter() {
+                    self.core
+                        .command_message(&SetGlobalSetting(self.core.target, *global_setting));
+                }
+            }
+            Key::Down => self.lfo_editor.select_next(),
+            Key::Up => self.lfo_editor.select_previous(),
+            Key::Right => self.lfo_editor.step_shape(true),
+            Key::Left => self.lfo_editor.step_shape(false),
+            Key::Char(']') => self.lfo_editor.step_phase(true),
+            Key::Char('[') => self.lfo_editor.step_phase(false),
+            _ => self.global_input(key),
+        }
+    }
+
+    fn autoglide_input(&mut self, press: KeyPress) {
+        let step = self
+            .autoglide_editor
+            .selected_setting()
+            .id()
+            .range()
+            .map_or(1, |range| press.step(range));
+        match press.key {
+            Key::Char('\n') => {
+                self.core.command_message(&SetGlobalSetting(
+                    self.core.target,
+                    self.autoglide_editor.selected_setting(),
+                ));
+            }
+            Key::Down | Key::Up => self.autoglide_editor.toggle_selection(),
+            Key::Right => self.autoglide_editor.step(step),
+            Key::Left => self.autoglide_editor.step(-step),
+            key => self.global_input(key),
+        }
+    }
+
+    /// The messages of the stream shown by the inspector.
+    pub fn inspected_stream(&self) -> &[HistoryEntry] {
+        match self.inspector.stream {
+            Stream::Received => &self.core.midi_in_messages,
+            Stream::Sent => &self.core.command_history,
+        }
+    }
+
+    fn toggle_bookmark(&mut self) {
+        let stream = match self.inspector.stream {
+            Stream::Received => &mut self.core.midi_in_messages,
+            Stream::Sent => &mut self.core.command_history,
+        };
+        if let Some(selected) = self.inspector.selected(stream.len()) {
+            let entry = &mut stream[selected];
+            entry.bookmarked = !entry.bookmarked;
+        }
+    }
+
+    /// Writes the bookmarked messages of both streams to a script, in the order they were sent
+    /// or received.
+    fn export_bookmarks(&self) {
+        let messages: Vec<Vec<u8>> =
+            history::merge(&self.core.command_history, &self.core.midi_in_messages)
+                .into_iter()
+                .filter(|(_, entry)| entry.bookmarked)
+                .map(|(_, entry)| entry.message.to_bytes())
+                .collect();
+        match inspect::export_bookmarks(&messages) {
+            Ok(path) => info!(
+                "Exported {} bookmarked messages to {}",
+                messages.len(),
+                path.display()
+            ),
+            Err(error) => error!("Could not export bookmarks: {}", error),
+        }
+    }
+
+    /// Logs how the inspected state dump differs from the one before it in the same stream.
+    fn diff_with_previous_dump(&self) {
+        let stream = self.inspected_stream();
+        let selected = match self.inspector.selected(stream.len()) {
+            Some(selected) => selected,
+            None => return,
+        };
+        let is_dump =
+            |entry: &HistoryEntry| matches!(entry.message, Message::Parsed(StateDump(..)));
+        if !is_dump(&stream[selected]) {
+            warn!("The inspected message is not a state dump");
+            return;
+        }
+        match stream[..selected].iter().rposition(is_dump) {
+            Some(previous) => {
+                info!("State dump changes since message {}:", previous);
+                let before = stream[previous].message.to_bytes();
+                for line in inspect::diff_dumps(&before, &stream[selected].message.to_bytes()) {
+                    info!("  {}", line);
+                }
+            }
+            None => warn!("No earlier state dump to compare with"),
+        }
+    }
+
+    fn inspected_message(&self) -> Option<&HistoryEntry> {
+        let stream = self.inspected_stream();
+        stream.get(self.inspector.selected(stream.len())?)
+    }
+
+    fn copy_to_clipboard(&mut self, text: String) {
+        if self.clipboard.is_none() {
+            match Clipboard::new() {
+                Ok(clipboard) => self.clipboard = Some(clipboard),
+                Err(error) => {
+                    error!("Could not open the clipboard: {}", error);
+                    return;
+                }
+            }
+        }
+        if let Some(clipboard) = &mut self.clipboard {
+            match clipboard.set_text(text.as_str()) {
+                Ok(()) => info!("Copied {}", text),
+                Err(error) => error!("Could not copy to the clipboard: {}", error),
+            }
+        }
+    }
+
+    fn inspect_input(&mut self, key: Key) {
+        let count = self.inspected_stream().len();
+        match key {
+            Key::Down => self.inspector.select_next(count),
+            Key::Up => self.inspector.select_previous(count),
+            Key::End => self.inspector.follow(),
+            Key::Left | Key::Right => self.inspector.toggle_stream(),
+            Key::Char('h') => {
+                if let Some(entry) = self.inspected_message() {
+                    let text = hex::encode(entry.message.to_bytes());
+                    self.copy_to_clipboard(text);
+                }
+            }
+            Key::Char('H') => {
+                if let Some(entry) = self.inspected_message() {
+                    let text = entry.text.clone();
+                    self.copy_to_clipboard(text);
+                }
+            }
+            Key::Char('R') => {
+                if let Some(bytes) = self.inspected_message().map(|e| e.message.to_bytes()) {
+                    self.core.command(&bytes);
+                }
+            }
+            Key::Char('*') => self.toggle_bookmark(),
+            Key::Char(c @ ']') | Key::Char(c @ '[') => {
+                // Borrowing the stream by field keeps the inspector mutable
+                let stream = match self.inspector.stream {
+                    Stream::Received => &self.core.midi_in_messages,
+                    Stream::Sent => &self.core.command_history,
+                };
+                self.inspector.select_bookmark(stream, c == ']')
+            }
+            Key::Char('E') => self.export_bookmarks(),
+            Key::Char('D') => self.diff_with_previous_dump(),
+            _ => self.global_input(key),
+        }
+    }
+
+    fn devices_input(&mut self, key: Key) {
+        let count = self.core.outputs().len();
+        match key {
+            Key::Down => self.device_selection = (self.device_selection + 1) % count,
+            Key::Up => self.device_selection = (self.device_selection + count - 1) % count,
+            Key::Char('\n') | Key::Char(' ') => self.core.toggle_output(self.device_selection),
+            Key::Char('C') => info!("Cleared {} queued commands", self.core.offline.clear()),
+            _ => self.global_input(key),
+        }
+    }
+
+    fn librarian_input(&mut self, key: Key) {
+        match key {
+            Key::Char('\n') => {
+                if let Some(dump) = self.librarian.selected() {
+                    info!("Sending dump {}", dump.name);
+                    let messages = dump.messages.clone();
+                    self.core.macros.play(messages);
+                }
+            }
+            Key::Char('R') => {
+                if self.librarian.receiving.is_some() {
+                    let count = self.librarian.stop_receiving();
+                    info!("Received {} SysEx messages", count);
+                    if count > 0 {
+                        self.prompt = Some(state::Prompt::new(
+                            self.locale.text("prompt.save_dump"),
+                            "",
+                            state::PromptAction::SaveDump,
+                        ))
+                    }
+                } else {
+                    self.librarian.start_receiving();
+                    info!("Receiving SysEx, R to stop");
+                }
+            }
+            Key::Char('Q') => {
+                self.prompt = Some(state::Prompt::new(
+                    self.locale.text("prompt.dump_request"),
+                    "",
+                    state::PromptAction::RequestDump,
+                ))
+            }
+            Key::Char('S') if self.librarian.received.is_some() => {
+                self.prompt = Some(state::Prompt::new(
+                    self.locale.text("prompt.save_dump"),
+                    "",
+                    state::PromptAction::SaveDump,
+                ))
+            }
+            Key::Char('D') => {
+                if let Some(name) = self.librarian.selected().map(|dump| dump.name.clone()) {
+                    match self.librarian.delete(&name) {
+                        Ok(()) => info!("Deleted dump {}", name),
+                        Err(error) => error!("Could not delete dump {}: {}", name, error),
+                    }
+                }
+            }
+            Key::Char('F') => {
+                self.prompt = Some(state::Prompt::new(
+                    self.locale.text("prompt.firmware_file"),
+                    "",
+                    state::PromptAction::InspectFirmware,
+                ))
+            }
+            Key::Char('U') => self.confirm_firmware_upload(),
+            Key::Down => self.librarian.select_next(),
+            Key::Up => self.librarian.select_previous(),
+            _ => self.global_input(key),
+        }
+    }
+
+    fn inspect_firmware(&mut self, path: &Path) {
+        let firmware = fs::read(path)
+            .map_err(|error| error.to_string())
+            .and_then(|bytes| Firmware::parse(&bytes).map_err(|error| error.to_string()));
+        match firmware {
+            Ok(mut firmware) => {
+                firmware.version = path
+                    .file_name()
+                    .and_then(|name| version_from_name(&name.to_string_lossy()));
+                info!(
+                    "Firmware for {} (device {:02x}), version {}, {} blocks, {} bytes, CRC-32 {:08x}",
+                    firmware.device_name().unwrap_or("an unknown device"),
+                    firmware.device,
+                    firmware.version.as_deref().unwrap_or("unknown"),
+                    firmware.blocks.len(),
+                    firmware.size(),
+                    firmware.checksum()
+                );
+                self.firmware = Some((path.to_path_buf(), firmware));
+            }
+            Err(error) => error!("Could not read firmware {}: {}", path.display(), error),
+        }
+    }
+
+    /// Asks for the checksum of the inspected firmware before sending it, so that it can't be
+    /// sent by accident or for another device.
+    fn confirm_firmware_upload(&mut self) {
+        let firmware = match &self.firmware {
+            Some((_, firmware)) => firmware,
+            None => {
+                warn!("Inspect a firmware file first (F)");
+                return;
+            }
+        };
+        if firmware.device != NEUTRON_DEVICE {
+            warn!(
+                "Firmware not sent, it is for {} instead of a Neutron",
+                firmware.device_name().unwrap_or("an unknown device")
+            );
+            return;
+        }
+        if self.core.upload.is_some() {
+            warn!("A firmware upload is already running");
+            return;
+        }
+        if !self.core.needs_confirmation(Risk::Destructive) {
+            let (path, firmware) = self.firmware.as_ref().unwrap();
+            self.core.start_upload(firmware, path);
+            return;
+        }
+        self.prompt = Some(state::Prompt::new(
+            &self.locale.format(
+                "prompt.send_firmware",
+                &[&format!("{:08x}", firmware.checksum())],
+            ),
+            "",
+            state::PromptAction::SendFirmware,
+        ));
+    }
+
+    /// Drops the command awaiting confirmation, aborting the calibration if it was the one
+    /// entering calibration mode.
+    fn cancel_confirmation(&mut self) {
+        let cancelled = self.core.cancel_confirmation();
+        if let (Some(wizard), Some(cancelled)) = (&mut self.calibration, cancelled) {
+            wizard.declined(&cancelled, self.core.target);
+        }
+    }
+
+    fn calibration_input(&mut self, key: Key) {
+        match key {
+            Key::Char('\n') => match &mut self.calibration {
+                Some(wizard) if wizard.is_running() => {
+                    if !self.core.connected || self.core.unresponsive {
+                        wizard.abort("The Neutron isn't connected");
+                        warn!("Calibration aborted, the Neutron isn't connected");
+                        return;
+                    }
+                    if let Some(message) = wizard.confirm(self.core.target) {
+                        info!("Entering calibration mode");
+                        self.core.command_message(&message);
+                    }
+                }
+                _ => {
+                    info!("Starting calibration");
+                    self.calibration = Some(CalibrationWizard::new());
+                }
+            },
+            Key::Char('A') => {
+                if let Some(wizard) = &mut self.calibration {
+                    if wizard.is_running() {
+                        wizard.abort("Aborted by the user");
+                        info!("Calibration aborted");
+                    }
+                }
+            }
+            _ => self.global_input(key),
+        }
+    }
+
+    /// Waits for the next terminal event and dispatches it, along with any MIDI messages and log
+    /// lines received since the last tick. Ticks come faster while messages are received.
     pub fn tick(&mut self) {
         // Unwrap since mpsc::RecvError should only happen if a channel is disconnected
-        let event = self.events.next().unwrap();
+        let event = match self.events.next().unwrap() {
+            events::Event::Tick => AppEvent::Tick,
+            events::Event::Input(press) => AppEvent::KeyInput(press),
+        };
+        if let AppEvent::Tick = event {
+            let mut received = Vec::new();
+            while let Some((source, bytes)) = self.core.try_receive_midi() {
+                received.push(match (source, Message::parse(&bytes)) {
+                    (Source::Port(port), _) => AppEvent::PortIn(port, bytes),
+                    (Source::Neutron, Message::Parsed(msg)) => AppEvent::MidiIn(msg),
+                    (Source::Neutron, Message::Raw(bytes)) => AppEvent::MidiRaw(bytes),
+                });
+            }
+            let now = Instant::now();
+            if !received.is_empty() || self.core.sync_check.is_some() {
+                self.tick_rate.traffic(now);
+            }
+            // Ticks come in time for the rate limiter's next message, however idle
+            let mut rate = self.tick_rate.rate(now);
+            if let Some(due) = self.core.limiter.next_due() {
+                rate = rate.min(due.saturating_duration_since(now).max(self.tick_rate.fast));
+            }
+            self.events.set_tick_rate(rate);
+            while let Some(line) = self.core.try_receive_log() {
+                received.push(AppEvent::LogLine(line));
+            }
+            for received_event in received {
+                self.dispatch(received_event);
+            }
+        }
+        self.dispatch(event);
+    }
+
+    /// The settings bound in the hotkey layer, with their expected values if known.
+    pub fn hotkeys(&self) -> Vec<(Hotkey, Option<GlobalSetting>)> {
+        let expected = self.core.neutron_state.expected();
+        self.core
+            .config
+            .hotkeys
+            .toggles
+            .iter()
+            .map(|hotkey| {
+                let current = expected
+                    .settings
+                    .values()
+                    .find(|s| s.id() == hotkey.setting);
+                (*hotkey, current.copied())
+            })
+            .collect()
+    }
 
+    /// Toggles the setting bound to the number `key`.
+    fn toggle_hotkey(&mut self, key: u8) {
+        let bound = self
+            .core
+            .config
+            .hotkeys
+            .toggles
+            .iter()
+            .find(|h| h.key == key);
+        match bound.map(|hotkey| hotkey.setting) {
+            Some(setting) => self.core.toggle(setting),
+            None => warn!("No setting bound to key {}", key),
+        }
+    }
+
+    /// Switches to the workspace at `index`, listening to the changes of its state instead. The
+    /// inspector selection and a pause point into the messages switched away from, so both end.
+    fn switch_workspace(&mut self, index: usize) {
+        if self.core.switch_workspace(index) {
+            self.setting_changes = self.core.neutron_state.subscribe();
+            self.inspector.follow();
+            self.paused = None;
+            info!(
+                "Switched to workspace {}",
+                self.core.workspaces.names(self.core.target)[index]
+            );
+        }
+    }
+
+    /// The panes shown on the current tab, to only draw when they changed.
+    pub fn visible_panes(&self) -> &'static [Pane] {
+        if self.setup.is_some() {
+            return &[Pane::Other, Pane::Status];
+        }
+        match self.tabs.current() {
+            "app" => &[Pane::Streams, Pane::State, Pane::Other, Pane::Status],
+            "state" | "lfo" | "glide" => &[Pane::State, Pane::Other, Pane::Status],
+            "inspect" | "librarian" => &[Pane::Streams, Pane::Other, Pane::Status],
+            "stats" => &[Pane::Stats, Pane::Status],
+            "logs" => &[Pane::Logs, Pane::Status],
+            _ => &[Pane::Other, Pane::Status],
+        }
+    }
+
+    /// Marks the panes the event changes for redrawing.
+    fn mark_changed(&mut self, event: &AppEvent) {
         match event {
-            events::Event::Tick => {
-                // Receive midi messages
-                if let Ok(msg) = self.midi_receiver.try_recv() {
-                    self.midi_in_messages.push(msg)
-                }
-                // Receive logs
-                if let Ok(log_msg) = self.log_receiver.try_recv() {
-                    self.log.push(log_msg)
-                }
-            }
-            events::Event::Input(key) => {
-                match key {
-                    Key::Char('q') => self.should_quit = true,
-                    Key::Char('s') => self.command(protocol::maybe_request_state().as_slice()),
-                    Key::Char('P') => self.command(
-                        SetGlobalSetting(Multicast, ParaphonicMode(On))
-                            .as_bytes()
-                            .as_slice(),
-                    ),
-                    Key::Char('p') => self.command(
-                        SetGlobalSetting(Multicast, ParaphonicMode(Off))
-                            .as_bytes()
-                            .as_slice(),
-                    ),
-                    Key::Char('Y') => self.command(
-                        SetGlobalSetting(Multicast, OscSync(On))
-                            .as_bytes()
-                            .as_slice(),
-                    ),
-                    Key::Char('y') => self.command(
-                        SetGlobalSetting(Multicast, OscSync(Off))
-                            .as_bytes()
-                            .as_slice(),
-                    ),
-
-                    // Menu stuff
-                    Key::Char('\n') => self.command(
-                        SetGlobalSetting(Multicast, MENU_MAPPINGS[self.basic_menu.selection].1)
-                            .as_bytes()
-                            .as_slice(),
-                    ),
-                    Key::Char('\t') => self.tabs.next(),
-                    Key::Down => {
-                        self.basic_menu.select_next();
+            AppEvent::MidiIn(_) | AppEvent::MidiRaw(_) | AppEvent::PortIn(_, _) => {
+                self.redraw.mark(Pane::Streams);
+                self.redraw.mark(Pane::State);
+                self.redraw.mark(Pane::Stats);
+            }
+            AppEvent::LogLine(_) => self.redraw.mark(Pane::Logs),
+            AppEvent::Tick => self.redraw.tick(Instant::now()),
+            AppEvent::KeyInput(_) | AppEvent::ConnectionChanged(_) => self.redraw.mark_all(),
+        }
+    }
+
+    /// Handles one event, the only way the app state changes.
+    pub fn dispatch(&mut self, event: AppEvent) {
+        self.mark_changed(&event);
+        match event {
+            AppEvent::KeyInput(press) => self.key_input(press),
+            AppEvent::MidiIn(msg) => {
+                self.librarian.receive(&msg.as_bytes());
+                self.core.receive(msg)
+            }
+            AppEvent::MidiRaw(bytes) => {
+                self.librarian.receive(&bytes);
+                self.core.receive_raw(bytes)
+            }
+            AppEvent::PortIn(port, bytes) => {
+                self.librarian.receive(&bytes);
+                self.core.receive_from_port(&port, bytes)
+            }
+            AppEvent::LogLine(line) => self.core.log.push(line),
+            AppEvent::ConnectionChanged(connected) => self.core.connection_changed(connected),
+            AppEvent::Tick => {
+                let now = Instant::now();
+                let sent = self.core.command_history.len();
+                self.core.tick(now);
+                // Sent by the rate limiter, macros or automation
+                if self.core.command_history.len() != sent {
+                    self.redraw.mark(Pane::Streams);
+                    self.redraw.mark(Pane::State);
+                    self.redraw.mark(Pane::Stats);
+                }
+                let prompting = self.prompt.is_some();
+                self.ask_confirmation();
+                if self.prompt.is_some() != prompting {
+                    self.redraw.mark(Pane::Other);
+                    self.redraw.mark(Pane::Status);
+                }
+                self.log_external_changes();
+                if let Some(wizard) = self.calibration.as_mut().filter(|w| w.is_running()) {
+                    if !self.core.connected || self.core.unresponsive {
+                        wizard.abort("The Neutron stopped responding");
+                        warn!("Calibration aborted, the Neutron stopped responding");
+                        self.redraw.mark(Pane::Other);
+                    }
+                }
+                if let Some(setup) = &mut self.setup {
+                    let state = &self.core.neutron_state;
+                    let answered = state.software_version.is_some() && state.state_dump.is_some();
+                    let step = setup.step;
+                    setup.update(answered, self.core.config.handshake.timeout, now);
+                    if setup.step != step {
+                        self.redraw.mark(Pane::Other);
+                        self.redraw.mark(Pane::Status);
                     }
-                    Key::Up => {
-                        self.basic_menu.select_previous();
+                }
+            }
+        }
+    }
+
+    /// Opens a prompt for the command the core holds back until confirmed.
+    fn ask_confirmation(&mut self) {
+        if self.prompt.is_some() {
+            return;
+        }
+        if let Some(bytes) = &self.core.awaiting_confirmation {
+            self.prompt = Some(state::Prompt::new(
+                &self.locale.format(
+                    "prompt.confirm",
+                    &[&Message::parse(bytes), &Risk::of(bytes).name()],
+                ),
+                "",
+                state::PromptAction::Confirm,
+            ));
+        }
+    }
+
+    /// Logs settings the Neutron reports with another value than was last sent, e.g. because
+    /// they were changed in another app.
+    fn log_external_changes(&mut self) {
+        let commanded = &self.core.neutron_state.commanded.settings;
+        for change in self.setting_changes.try_iter() {
+            if change.source == ChangeSource::Confirmed
+                && commanded.get(&change.setting.key()) != Some(&change.setting)
+            {
+                info!(
+                    "{} changed on the Neutron to {}",
+                    change.setting.id().id(),
+                    change.setting.value_text()
+                );
+            }
+        }
+    }
+
+    fn setup_input(&mut self, key: Key) {
+        let step = match &self.setup {
+            Some(setup) => setup.step,
+            None => return,
+        };
+        match (step, key) {
+            (SetupStep::SelectPort, Key::Down) => self.setup.as_mut().unwrap().select_next(),
+            (SetupStep::SelectPort, Key::Up) => self.setup.as_mut().unwrap().select_previous(),
+            (SetupStep::SelectPort, Key::Char('\n')) => {
+                let setup = self.setup.as_mut().unwrap();
+                if let Some(port) = setup.selected_port() {
+                    self.core.config.device.port = port.to_string();
+                    setup.start_query(Instant::now());
+                    if self.core.connect_device() {
+                        for request in Handshake::requests() {
+                            self.core.command(&request);
+                        }
                     }
-                    _ => {}
                 }
             }
+            // Skipping keeps the defaults, written so the setup doesn't run again
+            (SetupStep::SelectPort, Key::Esc) | (SetupStep::Done, Key::Char('\n')) => {
+                match self.core.config.save() {
+                    Ok(()) => info!("Saved configuration"),
+                    Err(error) => error!("Could not save configuration: {}", error),
+                }
+                self.setup = None;
+            }
+            (_, Key::Char('q')) => self.should_quit = true,
+            _ => {}
+        }
+    }
+
+    fn key_input(&mut self, press: KeyPress) {
+        let key = press.key;
+        if self.setup.is_some() {
+            self.setup_input(key)
+        } else if self.prompt.is_some() {
+            self.prompt_input(key)
+        } else if self.tabs.current() == "presets" {
+            self.presets_input(key)
+        } else if self.tabs.current() == "lfo" {
+            self.lfo_input(key)
+        } else if self.tabs.current() == "glide" {
+            self.autoglide_input(press)
+        } else if self.tabs.current() == "inspect" {
+            self.inspect_input(key)
+        } else if self.tabs.current() == "devices" {
+            self.devices_input(key)
+        } else if self.tabs.current() == "librarian" {
+            self.librarian_input(key)
+        } else if self.tabs.current() == "calibrate" {
+            self.calibration_input(key)
+        } else {
+            self.global_input(key)
+        }
+    }
+
+    fn global_input(&mut self, key: Key) {
+        match key {
+            Key::Char('q') => self.should_quit = true,
+            Key::Char('s') => {
+                self.core
+                    .command(protocol::maybe_request_state().as_slice());
+            }
+            Key::Char('p') => self.core.toggle(SettingId::ParaphonicMode),
+            Key::Char('y') => self.core.toggle(SettingId::OscSync),
+
+            Key::Char('m') => self.core.toggle_macro_recording(),
+            Key::Char('M') => self.merged_stream = !self.merged_stream,
+            Key::Char('v') => {
+                self.stream_filter = match self.stream_filter {
+                    None => Some(DeviceClass::Neutron),
+                    Some(DeviceClass::Neutron) => Some(DeviceClass::OtherBehringer),
+                    Some(DeviceClass::OtherBehringer) => Some(DeviceClass::OtherSysEx),
+                    Some(DeviceClass::OtherSysEx) => Some(DeviceClass::NotSysEx),
+                    Some(DeviceClass::NotSysEx) => None,
+                }
+            }
+            Key::Char('g') => self.coalesce = self.coalesce.next(),
+            Key::Char('f') => {
+                self.paused = match self.paused {
+                    Some(_) => None,
+                    None => Some(Pause::new(
+                        &self.core.command_history,
+                        &self.core.midi_in_messages,
+                    )),
+                }
+            }
+            Key::Char('?') => self.show_help = !self.show_help,
+            Key::Char('u') => self.core.resend_unconfirmed(),
+            Key::Char('e') => self.core.export_history(),
+            Key::Char('w') => self.core.export_session(),
+            Key::Char('W') => self.core.import_session(),
+            Key::Char('i') => self.core.import_script(),
+            Key::Char('x') => self.core.export_preset(),
+            Key::Char('X') => self.core.import_preset(),
+            Key::Char('r') => {
+                self.core.poller.toggle();
+                info!(
+                    "State polling {}",
+                    if self.core.poller.enabled {
+                        "on"
+                    } else {
+                        "off"
+                    }
+                );
+            }
+            Key::Char('l') => {
+                self.core
+                    .command_message(&SoftwareVersionRequest(self.core.target));
+            }
+            Key::Char('t') => {
+                self.core.through.enabled = !self.core.through.enabled;
+                info!(
+                    "Through {}",
+                    if self.core.through.enabled {
+                        "on"
+                    } else {
+                        "off"
+                    }
+                );
+            }
+            Key::F(n) => self.core.play_macro(n),
+            Key::Char('b') => self.switch_compare_slot(),
+            Key::Char('B') => self.morph_compare_slot(),
+            Key::Char('z') => self.randomize(),
+            Key::Char('L') => self.toggle_randomize_lock(),
+            Key::Char('a') => self.toggle_automation(),
+            Key::Char('k') => self.toggle_midi_learn(),
+            Key::Char('O') => {
+                self.prompt = Some(state::Prompt::new(
+                    self.locale.text("prompt.profile"),
+                    self.core.config.profile.as_deref().unwrap_or(""),
+                    state::PromptAction::Profile,
+                ))
+            }
+            Key::Char('c') => {
+                self.prompt = Some(state::Prompt::new(
+                    self.locale.text("prompt.midi_channel"),
+                    "",
+                    state::PromptAction::MidiChannel,
+                ))
+            }
+            Key::Char('d') => {
+                self.prompt = Some(state::Prompt::new(
+                    self.locale.text("prompt.dip_switches"),
+                    &self
+                        .core
+                        .neutron_state
+                        .dip_switches
+                        .map_or(String::new(), |dips| dips.to_string()),
+                    state::PromptAction::DipSwitches,
+                ))
+            }
+            Key::Char('!') => self.core.panic(),
+            Key::Char('V') if self.core.sync_check.is_none() => {
+                let bpm = self.core.tempo.bpm().unwrap_or(synccheck::DEFAULT_BPM);
+                self.core.start_sync_check(bpm)
+            }
+            Key::Char('K') => self.core.tap_tempo(),
+            Key::Char('o') => {
+                self.prompt = Some(state::Prompt::new(
+                    self.locale.text("prompt.export_report"),
+                    report::DEFAULT_REPORT_FILE,
+                    state::PromptAction::ExportReport,
+                ))
+            }
+            Key::Char('N') => {
+                self.prompt = Some(state::Prompt::new(
+                    self.locale.text("prompt.add_workspace"),
+                    "",
+                    state::PromptAction::AddWorkspace,
+                ))
+            }
+            Key::Char('T') => self.hotkey_layer = !self.hotkey_layer,
+            Key::Char(c @ '1'..='9') if self.hotkey_layer => {
+                self.toggle_hotkey(c.to_digit(10).unwrap_or(1) as u8)
+            }
+            Key::Char(c @ '1'..='9') => {
+                self.switch_workspace(c.to_digit(10).unwrap_or(1) as usize - 1)
+            }
+            Key::Char(':') => {
+                self.prompt = Some(state::Prompt::new(
+                    self.locale.text("prompt.set_setting"),
+                    "",
+                    state::PromptAction::SetSetting,
+                ))
+            }
+
+            // Menu stuff
+            Key::Char('\n') => {
+                self.core.command_message(&SetGlobalSetting(
+                    self.core.target,
+                    self.selected_menu_setting(),
+                ));
+            }
+            Key::Char('\t') => self.tabs.next(),
+            Key::Down => {
+                self.basic_menu.select_next();
+            }
+            Key::Up => {
+                self.basic_menu.select_previous();
+            }
+            _ => {}
         }
     }
 }
@@ -372,10 +1549,11 @@ pub const MENU_MAPPINGS: [(&str, GlobalSetting); 35] = [
 mod test {
 
     use crate::app::App;
+    use crate::options::Options;
 
     #[test]
     fn test() {
         //TODO
-        let app = App::new();
+        let app = App::new(&Options::default());
     }
 }
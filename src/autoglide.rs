@@ -0,0 +1,71 @@
+use rustron_lib::protocol::AutoglideSemitones;
+use rustron_lib::protocol::GlobalSetting;
+use rustron_lib::protocol::GlobalSetting::{Osc1Autoglide, Osc2Autoglide};
+
+/// Picks the autoglide of both oscillators, stepping by semitones.
+pub struct AutoglideEditor {
+    pub values: [AutoglideSemitones; 2],
+    pub selection: usize,
+}
+
+impl Default for AutoglideEditor {
+    fn default() -> AutoglideEditor {
+        AutoglideEditor::new()
+    }
+}
+
+impl AutoglideEditor {
+    pub fn new() -> AutoglideEditor {
+        AutoglideEditor {
+            values: [AutoglideSemitones::Zero; 2],
+            selection: 0,
+        }
+    }
+
+    pub fn toggle_selection(&mut self) {
+        self.selection = (self.selection + 1) % self.values.len();
+    }
+
+    pub fn step(&mut self, semitones: i32) {
+        let raw = i32::from(self.values[self.selection].semitones()) + semitones;
+        if let Some(Osc1Autoglide(stepped)) | Some(Osc2Autoglide(stepped)) =
+            self.selected_setting().id().with_raw_value(raw)
+        {
+            self.values[self.selection] = stepped;
+        }
+    }
+
+    pub fn selected_setting(&self) -> GlobalSetting {
+        match self.selection {
+            0 => Osc1Autoglide(self.values[0]),
+            _ => Osc2Autoglide(self.values[1]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rustron_lib::protocol::AutoglideSemitones::{MinusOne, MinusTwelve, PlusTwelve};
+    use rustron_lib::protocol::GlobalSetting::{Osc1Autoglide, Osc2Autoglide};
+
+    use crate::autoglide::AutoglideEditor;
+
+    #[test]
+    fn large_steps_stop_at_the_limit() {
+        let mut editor = AutoglideEditor::new();
+        editor.step(-100);
+        assert_eq!(editor.selected_setting(), Osc1Autoglide(MinusTwelve));
+    }
+
+    #[test]
+    fn steps_are_clamped() {
+        let mut editor = AutoglideEditor::new();
+        editor.step(-1);
+        assert_eq!(editor.selected_setting(), Osc1Autoglide(MinusOne));
+        editor.toggle_selection();
+        for _ in 0..20 {
+            editor.step(1);
+        }
+        assert_eq!(editor.selected_setting(), Osc2Autoglide(PlusTwelve));
+    }
+}
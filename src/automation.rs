@@ -0,0 +1,218 @@
+use std::time::{Duration, Instant};
+
+use rustron_lib::protocol::GlobalSetting;
+use rustron_lib::setting::SettingId;
+
+/// The shape an automation lane moves its setting in, like the shapes of an LFO.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Curve {
+    Sine,
+    Triangle,
+    RisingSaw,
+    FallingSaw,
+    Square,
+}
+
+const CURVES: [(&str, Curve); 5] = [
+    ("sine", Curve::Sine),
+    ("triangle", Curve::Triangle),
+    ("rising_saw", Curve::RisingSaw),
+    ("falling_saw", Curve::FallingSaw),
+    ("square", Curve::Square),
+];
+
+impl Curve {
+    pub fn from_name(name: &str) -> Option<Curve> {
+        CURVES
+            .iter()
+            .find(|(n, _)| *n == name.trim())
+            .map(|(_, curve)| *curve)
+    }
+
+    pub fn name(self) -> &'static str {
+        // Every curve has a name
+        CURVES.iter().find(|(_, c)| *c == self).unwrap().0
+    }
+
+    /// The curve at `phase` of a cycle, both from 0 to 1. Starts at the bottom, except for the
+    /// falling saw which starts at the top.
+    fn value(self, phase: f64) -> f64 {
+        match self {
+            Curve::Sine => (1.0 - (phase * std::f64::consts::TAU).cos()) / 2.0,
+            Curve::Triangle => 1.0 - (2.0 * phase - 1.0).abs(),
+            Curve::RisingSaw => phase,
+            Curve::FallingSaw => 1.0 - phase,
+            Curve::Square => {
+                if phase < 0.5 {
+                    0.0
+                } else {
+                    1.0
+                }
+            }
+        }
+    }
+}
+
+/// Moves one setting through its range along a curve, repeating every `period`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lane {
+    pub name: String,
+    pub setting: SettingId,
+    pub curve: Curve,
+    pub period: Duration,
+}
+
+impl Lane {
+    pub fn new(name: &str) -> Lane {
+        Lane {
+            name: name.to_string(),
+            setting: SettingId::VcfModDepth,
+            curve: Curve::Sine,
+            period: Duration::from_secs(4),
+        }
+    }
+
+    /// The value of the setting `elapsed` after the lane started, `None` for actions.
+    pub fn setting_at(&self, elapsed: Duration) -> Option<GlobalSetting> {
+        let range = self.setting.range()?;
+        let phase = if self.period.as_secs_f64() > 0.0 {
+            (elapsed.as_secs_f64() / self.period.as_secs_f64()).fract()
+        } else {
+            0.0
+        };
+        let span = f64::from(range.max - range.min);
+        let raw = i32::from(range.min) + (self.curve.value(phase) * span).round() as i32;
+        self.setting.with_raw_value(raw)
+    }
+}
+
+/// Runs the automation lanes, deciding which values to send when. Values are sent at most every
+/// `interval`, and only when they changed since the last one sent for the lane.
+pub struct Automation {
+    pub lanes: Vec<Lane>,
+    pub interval: Duration,
+    started: Option<Instant>,
+    last_sent: Option<Instant>,
+    last_values: Vec<Option<GlobalSetting>>,
+}
+
+impl Automation {
+    pub fn new(lanes: Vec<Lane>, interval: Duration) -> Automation {
+        Automation {
+            lanes,
+            interval,
+            started: None,
+            last_sent: None,
+            last_values: Vec::new(),
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.started.is_some()
+    }
+
+    /// Starts all lanes from the beginning of their curve at `now`, or stops them if running.
+    pub fn toggle(&mut self, now: Instant) {
+        self.started = match self.started {
+            Some(_) => None,
+            None => Some(now),
+        };
+        self.last_sent = None;
+        self.last_values = vec![None; self.lanes.len()];
+    }
+
+    /// The values to send at `now`.
+    pub fn due(&mut self, now: Instant) -> Vec<GlobalSetting> {
+        let started = match self.started {
+            Some(started) => started,
+            None => return Vec::new(),
+        };
+        if let Some(last) = self.last_sent {
+            if now.duration_since(last) < self.interval {
+                return Vec::new();
+            }
+        }
+        self.last_sent = Some(now);
+        let elapsed = now.saturating_duration_since(started);
+        let mut due = Vec::new();
+        for (lane, last) in self.lanes.iter().zip(self.last_values.iter_mut()) {
+            let value = lane.setting_at(elapsed);
+            if value.is_some() && value != *last {
+                due.extend(value);
+                *last = value;
+            }
+        }
+        due
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use rustron_lib::protocol::GlobalSetting::{LfoDepth, Osc1Autoglide};
+    use rustron_lib::protocol::{AutoglideSemitones, Percent};
+    use rustron_lib::setting::SettingId;
+
+    use crate::automation::{Automation, Curve, Lane};
+
+    #[test]
+    fn lanes_follow_their_curve() {
+        let lane = Lane {
+            name: String::from("wobble"),
+            setting: SettingId::LfoDepth,
+            curve: Curve::Triangle,
+            period: Duration::from_secs(4),
+        };
+        assert_eq!(
+            lane.setting_at(Duration::from_secs(0)),
+            Some(LfoDepth(Percent::from_byte(0)))
+        );
+        assert_eq!(
+            lane.setting_at(Duration::from_secs(2)),
+            Some(LfoDepth(Percent::from_byte(63)))
+        );
+        assert_eq!(
+            lane.setting_at(Duration::from_secs(5)),
+            lane.setting_at(Duration::from_secs(1))
+        );
+
+        let lane = Lane {
+            setting: SettingId::Osc1Autoglide,
+            curve: Curve::FallingSaw,
+            ..lane
+        };
+        assert_eq!(
+            lane.setting_at(Duration::from_secs(0)),
+            AutoglideSemitones::from_semitones(12).map(Osc1Autoglide)
+        );
+        assert_eq!(
+            Curve::from_name(Curve::RisingSaw.name()),
+            Some(Curve::RisingSaw)
+        );
+    }
+
+    #[test]
+    fn values_are_paced_and_sent_on_change() {
+        let lane = Lane {
+            curve: Curve::Square,
+            ..Lane::new("gate")
+        };
+        let mut automation = Automation::new(vec![lane], Duration::from_millis(50));
+        let start = Instant::now();
+        assert!(automation.due(start).is_empty());
+
+        automation.toggle(start);
+        assert!(automation.is_running());
+        assert_eq!(automation.due(start).len(), 1);
+        // Too soon, then unchanged until the second half of the cycle
+        assert!(automation.due(start + Duration::from_millis(10)).is_empty());
+        assert!(automation
+            .due(start + Duration::from_millis(100))
+            .is_empty());
+        assert_eq!(automation.due(start + Duration::from_secs(3)).len(), 1);
+
+        automation.toggle(start);
+        assert!(automation.due(start + Duration::from_secs(4)).is_empty());
+    }
+}
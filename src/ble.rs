@@ -0,0 +1,340 @@
+//! The Bluetooth LE MIDI transport, for a Neutron connected through a BLE MIDI adapter like the
+//! CME WIDI. Messages travel in packets of the BLE MIDI specification: a header byte, then each
+//! message after a timestamp byte, with SysEx split across as many packets as it takes.
+use std::error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use btleplug::api::{
+    Central, CentralEvent, Characteristic, Manager as _, Peripheral as _, ScanFilter, WriteType,
+};
+use btleplug::platform::{Adapter, Manager, Peripheral};
+use futures::StreamExt;
+use log::{error, info};
+use tokio::runtime::Runtime;
+use uuid::Uuid;
+
+use crate::midi::{InputQueue, Source};
+
+/// The BLE MIDI service and its one characteristic, carrying the packets both ways.
+const MIDI_SERVICE: Uuid = Uuid::from_u128(0x03b8_0e5a_ede8_4b33_a751_6ce3_4ec4_c700);
+const MIDI_CHARACTERISTIC: Uuid = Uuid::from_u128(0x7772_e5db_3868_4112_a1a9_f266_9d10_6bf3);
+
+/// How long to look for the adapter.
+const SCAN_TIMEOUT: Duration = Duration::from_secs(5);
+const SCAN_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The payload of a packet with the smallest MTU every device supports.
+const PACKET_SIZE: usize = 20;
+
+const SYSEX_START: u8 = 0xf0;
+const SYSEX_END: u8 = 0xf7;
+
+/// The number of data bytes following a status byte, `None` for SysEx.
+fn data_len(status: u8) -> Option<usize> {
+    match status {
+        0xc0..=0xdf | 0xf1 | 0xf3 => Some(1),
+        0x80..=0xef | 0xf2 => Some(2),
+        SYSEX_START => None,
+        _ => Some(0),
+    }
+}
+
+/// Splits `message` into BLE MIDI packets of at most `packet_size` bytes, stamped with the low
+/// 13 bits of `millis`.
+pub fn encode(message: &[u8], millis: u64, packet_size: usize) -> Vec<Vec<u8>> {
+    let header = 0x80 | ((millis >> 7) & 0x3f) as u8;
+    let timestamp = 0x80 | (millis & 0x7f) as u8;
+    let body = match message {
+        [SYSEX_START, body @ .., SYSEX_END] => body,
+        _ => return vec![[&[header, timestamp], message].concat()],
+    };
+    let mut packets = vec![vec![header, timestamp, SYSEX_START]];
+    for byte in body {
+        if packets
+            .last()
+            .is_some_and(|packet| packet.len() >= packet_size)
+        {
+            packets.push(vec![header]);
+        }
+        packets.last_mut().unwrap().push(*byte);
+    }
+    // The end of the SysEx message gets a timestamp of its own
+    if packets
+        .last()
+        .is_some_and(|packet| packet.len() + 2 > packet_size)
+    {
+        packets.push(vec![header]);
+    }
+    packets
+        .last_mut()
+        .unwrap()
+        .extend_from_slice(&[timestamp, SYSEX_END]);
+    packets
+}
+
+/// Takes the MIDI messages out of BLE MIDI packets, following running status and putting SysEx
+/// split across packets back together.
+#[derive(Debug, Default)]
+pub struct Decoder {
+    running_status: Option<u8>,
+    /// The SysEx message being received
+    sysex: Option<Vec<u8>>,
+}
+
+impl Decoder {
+    pub fn new() -> Decoder {
+        Decoder::default()
+    }
+
+    /// The messages completed by `packet`. Packets without a valid header are ignored.
+    pub fn decode(&mut self, packet: &[u8]) -> Vec<Vec<u8>> {
+        let mut messages = Vec::new();
+        let bytes = match packet {
+            [header, bytes @ ..] if header & 0xc0 == 0x80 => bytes,
+            _ => return messages,
+        };
+        let mut i = 0;
+        while i < bytes.len() {
+            // Data continuing a SysEx message or a message in running status, without timestamp
+            if bytes[i] < 0x80 {
+                i = self.data(bytes, i, &mut messages);
+                continue;
+            }
+            // A timestamp, followed by a status byte or data in running status
+            i += 1;
+            let status = match bytes.get(i) {
+                Some(status) if *status >= 0x80 => *status,
+                _ => continue,
+            };
+            i += 1;
+            match status {
+                SYSEX_END => {
+                    if let Some(mut sysex) = self.sysex.take() {
+                        sysex.push(SYSEX_END);
+                        messages.push(sysex);
+                    }
+                }
+                SYSEX_START => {
+                    self.running_status = None;
+                    self.sysex = Some(vec![SYSEX_START]);
+                }
+                // Real-time messages can come in the middle of anything
+                0xf8..=0xff => messages.push(vec![status]),
+                _ => {
+                    self.sysex = None;
+                    self.running_status = Some(status).filter(|status| *status < 0xf0);
+                    let len = data_len(status).unwrap_or(0);
+                    let end = (i + len).min(bytes.len());
+                    messages.push([&[status], &bytes[i..end]].concat());
+                    i = end;
+                }
+            }
+        }
+        messages
+    }
+
+    /// Takes the data bytes at `i` on, returning where they end.
+    fn data(&mut self, bytes: &[u8], i: usize, messages: &mut Vec<Vec<u8>>) -> usize {
+        let end = bytes[i..]
+            .iter()
+            .position(|byte| *byte >= 0x80)
+            .map_or(bytes.len(), |len| i + len);
+        if let Some(sysex) = &mut self.sysex {
+            sysex.extend_from_slice(&bytes[i..end]);
+            return end;
+        }
+        let status = match self.running_status {
+            Some(status) => status,
+            None => return end,
+        };
+        let len = data_len(status).unwrap_or(0).max(1);
+        for data in bytes[i..end].chunks(len) {
+            messages.push([&[status], data].concat());
+        }
+        end
+    }
+}
+
+/// A BLE MIDI adapter the Neutron is connected to. Connecting and writing wait for the adapter,
+/// so both belong on a thread of their own, see `MidiConnection::ble`.
+pub struct BleConnection {
+    runtime: Runtime,
+    peripheral: Peripheral,
+    characteristic: Characteristic,
+    started: Instant,
+    /// Cleared once the adapter disconnected
+    connected: Arc<AtomicBool>,
+}
+
+impl BleConnection {
+    /// Looks for an adapter whose name starts with `name`, connects to it and passes what it
+    /// receives on to `received`. Blocks for up to `SCAN_TIMEOUT` while looking.
+    pub fn open(name: &str, received: InputQueue) -> Result<BleConnection, Box<dyn error::Error>> {
+        let runtime = Runtime::new()?;
+        let (adapter, peripheral, characteristic) = runtime.block_on(connect(name))?;
+        let connected = Arc::new(AtomicBool::new(true));
+        let mut events = runtime.block_on(adapter.events())?;
+        let (id, watched) = (peripheral.id(), Arc::clone(&connected));
+        runtime.spawn(async move {
+            while let Some(event) = events.next().await {
+                if matches!(event, CentralEvent::DeviceDisconnected(ref gone) if *gone == id) {
+                    break;
+                }
+            }
+            watched.store(false, Ordering::SeqCst);
+        });
+        let notifying = peripheral.clone();
+        runtime.spawn(async move {
+            let mut notifications = match notifying.notifications().await {
+                Ok(notifications) => notifications,
+                Err(error) => return error!("Could not receive over BLE: {}", error),
+            };
+            let mut decoder = Decoder::new();
+            while let Some(notification) = notifications.next().await {
+                if notification.uuid != MIDI_CHARACTERISTIC {
+                    continue;
+                }
+                for message in decoder.decode(&notification.value) {
+                    received.push(Source::Neutron, message);
+                }
+            }
+        });
+        Ok(BleConnection {
+            runtime,
+            peripheral,
+            characteristic,
+            started: Instant::now(),
+            connected,
+        })
+    }
+
+    /// Whether the adapter is still connected, cleared by the runtime once it disconnects.
+    pub fn link(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.connected)
+    }
+
+    /// Sends `message`, waiting until the adapter took all of its packets.
+    pub fn send(&mut self, message: &[u8]) -> Result<(), String> {
+        if !self.connected.load(Ordering::SeqCst) {
+            return Err(String::from("The BLE MIDI device disconnected."));
+        }
+        let millis = self.started.elapsed().as_millis() as u64;
+        for packet in encode(message, millis, PACKET_SIZE) {
+            self.runtime
+                .block_on(self.peripheral.write(
+                    &self.characteristic,
+                    &packet,
+                    WriteType::WithoutResponse,
+                ))
+                .map_err(|error| error.to_string())?;
+        }
+        Ok(())
+    }
+}
+
+async fn connect(
+    name: &str,
+) -> Result<(Adapter, Peripheral, Characteristic), Box<dyn error::Error>> {
+    let adapter = Manager::new()
+        .await?
+        .adapters()
+        .await?
+        .into_iter()
+        .next()
+        .ok_or("No Bluetooth adapter found.")?;
+    adapter
+        .start_scan(ScanFilter {
+            services: vec![MIDI_SERVICE],
+        })
+        .await?;
+    let deadline = Instant::now() + SCAN_TIMEOUT;
+    let peripheral = loop {
+        if let Some(peripheral) = find_peripheral(&adapter.peripherals().await?, name).await {
+            break peripheral;
+        }
+        if Instant::now() >= deadline {
+            adapter.stop_scan().await?;
+            return Err(format!("Could not find BLE MIDI device {}.", name).into());
+        }
+        tokio::time::sleep(SCAN_INTERVAL).await;
+    };
+    adapter.stop_scan().await?;
+    peripheral.connect().await?;
+    peripheral.discover_services().await?;
+    let characteristic = peripheral
+        .characteristics()
+        .into_iter()
+        .find(|characteristic| characteristic.uuid == MIDI_CHARACTERISTIC)
+        .ok_or_else(|| format!("{} has no BLE MIDI characteristic.", name))?;
+    peripheral.subscribe(&characteristic).await?;
+    info!("Connected to BLE MIDI device {}", name);
+    Ok((adapter, peripheral, characteristic))
+}
+
+/// The first of `peripherals` whose name starts with `name`.
+async fn find_peripheral(peripherals: &[Peripheral], name: &str) -> Option<Peripheral> {
+    for peripheral in peripherals {
+        let local_name = match peripheral.properties().await {
+            Ok(Some(properties)) => properties.local_name,
+            _ => None,
+        };
+        if local_name.is_some_and(|local_name| local_name.starts_with(name)) {
+            return Some(peripheral.clone());
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ble::{encode, Decoder};
+
+    #[test]
+    fn sysex_is_split_across_packets() {
+        let message: Vec<u8> = [0xf0]
+            .iter()
+            .chain(&[0x11; 20])
+            .chain(&[0xf7])
+            .copied()
+            .collect();
+        let packets = encode(&message, 0x1234, 20);
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0][..3], [0xa4, 0xb4, 0xf0]);
+        assert_eq!(packets[0].len(), 20);
+        assert_eq!(packets[1][..1], [0xa4]);
+        assert_eq!(packets[1][packets[1].len() - 2..], [0xb4, 0xf7]);
+
+        let mut decoder = Decoder::new();
+        assert!(decoder.decode(&packets[0]).is_empty());
+        assert_eq!(decoder.decode(&packets[1]), vec![message]);
+        assert_eq!(
+            encode(&[0xb0, 0x07, 0x64], 0, 20),
+            vec![vec![0x80, 0x80, 0xb0, 0x07, 0x64]]
+        );
+    }
+
+    #[test]
+    fn running_status_and_real_time_are_decoded() {
+        let mut decoder = Decoder::new();
+        assert_eq!(
+            decoder
+                .decode(&[0x80, 0x81, 0x90, 0x40, 0x7f, 0x41, 0x7f, 0x82, 0xf8, 0x83, 0x42, 0x00]),
+            vec![
+                vec![0x90, 0x40, 0x7f],
+                vec![0x90, 0x41, 0x7f],
+                vec![0xf8],
+                vec![0x90, 0x42, 0x00]
+            ]
+        );
+        // A real-time message in the middle of SysEx, and a packet without header
+        assert!(decoder.decode(&[0x80, 0x80, 0xf0, 0x01]).is_empty());
+        assert_eq!(decoder.decode(&[0x80, 0x02, 0x81, 0xf8]), vec![vec![0xf8]]);
+        assert!(decoder.decode(&[0x03, 0xf7]).is_empty());
+        assert_eq!(
+            decoder.decode(&[0x80, 0x03, 0x82, 0xf7]),
+            vec![vec![0xf0, 0x01, 0x02, 0x03, 0xf7]]
+        );
+    }
+}
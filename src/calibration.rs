@@ -0,0 +1,142 @@
+use rustron_lib::protocol::{DeviceId, NeutronMessage};
+
+/// One step of the calibration: what to do on the Neutron, and the message sent once the user
+/// confirms it is done.
+pub struct CalibrationStep {
+    pub instruction: &'static str,
+    pub message: Option<fn(DeviceId) -> NeutronMessage>,
+}
+
+/// The steps in order. Entering calibration mode is the only calibration message known so far,
+/// the Neutron takes it from there.
+pub const STEPS: [CalibrationStep; 4] = [
+    CalibrationStep {
+        instruction: "Remove all patch cables from the patch bay",
+        message: None,
+    },
+    CalibrationStep {
+        instruction: "Let the Neutron warm up for at least 15 minutes",
+        message: None,
+    },
+    CalibrationStep {
+        instruction: "Keep hands off the keys and knobs, calibration mode is entered next",
+        message: Some(NeutronMessage::CalibrationModeCommand),
+    },
+    CalibrationStep {
+        instruction: "Wait until the Neutron has finished calibrating, then power cycle it",
+        message: None,
+    },
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CalibrationState {
+    /// Waiting for the user to confirm the step at this index
+    Step(usize),
+    Done,
+    /// Stopped before the end, with the reason
+    Aborted(String),
+}
+
+/// Walks through the calibration one step at a time, sending a step's message only after the
+/// user confirms it.
+pub struct CalibrationWizard {
+    pub state: CalibrationState,
+}
+
+impl Default for CalibrationWizard {
+    fn default() -> CalibrationWizard {
+        CalibrationWizard::new()
+    }
+}
+
+impl CalibrationWizard {
+    pub fn new() -> CalibrationWizard {
+        CalibrationWizard {
+            state: CalibrationState::Step(0),
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        matches!(self.state, CalibrationState::Step(_))
+    }
+
+    pub fn current(&self) -> Option<&'static CalibrationStep> {
+        match self.state {
+            CalibrationState::Step(index) => STEPS.get(index),
+            _ => None,
+        }
+    }
+
+    /// Confirms the current step and moves on, returning the message to send to `target`.
+    pub fn confirm(&mut self, target: DeviceId) -> Option<NeutronMessage> {
+        let index = match self.state {
+            CalibrationState::Step(index) => index,
+            _ => return None,
+        };
+        self.state = if index + 1 < STEPS.len() {
+            CalibrationState::Step(index + 1)
+        } else {
+            CalibrationState::Done
+        };
+        STEPS[index].message.map(|message| message(target))
+    }
+
+    /// Stops the calibration, nothing more is sent.
+    pub fn abort(&mut self, reason: &str) {
+        if self.is_running() {
+            self.state = CalibrationState::Aborted(reason.to_string());
+        }
+    }
+
+    /// Aborts if `cancelled`, a command held back for confirmation and then declined, was the
+    /// one entering calibration mode. Compared as bytes, the parser doesn't know that command.
+    pub fn declined(&mut self, cancelled: &[u8], target: DeviceId) {
+        if cancelled == NeutronMessage::CalibrationModeCommand(target).as_bytes() {
+            self.abort("Calibration mode wasn't confirmed");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rustron_lib::protocol::DeviceId::Multicast;
+    use rustron_lib::protocol::NeutronMessage::CalibrationModeCommand;
+
+    use crate::calibration::{CalibrationState, CalibrationWizard, STEPS};
+
+    #[test]
+    fn messages_are_sent_after_confirming() {
+        let mut wizard = CalibrationWizard::new();
+        assert_eq!(wizard.confirm(Multicast), None);
+        assert_eq!(wizard.confirm(Multicast), None);
+        assert_eq!(wizard.current().unwrap().instruction, STEPS[2].instruction);
+        assert_eq!(
+            wizard.confirm(Multicast),
+            Some(CalibrationModeCommand(Multicast))
+        );
+        assert_eq!(wizard.confirm(Multicast), None);
+        assert_eq!(wizard.state, CalibrationState::Done);
+
+        let mut wizard = CalibrationWizard::new();
+        wizard.abort("Neutron disconnected");
+        assert!(!wizard.is_running());
+        assert_eq!(wizard.confirm(Multicast), None);
+        assert_eq!(
+            wizard.state,
+            CalibrationState::Aborted(String::from("Neutron disconnected"))
+        );
+    }
+
+    #[test]
+    fn declining_calibration_mode_aborts() {
+        let mut wizard = CalibrationWizard::new();
+        while wizard.confirm(Multicast).is_none() {}
+        wizard.declined(&[0xf0, 0x00, 0xf7], Multicast);
+        assert!(wizard.is_running());
+        wizard.declined(&CalibrationModeCommand(Multicast).as_bytes(), Multicast);
+        assert_eq!(
+            wizard.state,
+            CalibrationState::Aborted(String::from("Calibration mode wasn't confirmed"))
+        );
+    }
+}
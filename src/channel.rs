@@ -0,0 +1,110 @@
+use std::time::{Duration, Instant};
+
+use rustron_lib::protocol::DeviceId::Multicast;
+use rustron_lib::protocol::GlobalSetting::MidiChannel;
+use rustron_lib::protocol::NeutronMessage::{
+    SetGlobalSetting, SoftwareVersionRequest, SoftwareVersionResponse,
+};
+use rustron_lib::protocol::{Channel, DeviceId, NeutronMessage};
+
+/// Guides a MIDI channel change: the new channel is sent to the Neutron, which is then asked for
+/// its software version on that channel. The change only counts once the Neutron has answered
+/// there, otherwise the previous channel is restored.
+pub struct ChannelWizard {
+    pub previous: DeviceId,
+    pub channel: Channel,
+    started: Instant,
+    timeout: Duration,
+    verified: bool,
+}
+
+impl ChannelWizard {
+    pub fn start(previous: DeviceId, channel: Channel, timeout: Duration) -> ChannelWizard {
+        ChannelWizard {
+            previous,
+            channel,
+            started: Instant::now(),
+            timeout,
+            verified: false,
+        }
+    }
+
+    /// The target device once the change has been verified.
+    pub fn target(&self) -> DeviceId {
+        DeviceId::Channel(self.channel)
+    }
+
+    /// The channel change followed by the verification request on the new channel.
+    pub fn requests(&self) -> Vec<Vec<u8>> {
+        vec![
+            SetGlobalSetting(self.previous, MidiChannel(self.channel)).as_bytes(),
+            SoftwareVersionRequest(self.target()).as_bytes(),
+        ]
+    }
+
+    /// The message restoring the previous channel, if it is known. Sent to all devices since the
+    /// Neutron may or may not have switched.
+    pub fn rollback(&self) -> Option<Vec<u8>> {
+        match self.previous {
+            DeviceId::Channel(channel) => {
+                Some(SetGlobalSetting(Multicast, MidiChannel(channel)).as_bytes())
+            }
+            Multicast => None,
+        }
+    }
+
+    pub fn receive(&mut self, message: &NeutronMessage) {
+        if let SoftwareVersionResponse(device_id, _) = message {
+            if *device_id == self.target() {
+                self.verified = true;
+            }
+        }
+    }
+
+    pub fn is_verified(&self) -> bool {
+        self.verified
+    }
+
+    pub fn has_timed_out(&self, now: Instant) -> bool {
+        now.duration_since(self.started) > self.timeout
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use rustron_lib::protocol::Channel::{Five, One};
+    use rustron_lib::protocol::DeviceId::{Channel, Multicast};
+    use rustron_lib::protocol::GlobalSetting::MidiChannel;
+    use rustron_lib::protocol::NeutronMessage::{SetGlobalSetting, SoftwareVersionResponse};
+
+    use crate::channel::ChannelWizard;
+
+    #[test]
+    fn only_a_response_on_the_new_channel_verifies() {
+        let mut wizard = ChannelWizard::start(Channel(One), Five, Duration::from_secs(1));
+        wizard.receive(&SoftwareVersionResponse(
+            Channel(One),
+            String::from("2.0.2"),
+        ));
+        assert!(!wizard.is_verified());
+        wizard.receive(&SoftwareVersionResponse(
+            Channel(Five),
+            String::from("2.0.2"),
+        ));
+        assert!(wizard.is_verified());
+        assert!(wizard.has_timed_out(Instant::now() + Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn rollback_restores_the_previous_channel() {
+        let wizard = ChannelWizard::start(Channel(One), Five, Duration::from_secs(1));
+        assert_eq!(
+            wizard.rollback(),
+            Some(SetGlobalSetting(Multicast, MidiChannel(One)).as_bytes())
+        );
+        let wizard = ChannelWizard::start(Multicast, Five, Duration::from_secs(1));
+        assert_eq!(wizard.rollback(), None);
+    }
+}
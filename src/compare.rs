@@ -0,0 +1,126 @@
+use std::fmt::{Display, Formatter};
+
+use rustron_lib::preset;
+use rustron_lib::preset::Preset;
+use rustron_lib::protocol::GlobalSetting;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Slot {
+    A,
+    B,
+}
+
+impl Display for Slot {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Slot::A => write!(f, "A"),
+            Slot::B => write!(f, "B"),
+        }
+    }
+}
+
+/// Two setting snapshots to switch the Neutron between, for auditioning changes.
+pub struct AbCompare {
+    a: Option<Preset>,
+    b: Option<Preset>,
+    pub active: Slot,
+}
+
+impl Default for AbCompare {
+    fn default() -> AbCompare {
+        AbCompare::new()
+    }
+}
+
+impl AbCompare {
+    pub fn new() -> AbCompare {
+        AbCompare {
+            a: None,
+            b: None,
+            active: Slot::A,
+        }
+    }
+
+    fn slot_mut(&mut self, slot: Slot) -> &mut Option<Preset> {
+        match slot {
+            Slot::A => &mut self.a,
+            Slot::B => &mut self.b,
+        }
+    }
+
+    /// Stores `current` in the active slot and switches to the other one, returning its
+    /// snapshot. An empty slot starts out as a copy of `current` and returns `None`.
+    fn store_and_switch(&mut self, current: &Preset) -> Option<Preset> {
+        let other = match self.active {
+            Slot::A => Slot::B,
+            Slot::B => Slot::A,
+        };
+        *self.slot_mut(self.active) = Some(current.clone());
+        self.active = other;
+        match self.slot_mut(other) {
+            Some(target) => Some(target.clone()),
+            empty => {
+                *empty = Some(current.clone());
+                None
+            }
+        }
+    }
+
+    /// Stores `current` in the active slot and switches to the other one. Returns the settings
+    /// that have to be sent to bring the Neutron to the other snapshot, which is nothing if the
+    /// other slot was empty, it then starts out as a copy of `current`.
+    pub fn switch(&mut self, current: Preset) -> Vec<GlobalSetting> {
+        self.store_and_switch(&current)
+            .map_or_else(Vec::new, |target| {
+                current
+                    .diff(&target)
+                    .into_iter()
+                    .filter_map(|(_, target)| target)
+                    .collect()
+            })
+    }
+
+    /// Like `switch`, but gets to the other snapshot gradually in `steps` steps, see
+    /// `preset::morph`.
+    pub fn morph(&mut self, current: Preset, steps: u32) -> Vec<Vec<GlobalSetting>> {
+        self.store_and_switch(&current)
+            .map_or_else(Vec::new, |target| preset::morph(&current, &target, steps))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rustron_lib::preset::Preset;
+    use rustron_lib::protocol::GlobalSetting::{OscSync, ParaphonicMode};
+    use rustron_lib::protocol::ToggleOption::{Off, On};
+
+    use crate::compare::{AbCompare, Slot};
+
+    fn preset(paraphonic: bool) -> Preset {
+        let mut preset = Preset::new();
+        preset.set(ParaphonicMode(paraphonic.into()));
+        preset.set(OscSync(On));
+        preset
+    }
+
+    #[test]
+    fn switching_sends_only_differences() {
+        let mut compare = AbCompare::new();
+        assert!(compare.switch(preset(false)).is_empty());
+        assert_eq!(compare.active, Slot::B);
+        // Tweak B, then go back to A
+        assert_eq!(compare.switch(preset(true)), vec![ParaphonicMode(Off)]);
+        assert_eq!(compare.active, Slot::A);
+        assert_eq!(compare.switch(preset(false)), vec![ParaphonicMode(On)]);
+    }
+
+    #[test]
+    fn morphing_switches_slots() {
+        let mut compare = AbCompare::new();
+        assert!(compare.morph(preset(false), 4).is_empty());
+        let steps = compare.morph(preset(true), 4);
+        assert_eq!(steps.len(), 4);
+        assert_eq!(steps[3], vec![ParaphonicMode(Off)]);
+        assert_eq!(compare.active, Slot::A);
+    }
+}
@@ -0,0 +1,1027 @@
+use std::error;
+use std::fmt::Display;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use rustron_lib::setting::SettingId;
+
+use crate::automation::{Curve, Lane};
+use crate::hotkeys::Hotkey;
+use crate::locale::Locale;
+use crate::macros;
+use crate::macros::{Macro, Step};
+use crate::midi;
+use crate::randomize;
+use crate::risk::Risk;
+
+const CONFIG_FILE_NAME: &str = "rustron.conf";
+
+/// Persistent user configuration, stored as a small INI-like text file.
+///
+/// ```text
+/// [device]
+/// port = Neutron
+/// max_sysex = 65536
+///
+/// [handshake]
+/// enabled = true
+/// timeout = 2000
+///
+/// [polling]
+/// enabled = false
+/// interval = 1000
+/// timeout = 5000
+/// fast_tick = 20
+/// idle_tick = 250
+///
+/// [presets]
+/// directory = /home/user/.config/rustron/presets
+///
+/// [input]
+/// ports = Keystation, nanoKONTROL
+///
+/// [output]
+/// interval = 20
+/// ports = Neutron(2)
+/// queue = 256
+/// queue_ttl = 30000
+/// panic_reset = true
+///
+/// [through]
+/// enabled = true
+/// filters = drop_state_requests, device_id 2
+///
+/// [session]
+/// restore = true
+///
+/// [morph]
+/// duration = 2000
+///
+/// [randomize]
+/// settings = osc1.range, osc2.range, lfo.depth, vcf.mod_depth
+/// locked = osc2.range
+///
+/// [automation]
+/// interval = 50
+///
+/// [lane wobble]
+/// setting = vcf.mod_depth
+/// curve = sine
+/// period = 4000
+///
+/// [mappings]
+/// file = mappings.txt
+/// takeover = false
+///
+/// [librarian]
+/// directory = /home/user/.config/rustron/sysex
+///
+/// [safety]
+/// confirm = destructive
+///
+/// [ui]
+/// locale = de
+///
+/// [hotkeys]
+/// 1 = paraphonic
+/// 2 = osc.sync
+///
+/// [mqtt]
+/// broker = localhost:1883
+/// topic = neutron
+///
+/// [profile laptop]
+/// port = Neutron
+/// inputs = Keystation
+/// outputs =
+/// presets = /home/user/presets
+/// mappings = laptop.txt
+///
+/// [macro init]
+/// key = F1
+/// f0002032287f0a0f01f7
+/// wait 500ms
+/// f0002032287f0a0e00f7
+/// ```
+///
+/// Sections are named `[<kind>]` or `[<kind> <name>]`, `key = value` lines set options of the
+/// current section and any other non-empty line inside a macro section is a hex encoded message
+/// or a pause, see `Step`.
+/// A profile overrides the ports, preset directory and mapping file for one hardware setup,
+/// see `Config::use_profile`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Config {
+    pub device: DeviceConfig,
+    pub handshake: HandshakeConfig,
+    pub polling: PollingConfig,
+    pub presets: PresetsConfig,
+    pub input: InputConfig,
+    pub output: OutputConfig,
+    pub through: ThroughConfig,
+    pub session: SessionConfig,
+    pub morph: MorphConfig,
+    pub randomize: RandomizeConfig,
+    pub automation: AutomationConfig,
+    pub mappings: MappingsConfig,
+    pub librarian: LibrarianConfig,
+    pub safety: SafetyConfig,
+    pub ui: UiConfig,
+    pub hotkeys: HotkeysConfig,
+    pub mqtt: MqttConfig,
+    pub macros: Vec<Macro>,
+    pub profiles: Vec<Profile>,
+    /// The profile in use, its values are swapped in while in use
+    pub profile: Option<String>,
+}
+
+/// Which MIDI ports are the Neutron.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeviceConfig {
+    /// The beginning of the names of the Neutron's input and output ports
+    pub port: String,
+    /// The longest SysEx message received in chunks that is put back together, see `InputQueue`
+    pub max_sysex: usize,
+}
+
+impl Default for DeviceConfig {
+    fn default() -> Self {
+        DeviceConfig {
+            port: String::from("Neutron"),
+            max_sysex: midi::DEFAULT_MAX_SYSEX,
+        }
+    }
+}
+
+/// Controls the requests sent to the Neutron when connecting.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HandshakeConfig {
+    pub enabled: bool,
+    pub timeout: Duration,
+}
+
+impl Default for HandshakeConfig {
+    fn default() -> Self {
+        HandshakeConfig {
+            enabled: true,
+            timeout: Duration::from_millis(2000),
+        }
+    }
+}
+
+/// Controls the periodic state requests sent to the Neutron.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PollingConfig {
+    pub enabled: bool,
+    pub interval: Duration,
+    /// How long polls may go unanswered before the Neutron counts as unresponsive
+    pub timeout: Duration,
+    /// The time between ticks while messages are received
+    pub fast_tick: Duration,
+    /// The time between ticks when no messages were received for a while
+    pub idle_tick: Duration,
+}
+
+impl Default for PollingConfig {
+    fn default() -> Self {
+        PollingConfig {
+            enabled: false,
+            interval: Duration::from_millis(1000),
+            timeout: Duration::from_millis(5000),
+            fast_tick: Duration::from_millis(20),
+            idle_tick: Duration::from_millis(250),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PresetsConfig {
+    pub directory: PathBuf,
+}
+
+impl Default for PresetsConfig {
+    fn default() -> Self {
+        PresetsConfig {
+            directory: Config::directory()
+                .map(|dir| dir.join("presets"))
+                .unwrap_or_else(|| PathBuf::from("presets")),
+        }
+    }
+}
+
+/// MIDI inputs to receive from besides the Neutron.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InputConfig {
+    /// Beginnings of port names, e.g. of a controller keyboard
+    pub ports: Vec<String>,
+}
+
+/// Controls how fast messages are sent to the Neutron.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutputConfig {
+    /// Minimum time between two messages, zero sends everything right away
+    pub interval: Duration,
+    /// Beginnings of the names of ports to send to besides the Neutron, e.g. a poly chained one
+    pub ports: Vec<String>,
+    /// How many commands wait while no Neutron is connected, see `OfflineQueue`
+    pub queue: usize,
+    /// How long commands wait before they are dropped as stale
+    pub queue_ttl: Duration,
+    /// Whether the panic key also resets and unmutes the key range
+    pub panic_reset: bool,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        OutputConfig {
+            interval: Duration::from_millis(20),
+            ports: Vec::new(),
+            queue: 256,
+            queue_ttl: Duration::from_millis(30000),
+            panic_reset: true,
+        }
+    }
+}
+
+/// Forwarding of messages from the other inputs to the outputs, see `Through`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ThroughConfig {
+    pub enabled: bool,
+    /// Names of filters applied in order, see `through::named_filter`
+    pub filters: Vec<String>,
+}
+
+/// Whether the session is saved on exit and restored on startup, see `Session`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SessionConfig {
+    pub restore: bool,
+}
+
+/// Controls morphing between the A/B snapshots, see `preset::morph`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MorphConfig {
+    pub duration: Duration,
+}
+
+impl Default for MorphConfig {
+    fn default() -> Self {
+        MorphConfig {
+            duration: Duration::from_millis(2000),
+        }
+    }
+}
+
+/// Which settings the randomize action touches, see `Randomizer`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RandomizeConfig {
+    pub settings: Vec<SettingId>,
+    /// Settings kept at their value, can be changed at runtime
+    pub locked: Vec<SettingId>,
+}
+
+impl Default for RandomizeConfig {
+    fn default() -> Self {
+        RandomizeConfig {
+            settings: randomize::sound_settings(),
+            locked: Vec::new(),
+        }
+    }
+}
+
+/// The automation lanes and how often they send, see `Automation`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AutomationConfig {
+    pub interval: Duration,
+    pub lanes: Vec<Lane>,
+}
+
+impl Default for AutomationConfig {
+    fn default() -> Self {
+        AutomationConfig {
+            interval: macros::DEFAULT_PACING,
+            lanes: Vec::new(),
+        }
+    }
+}
+
+/// Where the controller mappings are stored, see `CcMap`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MappingsConfig {
+    /// File name in the configuration directory
+    pub file: String,
+    /// Whether learned mappings use soft takeover
+    pub takeover: bool,
+}
+
+impl Default for MappingsConfig {
+    fn default() -> Self {
+        MappingsConfig {
+            file: String::from("mappings.txt"),
+            takeover: false,
+        }
+    }
+}
+
+/// Where the SysEx librarian keeps its dumps, see `Librarian`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LibrarianConfig {
+    pub directory: PathBuf,
+}
+
+impl Default for LibrarianConfig {
+    fn default() -> Self {
+        LibrarianConfig {
+            directory: Config::directory()
+                .map(|dir| dir.join("sysex"))
+                .unwrap_or_else(|| PathBuf::from("sysex")),
+        }
+    }
+}
+
+/// Which commands are held back until confirmed, see `Risk`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SafetyConfig {
+    /// Commands of this risk or higher need to be confirmed, `None` sends everything right away
+    pub confirm: Option<Risk>,
+}
+
+impl Default for SafetyConfig {
+    fn default() -> Self {
+        SafetyConfig {
+            confirm: Some(Risk::Destructive),
+        }
+    }
+}
+
+/// How the interface is shown.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UiConfig {
+    /// The language, `None` picks it from the environment, see `Locale::select`
+    pub locale: Option<Locale>,
+}
+
+/// The settings bound to the number keys while the hotkey layer is on, see `Hotkey`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HotkeysConfig {
+    /// Sorted by key, at most one setting per key
+    pub toggles: Vec<Hotkey>,
+}
+
+/// The MQTT bridge for home automation, see `mqtt`. Needs rustron built with the mqtt feature.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MqttConfig {
+    /// The host and port of the broker, the bridge is off when `None`
+    pub broker: Option<(String, u16)>,
+    /// The topic the setting topics are under
+    pub topic: String,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        MqttConfig {
+            broker: None,
+            topic: String::from("neutron"),
+        }
+    }
+}
+
+/// Overrides for one hardware setup, `None` keeps the value of the base configuration.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Profile {
+    pub name: String,
+    pub port: Option<String>,
+    pub inputs: Option<Vec<String>>,
+    pub outputs: Option<Vec<String>>,
+    pub presets: Option<PathBuf>,
+    pub mappings: Option<String>,
+}
+
+impl Profile {
+    pub fn new(name: &str) -> Profile {
+        Profile {
+            name: name.to_string(),
+            port: None,
+            inputs: None,
+            outputs: None,
+            presets: None,
+            mappings: None,
+        }
+    }
+}
+
+fn swap_override<T>(value: &mut T, profile_value: &mut Option<T>) {
+    if let Some(profile_value) = profile_value {
+        std::mem::swap(value, profile_value);
+    }
+}
+
+enum Section {
+    None,
+    Device,
+    Handshake,
+    Polling,
+    Presets,
+    Input,
+    Output,
+    Through,
+    Session,
+    Morph,
+    Randomize,
+    Automation,
+    Lane,
+    Mappings,
+    Librarian,
+    Safety,
+    Ui,
+    Hotkeys,
+    Mqtt,
+    Profile,
+    Macro,
+}
+
+impl Config {
+    /// The directory where the configuration and other user data is stored.
+    pub fn directory() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(base.join("rustron"))
+    }
+
+    /// The directory where state that changes as the application runs is kept, like the prompt
+    /// history and the last session, apart from the configuration.
+    pub fn data_directory() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| {
+                std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share"))
+            })?;
+        Some(base.join("rustron"))
+    }
+
+    pub fn path() -> Option<PathBuf> {
+        Some(Config::directory()?.join(CONFIG_FILE_NAME))
+    }
+
+    /// Whether a configuration file has been written, `false` on the first run.
+    pub fn exists() -> bool {
+        Config::path().is_some_and(|path| path.exists())
+    }
+
+    /// Loads the configuration file, falling back to the defaults if it doesn't exist yet.
+    pub fn load() -> Result<Config, Box<dyn error::Error>> {
+        match Config::path() {
+            Some(path) if path.exists() => Config::parse(&fs::read_to_string(path)?),
+            _ => Ok(Default::default()),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn error::Error>> {
+        let path = Config::path().ok_or("Could not determine configuration directory.")?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.to_string())?;
+        Ok(())
+    }
+
+    pub fn parse(input: &str) -> Result<Config, Box<dyn error::Error>> {
+        let mut config = Config::default();
+        let mut section = Section::None;
+        for (number, line) in input.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            config
+                .parse_line(&mut section, line)
+                .map_err(|error| format!("line {}: {}", number + 1, error))?;
+        }
+        Ok(config)
+    }
+
+    /// Uses the overrides of the profile `name` instead of those of the current profile, or the
+    /// base values for `None`.
+    pub fn use_profile(&mut self, name: Option<&str>) -> Result<(), Box<dyn error::Error>> {
+        if let Some(name) = name {
+            if self.profiles.iter().all(|profile| profile.name != name) {
+                return Err(format!("unknown profile {}", name).into());
+            }
+        }
+        self.swap_profile();
+        self.profile = name.map(str::to_string);
+        self.swap_profile();
+        Ok(())
+    }
+
+    /// Swaps the values of the current profile with those in use, applying it or undoing that.
+    fn swap_profile(&mut self) {
+        let name = match &self.profile {
+            Some(name) => name,
+            None => return,
+        };
+        if let Some(profile) = self.profiles.iter_mut().find(|p| p.name == *name) {
+            swap_override(&mut self.device.port, &mut profile.port);
+            swap_override(&mut self.input.ports, &mut profile.inputs);
+            swap_override(&mut self.output.ports, &mut profile.outputs);
+            swap_override(&mut self.presets.directory, &mut profile.presets);
+            swap_override(&mut self.mappings.file, &mut profile.mappings);
+        }
+    }
+
+    fn parse_line(
+        &mut self,
+        section: &mut Section,
+        line: &str,
+    ) -> Result<(), Box<dyn error::Error>> {
+        if line.starts_with('[') && line.ends_with(']') {
+            let mut header = line[1..line.len() - 1].splitn(2, ' ');
+            *section = match (header.next(), header.next()) {
+                (Some("device"), None) => Section::Device,
+                (Some("handshake"), None) => Section::Handshake,
+                (Some("polling"), None) => Section::Polling,
+                (Some("presets"), None) => Section::Presets,
+                (Some("input"), None) => Section::Input,
+                (Some("output"), None) => Section::Output,
+                (Some("through"), None) => Section::Through,
+                (Some("session"), None) => Section::Session,
+                (Some("morph"), None) => Section::Morph,
+                (Some("randomize"), None) => Section::Randomize,
+                (Some("automation"), None) => Section::Automation,
+                (Some("lane"), Some(name)) => {
+                    self.automation.lanes.push(Lane::new(name.trim()));
+                    Section::Lane
+                }
+                (Some("mappings"), None) => Section::Mappings,
+                (Some("librarian"), None) => Section::Librarian,
+                (Some("safety"), None) => Section::Safety,
+                (Some("ui"), None) => Section::Ui,
+                (Some("hotkeys"), None) => Section::Hotkeys,
+                (Some("mqtt"), None) => Section::Mqtt,
+                (Some("profile"), Some(name)) => {
+                    self.profiles.push(Profile::new(name.trim()));
+                    Section::Profile
+                }
+                (Some("macro"), Some(name)) => {
+                    self.macros.push(Macro::new(name.trim()));
+                    Section::Macro
+                }
+                _ => return Err(format!("unknown section {}", line).into()),
+            };
+            return Ok(());
+        }
+        match (section, key_value(line)) {
+            (Section::Device, Some(("port", value))) => self.device.port = value.to_string(),
+            (Section::Device, Some(("max_sysex", value))) => {
+                self.device.max_sysex = value.parse()?
+            }
+            (Section::Handshake, Some(("enabled", value))) => {
+                self.handshake.enabled = value.parse()?
+            }
+            (Section::Handshake, Some(("timeout", value))) => {
+                self.handshake.timeout = Duration::from_millis(value.parse()?)
+            }
+            (Section::Polling, Some(("enabled", value))) => self.polling.enabled = value.parse()?,
+            (Section::Polling, Some(("interval", value))) => {
+                self.polling.interval = Duration::from_millis(value.parse()?)
+            }
+            (Section::Polling, Some(("timeout", value))) => {
+                self.polling.timeout = Duration::from_millis(value.parse()?)
+            }
+            (Section::Polling, Some(("fast_tick", value))) => {
+                self.polling.fast_tick = parse_tick(value)?
+            }
+            (Section::Polling, Some(("idle_tick", value))) => {
+                self.polling.idle_tick = parse_tick(value)?
+            }
+            (Section::Presets, Some(("directory", value))) => {
+                self.presets.directory = PathBuf::from(value)
+            }
+            (Section::Input, Some(("ports", value))) => self.input.ports = parse_list(value),
+            (Section::Output, Some(("interval", value))) => {
+                self.output.interval = Duration::from_millis(value.parse()?)
+            }
+            (Section::Output, Some(("ports", value))) => self.output.ports = parse_list(value),
+            (Section::Output, Some(("queue", value))) => self.output.queue = value.parse()?,
+            (Section::Output, Some(("queue_ttl", value))) => {
+                self.output.queue_ttl = Duration::from_millis(value.parse()?)
+            }
+            (Section::Output, Some(("panic_reset", value))) => {
+                self.output.panic_reset = value.parse()?
+            }
+            (Section::Through, Some(("enabled", value))) => self.through.enabled = value.parse()?,
+            (Section::Through, Some(("filters", value))) => {
+                self.through.filters = parse_list(value)
+            }
+            (Section::Session, Some(("restore", value))) => self.session.restore = value.parse()?,
+            (Section::Morph, Some(("duration", value))) => {
+                self.morph.duration = Duration::from_millis(value.parse()?)
+            }
+            (Section::Randomize, Some(("settings", value))) => {
+                self.randomize.settings = parse_settings(value)?
+            }
+            (Section::Randomize, Some(("locked", value))) => {
+                self.randomize.locked = parse_settings(value)?
+            }
+            (Section::Automation, Some(("interval", value))) => {
+                self.automation.interval = Duration::from_millis(value.parse()?)
+            }
+            (Section::Lane, Some((key, value))) => {
+                // A lane section is always preceded by its header
+                let lane = self.automation.lanes.last_mut().unwrap();
+                match key {
+                    "setting" => {
+                        lane.setting = SettingId::from_id(value)
+                            .ok_or_else(|| format!("unknown setting {}", value))?
+                    }
+                    "curve" => {
+                        lane.curve = Curve::from_name(value)
+                            .ok_or_else(|| format!("unknown curve {}", value))?
+                    }
+                    "period" => lane.period = Duration::from_millis(value.parse()?),
+                    _ => return Err(format!("unknown key {}", key).into()),
+                }
+            }
+            (Section::Mappings, Some(("file", value))) => self.mappings.file = value.to_string(),
+            (Section::Mappings, Some(("takeover", value))) => {
+                self.mappings.takeover = value.parse()?
+            }
+            (Section::Librarian, Some(("directory", value))) => {
+                self.librarian.directory = PathBuf::from(value)
+            }
+            (Section::Safety, Some(("confirm", "none"))) => self.safety.confirm = None,
+            (Section::Safety, Some(("confirm", value))) => {
+                self.safety.confirm =
+                    Some(Risk::from_name(value).ok_or_else(|| format!("unknown risk {}", value))?)
+            }
+            (Section::Ui, Some(("locale", "auto"))) => self.ui.locale = None,
+            (Section::Ui, Some(("locale", value))) => {
+                self.ui.locale = Some(
+                    Locale::from_name(value).ok_or_else(|| format!("unknown locale {}", value))?,
+                )
+            }
+            (Section::Hotkeys, Some((key, value))) => {
+                let setting = SettingId::from_id(value)
+                    .ok_or_else(|| format!("unknown setting {}", value))?;
+                let hotkey = Hotkey::new(key.parse()?, setting)
+                    .ok_or_else(|| format!("{} can't be bound to key {}", value, key))?;
+                let toggles = &mut self.hotkeys.toggles;
+                toggles.retain(|other| other.key != hotkey.key);
+                toggles.push(hotkey);
+                toggles.sort_by_key(|hotkey| hotkey.key);
+            }
+            (Section::Mqtt, Some(("broker", ""))) => self.mqtt.broker = None,
+            (Section::Mqtt, Some(("broker", value))) => {
+                let (host, port) = value
+                    .rsplit_once(':')
+                    .ok_or_else(|| format!("expected host:port, got {}", value))?;
+                self.mqtt.broker = Some((host.to_string(), port.parse()?))
+            }
+            (Section::Mqtt, Some(("topic", value))) => {
+                self.mqtt.topic = value.trim_end_matches('/').to_string()
+            }
+            (Section::Profile, Some((key, value))) => {
+                // A profile section is always preceded by its header
+                let profile = self.profiles.last_mut().unwrap();
+                match key {
+                    "port" => profile.port = Some(value.to_string()),
+                    "inputs" => profile.inputs = Some(parse_list(value)),
+                    "outputs" => profile.outputs = Some(parse_list(value)),
+                    "presets" => profile.presets = Some(PathBuf::from(value)),
+                    "mappings" => profile.mappings = Some(value.to_string()),
+                    _ => return Err(format!("unknown key {}", key).into()),
+                }
+            }
+            (Section::Macro, Some(("key", value))) => {
+                // A macro section is always preceded by its header
+                self.macros.last_mut().unwrap().key = Some(parse_function_key(value)?)
+            }
+            (Section::Macro, None) => self
+                .macros
+                .last_mut()
+                .unwrap()
+                .steps
+                .push(Step::parse(line)?),
+            (Section::None, _) => return Err("entry outside of a section".into()),
+            (_, Some((key, _))) => return Err(format!("unknown key {}", key).into()),
+            (_, None) => return Err(format!("invalid entry {}", line).into()),
+        }
+        Ok(())
+    }
+}
+
+impl Display for Config {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.profile.is_some() {
+            // Written with the base values, the profile keeps its overrides
+            let mut base = self.clone();
+            base.swap_profile();
+            base.profile = None;
+            return base.fmt(f);
+        }
+        writeln!(f, "[device]")?;
+        writeln!(f, "port = {}", self.device.port)?;
+        writeln!(f, "max_sysex = {}", self.device.max_sysex)?;
+        writeln!(f)?;
+        writeln!(f, "[handshake]")?;
+        writeln!(f, "enabled = {}", self.handshake.enabled)?;
+        writeln!(f, "timeout = {}", self.handshake.timeout.as_millis())?;
+        writeln!(f)?;
+        writeln!(f, "[polling]")?;
+        writeln!(f, "enabled = {}", self.polling.enabled)?;
+        writeln!(f, "interval = {}", self.polling.interval.as_millis())?;
+        writeln!(f, "timeout = {}", self.polling.timeout.as_millis())?;
+        writeln!(f, "fast_tick = {}", self.polling.fast_tick.as_millis())?;
+        writeln!(f, "idle_tick = {}", self.polling.idle_tick.as_millis())?;
+        writeln!(f)?;
+        writeln!(f, "[presets]")?;
+        writeln!(f, "directory = {}", self.presets.directory.display())?;
+        writeln!(f)?;
+        writeln!(f, "[input]")?;
+        writeln!(f, "ports = {}", self.input.ports.join(", "))?;
+        writeln!(f)?;
+        writeln!(f, "[output]")?;
+        writeln!(f, "interval = {}", self.output.interval.as_millis())?;
+        writeln!(f, "ports = {}", self.output.ports.join(", "))?;
+        writeln!(f, "queue = {}", self.output.queue)?;
+        writeln!(f, "queue_ttl = {}", self.output.queue_ttl.as_millis())?;
+        writeln!(f, "panic_reset = {}", self.output.panic_reset)?;
+        writeln!(f)?;
+        writeln!(f, "[through]")?;
+        writeln!(f, "enabled = {}", self.through.enabled)?;
+        writeln!(f, "filters = {}", self.through.filters.join(", "))?;
+        writeln!(f)?;
+        writeln!(f, "[session]")?;
+        writeln!(f, "restore = {}", self.session.restore)?;
+        writeln!(f)?;
+        writeln!(f, "[morph]")?;
+        writeln!(f, "duration = {}", self.morph.duration.as_millis())?;
+        writeln!(f)?;
+        let ids = |settings: &[SettingId]| {
+            settings
+                .iter()
+                .map(|id| id.id())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        writeln!(f, "[randomize]")?;
+        writeln!(f, "settings = {}", ids(&self.randomize.settings))?;
+        writeln!(f, "locked = {}", ids(&self.randomize.locked))?;
+        writeln!(f)?;
+        writeln!(f, "[automation]")?;
+        writeln!(f, "interval = {}", self.automation.interval.as_millis())?;
+        writeln!(f)?;
+        for lane in &self.automation.lanes {
+            writeln!(f, "[lane {}]", lane.name)?;
+            writeln!(f, "setting = {}", lane.setting.id())?;
+            writeln!(f, "curve = {}", lane.curve.name())?;
+            writeln!(f, "period = {}", lane.period.as_millis())?;
+            writeln!(f)?;
+        }
+        writeln!(f, "[mappings]")?;
+        writeln!(f, "file = {}", self.mappings.file)?;
+        writeln!(f, "takeover = {}", self.mappings.takeover)?;
+        writeln!(f)?;
+        writeln!(f, "[librarian]")?;
+        writeln!(f, "directory = {}", self.librarian.directory.display())?;
+        writeln!(f)?;
+        writeln!(f, "[safety]")?;
+        writeln!(
+            f,
+            "confirm = {}",
+            self.safety.confirm.map_or("none", Risk::name)
+        )?;
+        writeln!(f)?;
+        writeln!(f, "[ui]")?;
+        writeln!(
+            f,
+            "locale = {}",
+            self.ui.locale.map_or("auto", Locale::name)
+        )?;
+        writeln!(f)?;
+        writeln!(f, "[hotkeys]")?;
+        for hotkey in &self.hotkeys.toggles {
+            writeln!(f, "{} = {}", hotkey.key, hotkey.setting.id())?;
+        }
+        writeln!(f)?;
+        writeln!(f, "[mqtt]")?;
+        match &self.mqtt.broker {
+            Some((host, port)) => writeln!(f, "broker = {}:{}", host, port)?,
+            None => writeln!(f, "broker =")?,
+        }
+        writeln!(f, "topic = {}", self.mqtt.topic)?;
+        writeln!(f)?;
+        for profile in &self.profiles {
+            writeln!(f, "[profile {}]", profile.name)?;
+            if let Some(port) = &profile.port {
+                writeln!(f, "port = {}", port)?;
+            }
+            if let Some(inputs) = &profile.inputs {
+                writeln!(f, "inputs = {}", inputs.join(", "))?;
+            }
+            if let Some(outputs) = &profile.outputs {
+                writeln!(f, "outputs = {}", outputs.join(", "))?;
+            }
+            if let Some(presets) = &profile.presets {
+                writeln!(f, "presets = {}", presets.display())?;
+            }
+            if let Some(mappings) = &profile.mappings {
+                writeln!(f, "mappings = {}", mappings)?;
+            }
+            writeln!(f)?;
+        }
+        for m in &self.macros {
+            writeln!(f, "[macro {}]", m.name)?;
+            if let Some(key) = m.key {
+                writeln!(f, "key = F{}", key)?;
+            }
+            for step in &m.steps {
+                writeln!(f, "{}", step)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+fn key_value(line: &str) -> Option<(&str, &str)> {
+    let mut entry = line.splitn(2, '=');
+    match (entry.next(), entry.next()) {
+        (Some(key), Some(value)) => Some((key.trim(), value.trim())),
+        _ => None,
+    }
+}
+
+fn parse_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn parse_settings(value: &str) -> Result<Vec<SettingId>, Box<dyn error::Error>> {
+    parse_list(value)
+        .iter()
+        .map(|id| SettingId::from_id(id).ok_or_else(|| format!("unknown setting {}", id).into()))
+        .collect()
+}
+
+/// A tick of the event thread in milliseconds, which would spin without sleeping at 0.
+fn parse_tick(value: &str) -> Result<Duration, Box<dyn error::Error>> {
+    match value.parse()? {
+        0 => Err(format!("invalid tick {}, expected at least 1 ms", value).into()),
+        millis => Ok(Duration::from_millis(millis)),
+    }
+}
+
+fn parse_function_key(value: &str) -> Result<u8, Box<dyn error::Error>> {
+    if let Some(Ok(number @ 1..=12)) = value.strip_prefix('F').map(str::parse::<u8>) {
+        return Ok(number);
+    }
+    Err(format!("invalid key binding {}, expected F1-F12", value).into())
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use rustron_lib::protocol::LfoIndex;
+    use rustron_lib::setting::SettingId;
+
+    use crate::automation::{Curve, Lane};
+    use crate::config::{
+        AutomationConfig, Config, DeviceConfig, HandshakeConfig, HotkeysConfig, InputConfig,
+        LibrarianConfig, MappingsConfig, MorphConfig, MqttConfig, OutputConfig, PollingConfig,
+        PresetsConfig, Profile, RandomizeConfig, SafetyConfig, SessionConfig, ThroughConfig,
+        UiConfig,
+    };
+    use crate::hotkeys::Hotkey;
+    use crate::locale::Locale;
+    use crate::macros::{Macro, Step};
+
+    #[test]
+    fn config_round_trip() {
+        let config = Config {
+            device: DeviceConfig {
+                port: String::from("Neutron(2)"),
+                max_sysex: 4096,
+            },
+            handshake: HandshakeConfig {
+                enabled: false,
+                timeout: Duration::from_millis(500),
+            },
+            polling: PollingConfig {
+                enabled: true,
+                interval: Duration::from_millis(250),
+                timeout: Duration::from_millis(3000),
+                fast_tick: Duration::from_millis(10),
+                idle_tick: Duration::from_millis(500),
+            },
+            presets: PresetsConfig {
+                directory: PathBuf::from("/tmp/presets"),
+            },
+            input: InputConfig {
+                ports: vec![String::from("Keystation 49"), String::from("nanoKONTROL2")],
+            },
+            output: OutputConfig {
+                interval: Duration::from_millis(0),
+                ports: vec![String::from("Neutron(2)")],
+                queue: 16,
+                queue_ttl: Duration::from_millis(5000),
+                panic_reset: false,
+            },
+            through: ThroughConfig {
+                enabled: true,
+                filters: vec![String::from("device_id 2"), String::from("drop_unparsed")],
+            },
+            session: SessionConfig { restore: true },
+            morph: MorphConfig {
+                duration: Duration::from_millis(500),
+            },
+            randomize: RandomizeConfig {
+                settings: vec![SettingId::LfoDepth, SettingId::LfoShapeOrder(LfoIndex::Two)],
+                locked: vec![],
+            },
+            automation: AutomationConfig {
+                interval: Duration::from_millis(100),
+                lanes: vec![Lane {
+                    name: String::from("wobble"),
+                    setting: SettingId::Osc2Autoglide,
+                    curve: Curve::Triangle,
+                    period: Duration::from_millis(1500),
+                }],
+            },
+            mappings: MappingsConfig {
+                file: String::from("studio.txt"),
+                takeover: true,
+            },
+            librarian: LibrarianConfig {
+                directory: PathBuf::from("/tmp/sysex"),
+            },
+            safety: SafetyConfig { confirm: None },
+            ui: UiConfig {
+                locale: Some(Locale::German),
+            },
+            hotkeys: HotkeysConfig {
+                toggles: vec![
+                    Hotkey::new(1, SettingId::ParaphonicMode).unwrap(),
+                    Hotkey::new(4, SettingId::Osc1BlendMode).unwrap(),
+                ],
+            },
+            mqtt: MqttConfig {
+                broker: Some((String::from("192.168.1.2"), 1884)),
+                topic: String::from("studio/neutron"),
+            },
+            profiles: vec![Profile {
+                port: Some(String::from("Neutron")),
+                outputs: Some(vec![]),
+                ..Profile::new("laptop")
+            }],
+            profile: None,
+            macros: vec![Macro {
+                name: String::from("init"),
+                key: Some(1),
+                steps: vec![
+                    Step::Message(vec![
+                        0xf0, 0x00, 0x20, 0x32, 0x28, 0x7f, 0x0a, 0x0f, 0x01, 0xf7,
+                    ]),
+                    Step::Wait(Duration::from_millis(250)),
+                ],
+            }],
+        };
+        assert_eq!(Config::parse(&config.to_string()).unwrap(), config);
+    }
+
+    #[test]
+    fn profiles_override_the_base_values() {
+        let input = "[device]\nport = Neutron(2)\n\n[output]\nports = Neutron(2)\n\n\
+                     [profile laptop]\nport = Neutron\noutputs =\n";
+        let base = Config::parse(input).unwrap();
+        let mut config = Config::parse(input).unwrap();
+        assert!(config.use_profile(Some("studio")).is_err());
+
+        config.use_profile(Some("laptop")).unwrap();
+        assert_eq!(config.device.port, "Neutron");
+        assert!(config.output.ports.is_empty());
+        // Saving keeps the overrides in the profile
+        assert_eq!(Config::parse(&config.to_string()).unwrap(), base);
+
+        config.use_profile(None).unwrap();
+        assert_eq!(config, base);
+    }
+
+    #[test]
+    fn invalid_entries_are_rejected() {
+        assert!(Config::parse("[mqtt]\nbroker = localhost\n").is_err());
+        assert!(Config::parse("[macro init]\nkey = F13\n").is_err());
+        assert!(Config::parse("key = F1\n").is_err());
+        assert!(Config::parse("[handshake]\nenabled = maybe\n").is_err());
+        assert!(Config::parse("[handshake]\nf0f7\n").is_err());
+        assert!(Config::parse("[lane wobble]\ncurve = wiggle\n").is_err());
+        assert!(Config::parse("[polling]\nfast_tick = 0\n").is_err());
+        assert!(Config::parse("[polling]\nidle_tick = 0\n").is_err());
+    }
+}
@@ -0,0 +1,1685 @@
+use std::error;
+use std::fs;
+use std::io;
+use std::mem;
+use std::path::Path;
+use std::sync::mpsc;
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+use std::time::Instant;
+
+use flexi_logger::DeferredNow;
+use log::{error, info, warn, LevelFilter, Record};
+
+use rustron_lib::firmware::Firmware;
+use rustron_lib::preset;
+use rustron_lib::preset::Preset;
+use rustron_lib::protocol::{
+    Channel, DeviceId,
+    DeviceId::Multicast,
+    GlobalSetting, NeutronMessage,
+    NeutronMessage::{GlobalSettingUpdate, SetGlobalSetting, StateDump, StateRequest},
+    ToggleOption,
+};
+use rustron_lib::setting::SettingId;
+
+use crate::acks::{AckTracker, DEFAULT_ACK_TIMEOUT};
+use crate::automation::Automation;
+use crate::channel::ChannelWizard;
+use crate::config::Config;
+use crate::emulator::{Corruptor, Emulator};
+use crate::flash::Upload;
+use crate::handshake::Handshake;
+use crate::history::{HistoryEntry, Message};
+use crate::latency::LatencyProbe;
+use crate::macros::Macros;
+use crate::mapping::CcMap;
+use crate::midi;
+use crate::midi::{
+    ConnectionEvent, Destination, InputQueue, RateLimiter, Source, StatePoller,
+    INPUT_QUEUE_CAPACITY,
+};
+use crate::options::Options;
+use crate::poll::PollTracker;
+use crate::queue::OfflineQueue;
+use crate::remote;
+use crate::risk::Risk;
+use crate::scenario::Scenario;
+use crate::script;
+use crate::smf;
+use crate::smf::TimedMessage;
+use crate::stats::Stats;
+use crate::synccheck::{Step, SyncCheck, CLOCK};
+use crate::tempo::TapTempo;
+use crate::through::Through;
+use crate::workspace::{Workspace, Workspaces};
+
+/// Default file name used when exporting or importing the configuration as SysEx.
+const DEFAULT_SYX_FILE: &str = "rustron-preset.syx";
+
+pub mod state {
+    use std::collections::BTreeMap;
+    use std::sync::mpsc;
+
+    use rustron_lib::preset;
+    use rustron_lib::preset::Preset;
+    use rustron_lib::protocol::GlobalSetting;
+    use rustron_lib::protocol::NeutronMessage;
+    use rustron_lib::protocol::ToggleOption;
+    use rustron_lib::setting::SettingId;
+
+    use crate::dips;
+    use crate::dips::{DipSwitches, MidiAdvice};
+
+    /// What toggling a setting takes, see `AppCore::toggle`.
+    #[derive(Clone, Debug, PartialEq)]
+    pub enum Toggle {
+        /// Sending its other value
+        Set(GlobalSetting),
+        /// Requesting the state first, its value is decoded from state dumps
+        RequestState,
+        /// Setting it once first, its value is only known from what was set
+        Unknown,
+    }
+
+    #[derive(Clone, Default, PartialEq)]
+    pub struct GlobalSettingsState {
+        // TODO device_id stuff
+        device_id: u8,
+        pub paraphonic_mode: bool,
+        pub osc_sync: bool,
+        /// The last value of every setting, keyed by `GlobalSetting::key`
+        pub settings: BTreeMap<Vec<u8>, GlobalSetting>,
+    }
+
+    impl GlobalSettingsState {
+        /// The known settings as display name and value
+        pub fn fields(&self) -> [(&'static str, bool); 2] {
+            [
+                ("Paraphonic mode", self.paraphonic_mode),
+                ("OSC sync", self.osc_sync),
+            ]
+        }
+
+        /// Returns whether the value of the setting changed
+        fn update(&mut self, global_setting: GlobalSetting) -> bool {
+            let changed = match global_setting {
+                // Actions, not settings
+                GlobalSetting::LfoResetOrder | GlobalSetting::KeyRangeReset => false,
+                _ => {
+                    self.settings.insert(global_setting.key(), global_setting)
+                        != Some(global_setting)
+                }
+            };
+            match global_setting {
+                GlobalSetting::ParaphonicMode(t) => self.paraphonic_mode = t.into(),
+                GlobalSetting::OscSync(t) => self.osc_sync = t.into(),
+                GlobalSetting::Osc1BlendMode(_) => {}
+                GlobalSetting::Osc2BlendMode(_) => {}
+                GlobalSetting::Osc1TunePotBypass(_) => {}
+                GlobalSetting::Osc2TunePotBypass(_) => {}
+                GlobalSetting::Osc1Range(_) => {}
+                GlobalSetting::Osc2Range(_) => {}
+                GlobalSetting::Osc2KeyTrack(_) => {}
+                GlobalSetting::Osc1Autoglide(_) => {}
+                GlobalSetting::Osc2Autoglide(_) => {}
+                GlobalSetting::LfoBlendMode(_) => {}
+                GlobalSetting::LfoKeySync(_) => {}
+                GlobalSetting::LfoOneShot(_) => {}
+                GlobalSetting::LfoRetrigger(_) => {}
+                GlobalSetting::LfoMidiSync(_) => {}
+                GlobalSetting::LfoDepth(_) => {}
+                GlobalSetting::LfoShapeOrder(_, _) => {}
+                GlobalSetting::LfoShapePhase(_, _) => {}
+                GlobalSetting::LfoResetOrder => {}
+                GlobalSetting::VcfKeyTracking(_) => {}
+                GlobalSetting::VcfModDepth(_) => {}
+                GlobalSetting::VcfModSource(_) => {}
+                GlobalSetting::MidiChannel(_) => {}
+                GlobalSetting::DisableMidiDips(_) => {}
+                GlobalSetting::PolyChainMode(_) => {}
+                GlobalSetting::KeyRangeMute(_) => {}
+                GlobalSetting::KeyRangeReset => {}
+                GlobalSetting::AssignOut(_) => {}
+                GlobalSetting::EnvRetriggerMode(_) => {}
+            }
+            changed
+        }
+
+        /// Whether this state agrees with `global_setting`, `None` if the setting isn't tracked
+        fn agrees_with(&self, global_setting: GlobalSetting) -> Option<bool> {
+            match global_setting {
+                GlobalSetting::ParaphonicMode(t) => {
+                    Some(ToggleOption::from(self.paraphonic_mode) == t)
+                }
+                GlobalSetting::OscSync(t) => Some(ToggleOption::from(self.osc_sync) == t),
+                _ => None,
+            }
+        }
+    }
+
+    /// Which side of the state a change happened on.
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    pub enum ChangeSource {
+        /// A setting was sent to the Neutron
+        Commanded,
+        /// The Neutron reported a setting
+        Confirmed,
+    }
+
+    /// A setting that took a new value, see `NeutronState::subscribe`.
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    pub struct SettingChange {
+        pub setting: GlobalSetting,
+        pub source: ChangeSource,
+    }
+
+    #[derive(Default)]
+    pub struct NeutronState {
+        /// What we last sent to the Neutron
+        pub commanded: GlobalSettingsState,
+        /// What the Neutron last reported, through setting updates or state dumps
+        pub confirmed: GlobalSettingsState,
+        /// Sent settings the Neutron hasn't confirmed yet, keyed by `GlobalSetting::key`
+        pub unconfirmed: BTreeMap<Vec<u8>, GlobalSetting>,
+        pub software_version: Option<String>,
+        pub state_dump: Option<Vec<u8>>,
+        /// The DIP switches on the back of the Neutron, entered by the user
+        pub dip_switches: Option<DipSwitches>,
+        /// Settings where the last state dump differed from what we expected
+        pub drift: Vec<&'static str>,
+        subscribers: Vec<mpsc::Sender<SettingChange>>,
+    }
+
+    impl NeutronState {
+        pub fn new() -> NeutronState {
+            // TODO device_id
+            Default::default()
+        }
+
+        /// Pushes every change of a setting's commanded or confirmed value to the returned
+        /// receiver, so frontends don't have to poll the state. Dropping the receiver
+        /// unsubscribes.
+        pub fn subscribe(&mut self) -> mpsc::Receiver<SettingChange> {
+            let (sender, receiver) = mpsc::channel();
+            self.subscribers.push(sender);
+            receiver
+        }
+
+        fn notify(&mut self, setting: GlobalSetting, source: ChangeSource) {
+            let change = SettingChange { setting, source };
+            self.subscribers
+                .retain(|subscriber| subscriber.send(change).is_ok());
+        }
+
+        pub fn update(&mut self, message: NeutronMessage) {
+            match message {
+                NeutronMessage::SetGlobalSetting(_, global_setting) => {
+                    // Messages sent to the Neutron
+                    if self.commanded.update(global_setting) {
+                        self.notify(global_setting, ChangeSource::Commanded);
+                    }
+                    self.unconfirmed
+                        .insert(global_setting.key(), global_setting);
+                }
+                NeutronMessage::GlobalSettingUpdate(_, global_setting) => {
+                    // Messages sent from the Neutron
+                    if self.confirmed.update(global_setting) {
+                        self.notify(global_setting, ChangeSource::Confirmed);
+                    }
+                    if self.unconfirmed.get(&global_setting.key()) == Some(&global_setting) {
+                        self.unconfirmed.remove(&global_setting.key());
+                    }
+                }
+                NeutronMessage::RestoreGlobalSetting(_) => {}
+                NeutronMessage::CalibrationModeCommand(_) => {}
+                NeutronMessage::SoftwareVersionRequest(_) => {}
+                NeutronMessage::SoftwareVersionResponse(_, version) => {
+                    self.software_version = Some(version)
+                }
+                NeutronMessage::StateRequest(_) => {}
+                NeutronMessage::StateDump(_, payload) => self.state_dump_update(payload),
+            }
+        }
+
+        /// Whether the confirmed value of the setting named `field` differs from the commanded one
+        pub fn is_mismatched(&self, field: &str) -> bool {
+            self.commanded
+                .fields()
+                .iter()
+                .zip(self.confirmed.fields().iter())
+                .any(|(commanded, confirmed)| commanded.0 == field && commanded != confirmed)
+        }
+
+        /// What we expect the Neutron to be in: the last confirmed state plus everything sent
+        /// since
+        pub fn expected(&self) -> GlobalSettingsState {
+            let mut expected = self.confirmed.clone();
+            for global_setting in self.unconfirmed.values() {
+                expected.update(*global_setting);
+            }
+            expected
+        }
+
+        /// How to switch the two valued setting `id` to its other value, from the confirmed
+        /// value or the one sent last while that waits for confirmation.
+        pub fn toggle(&self, id: SettingId) -> Toggle {
+            let expected = self.expected();
+            match expected.settings.values().find(|s| s.id() == id) {
+                // Two valued settings always have another value
+                Some(current) => Toggle::Set(current.toggled().unwrap()),
+                None if preset::STATE_DUMP_BITS.iter().any(|bit| bit.setting == id) => {
+                    Toggle::RequestState
+                }
+                None => Toggle::Unknown,
+            }
+        }
+
+        /// The effective MIDI configuration given the DIP switches and the expected settings
+        pub fn midi_advice(&self) -> MidiAdvice {
+            let expected = self.expected();
+            let mut midi_channel = None;
+            let mut disable_midi_dips = None;
+            for global_setting in expected.settings.values() {
+                match global_setting {
+                    GlobalSetting::MidiChannel(c) => midi_channel = Some(*c),
+                    GlobalSetting::DisableMidiDips(t) => disable_midi_dips = Some(*t),
+                    _ => {}
+                }
+            }
+            dips::advise(self.dip_switches, midi_channel, disable_midi_dips)
+        }
+
+        /// The expected configuration of the Neutron as a preset
+        pub fn preset(&self) -> Preset {
+            let mut preset = Preset::new();
+            for global_setting in self.expected().settings.values() {
+                preset.set(*global_setting);
+            }
+            preset
+        }
+
+        fn state_dump_update(&mut self, payload: Vec<u8>) {
+            let expected = self.expected();
+            let mut reported = self.confirmed.clone();
+            for global_setting in preset::from_state_dump(&payload) {
+                if reported.update(global_setting) {
+                    self.notify(global_setting, ChangeSource::Confirmed);
+                }
+            }
+            // Nothing to compare against before the first dump
+            if self.state_dump.is_some() {
+                self.drift = expected
+                    .fields()
+                    .iter()
+                    .zip(reported.fields().iter())
+                    .filter(|(expected, reported)| expected != reported)
+                    .map(|(expected, _)| expected.0)
+                    .collect();
+            }
+            self.unconfirmed
+                .retain(|_, global_setting| reported.agrees_with(*global_setting) != Some(true));
+            self.confirmed = reported;
+            self.state_dump = Some(payload);
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use crate::core::state::{ChangeSource, NeutronState, SettingChange, Toggle};
+        use rustron_lib::protocol::Channel::One;
+        use rustron_lib::protocol::DeviceId::Channel;
+        use rustron_lib::protocol::GlobalSetting::{LfoResetOrder, OscSync, ParaphonicMode};
+        use rustron_lib::protocol::NeutronMessage::{
+            GlobalSettingUpdate, SetGlobalSetting, StateDump,
+        };
+        use rustron_lib::protocol::ToggleOption::{Off, On};
+        use rustron_lib::protocol::{BlendMode, GlobalSetting};
+        use rustron_lib::setting::SettingId;
+
+        #[test]
+        fn state_dump_is_decoded() {
+            let mut ns = NeutronState::new();
+            ns.update(StateDump(
+                Channel(One),
+                vec![0x7b, 0x02, 0x00, 0x00, 0x02, 0x31, 0x08, 0x59],
+            ));
+            assert!(ns.confirmed.osc_sync);
+            assert!(ns.confirmed.paraphonic_mode);
+            ns.update(StateDump(
+                Channel(One),
+                vec![0x6b, 0x02, 0x00, 0x00, 0x02, 0x31, 0x08, 0x58],
+            ));
+            assert!(!ns.confirmed.osc_sync);
+            assert!(!ns.confirmed.paraphonic_mode);
+            assert_eq!(ns.drift, vec!["Paraphonic mode", "OSC sync"]);
+        }
+
+        #[test]
+        fn settings_are_toggled_once_known() {
+            let mut ns = NeutronState::new();
+            assert_eq!(ns.toggle(SettingId::OscSync), Toggle::RequestState);
+            // Not in state dumps, requesting the state wouldn't tell
+            assert_eq!(ns.toggle(SettingId::LfoBlendMode), Toggle::Unknown);
+            ns.update(SetGlobalSetting(
+                Channel(One),
+                GlobalSetting::LfoBlendMode(BlendMode::Blend),
+            ));
+            assert_eq!(
+                ns.toggle(SettingId::LfoBlendMode),
+                Toggle::Set(GlobalSetting::LfoBlendMode(BlendMode::Switch))
+            );
+            ns.update(StateDump(
+                Channel(One),
+                vec![0x7b, 0x02, 0x00, 0x00, 0x02, 0x31, 0x08, 0x59],
+            ));
+            assert_eq!(ns.toggle(SettingId::OscSync), Toggle::Set(OscSync(Off)));
+        }
+
+        #[test]
+        fn paraphonic_mode_is_updated() {
+            let mut ns = NeutronState::new();
+            assert!(!ns.commanded.paraphonic_mode);
+            ns.update(SetGlobalSetting(Channel(One), ParaphonicMode(On)));
+            assert!(ns.commanded.paraphonic_mode);
+            assert!(!ns.confirmed.paraphonic_mode);
+            assert!(ns.is_mismatched("Paraphonic mode"));
+            ns.update(GlobalSettingUpdate(Channel(One), ParaphonicMode(Off)));
+            assert!(!ns.confirmed.paraphonic_mode);
+            assert_eq!(ns.unconfirmed.len(), 1);
+            ns.update(GlobalSettingUpdate(Channel(One), ParaphonicMode(On)));
+            assert!(ns.confirmed.paraphonic_mode);
+            assert!(ns.unconfirmed.is_empty());
+        }
+
+        #[test]
+        fn preset_prefers_unconfirmed_settings() {
+            let mut ns = NeutronState::new();
+            ns.update(GlobalSettingUpdate(Channel(One), OscSync(On)));
+            ns.update(GlobalSettingUpdate(Channel(One), ParaphonicMode(On)));
+            ns.update(SetGlobalSetting(Channel(One), ParaphonicMode(Off)));
+            ns.update(SetGlobalSetting(Channel(One), LfoResetOrder));
+            let mut settings = ns.preset().settings;
+            settings.sort_by_key(|s| s.key());
+            assert_eq!(settings, vec![OscSync(On), ParaphonicMode(Off)]);
+        }
+
+        #[test]
+        fn changes_are_pushed_to_subscribers() {
+            let mut ns = NeutronState::new();
+            let changes = ns.subscribe();
+            drop(ns.subscribe());
+            ns.update(SetGlobalSetting(Channel(One), OscSync(On)));
+            ns.update(SetGlobalSetting(Channel(One), OscSync(On)));
+            ns.update(GlobalSettingUpdate(Channel(One), OscSync(On)));
+            ns.update(StateDump(
+                Channel(One),
+                vec![0x7b, 0x02, 0x00, 0x00, 0x02, 0x31, 0x08, 0x59],
+            ));
+            let change = |setting, source| SettingChange { setting, source };
+            assert_eq!(
+                changes.try_iter().collect::<Vec<_>>(),
+                vec![
+                    change(OscSync(On), ChangeSource::Commanded),
+                    change(OscSync(On), ChangeSource::Confirmed),
+                    change(ParaphonicMode(On), ChangeSource::Confirmed),
+                ]
+            );
+        }
+
+        #[test]
+        fn state_dump_confirms_sent_settings() {
+            let mut ns = NeutronState::new();
+            ns.update(SetGlobalSetting(Channel(One), OscSync(On)));
+            ns.update(SetGlobalSetting(Channel(One), ParaphonicMode(On)));
+            ns.update(StateDump(
+                Channel(One),
+                vec![0x7b, 0x02, 0x00, 0x00, 0x02, 0x31, 0x08, 0x58],
+            ));
+            assert_eq!(
+                ns.unconfirmed.values().collect::<Vec<_>>(),
+                vec![&ParaphonicMode(On)]
+            );
+        }
+    }
+}
+
+/// Connects to the Neutron and the other ports in the configuration, returns whether the
+/// Neutron was found. When simulating only the emulated Neutron is connected.
+fn open_connection(
+    config: &Config,
+    midi_input: &InputQueue,
+    options: &Options,
+) -> (midi::MidiConnection, bool) {
+    if options.simulate {
+        let scenario = match &options.scenario {
+            Some(path) => Scenario::load(path).unwrap_or_else(|error| {
+                warn!("Could not load scenario {}: {}", path.display(), error);
+                Default::default()
+            }),
+            None => Default::default(),
+        };
+        let emulator = Emulator::with_scenario(midi_input.clone(), &scenario);
+        return (midi::MidiConnection::simulated(emulator), true);
+    }
+    let other_transport =
+        open_jack(config, midi_input, options).or_else(|| open_ble(config, midi_input, options));
+    let (mut connection, connected) = match other_transport {
+        Some(jack) => jack,
+        None => {
+            let mut connection = midi::MidiConnection::new(&config.device.port);
+            let connected = match connection.register_midi_in(midi_input.clone()) {
+                Ok(()) => true,
+                Err(error) => {
+                    warn!("{}", error);
+                    false
+                }
+            };
+            (connection, connected)
+        }
+    };
+    for port in &config.output.ports {
+        connection.add_output(port);
+    }
+    connection.connect_outputs(Instant::now());
+    #[cfg(unix)]
+    {
+        if let Err(error) = connection.open_monitor() {
+            warn!("Could not open the {} port: {}", midi::MONITOR_PORT, error);
+        }
+    }
+    for port in &config.input.ports {
+        match connection.listen(port, midi_input.clone()) {
+            Ok(name) => info!("Listening on {}", name),
+            Err(error) => warn!("{}", error),
+        }
+    }
+    (connection, connected)
+}
+
+/// Connects to the Neutron through JACK with `--jack`, `None` to use the ALSA ports instead.
+#[cfg(feature = "jack")]
+fn open_jack(
+    config: &Config,
+    midi_input: &InputQueue,
+    options: &Options,
+) -> Option<(midi::MidiConnection, bool)> {
+    if !options.jack {
+        return None;
+    }
+    match midi::MidiConnection::jack(&config.device.port, midi_input.clone()) {
+        Ok(connection) => Some((connection, true)),
+        Err(error) => {
+            warn!(
+                "Could not open the JACK client, using the ALSA ports: {}",
+                error
+            );
+            None
+        }
+    }
+}
+
+#[cfg(not(feature = "jack"))]
+fn open_jack(_: &Config, _: &InputQueue, _: &Options) -> Option<(midi::MidiConnection, bool)> {
+    None
+}
+
+/// Connects to the Neutron through a BLE MIDI adapter named like `[device] port` with `--ble`,
+/// `None` to use the ALSA ports instead.
+#[cfg(feature = "ble")]
+fn open_ble(
+    config: &Config,
+    midi_input: &InputQueue,
+    options: &Options,
+) -> Option<(midi::MidiConnection, bool)> {
+    if !options.ble {
+        return None;
+    }
+    // Connected once the Neutron's destination reports it, see `AppCore::tick`
+    Some((
+        midi::MidiConnection::ble(&config.device.port, midi_input.clone()),
+        false,
+    ))
+}
+
+#[cfg(not(feature = "ble"))]
+fn open_ble(_: &Config, _: &InputQueue, _: &Options) -> Option<(midi::MidiConnection, bool)> {
+    None
+}
+
+struct ApplicationLogger {
+    level: LevelFilter,
+    sender: mpsc::SyncSender<String>,
+}
+
+impl ApplicationLogger {
+    fn new(sender: mpsc::SyncSender<String>) -> Self {
+        ApplicationLogger {
+            level: LevelFilter::Trace,
+            sender,
+        }
+    }
+}
+
+impl flexi_logger::writers::LogWriter for ApplicationLogger {
+    fn write(&self, _now: &mut DeferredNow, record: &Record) -> io::Result<()> {
+        self.sender
+            .send(format!(
+                "{}:{} -- {}",
+                record.level(),
+                record.target(),
+                record.args()
+            ))
+            .unwrap();
+        Ok(())
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn max_log_level(&self) -> LevelFilter {
+        self.level
+    }
+}
+
+/// What became of a command, see `AppCore::command`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CommandStatus {
+    /// Sent, or queued until the rate limiter or the connection lets it through
+    Sent,
+    /// Not sent in read-only mode, or because another command awaits confirmation
+    Blocked,
+    /// Held back until it is confirmed
+    Held,
+}
+
+/// Everything about talking to the Neutron that doesn't depend on how it is presented: the MIDI
+/// connection, the tracked state, command dispatch and logging. Frontends own an `AppCore`, feed
+/// it events and read its public state to render.
+pub struct AppCore {
+    pub neutron_state: state::NeutronState,
+    pub command_history: Vec<HistoryEntry>,
+    // TODO will grow indefinitely, does it matter?
+    pub midi_in_messages: Vec<HistoryEntry>,
+    pub stats: Stats,
+    pub log: Vec<String>,
+    pub macros: Macros,
+    pub automation: Automation,
+    /// The running LFO sync check, see `start_sync_check`
+    pub sync_check: Option<SyncCheck>,
+    /// The tempo the clock of the sync check follows, see `tap_tempo`
+    pub tempo: TapTempo,
+    /// The running firmware upload, see `start_upload`
+    pub upload: Option<Upload>,
+    /// A command held back until the user confirms it, see `confirm`
+    pub awaiting_confirmation: Option<Vec<u8>>,
+    /// Controllers on the other inputs mapped to settings
+    pub cc_map: CcMap,
+    pub handshake: Option<Handshake>,
+    /// The device commands are sent to, changed through the MIDI channel wizard
+    pub target: DeviceId,
+    pub channel_wizard: Option<ChannelWizard>,
+    /// The other devices, whose state and messages are kept apart, see `switch_workspace`
+    pub workspaces: Workspaces,
+    pub poller: StatePoller,
+    /// The answers to state requests, grouped
+    pub polls: PollTracker,
+    pub limiter: RateLimiter,
+    /// Commands sent while the Neutron isn't connected
+    pub offline: OfflineQueue,
+    /// Forwards messages from the other inputs to the outputs
+    pub through: Through,
+    pub acks: AckTracker,
+    pub latency: LatencyProbe,
+    /// Whether the MIDI input from the Neutron is connected
+    pub connected: bool,
+    /// Polls went unanswered for longer than the polling timeout
+    pub unresponsive: bool,
+    pub options: Options,
+    /// Corrupts received messages in the error injection debug mode
+    pub corruptor: Option<Corruptor>,
+    pub config: Config,
+    connection: midi::MidiConnection,
+    /// The buffer commands are encoded into, see `command_message`
+    scratch: Vec<u8>,
+    midi_input: InputQueue,
+    log_receiver: Receiver<String>,
+    /// The requests of remote control surfaces, see `remote`
+    remote: (Sender<remote::Request>, Receiver<remote::Request>),
+}
+
+impl AppCore {
+    /// Sets up logging, loads the configuration and connects to the Neutron. Returns the core and
+    /// whether the connection succeeded, to be passed on to `connection_changed`.
+    pub fn new(options: &Options) -> (AppCore, bool) {
+        // Wire up logging
+        let (app_log_sender, app_log_receiver) = mpsc::sync_channel(1000);
+        flexi_logger::Logger::with_env_or_str("info")
+            .log_target(flexi_logger::LogTarget::Writer(Box::new(
+                ApplicationLogger::new(app_log_sender),
+            )))
+            .start()
+            .unwrap();
+
+        let mut config = Config::load().unwrap_or_else(|error| {
+            warn!("Could not load configuration: {}", error);
+            Default::default()
+        });
+        if let Err(error) = config.use_profile(options.profile.as_deref()) {
+            warn!("Could not use profile: {}", error);
+        }
+        let mut cc_map = CcMap::load(&config.mappings.file).unwrap_or_else(|error| {
+            warn!("Could not load controller mappings: {}", error);
+            Default::default()
+        });
+        cc_map.takeover = config.mappings.takeover;
+        let midi_input = InputQueue::with_max_sysex(INPUT_QUEUE_CAPACITY, config.device.max_sysex);
+        let (connection, connected) = open_connection(&config, &midi_input, options);
+        let mut through = Through::builder();
+        for filter in &config.through.filters {
+            through = match through.named(filter) {
+                Ok(through) => through,
+                Err(error) => {
+                    warn!("Could not add through filter: {}", error);
+                    // Forwarding without all filters could do harm
+                    Through::builder().filter("broken configuration", |_| None)
+                }
+            };
+        }
+        let mut through = through.build();
+        through.enabled = config.through.enabled;
+
+        let core = AppCore {
+            neutron_state: state::NeutronState::new(),
+            command_history: Vec::new(),
+            midi_in_messages: Vec::new(),
+            stats: Stats::new(Instant::now()),
+            log: Vec::new(),
+            macros: Macros::new(config.macros.clone()),
+            automation: Automation::new(
+                config.automation.lanes.clone(),
+                config.automation.interval,
+            ),
+            sync_check: None,
+            tempo: TapTempo::new(),
+            upload: None,
+            awaiting_confirmation: None,
+            cc_map,
+            handshake: None,
+            target: Multicast,
+            channel_wizard: None,
+            workspaces: Workspaces::new(Multicast),
+            poller: StatePoller::new(config.polling.enabled, config.polling.interval),
+            polls: PollTracker::new(),
+            limiter: RateLimiter::new(config.output.interval),
+            offline: OfflineQueue::new(config.output.queue, config.output.queue_ttl),
+            through,
+            acks: AckTracker::new(DEFAULT_ACK_TIMEOUT),
+            latency: LatencyProbe::new(),
+            connected: false,
+            unresponsive: false,
+            options: options.clone(),
+            corruptor: Some(options.corrupt)
+                .filter(|fraction| *fraction > 0.0)
+                .map(|fraction| Corruptor::new(fraction, 1)),
+            config,
+            connection,
+            scratch: Vec::with_capacity(NeutronMessage::MAX_LEN),
+            midi_input,
+            log_receiver: app_log_receiver,
+            remote: mpsc::channel(),
+        };
+        #[cfg(feature = "grpc")]
+        {
+            if let Some(address) = options.grpc {
+                crate::grpc::serve(address, core.remote());
+            }
+        }
+        #[cfg(feature = "mqtt")]
+        {
+            if core.config.mqtt.broker.is_some() {
+                crate::mqtt::start(&core.config.mqtt, core.remote());
+            }
+        }
+        #[cfg(not(feature = "mqtt"))]
+        {
+            if core.config.mqtt.broker.is_some() {
+                warn!(
+                    "An MQTT broker is configured, but rustron was built without the mqtt feature"
+                );
+            }
+        }
+        (core, connected)
+    }
+
+    /// Reconnects to the Neutron on the port in the configuration, forgetting what is known
+    /// about the previous one. Returns whether the connection succeeded.
+    pub fn connect_device(&mut self) -> bool {
+        self.neutron_state = state::NeutronState::new();
+        self.reconnect()
+    }
+
+    /// Switches to the profile `name`, or the base configuration for `None`, reloading the
+    /// controller mappings and reconnecting with its ports.
+    pub fn switch_profile(&mut self, name: Option<&str>) -> Result<(), Box<dyn error::Error>> {
+        self.config.use_profile(name)?;
+        info!("Using profile {}", name.unwrap_or("none"));
+        self.cc_map = CcMap::load(&self.config.mappings.file).unwrap_or_else(|error| {
+            warn!("Could not load controller mappings: {}", error);
+            Default::default()
+        });
+        self.cc_map.takeover = self.config.mappings.takeover;
+        self.connect_device();
+        Ok(())
+    }
+
+    /// Opens the connection again, passing the outcome on to `connection_changed` so the
+    /// commands queued meanwhile are sent.
+    fn reconnect(&mut self) -> bool {
+        let (connection, connected) =
+            open_connection(&self.config, &self.midi_input, &self.options);
+        self.connection = connection;
+        self.connection_changed(connected);
+        connected
+    }
+
+    /// The next MIDI message received from the Neutron or another port listened on, if any.
+    pub fn try_receive_midi(&mut self) -> Option<(Source, Vec<u8>)> {
+        let (source, mut bytes) = self.midi_input.pop()?;
+        if source == Source::Neutron {
+            self.connection.monitor(&bytes);
+        }
+        if let Some(corruptor) = &mut self.corruptor {
+            bytes = corruptor.apply(bytes);
+        }
+        #[cfg(feature = "debug-parser")]
+        {
+            if self.options.debug_parser {
+                if let Err(trace) = rustron_lib::parser::trace(&bytes) {
+                    info!("Could not parse {}:", hex::encode(&bytes));
+                    for line in trace {
+                        info!("  {}", line);
+                    }
+                }
+            }
+        }
+        Some((source, bytes))
+    }
+
+    /// How many received messages were dropped because they weren't handled in time.
+    pub fn dropped_midi(&self) -> usize {
+        self.midi_input.dropped()
+    }
+
+    /// The next log line, if any.
+    pub fn try_receive_log(&self) -> Option<String> {
+        self.log_receiver.try_recv().ok()
+    }
+
+    /// How many messages wait to be sent to the outputs.
+    pub fn send_queue_depth(&self) -> usize {
+        self.connection
+            .outputs()
+            .iter()
+            .map(Destination::queued)
+            .sum()
+    }
+
+    /// The ports commands are sent to, the Neutron first.
+    pub fn outputs(&self) -> &[Destination] {
+        self.connection.outputs()
+    }
+
+    pub fn toggle_output(&mut self, index: usize) {
+        if let Some(enabled) = self.connection.toggle_output(index) {
+            info!(
+                "Sending to {} {}",
+                self.connection.outputs()[index].name,
+                if enabled { "enabled" } else { "disabled" }
+            );
+        }
+    }
+
+    pub fn connection_changed(&mut self, connected: bool) {
+        self.connected = connected;
+        if connected && !self.offline.is_empty() {
+            let now = Instant::now();
+            let dropped = self.offline.dropped;
+            let queued = self.offline.drain(now);
+            info!(
+                "Sending {} queued commands, {} dropped",
+                queued.len(),
+                dropped
+            );
+            for bytes in queued {
+                if self.limiter.submit(&bytes, now) {
+                    self.transmit(&bytes);
+                }
+            }
+        }
+        if connected && self.config.handshake.enabled {
+            self.start_handshake();
+        }
+    }
+
+    /// Records a message received on another port than the Neutron's, and forwards it to the
+    /// outputs if through is enabled. Only what is forwarded affects the tracked state.
+    pub fn receive_from_port(&mut self, port: &str, bytes: Vec<u8>) {
+        let entry = HistoryEntry::from_port(Message::parse(&bytes), port);
+        self.stats.received.record(&entry.message, entry.time);
+        self.midi_in_messages.push(entry);
+        if let Some(mapping) = self.cc_map.learn_from(&bytes) {
+            info!(
+                "Mapped controller {} on channel {} to {}",
+                mapping.controller,
+                mapping.channel.number(),
+                mapping.setting.id()
+            );
+            if let Err(error) = self.cc_map.save(&self.config.mappings.file) {
+                error!("Could not save controller mappings: {}", error);
+            }
+            return;
+        }
+        let expected = self.neutron_state.expected();
+        let current = |id: SettingId| {
+            expected
+                .settings
+                .values()
+                .find(|setting| setting.id() == id)
+                .and_then(GlobalSetting::raw_value)
+        };
+        if let Some(global_setting) = self.cc_map.translate(&bytes, current) {
+            self.command_message(&SetGlobalSetting(self.target, global_setting));
+            return;
+        }
+        if self.through.enabled {
+            if let Some(forwarded) = self.through.process(bytes) {
+                self.command(&forwarded);
+            }
+        }
+    }
+
+    pub fn receive_raw(&mut self, bytes: Vec<u8>) {
+        if let Some(check) = &mut self.sync_check {
+            check.receive_raw(&bytes);
+        }
+        let entry = HistoryEntry::new(Message::Raw(bytes));
+        self.stats.received.record(&entry.message, entry.time);
+        // Messages following a state dump are shown with it
+        if let Message::Raw(bytes) = &entry.message {
+            if let Some(poll) = self.polls.follow_up(bytes, entry.time) {
+                // Gone if the received messages were replaced since the state dump
+                if let Some(dump) = self.midi_in_messages.get_mut(poll.entry) {
+                    dump.text = poll.summary();
+                    return;
+                }
+            }
+        }
+        self.midi_in_messages.push(entry);
+    }
+
+    /// Periodic work: timeouts, polling and macro playback.
+    pub fn tick(&mut self, now: Instant) {
+        self.check_handshake();
+        self.check_channel_wizard();
+        for global_setting in self.acks.expire(now) {
+            warn!("No acknowledgment for {:?}", global_setting);
+        }
+        self.stats.advance(now);
+        // Not even counted while the Neutron takes a firmware, unanswered they would stop it
+        if self.upload.is_none() && self.poller.poll(now) {
+            self.poll_state();
+        }
+        self.check_watchdog(now);
+        let expired = self.offline.expire(now);
+        if expired > 0 {
+            warn!("Dropped {} queued commands waiting for too long", expired);
+        }
+        let mut send_failed = false;
+        self.connection.connect_outputs(now);
+        for event in self.connection.poll_connections() {
+            // The Neutron's own output connects in the background over BLE, and may go away
+            let neutron = |port: &String| *port == self.connection.outputs()[0].name;
+            match &event {
+                ConnectionEvent::SendFailed(_, _) => {
+                    send_failed = true;
+                    warn!("{}", event)
+                }
+                ConnectionEvent::Failed(port, _) => {
+                    warn!("{}", event);
+                    if neutron(port) && self.connected {
+                        self.connection_changed(false);
+                    }
+                }
+                ConnectionEvent::Connected(port) => {
+                    info!("{}", event);
+                    if neutron(port) && !self.connected {
+                        self.connection_changed(true);
+                    }
+                }
+                _ => info!("{}", event),
+            }
+        }
+        self.run_upload(now, send_failed);
+        self.pause_limiter();
+        for global_setting in self.automation.due(now) {
+            self.command_message(&SetGlobalSetting(self.target, global_setting));
+        }
+        self.run_sync_check(now);
+        while let Ok(request) = self.remote.1.try_recv() {
+            self.answer(request);
+        }
+        // Play back macros
+        while let Some(msg) = self.macros.next_message(now) {
+            self.command(msg.as_slice());
+        }
+        while let Some(bytes) = self.limiter.poll(now) {
+            self.transmit(&bytes);
+        }
+    }
+
+    /// Starts sending `firmware`, read from `path`, to the Neutron. An earlier upload of the same
+    /// file that was interrupted is resumed.
+    pub fn start_upload(&mut self, firmware: &Firmware, path: &Path) {
+        if self.options.read_only {
+            warn!("Read-only mode, not sending firmware");
+            return;
+        }
+        if !self.connected || self.unresponsive {
+            warn!("Firmware not sent, the Neutron isn't connected");
+            return;
+        }
+        let upload = Upload::start(firmware, Some(Upload::progress_path(path)));
+        if upload.next > 0 {
+            info!(
+                "Resuming firmware upload at block {} of {}",
+                upload.next + 1,
+                upload.len()
+            );
+        } else {
+            warn!("Sending firmware, {} blocks", upload.len());
+        }
+        self.upload = Some(upload);
+        self.pause_limiter();
+    }
+
+    /// Holds back the commands going through the rate limiter while the Neutron doesn't respond,
+    /// or takes a firmware.
+    fn pause_limiter(&mut self) {
+        self.limiter.paused = self.unresponsive || self.upload.is_some();
+    }
+
+    fn run_upload(&mut self, now: Instant, send_failed: bool) {
+        let upload = match &mut self.upload {
+            Some(upload) => upload,
+            None => return,
+        };
+        if send_failed || !self.connected || self.unresponsive {
+            error!(
+                "Firmware upload stopped at block {} of {}, start it again to resume",
+                upload.next + 1,
+                upload.len()
+            );
+            self.upload = None;
+            return;
+        }
+        if let Some(delivering) = &upload.delivering {
+            let result = match delivering.try_recv() {
+                Ok(result) => result,
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => Err(String::from("sending stopped")),
+            };
+            upload.delivering = None;
+            if let Err(error) = result {
+                error!("Firmware upload stopped: {}", error);
+                self.upload = None;
+                return;
+            }
+            if let Err(error) = upload.sent(now) {
+                warn!("Could not save the firmware upload progress: {}", error);
+            }
+        }
+        if let Some(block) = upload.due(now) {
+            // Only the Neutron gets the firmware, the progress is saved once it was sent
+            match self.connection.send_to_neutron(block) {
+                Ok(delivery) => upload.delivering = Some(delivery),
+                Err(error) => {
+                    error!("Firmware upload stopped: {}", error);
+                    self.upload = None;
+                    return;
+                }
+            }
+        }
+        if upload.is_done() {
+            info!("Sent all {} firmware blocks", upload.len());
+            self.upload = None;
+        }
+    }
+
+    /// Starts checking that the LFO syncs to MIDI clock at `bpm`, see `SyncCheck`.
+    pub fn start_sync_check(&mut self, bpm: u32) {
+        if self.options.read_only {
+            warn!("Read-only mode, the LFO sync check needs to send");
+            return;
+        }
+        let restore = self
+            .neutron_state
+            .expected()
+            .settings
+            .values()
+            .find_map(|global_setting| match global_setting {
+                GlobalSetting::LfoMidiSync(t) => Some(*t),
+                _ => None,
+            })
+            .unwrap_or(ToggleOption::Off);
+        info!("Checking LFO sync at {} BPM", bpm);
+        self.sync_check = Some(SyncCheck::start(bpm, restore, Instant::now()));
+    }
+
+    /// Counts a tap of the tempo key, a running sync check follows the tapped tempo right away.
+    pub fn tap_tempo(&mut self) {
+        let now = Instant::now();
+        let bpm = match self.tempo.tap(now) {
+            Some(bpm) => bpm,
+            None => return,
+        };
+        info!("Tapped {} BPM", bpm);
+        if let Some(check) = &mut self.sync_check {
+            check.set_bpm(bpm, now);
+        }
+    }
+
+    fn run_sync_check(&mut self, now: Instant) {
+        let steps = match &mut self.sync_check {
+            Some(check) => check.due(now),
+            None => return,
+        };
+        for step in steps {
+            match step {
+                // Clocks skip the rate limiter and history, they would drown out everything else
+                Step::Clock if self.connected && self.upload.is_none() => {
+                    if let Err(error) = self.connection.send_message(&[CLOCK]) {
+                        error!("{}", error);
+                    }
+                }
+                Step::Clock => {}
+                Step::Setting(global_setting) => {
+                    self.command_message(&SetGlobalSetting(self.target, global_setting));
+                }
+                Step::Poll => self.poll_state(),
+            }
+        }
+        if let Some(report) = self.sync_check.as_ref().and_then(SyncCheck::report) {
+            if report.responded() {
+                info!("{}", report);
+            } else {
+                warn!("{}", report);
+            }
+            self.sync_check = None;
+        }
+    }
+
+    fn start_handshake(&mut self) {
+        info!("Starting handshake");
+        self.handshake = Some(Handshake::start(self.config.handshake.timeout));
+        for request in Handshake::requests() {
+            self.command(request.as_slice());
+        }
+    }
+
+    /// Switches to the workspace at `index`: its target, state and messages become the ones of
+    /// the core, and the current ones are kept for switching back. Returns whether it changed.
+    pub fn switch_workspace(&mut self, index: usize) -> bool {
+        let mut live = Workspace {
+            target: self.target,
+            neutron_state: mem::replace(&mut self.neutron_state, state::NeutronState::new()),
+            command_history: mem::take(&mut self.command_history),
+            midi_in_messages: mem::take(&mut self.midi_in_messages),
+        };
+        let switched = self.workspaces.activate(index, &mut live);
+        self.target = live.target;
+        self.neutron_state = live.neutron_state;
+        self.command_history = live.command_history;
+        self.midi_in_messages = live.midi_in_messages;
+        // A poll in progress points into the messages switched away from
+        self.polls = PollTracker::new();
+        switched
+    }
+
+    pub fn receive(&mut self, msg: NeutronMessage) {
+        if let Some(workspace) = self.workspaces.background_for(&msg) {
+            let entry = HistoryEntry::new(Message::Parsed(msg.clone()));
+            self.stats.received.record(&entry.message, entry.time);
+            workspace.receive(&msg, entry);
+            return;
+        }
+        self.latency.received(&msg, Instant::now());
+        if let Some(check) = &mut self.sync_check {
+            check.receive(&msg);
+        }
+        if let Some(handshake) = &mut self.handshake {
+            handshake.receive(&msg);
+        }
+        if let Some(wizard) = &mut self.channel_wizard {
+            wizard.receive(&msg);
+        }
+        if let GlobalSettingUpdate(_, global_setting) = msg {
+            self.acks.acknowledged(global_setting);
+        }
+        if let StateDump(_, _) = msg {
+            self.poller.answered();
+        }
+        self.neutron_state.update(msg.clone());
+        if matches!(msg, StateDump(_, _)) && !self.neutron_state.drift.is_empty() {
+            warn!(
+                "Neutron state drifted from expected: {}",
+                self.neutron_state.drift.join(", ")
+            );
+        }
+        let entry = HistoryEntry::new(Message::Parsed(msg));
+        self.stats.received.record(&entry.message, entry.time);
+        if let Message::Parsed(StateDump(_, payload)) = &entry.message {
+            self.polls.state(
+                payload,
+                self.midi_in_messages.len(),
+                &entry.text,
+                entry.time,
+            );
+        }
+        self.midi_in_messages.push(entry);
+    }
+
+    pub fn resend_unconfirmed(&mut self) {
+        let unconfirmed: Vec<GlobalSetting> =
+            self.neutron_state.unconfirmed.values().cloned().collect();
+        info!("Resending {} unconfirmed settings", unconfirmed.len());
+        for global_setting in unconfirmed {
+            self.command_message(&SetGlobalSetting(self.target, global_setting));
+        }
+    }
+
+    pub fn export_history(&mut self) {
+        let path = Path::new(script::DEFAULT_SCRIPT_FILE);
+        let messages: Vec<Vec<u8>> = self
+            .command_history
+            .iter()
+            .map(|entry| entry.message.to_bytes())
+            .collect();
+        match script::export(path, &messages) {
+            Ok(()) => info!(
+                "Exported {} commands to {}",
+                self.command_history.len(),
+                path.display()
+            ),
+            Err(error) => error!("Could not export command history: {}", error),
+        }
+    }
+
+    /// Records the sent and received messages with their timing to a MIDI file.
+    pub fn export_session(&mut self) {
+        let path = Path::new(smf::DEFAULT_SMF_FILE);
+        let start = self
+            .command_history
+            .iter()
+            .chain(self.midi_in_messages.iter())
+            .map(|entry| entry.time)
+            .min();
+        let timed = |history: &[HistoryEntry]| -> Vec<TimedMessage> {
+            history
+                .iter()
+                .map(|entry| {
+                    let time = start.map_or_else(Default::default, |start| entry.time - start);
+                    (time, entry.message.to_bytes())
+                })
+                .collect()
+        };
+        let sent = timed(&self.command_history);
+        let received = timed(&self.midi_in_messages);
+        match smf::export(path, &sent, &received) {
+            Ok(()) => info!(
+                "Recorded {} sent and {} received messages to {}",
+                sent.len(),
+                received.len(),
+                path.display()
+            ),
+            Err(error) => error!("Could not record session: {}", error),
+        }
+    }
+
+    /// Sends the SysEx messages of a MIDI file at their recorded times.
+    pub fn import_session(&mut self) {
+        let path = Path::new(smf::DEFAULT_SMF_FILE);
+        match smf::import(path) {
+            Ok(messages) => {
+                info!(
+                    "Playing {} SysEx messages from {}",
+                    messages.len(),
+                    path.display()
+                );
+                self.macros.play_timed(messages);
+            }
+            Err(error) => error!("Could not import {}: {}", path.display(), error),
+        }
+    }
+
+    pub fn import_script(&mut self) {
+        let path = Path::new(script::DEFAULT_SCRIPT_FILE);
+        match script::import(path) {
+            Ok(steps) => {
+                info!("Running {} steps from {}", steps.len(), path.display());
+                if let Err(error) = self.macros.play_steps(steps) {
+                    error!("Could not run {}: {}", path.display(), error);
+                }
+            }
+            Err(error) => error!("Could not import {}: {}", path.display(), error),
+        }
+    }
+
+    pub fn export_preset(&mut self) {
+        let path = Path::new(DEFAULT_SYX_FILE);
+        let preset = self.neutron_state.preset();
+        match fs::write(path, preset::to_syx(&preset)) {
+            Ok(()) => info!(
+                "Exported {} settings to {}",
+                preset.settings.len(),
+                path.display()
+            ),
+            Err(error) => error!("Could not export {}: {}", path.display(), error),
+        }
+    }
+
+    pub fn import_preset(&mut self) {
+        let path = Path::new(DEFAULT_SYX_FILE);
+        let preset = fs::read(path)
+            .map_err(|error| error.to_string())
+            .and_then(|bytes| preset::from_app_dump(&bytes).map_err(|error| error.to_string()));
+        match preset {
+            Ok(preset) => {
+                info!("Loaded {}", path.display());
+                self.send_preset(&preset);
+            }
+            Err(error) => error!("Could not import {}: {}", path.display(), error),
+        }
+    }
+
+    pub fn send_preset(&mut self, preset: &Preset) {
+        info!("Sending {} settings", preset.settings.len());
+        let target = self.target;
+        self.macros.play(
+            preset
+                .settings
+                .iter()
+                .map(|s| SetGlobalSetting(target, *s).as_bytes())
+                .collect(),
+        );
+    }
+
+    pub fn start_channel_wizard(&mut self, channel: Channel) {
+        info!("Changing MIDI channel to {}", channel.number());
+        let wizard = ChannelWizard::start(self.target, channel, self.config.handshake.timeout);
+        for request in wizard.requests() {
+            self.command(request.as_slice());
+        }
+        self.channel_wizard = Some(wizard);
+    }
+
+    fn check_channel_wizard(&mut self) {
+        let wizard = match &self.channel_wizard {
+            Some(wizard) => wizard,
+            None => return,
+        };
+        if wizard.is_verified() {
+            info!(
+                "The Neutron responded on MIDI channel {}",
+                wizard.channel.number()
+            );
+            self.target = wizard.target();
+            self.channel_wizard = None;
+        } else if wizard.has_timed_out(Instant::now()) {
+            let rollback = wizard.rollback();
+            match (&rollback, wizard.previous) {
+                (Some(_), DeviceId::Channel(previous)) => error!(
+                    "The Neutron did not respond on MIDI channel {}, restoring channel {}",
+                    wizard.channel.number(),
+                    previous.number()
+                ),
+                _ => error!(
+                    "The Neutron did not respond on MIDI channel {}, the previous channel is \
+                     unknown so it could not be restored",
+                    wizard.channel.number()
+                ),
+            }
+            self.channel_wizard = None;
+            if let Some(message) = rollback {
+                self.command(message.as_slice());
+            }
+        }
+    }
+
+    fn poll_state(&mut self) {
+        if self.upload.is_some() {
+            return;
+        }
+        // Polls are not recorded in the command history, they would drown out everything else
+        let request = StateRequest(self.target);
+        if let Err(error) = self.connection.send(&request) {
+            error!("{}", error);
+        }
+        self.latency.sent(&request, Instant::now());
+        self.polls.requested(Instant::now());
+    }
+
+    /// Marks the Neutron as unresponsive when polls go unanswered for too long, holding back
+    /// outgoing messages and trying to reconnect each time the timeout passes again.
+    fn check_watchdog(&mut self, now: Instant) {
+        match self.poller.unanswered_for(now) {
+            Some(unanswered) if unanswered > self.config.polling.timeout => {
+                if !self.unresponsive {
+                    warn!(
+                        "The Neutron did not answer polls for {} s, holding back messages",
+                        unanswered.as_secs()
+                    );
+                    self.unresponsive = true;
+                    self.pause_limiter();
+                }
+                info!("Reconnecting to {}", self.config.device.port);
+                self.reconnect();
+                self.poller.restart_count(now);
+            }
+            None if self.unresponsive => {
+                info!("The Neutron responds again");
+                self.unresponsive = false;
+                self.pause_limiter();
+            }
+            _ => {}
+        }
+    }
+
+    fn check_handshake(&mut self) {
+        if let Some(handshake) = &self.handshake {
+            if handshake.is_complete() {
+                info!("Handshake complete");
+                self.handshake = None;
+            } else if handshake.has_timed_out(Instant::now()) {
+                warn!("The Neutron did not respond to the handshake");
+                self.handshake = None;
+            }
+        }
+    }
+
+    /// Where remote control surfaces send their requests, answered on each tick.
+    pub fn remote(&self) -> Sender<remote::Request> {
+        self.remote.0.clone()
+    }
+
+    /// Answers a request of a remote control surface. Settings are sent through `command` like
+    /// any other, so read-only mode and confirmations apply to them too.
+    fn answer(&mut self, request: remote::Request) {
+        let expected = self.neutron_state.expected();
+        let value = |global_setting: &GlobalSetting| {
+            let confirmed = self
+                .neutron_state
+                .confirmed
+                .settings
+                .get(&global_setting.key());
+            (*global_setting, confirmed == Some(global_setting))
+        };
+        // Replies are dropped when the surface gave up waiting
+        match request {
+            remote::Request::Set {
+                setting,
+                value,
+                reply,
+            } => {
+                let result = GlobalSetting::from_name_value(&setting, &value)
+                    .map_err(|error| error.to_string())
+                    .map(|global_setting| {
+                        self.command_message(&SetGlobalSetting(self.target, global_setting))
+                    });
+                let _ = reply.send(result);
+            }
+            remote::Request::Get { setting, reply } => {
+                let result = SettingId::from_id(&setting)
+                    .ok_or_else(|| format!("unknown setting {}", setting))
+                    .map(|id| {
+                        let current = expected.settings.values().find(|s| s.id() == id);
+                        current.map(value)
+                    });
+                let _ = reply.send(result);
+            }
+            remote::Request::Subscribe { reply } => {
+                let _ = reply.send(self.neutron_state.subscribe());
+            }
+            remote::Request::Dump { reply } => {
+                let settings = expected.settings.values().map(value).collect();
+                let _ = reply.send((settings, self.neutron_state.state_dump.clone()));
+            }
+        }
+    }
+
+    /// The expected settings with their marks for the state view: `*` while the last command for
+    /// the setting waits for an acknowledgment and `!` when it differs from `loaded`.
+    pub fn marked_settings(&self, loaded: Option<&Preset>) -> Vec<(GlobalSetting, String)> {
+        self.neutron_state
+            .expected()
+            .settings
+            .values()
+            .map(|global_setting| {
+                let mut mark = String::new();
+                if self.acks.is_pending(global_setting) {
+                    mark.push('*');
+                }
+                if loaded
+                    .and_then(|preset| preset.get(&global_setting.key()))
+                    .is_some_and(|value| value != *global_setting)
+                {
+                    mark.push('!');
+                }
+                (*global_setting, mark)
+            })
+            .collect()
+    }
+
+    /// Switches a setting with two values to its other one, see `NeutronState::toggle`. While
+    /// the value isn't known the state is requested instead, if state dumps carry it, rather than
+    /// guessing and maybe sending what is already set.
+    pub fn toggle(&mut self, id: SettingId) {
+        if !id.is_two_valued() {
+            warn!("{} can't be toggled", id.id());
+            return;
+        }
+        match self.neutron_state.toggle(id) {
+            state::Toggle::Set(toggled) => {
+                self.command_message(&SetGlobalSetting(self.target, toggled));
+            }
+            state::Toggle::RequestState => {
+                warn!(
+                    "The value of {} isn't known yet, requesting the state",
+                    id.id()
+                );
+                self.command_message(&StateRequest(self.target));
+            }
+            state::Toggle::Unknown => warn!(
+                "The value of {} isn't known yet, set it once before toggling it",
+                id.id()
+            ),
+        }
+    }
+
+    /// Silences the Neutron right away: stops macros, automation and the sync check, drops the
+    /// commands waiting for the rate limiter and sends All Sound Off and All Notes Off past it.
+    /// A stopped sync check sets MIDI sync back as it was. With `[output] panic_reset` the key
+    /// range is also reset and unmuted.
+    pub fn panic(&mut self) {
+        warn!("Panic: silencing the Neutron");
+        self.macros.stop_playing();
+        let sync_check = self.sync_check.take();
+        if self.automation.is_running() {
+            self.automation.toggle(Instant::now());
+        }
+        let dropped = self.limiter.clear();
+        if dropped > 0 {
+            info!("Dropped {} commands waiting to be sent", dropped);
+        }
+        let flushed = self.connection.flush();
+        if flushed > 0 {
+            info!("Dropped {} messages waiting for the outputs", flushed);
+        }
+        for message in midi::panic_messages() {
+            self.transmit(&message);
+        }
+        if let Some(sync_check) = sync_check {
+            let restore = GlobalSetting::LfoMidiSync(sync_check.restore());
+            self.command_message(&SetGlobalSetting(self.target, restore));
+        }
+        if self.config.output.panic_reset {
+            for global_setting in &[
+                GlobalSetting::KeyRangeReset,
+                GlobalSetting::KeyRangeMute(ToggleOption::Off),
+            ] {
+                self.command_message(&SetGlobalSetting(self.target, *global_setting));
+            }
+        }
+    }
+
+    /// Updates the tracked state for a command and sends it through the rate limiter. Nothing
+    /// happens for commands blocked by read-only mode, and commands as risky as
+    /// `[safety] confirm` are held back until confirmed.
+    pub fn command(&mut self, bytes: &[u8]) -> CommandStatus {
+        if !self.options.allows(bytes) {
+            warn!("Read-only mode, not sending {}", Message::parse(bytes));
+            return CommandStatus::Blocked;
+        }
+        if self.needs_confirmation(Risk::of(bytes)) {
+            if self.awaiting_confirmation.is_some() {
+                warn!(
+                    "Not sending {}, another command awaits confirmation",
+                    Message::parse(bytes)
+                );
+                return CommandStatus::Blocked;
+            }
+            info!("{} needs to be confirmed", Message::parse(bytes));
+            self.awaiting_confirmation = Some(bytes.to_vec());
+            return CommandStatus::Held;
+        }
+        self.send_command(bytes);
+        CommandStatus::Sent
+    }
+
+    /// Encodes `message` into a buffer kept for it and passes it on to `command`.
+    pub fn command_message(&mut self, message: &NeutronMessage) -> CommandStatus {
+        let mut scratch = mem::take(&mut self.scratch);
+        scratch.clear();
+        message.write_to(&mut scratch);
+        let status = self.command(&scratch);
+        self.scratch = scratch;
+        status
+    }
+
+    /// Whether actions of `risk` need to be confirmed before they are sent.
+    pub fn needs_confirmation(&self, risk: Risk) -> bool {
+        self.config
+            .safety
+            .confirm
+            .is_some_and(|confirm| risk >= confirm)
+    }
+
+    /// Sends the command awaiting confirmation.
+    pub fn confirm(&mut self) {
+        if let Some(bytes) = self.awaiting_confirmation.take() {
+            self.send_command(&bytes);
+        }
+    }
+
+    /// Drops the command awaiting confirmation, returning it.
+    pub fn cancel_confirmation(&mut self) -> Option<Vec<u8>> {
+        let bytes = self.awaiting_confirmation.take()?;
+        info!("Not sending {}", Message::parse(&bytes));
+        Some(bytes)
+    }
+
+    fn send_command(&mut self, bytes: &[u8]) {
+        let message = Message::parse(bytes);
+        if let Message::Parsed(msg) = &message {
+            let mut affects_midi_channel = false;
+            if let SetGlobalSetting(_, global_setting) = msg {
+                self.acks.sent(*global_setting, Instant::now());
+                affects_midi_channel = matches!(
+                    global_setting,
+                    GlobalSetting::MidiChannel(_) | GlobalSetting::DisableMidiDips(_)
+                );
+            }
+            self.neutron_state.update(msg.clone());
+            if affects_midi_channel {
+                for warning in self.neutron_state.midi_advice().warnings {
+                    warn!("{}", warning);
+                }
+            }
+        }
+        self.macros.record(bytes);
+        if self.limiter.submit(bytes, Instant::now()) {
+            self.transmit(bytes);
+        }
+    }
+
+    /// Sends a message that passed the rate limiter and adds it to the history, or queues it
+    /// until the Neutron is connected. Nothing is sent during a firmware upload.
+    fn transmit(&mut self, bytes: &[u8]) {
+        if !self.options.allows(bytes) {
+            warn!("Read-only mode, not sending {}", Message::parse(bytes));
+            return;
+        }
+        if self.upload.is_some() {
+            warn!(
+                "Not sending {} during the firmware upload",
+                Message::parse(bytes)
+            );
+            return;
+        }
+        if !self.connected {
+            self.offline.push(bytes.to_vec(), Instant::now());
+            return;
+        }
+        if let Err(error) = self.connection.send_message(bytes) {
+            error!("{}", error);
+        };
+        let entry = HistoryEntry::new(Message::parse(bytes));
+        if let Message::Parsed(msg) = &entry.message {
+            self.latency.sent(msg, entry.time);
+            if let StateRequest(_) = msg {
+                self.polls.requested(entry.time);
+            }
+        }
+        self.stats.sent.record(&entry.message, entry.time);
+        self.command_history.push(entry);
+    }
+
+    pub fn toggle_macro_recording(&mut self) {
+        if !self.macros.is_recording() {
+            info!("Recording macro");
+            self.macros.start_recording();
+            return;
+        }
+        match self.macros.stop_recording() {
+            Some(recorded) => {
+                match recorded.key {
+                    Some(key) => info!("Recorded {}, bound to F{}", recorded.name, key),
+                    None => info!("Recorded {}", recorded.name),
+                }
+                self.config.macros = self.macros.macros.clone();
+                if let Err(error) = self.config.save() {
+                    error!("Could not save macros: {}", error);
+                }
+            }
+            None => info!("Discarded empty macro"),
+        }
+    }
+
+    pub fn play_macro(&mut self, key: u8) {
+        match self.macros.play_bound(key) {
+            Ok(true) => {}
+            Ok(false) => warn!("No macro bound to F{}", key),
+            Err(error) => error!("Could not play the macro bound to F{}: {}", key, error),
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use std::error;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use rustron_lib::protocol::{Channel, ToggleOption};
+
+/// The four MIDI channel DIP switches on the back of the Neutron, as entered by the user since
+/// they can't be read over MIDI. Switch 1 is the least significant bit of the channel.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DipSwitches(pub [bool; 4]);
+
+impl DipSwitches {
+    /// The MIDI channel selected by the switches.
+    pub fn channel(self) -> Channel {
+        let value = self
+            .0
+            .iter()
+            .rev()
+            .fold(0, |value, on| (value << 1) | u8::from(*on));
+        // Four bits always make a valid channel
+        Channel::from_number(value + 1).unwrap()
+    }
+}
+
+impl Display for DipSwitches {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for on in self.0.iter() {
+            write!(f, "{}", if *on { '1' } else { '0' })?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for DipSwitches {
+    type Err = Box<dyn error::Error>;
+
+    /// Parses the switch positions from switch 1 to 4, e.g. `0100`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut switches = [false; 4];
+        if s.len() != switches.len() {
+            return Err(format!("expected 4 switch positions, got {}", s).into());
+        }
+        for (switch, c) in switches.iter_mut().zip(s.chars()) {
+            *switch = match c {
+                '0' => false,
+                '1' => true,
+                _ => return Err(format!("invalid switch position {}, expected 0 or 1", c).into()),
+            };
+        }
+        Ok(DipSwitches(switches))
+    }
+}
+
+/// The MIDI channel the Neutron ends up listening on, and where that comes from.
+#[derive(Debug, PartialEq)]
+pub struct MidiAdvice {
+    pub channel: Option<Channel>,
+    pub source: &'static str,
+    pub warnings: Vec<String>,
+}
+
+/// Works out the effective MIDI channel from the DIP switches and the `MidiChannel` and
+/// `DisableMidiDips` settings, warning about settings that the switches override.
+pub fn advise(
+    dip_switches: Option<DipSwitches>,
+    midi_channel: Option<Channel>,
+    disable_midi_dips: Option<ToggleOption>,
+) -> MidiAdvice {
+    if disable_midi_dips == Some(ToggleOption::On) {
+        return MidiAdvice {
+            channel: midi_channel,
+            source: "MIDI channel setting",
+            warnings: Vec::new(),
+        };
+    }
+    let mut warnings = Vec::new();
+    match (midi_channel, dip_switches) {
+        (Some(channel), Some(dips)) if channel != dips.channel() => warnings.push(format!(
+            "MIDI channel {} is overridden by the DIP switches (channel {}), disable the DIP \
+             switches for it to take effect",
+            channel.number(),
+            dips.channel().number()
+        )),
+        (Some(channel), None) => warnings.push(format!(
+            "MIDI channel {} only takes effect if the DIP switches are disabled",
+            channel.number()
+        )),
+        _ => {}
+    }
+    MidiAdvice {
+        channel: dip_switches.map(DipSwitches::channel),
+        source: "DIP switches",
+        warnings,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rustron_lib::protocol::Channel::{Five, One, Sixteen, Three};
+    use rustron_lib::protocol::ToggleOption::{Off, On};
+
+    use crate::dips::{advise, DipSwitches};
+
+    #[test]
+    fn switches_select_channel() {
+        let dips: DipSwitches = "0100".parse().unwrap();
+        assert_eq!(dips.channel(), Three);
+        assert_eq!(dips.to_string(), "0100");
+        assert_eq!("1111".parse::<DipSwitches>().unwrap().channel(), Sixteen);
+        assert!("012".parse::<DipSwitches>().is_err());
+        assert!("0120".parse::<DipSwitches>().is_err());
+    }
+
+    #[test]
+    fn overridden_channel_is_warned_about() {
+        let dips = Some(DipSwitches([false; 4]));
+        let advice = advise(dips, Some(Five), Some(Off));
+        assert_eq!(advice.channel, Some(One));
+        assert_eq!(advice.warnings.len(), 1);
+
+        let advice = advise(dips, Some(Five), Some(On));
+        assert_eq!(advice.channel, Some(Five));
+        assert!(advice.warnings.is_empty());
+
+        assert!(advise(dips, Some(One), None).warnings.is_empty());
+    }
+}
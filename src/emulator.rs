@@ -0,0 +1,326 @@
+use std::thread;
+use std::time::Duration;
+
+use rustron_lib::parser::neutron_message;
+use rustron_lib::preset;
+use rustron_lib::preset::Preset;
+use rustron_lib::protocol::GlobalSetting::{KeyRangeReset, LfoResetOrder, MidiChannel};
+use rustron_lib::protocol::NeutronMessage::{
+    GlobalSettingUpdate, RestoreGlobalSetting, SetGlobalSetting, SoftwareVersionRequest,
+    SoftwareVersionResponse, StateDump, StateRequest,
+};
+use rustron_lib::protocol::{Channel, DeviceId, NeutronMessage};
+
+use crate::midi::{InputQueue, Source};
+use crate::scenario::Scenario;
+
+/// The payload of a state dump captured from a Neutron with osc sync and paraphonic mode off, see
+/// `rustron-lib/tests/fixtures`. The known bits are overwritten with the emulated settings.
+const BASE_STATE_DUMP: [u8; 24] = [
+    0x6b, 0x02, 0x00, 0x00, 0x02, 0x31, 0x08, 0x58, 0x46, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x7f, 0x0f, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
+];
+
+/// A small xorshift generator, enough to inject faults and randomize settings reproducibly.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        // Xorshift never leaves zero
+        Rng(seed.max(1))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// `true` with a probability of `fraction`.
+    pub fn chance(&mut self, fraction: f64) -> bool {
+        fraction > 0.0 && ((self.next_u64() >> 11) as f64 / (1u64 << 53) as f64) < fraction
+    }
+
+    /// A number below `bound`, which must not be zero.
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+/// Corrupts a fraction of the received messages before they are parsed, to exercise how
+/// malformed traffic is handled. Only used with the emulator.
+pub struct Corruptor {
+    pub fraction: f64,
+    /// How many messages were corrupted so far
+    pub corrupted: usize,
+    rng: Rng,
+}
+
+impl Corruptor {
+    pub fn new(fraction: f64, seed: u64) -> Corruptor {
+        Corruptor {
+            fraction,
+            corrupted: 0,
+            rng: Rng::new(seed),
+        }
+    }
+
+    /// Changes, cuts off or inserts a byte of some of the messages.
+    pub fn apply(&mut self, mut bytes: Vec<u8>) -> Vec<u8> {
+        if bytes.is_empty() || !self.rng.chance(self.fraction) {
+            return bytes;
+        }
+        self.corrupted += 1;
+        let byte = self.rng.next_u64() as u8;
+        match self.rng.below(3) {
+            0 => {
+                let index = self.rng.below(bytes.len());
+                bytes[index] = byte;
+            }
+            1 => bytes.truncate(self.rng.below(bytes.len())),
+            _ => {
+                let index = self.rng.below(bytes.len() + 1);
+                bytes.insert(index, byte)
+            }
+        }
+        bytes
+    }
+}
+
+/// Stands in for the Neutron when simulating: keeps the settings sent to it and answers like the
+/// real one does, as far as we know how it does. A `Scenario` can make it slow and unreliable.
+pub struct Emulator {
+    pub channel: Channel,
+    pub version: String,
+    pub settings: Preset,
+    pub latency: Duration,
+    /// Fraction of setting updates not sent
+    pub drop_acks: f64,
+    /// Fraction of answers with a corrupted byte
+    pub garble: f64,
+    /// The size of the pieces answers arrive in, see `Scenario`
+    pub chunk: Option<usize>,
+    rng: Rng,
+    /// Where the answers go, as if received from the Neutron
+    queue: InputQueue,
+}
+
+impl Emulator {
+    pub fn new(queue: InputQueue) -> Emulator {
+        Emulator {
+            channel: Channel::One,
+            version: String::from("2.0.2"),
+            settings: preset::factory_defaults(),
+            latency: Duration::from_millis(0),
+            drop_acks: 0.0,
+            garble: 0.0,
+            chunk: None,
+            rng: Rng::new(1),
+            queue,
+        }
+    }
+
+    pub fn with_scenario(queue: InputQueue, scenario: &Scenario) -> Emulator {
+        let mut emulator = Emulator::new(queue);
+        if let Some(version) = &scenario.version {
+            emulator.version = version.clone();
+        }
+        if let Some(channel) = scenario.channel {
+            emulator.channel = channel;
+        }
+        for global_setting in &scenario.settings {
+            emulator.settings.set(*global_setting);
+        }
+        emulator.latency = scenario.latency;
+        emulator.drop_acks = scenario.drop_acks;
+        emulator.garble = scenario.garble;
+        emulator.chunk = scenario.chunk;
+        emulator.rng = Rng::new(scenario.seed);
+        emulator
+    }
+
+    fn is_addressed(&self, device: DeviceId) -> bool {
+        match device {
+            DeviceId::Multicast => true,
+            DeviceId::Channel(channel) => channel == self.channel,
+        }
+    }
+
+    /// Handles a message sent to the Neutron, returning the answers.
+    pub fn respond(&mut self, message: &[u8]) -> Vec<NeutronMessage> {
+        let message = match neutron_message(message) {
+            Ok((_, message)) => message,
+            Err(_) => return Vec::new(),
+        };
+        let id = DeviceId::Channel(self.channel);
+        match message {
+            SetGlobalSetting(device, setting) if self.is_addressed(device) => {
+                match setting {
+                    LfoResetOrder | KeyRangeReset => return Vec::new(),
+                    MidiChannel(channel) => self.channel = channel,
+                    _ => {}
+                }
+                self.settings.set(setting);
+                vec![GlobalSettingUpdate(id, setting)]
+            }
+            RestoreGlobalSetting(device) if self.is_addressed(device) => {
+                self.settings = preset::factory_defaults();
+                Vec::new()
+            }
+            SoftwareVersionRequest(device) if self.is_addressed(device) => {
+                vec![SoftwareVersionResponse(id, self.version.clone())]
+            }
+            StateRequest(device) if self.is_addressed(device) => vec![StateDump(
+                id,
+                preset::to_state_dump(&self.settings, &BASE_STATE_DUMP),
+            )],
+            _ => Vec::new(),
+        }
+    }
+
+    /// The bytes of the answers as they arrive, with the faults of the scenario.
+    pub fn transmit(&mut self, answers: Vec<NeutronMessage>) -> Vec<Vec<u8>> {
+        let mut transmitted = Vec::new();
+        for answer in answers {
+            if matches!(answer, GlobalSettingUpdate(..)) && self.rng.chance(self.drop_acks) {
+                continue;
+            }
+            let mut bytes = answer.as_bytes();
+            if self.rng.chance(self.garble) {
+                // Anything but the SysEx start and end
+                let index = 1 + self.rng.below(bytes.len() - 2);
+                bytes[index] = self.rng.next_u64() as u8 & 0x7f;
+            }
+            transmitted.push(bytes);
+        }
+        transmitted
+    }
+
+    /// Handles a message sent to the Neutron, queueing the answers as received after the
+    /// latency.
+    pub fn receive(&mut self, message: &[u8]) {
+        let answers = self.respond(message);
+        let mut transmitted = self.transmit(answers);
+        if let Some(size) = self.chunk {
+            transmitted = transmitted
+                .iter()
+                .flat_map(|bytes| bytes.chunks(size).map(<[u8]>::to_vec))
+                .collect();
+        }
+        if self.latency.as_millis() == 0 {
+            for bytes in transmitted {
+                self.queue.push(Source::Neutron, bytes);
+            }
+        } else if !transmitted.is_empty() {
+            let (queue, latency) = (self.queue.clone(), self.latency);
+            thread::spawn(move || {
+                thread::sleep(latency);
+                for bytes in transmitted {
+                    queue.push(Source::Neutron, bytes);
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rustron_lib::protocol::Channel::{One, Two};
+    use rustron_lib::protocol::DeviceId::{Channel, Multicast};
+    use rustron_lib::protocol::GlobalSetting::{MidiChannel, OscSync};
+    use rustron_lib::protocol::NeutronMessage::{
+        GlobalSettingUpdate, SetGlobalSetting, SoftwareVersionRequest, StateDump, StateRequest,
+    };
+    use rustron_lib::protocol::ToggleOption::On;
+
+    use crate::core::state::NeutronState;
+    use crate::emulator::{Corruptor, Emulator};
+    use crate::midi::{InputQueue, Source};
+    use crate::scenario::Scenario;
+
+    #[test]
+    fn emulator_answers_like_the_neutron() {
+        let queue = InputQueue::new(16);
+        let mut emulator = Emulator::new(queue.clone());
+        emulator.receive(&SetGlobalSetting(Multicast, OscSync(On)).as_bytes());
+        assert_eq!(
+            queue.pop(),
+            Some((
+                Source::Neutron,
+                GlobalSettingUpdate(Channel(One), OscSync(On)).as_bytes()
+            ))
+        );
+
+        // A state dump carries the setting, in a payload as long as the Neutron's
+        let dump = emulator.respond(&StateRequest(Multicast).as_bytes());
+        assert!(matches!(&dump[0], StateDump(_, payload) if payload.len() == 24));
+        let mut state = NeutronState::new();
+        state.update(dump[0].clone());
+        assert!(state.confirmed.osc_sync);
+
+        // Only the own channel is answered
+        emulator.receive(&SetGlobalSetting(Multicast, MidiChannel(Two)).as_bytes());
+        queue.pop();
+        assert!(emulator
+            .respond(&SoftwareVersionRequest(Channel(One)).as_bytes())
+            .is_empty());
+        assert_eq!(
+            emulator
+                .respond(&SoftwareVersionRequest(Channel(Two)).as_bytes())
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn scenario_faults_are_injected() {
+        let scenario = Scenario {
+            drop_acks: 1.0,
+            garble: 1.0,
+            seed: 7,
+            settings: vec![OscSync(On)],
+            ..Default::default()
+        };
+        let mut emulator = Emulator::with_scenario(InputQueue::new(16), &scenario);
+        assert_eq!(emulator.settings.get(&OscSync(On).key()), Some(OscSync(On)));
+
+        let ack = emulator.respond(&SetGlobalSetting(Multicast, OscSync(On)).as_bytes());
+        assert!(emulator.transmit(ack).is_empty());
+
+        let version = emulator.respond(&SoftwareVersionRequest(Multicast).as_bytes());
+        let expected = version[0].as_bytes();
+        let garbled = emulator.transmit(version).remove(0);
+        assert_eq!(garbled.len(), expected.len());
+        assert_eq!(garbled.first(), expected.first());
+        assert_eq!(garbled.last(), expected.last());
+    }
+
+    #[test]
+    fn answers_in_chunks_arrive_whole() {
+        let scenario = Scenario {
+            chunk: Some(8),
+            ..Default::default()
+        };
+        let queue = InputQueue::new(16);
+        let mut emulator = Emulator::with_scenario(queue.clone(), &scenario);
+        let dump = emulator.respond(&StateRequest(Multicast).as_bytes())[0].as_bytes();
+        assert!(dump.len() > 8);
+        emulator.receive(&StateRequest(Multicast).as_bytes());
+        assert_eq!(queue.pop(), Some((Source::Neutron, dump)));
+    }
+
+    #[test]
+    fn corruptor_corrupts_the_fraction() {
+        let message = StateRequest(Multicast).as_bytes();
+        let mut corruptor = Corruptor::new(0.0, 1);
+        assert_eq!(corruptor.apply(message.clone()), message);
+        let mut corruptor = Corruptor::new(1.0, 1);
+        for _ in 0..10 {
+            // Changing a byte may keep it the same by chance, so only count
+            corruptor.apply(message.clone());
+        }
+        assert_eq!(corruptor.corrupted, 10);
+        assert_eq!(corruptor.apply(Vec::new()), Vec::<u8>::new());
+    }
+}
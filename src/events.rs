@@ -22,22 +22,131 @@
 ///OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 ///SOFTWARE.
 use std::io;
-use std::sync::mpsc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use termion::event::Key;
+use termion::event::{self, Key};
 use termion::input::TermRead;
 
+use rustron_lib::setting::ValueRange;
+
+/// How close presses of the same key must follow each other to count as holding it down.
+const REPEAT_WINDOW: Duration = Duration::from_millis(100);
+
+/// How long after the last received message the tick rate backs off to the idle rate.
+pub const IDLE_AFTER: Duration = Duration::from_secs(2);
+
+/// Picks the tick rate: fast while MIDI messages are received, so bursts are handled and drawn
+/// quickly, and slow once idle to save power.
+pub struct AdaptiveTick {
+    pub fast: Duration,
+    pub idle: Duration,
+    last_traffic: Option<Instant>,
+}
+
+impl AdaptiveTick {
+    pub fn new(fast: Duration, idle: Duration) -> AdaptiveTick {
+        AdaptiveTick {
+            fast,
+            idle,
+            last_traffic: None,
+        }
+    }
+
+    /// Notes that messages were received at `now`.
+    pub fn traffic(&mut self, now: Instant) {
+        self.last_traffic = Some(now);
+    }
+
+    pub fn rate(&self, now: Instant) -> Duration {
+        match self.last_traffic {
+            Some(time) if now.duration_since(time) < IDLE_AFTER => self.fast,
+            _ => self.idle,
+        }
+    }
+}
+
 pub enum Event<I> {
     Input(I),
     Tick,
 }
 
+/// A key press. The terminal repeats a key while it is held down, `repeats` counts those so
+/// values can step faster the longer it is held. Shift is only detected on the arrow keys.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyPress {
+    pub key: Key,
+    pub shift: bool,
+    pub repeats: u32,
+}
+
+impl KeyPress {
+    /// How much faster values step while the key is held.
+    pub fn acceleration(&self) -> i32 {
+        match self.repeats {
+            0..=9 => 1,
+            10..=19 => 2,
+            20..=39 => 4,
+            _ => 8,
+        }
+    }
+
+    /// The size of one step in `range`, coarse with Shift and accelerated while held.
+    pub fn step(&self, range: ValueRange) -> i32 {
+        let step = if self.shift {
+            range.coarse_step
+        } else {
+            range.step
+        };
+        i32::from(step) * self.acceleration()
+    }
+}
+
+/// Turns key presses into `KeyPress`es, counting repeats of the same key.
+#[derive(Default)]
+pub struct KeyRepeat {
+    last: Option<(Key, bool, Instant)>,
+    repeats: u32,
+}
+
+impl KeyRepeat {
+    pub fn press(&mut self, key: Key, shift: bool, now: Instant) -> KeyPress {
+        self.repeats = match self.last {
+            Some((last_key, last_shift, time))
+                if last_key == key && last_shift == shift && now - time <= REPEAT_WINDOW =>
+            {
+                self.repeats + 1
+            }
+            _ => 0,
+        };
+        self.last = Some((key, shift, now));
+        KeyPress {
+            key,
+            shift,
+            repeats: self.repeats,
+        }
+    }
+}
+
+/// Parses the Shift+arrow sequences termion leaves unsupported, `ESC [ 1 ; 2 A-D`.
+fn shift_arrow(sequence: &[u8]) -> Option<Key> {
+    match sequence {
+        [0x1b, b'[', b'1', b';', b'2', b'A'] => Some(Key::Up),
+        [0x1b, b'[', b'1', b';', b'2', b'B'] => Some(Key::Down),
+        [0x1b, b'[', b'1', b';', b'2', b'C'] => Some(Key::Right),
+        [0x1b, b'[', b'1', b';', b'2', b'D'] => Some(Key::Left),
+        _ => None,
+    }
+}
+
 /// A small event handler that wrap termion input and tick events. Each event
 /// type is handled in its own thread and returned to a common `Receiver`
 pub struct Events {
-    rx: mpsc::Receiver<Event<Key>>,
+    rx: mpsc::Receiver<Event<KeyPress>>,
+    /// The time between ticks in milliseconds, read by the tick thread before every tick
+    tick_rate: Arc<AtomicU64>,
     input_handle: thread::JoinHandle<()>,
     tick_handle: thread::JoinHandle<()>,
 }
@@ -57,46 +166,119 @@ impl Default for Config {
     }
 }
 
+impl Default for Events {
+    fn default() -> Events {
+        Events::new()
+    }
+}
+
 impl Events {
+    /// Events with the default tick rate and exit key.
     pub fn new() -> Events {
         Events::with_config(Config::default())
     }
 
+    /// Starts the threads reading the terminal input and sending ticks.
     pub fn with_config(config: Config) -> Events {
         let (tx, rx) = mpsc::channel();
         let input_handle = {
             let tx = tx.clone();
             thread::spawn(move || {
                 let stdin = io::stdin();
-                for evt in stdin.keys() {
-                    if let Ok(key) = evt {
-                        if tx.send(Event::Input(key)).is_err() {
-                            return;
-                        }
-                        if key == config.exit_key {
-                            return;
-                        }
+                let mut repeat = KeyRepeat::default();
+                for evt in stdin.events() {
+                    let (key, shift) = match evt {
+                        Ok(event::Event::Key(key)) => (key, false),
+                        Ok(event::Event::Unsupported(sequence)) => match shift_arrow(&sequence) {
+                            Some(key) => (key, true),
+                            None => continue,
+                        },
+                        _ => continue,
+                    };
+                    let press = repeat.press(key, shift, Instant::now());
+                    if tx.send(Event::Input(press)).is_err() {
+                        return;
+                    }
+                    if key == config.exit_key {
+                        return;
                     }
                 }
             })
         };
+        let tick_rate = Arc::new(AtomicU64::new(config.tick_rate.as_millis() as u64));
         let tick_handle = {
+            let tick_rate = Arc::clone(&tick_rate);
             thread::spawn(move || {
                 let tx = tx.clone();
                 loop {
                     tx.send(Event::Tick).unwrap();
-                    thread::sleep(config.tick_rate);
+                    thread::sleep(Duration::from_millis(tick_rate.load(Ordering::Relaxed)));
                 }
             })
         };
         Events {
             rx,
+            tick_rate,
             input_handle,
             tick_handle,
         }
     }
 
-    pub fn next(&self) -> Result<Event<Key>, mpsc::RecvError> {
+    /// Changes the time between ticks, from the next tick on.
+    pub fn set_tick_rate(&self, rate: Duration) {
+        self.tick_rate
+            .store(rate.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    pub fn next(&self) -> Result<Event<KeyPress>, mpsc::RecvError> {
         self.rx.recv()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use termion::event::Key;
+
+    use rustron_lib::setting::SettingId;
+
+    use crate::events::{shift_arrow, AdaptiveTick, KeyRepeat, IDLE_AFTER};
+
+    #[test]
+    fn held_keys_step_faster() {
+        let mut repeat = KeyRepeat::default();
+        let start = Instant::now();
+        let range = SettingId::LfoDepth.range().unwrap();
+        let mut press = repeat.press(Key::Right, false, start);
+        assert_eq!(press.step(range), 1);
+        for i in 1..=20 {
+            press = repeat.press(Key::Right, false, start + Duration::from_millis(i * 30));
+        }
+        assert_eq!(press.repeats, 20);
+        assert_eq!(press.step(range), 4);
+
+        // A pause or another key starts over
+        let later = start + Duration::from_secs(1);
+        assert_eq!(repeat.press(Key::Right, false, later).repeats, 0);
+        assert_eq!(repeat.press(Key::Left, false, later).repeats, 0);
+        assert_eq!(repeat.press(Key::Left, true, later).step(range), 8);
+    }
+
+    #[test]
+    fn shift_arrows_are_parsed() {
+        assert_eq!(shift_arrow(b"\x1b[1;2C"), Some(Key::Right));
+        assert_eq!(shift_arrow(b"\x1b[1;5C"), None);
+    }
+
+    #[test]
+    fn ticks_are_fast_while_messages_are_received() {
+        let (fast, idle) = (Duration::from_millis(20), Duration::from_millis(250));
+        let mut tick = AdaptiveTick::new(fast, idle);
+        let start = Instant::now();
+        assert_eq!(tick.rate(start), idle);
+        tick.traffic(start);
+        assert_eq!(tick.rate(start + Duration::from_millis(500)), fast);
+        assert_eq!(tick.rate(start + IDLE_AFTER), idle);
+    }
+}
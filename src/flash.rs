@@ -0,0 +1,140 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use rustron_lib::firmware::Firmware;
+
+use crate::midi::Delivery;
+
+/// The time between firmware blocks, giving the device time to write each one.
+pub const BLOCK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Sends the blocks of a firmware file one at a time, saving after each block how far it got.
+/// Starting the same file again resumes after the last block sent, a changed file starts over
+/// since the progress is kept with its checksum.
+pub struct Upload {
+    blocks: Vec<Vec<u8>>,
+    checksum: u32,
+    /// The index of the next block to send
+    pub next: usize,
+    last_sent: Option<Instant>,
+    /// The block handed to the port and not confirmed sent yet
+    pub delivering: Option<Delivery>,
+    /// Where the progress is saved, not saved when `None`
+    progress: Option<PathBuf>,
+}
+
+impl Upload {
+    /// The progress file kept next to the firmware file at `path`.
+    pub fn progress_path(path: &Path) -> PathBuf {
+        let mut name = path.as_os_str().to_owned();
+        name.push(".progress");
+        PathBuf::from(name)
+    }
+
+    pub fn start(firmware: &Firmware, progress: Option<PathBuf>) -> Upload {
+        let checksum = firmware.checksum();
+        let next = progress
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|text| {
+                let (saved, next) = text.trim().split_once(' ')?;
+                if u32::from_str_radix(saved, 16).ok()? != checksum {
+                    return None;
+                }
+                next.parse().ok()
+            })
+            .filter(|next| *next < firmware.blocks.len())
+            .unwrap_or(0);
+        Upload {
+            blocks: firmware.blocks.clone(),
+            checksum,
+            next,
+            last_sent: None,
+            delivering: None,
+            progress,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.next >= self.blocks.len()
+    }
+
+    /// The next block, once the last one was sent and `BLOCK_INTERVAL` has passed since.
+    pub fn due(&self, now: Instant) -> Option<&[u8]> {
+        if self.delivering.is_some()
+            || self
+                .last_sent
+                .is_some_and(|last| now.saturating_duration_since(last) < BLOCK_INTERVAL)
+        {
+            return None;
+        }
+        self.blocks.get(self.next).map(Vec::as_slice)
+    }
+
+    /// Notes that the port sent the block from `due` and saves the progress, which is removed once
+    /// all blocks were sent.
+    pub fn sent(&mut self, now: Instant) -> io::Result<()> {
+        self.next += 1;
+        self.last_sent = Some(now);
+        match &self.progress {
+            Some(path) if self.is_done() => fs::remove_file(path).or_else(|error| {
+                if error.kind() == io::ErrorKind::NotFound {
+                    Ok(())
+                } else {
+                    Err(error)
+                }
+            }),
+            Some(path) => fs::write(path, format!("{:08x} {}\n", self.checksum, self.next)),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::time::Instant;
+
+    use rustron_lib::firmware::Firmware;
+
+    use crate::flash::{Upload, BLOCK_INTERVAL};
+
+    #[test]
+    fn uploads_resume_after_the_last_block_sent() {
+        let block = |n| vec![0xf0, 0x00, 0x20, 0x32, 0x28, 0x7f, n, 0xf7];
+        let firmware = Firmware::parse(&[block(1), block(2), block(3)].concat()).unwrap();
+        let path = std::env::temp_dir().join("rustron-firmware-test.syx");
+        let progress = Upload::progress_path(&path);
+        let _ = fs::remove_file(&progress);
+
+        let start = Instant::now();
+        let mut upload = Upload::start(&firmware, Some(progress.clone()));
+        assert_eq!(upload.due(start), Some(&block(1)[..]));
+        upload.sent(start).unwrap();
+        assert_eq!(upload.due(start), None);
+        assert_eq!(upload.due(start + BLOCK_INTERVAL), Some(&block(2)[..]));
+        upload.sent(start + BLOCK_INTERVAL).unwrap();
+
+        let mut upload = Upload::start(&firmware, Some(progress.clone()));
+        assert_eq!(upload.next, 2);
+        upload.sent(start).unwrap();
+        assert!(upload.is_done());
+        assert!(!progress.exists());
+
+        // Another file starts over
+        let other = Firmware::parse(&[block(1), block(4)].concat()).unwrap();
+        fs::write(&progress, format!("{:08x} 1\n", firmware.checksum())).unwrap();
+        assert_eq!(Upload::start(&other, Some(progress.clone())).next, 0);
+        fs::remove_file(&progress).unwrap();
+    }
+}
@@ -0,0 +1,298 @@
+//! The gRPC server of `proto/rustron.proto`, for hardware-in-the-loop test rigs. The messages
+//! and the service are written out here instead of generated, so building doesn't need `protoc`;
+//! they must be kept in step with the `.proto` file.
+// Methods return tonic's `Status` as they do in generated services, large as it is
+#![allow(clippy::result_large_err)]
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::mpsc::Sender;
+use std::thread;
+
+use log::{error, info};
+use tonic::body::BoxBody;
+use tonic::codec::ProstCodec;
+use tonic::codegen::tokio_stream::wrappers::ReceiverStream;
+use tonic::codegen::{http, Body, BoxFuture, Context, Poll, Service, StdError};
+use tonic::server::{Grpc, NamedService, ServerStreamingService, UnaryService};
+use tonic::Status;
+
+use crate::core::state::{ChangeSource, SettingChange as Change};
+use crate::core::CommandStatus;
+use crate::remote::{ask, Request, Value};
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Empty {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SetRequest {
+    #[prost(string, tag = "1")]
+    pub setting: String,
+    #[prost(string, tag = "2")]
+    pub value: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetRequest {
+    #[prost(string, tag = "1")]
+    pub setting: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Setting {
+    #[prost(string, tag = "1")]
+    pub setting: String,
+    #[prost(string, tag = "2")]
+    pub value: String,
+    #[prost(bool, tag = "3")]
+    pub confirmed: bool,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SettingChange {
+    #[prost(string, tag = "1")]
+    pub setting: String,
+    #[prost(string, tag = "2")]
+    pub value: String,
+    #[prost(string, tag = "3")]
+    pub source: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DumpReply {
+    #[prost(message, repeated, tag = "1")]
+    pub settings: Vec<Setting>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub state_dump: Vec<u8>,
+}
+
+impl From<Value> for Setting {
+    fn from((global_setting, confirmed): Value) -> Setting {
+        Setting {
+            setting: global_setting.id().id(),
+            value: global_setting.value_text(),
+            confirmed,
+        }
+    }
+}
+
+impl From<Change> for SettingChange {
+    fn from(change: Change) -> SettingChange {
+        SettingChange {
+            setting: change.setting.id().id(),
+            value: change.setting.value_text(),
+            source: String::from(match change.source {
+                ChangeSource::Commanded => "commanded",
+                ChangeSource::Confirmed => "confirmed",
+            }),
+        }
+    }
+}
+
+/// The stream of `Subscribe`.
+type Changes = ReceiverStream<Result<SettingChange, Status>>;
+
+/// The `rustron.Neutron` service, answering through the request channel of the core.
+#[derive(Clone)]
+pub struct NeutronServer {
+    requests: Sender<Request>,
+}
+
+impl NamedService for NeutronServer {
+    const NAME: &'static str = "rustron.Neutron";
+}
+
+impl NeutronServer {
+    fn set(&self, request: SetRequest) -> Result<Empty, Status> {
+        let status = ask(&self.requests, |reply| Request::Set {
+            setting: request.setting,
+            value: request.value,
+            reply,
+        })
+        .map_err(Status::unavailable)?
+        .map_err(Status::invalid_argument)?;
+        match status {
+            CommandStatus::Sent => Ok(Empty {}),
+            CommandStatus::Blocked => Err(Status::permission_denied(
+                "not sent, rustron is read-only or awaits confirmation of another command",
+            )),
+            CommandStatus::Held => Err(Status::failed_precondition(
+                "held back until confirmed in rustron",
+            )),
+        }
+    }
+
+    fn get(&self, request: GetRequest) -> Result<Setting, Status> {
+        let value = ask(&self.requests, |reply| Request::Get {
+            setting: request.setting.clone(),
+            reply,
+        })
+        .map_err(Status::unavailable)?
+        .map_err(Status::invalid_argument)?;
+        Ok(value.map_or(
+            Setting {
+                setting: request.setting,
+                value: String::new(),
+                confirmed: false,
+            },
+            Setting::from,
+        ))
+    }
+
+    fn dump(&self, _: Empty) -> Result<DumpReply, Status> {
+        let (settings, state_dump) =
+            ask(&self.requests, |reply| Request::Dump { reply }).map_err(Status::unavailable)?;
+        Ok(DumpReply {
+            settings: settings.into_iter().map(Setting::from).collect(),
+            state_dump: state_dump.unwrap_or_default(),
+        })
+    }
+
+    /// Forwards the changes to the stream until either side is gone.
+    fn subscribe(&self, _: Empty) -> Result<Changes, Status> {
+        let changes = ask(&self.requests, |reply| Request::Subscribe { reply })
+            .map_err(Status::unavailable)?;
+        let (sender, stream) = tokio::sync::mpsc::channel(64);
+        thread::spawn(move || {
+            for change in changes {
+                if sender.blocking_send(Ok(change.into())).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(ReceiverStream::new(stream))
+    }
+}
+
+/// A method of `NeutronServer`, run on the blocking threads since it waits for the core.
+struct Method<F>(NeutronServer, F);
+
+impl<Q, R, F> UnaryService<Q> for Method<F>
+where
+    Q: Send + 'static,
+    R: Send + 'static,
+    F: Fn(&NeutronServer, Q) -> Result<R, Status> + Copy + Send + 'static,
+{
+    type Response = R;
+    type Future = BoxFuture<tonic::Response<R>, Status>;
+
+    fn call(&mut self, request: tonic::Request<Q>) -> Self::Future {
+        let (server, method) = (self.0.clone(), self.1);
+        Box::pin(async move {
+            let request = request.into_inner();
+            tokio::task::spawn_blocking(move || method(&server, request))
+                .await
+                .map_err(|error| Status::internal(error.to_string()))?
+                .map(tonic::Response::new)
+        })
+    }
+}
+
+impl<Q, R, F> ServerStreamingService<Q> for Method<F>
+where
+    Q: Send + 'static,
+    R: Send + 'static,
+    F: Fn(&NeutronServer, Q) -> Result<ReceiverStream<Result<R, Status>>, Status>
+        + Copy
+        + Send
+        + 'static,
+{
+    type Response = R;
+    type ResponseStream = ReceiverStream<Result<R, Status>>;
+    type Future = BoxFuture<tonic::Response<Self::ResponseStream>, Status>;
+
+    fn call(&mut self, request: tonic::Request<Q>) -> Self::Future {
+        let (server, method) = (self.0.clone(), self.1);
+        Box::pin(async move {
+            let request = request.into_inner();
+            tokio::task::spawn_blocking(move || method(&server, request))
+                .await
+                .map_err(|error| Status::internal(error.to_string()))?
+                .map(tonic::Response::new)
+        })
+    }
+}
+
+impl<B> Service<http::Request<B>> for NeutronServer
+where
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        let server = self.clone();
+        match request.uri().path() {
+            "/rustron.Neutron/Set" => Box::pin(async move {
+                let method = Method(server, NeutronServer::set);
+                Ok(Grpc::new(ProstCodec::default())
+                    .unary(method, request)
+                    .await)
+            }),
+            "/rustron.Neutron/Get" => Box::pin(async move {
+                let method = Method(server, NeutronServer::get);
+                Ok(Grpc::new(ProstCodec::default())
+                    .unary(method, request)
+                    .await)
+            }),
+            "/rustron.Neutron/Dump" => Box::pin(async move {
+                let method = Method(server, NeutronServer::dump);
+                Ok(Grpc::new(ProstCodec::default())
+                    .unary(method, request)
+                    .await)
+            }),
+            "/rustron.Neutron/Subscribe" => Box::pin(async move {
+                let method = Method(server, NeutronServer::subscribe);
+                Ok(Grpc::new(ProstCodec::default())
+                    .server_streaming(method, request)
+                    .await)
+            }),
+            _ => Box::pin(async { Ok(Status::unimplemented("unknown method").into_http()) }),
+        }
+    }
+}
+
+/// Serves the `rustron.Neutron` service at `address` on its own thread, passing the requests on
+/// to the core through `requests`, see `AppCore::remote`.
+pub fn serve(address: SocketAddr, requests: Sender<Request>) {
+    thread::spawn(move || {
+        let runtime = match tokio::runtime::Runtime::new() {
+            Ok(runtime) => runtime,
+            Err(error) => return error!("Could not start the gRPC server: {}", error),
+        };
+        info!("Serving gRPC on {}", address);
+        let server = tonic::transport::Server::builder()
+            .add_service(NeutronServer { requests })
+            .serve(address);
+        if let Err(error) = runtime.block_on(server) {
+            error!("The gRPC server stopped: {}", error);
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use prost::Message;
+
+    use rustron_lib::protocol::GlobalSetting::Osc1Range;
+    use rustron_lib::protocol::OscRange::Sixteen;
+
+    use crate::grpc::{DumpReply, Setting};
+
+    #[test]
+    fn settings_are_encoded_by_name() {
+        let setting = Setting::from((Osc1Range(Sixteen), true));
+        assert_eq!(setting.setting, "osc1.range");
+        assert_eq!(setting.value, "16");
+        let reply = DumpReply {
+            settings: vec![setting],
+            state_dump: vec![0xf0, 0xf7],
+        };
+        assert_eq!(DumpReply::decode(&*reply.encode_to_vec()).unwrap(), reply);
+    }
+}
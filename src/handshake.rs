@@ -0,0 +1,75 @@
+use std::time::{Duration, Instant};
+
+use rustron_lib::protocol::DeviceId::Multicast;
+use rustron_lib::protocol::NeutronMessage;
+use rustron_lib::protocol::NeutronMessage::{
+    SoftwareVersionRequest, SoftwareVersionResponse, StateDump, StateRequest,
+};
+
+/// Mirrors what the official app does when connecting: ask for the software version and the
+/// current state, and wait until the Neutron has answered both (or the timeout passes).
+pub struct Handshake {
+    started: Instant,
+    timeout: Duration,
+    awaiting_version: bool,
+    awaiting_state: bool,
+}
+
+impl Handshake {
+    pub fn start(timeout: Duration) -> Handshake {
+        Handshake {
+            started: Instant::now(),
+            timeout,
+            awaiting_version: true,
+            awaiting_state: true,
+        }
+    }
+
+    /// The requests to send to the Neutron to start the handshake.
+    pub fn requests() -> Vec<Vec<u8>> {
+        vec![
+            SoftwareVersionRequest(Multicast).as_bytes(),
+            StateRequest(Multicast).as_bytes(),
+        ]
+    }
+
+    pub fn receive(&mut self, message: &NeutronMessage) {
+        match message {
+            SoftwareVersionResponse(_, _) => self.awaiting_version = false,
+            StateDump(_, _) => self.awaiting_state = false,
+            _ => {}
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        !self.awaiting_version && !self.awaiting_state
+    }
+
+    pub fn has_timed_out(&self, now: Instant) -> bool {
+        now.duration_since(self.started) > self.timeout
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use rustron_lib::protocol::Channel::One;
+    use rustron_lib::protocol::DeviceId::Channel;
+    use rustron_lib::protocol::NeutronMessage::{SoftwareVersionResponse, StateDump};
+
+    use crate::handshake::Handshake;
+
+    #[test]
+    fn handshake_completes_after_both_responses() {
+        let mut handshake = Handshake::start(Duration::from_secs(1));
+        handshake.receive(&SoftwareVersionResponse(
+            Channel(One),
+            String::from("2.0.2"),
+        ));
+        assert!(!handshake.is_complete());
+        handshake.receive(&StateDump(Channel(One), vec![0x6b]));
+        assert!(handshake.is_complete());
+        assert!(handshake.has_timed_out(Instant::now() + Duration::from_secs(2)));
+    }
+}
@@ -0,0 +1,320 @@
+use std::fmt::{Display, Formatter};
+use std::time::Instant;
+
+use rustron_lib::device::crave::Crave;
+use rustron_lib::device::{detect, BehringerDevice};
+use rustron_lib::parser::{device_message, neutron_message};
+use rustron_lib::protocol::{classify, format_behringer_packet, DeviceClass, NeutronMessage};
+
+use crate::inspect::Stream;
+use crate::stats;
+
+/// A sent or received message, parsed once when it is stored.
+#[derive(Debug, PartialEq)]
+pub enum Message {
+    Parsed(NeutronMessage),
+    /// Anything that isn't a known Neutron message
+    Raw(Vec<u8>),
+}
+
+impl Message {
+    pub fn parse(bytes: &[u8]) -> Message {
+        match neutron_message(bytes) {
+            Ok((_, msg)) => Message::Parsed(msg),
+            Err(_) => Message::Raw(bytes.to_vec()),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Message::Parsed(msg) => msg.as_bytes(),
+            Message::Raw(bytes) => bytes.clone(),
+        }
+    }
+}
+
+impl Display for Message {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Message::Parsed(msg) => write!(f, "{}", msg),
+            Message::Raw(bytes) if classify(bytes) == DeviceClass::OtherBehringer => {
+                if let Ok((_, msg)) = device_message::<Crave>(bytes) {
+                    return write!(f, "{}: {}", Crave::NAME, msg);
+                }
+                match detect(bytes) {
+                    Some(name) => write!(f, "{} {}", name, format_behringer_packet(bytes)),
+                    None => write!(f, "{}", format_behringer_packet(bytes)),
+                }
+            }
+            Message::Raw(bytes) => write!(f, "{}", hex::encode(bytes)),
+        }
+    }
+}
+
+/// A message together with its description, formatted once when stored so rendering doesn't
+/// have to format every visible message each frame.
+pub struct HistoryEntry {
+    pub message: Message,
+    pub text: String,
+    /// When the message was sent or received
+    pub time: Instant,
+    pub device: DeviceClass,
+    /// Marked in the inspector, to find and export it later
+    pub bookmarked: bool,
+}
+
+impl HistoryEntry {
+    pub fn new(message: Message) -> HistoryEntry {
+        let text = message.to_string();
+        let device = match &message {
+            Message::Parsed(_) => DeviceClass::Neutron,
+            Message::Raw(bytes) => classify(bytes),
+        };
+        HistoryEntry {
+            message,
+            text,
+            time: Instant::now(),
+            device,
+            bookmarked: false,
+        }
+    }
+
+    /// An entry received from another port than the Neutron's, with the port shown in the text.
+    pub fn from_port(message: Message, port: &str) -> HistoryEntry {
+        let mut entry = HistoryEntry::new(message);
+        entry.text = format!("[{}] {}", port, entry.text);
+        entry
+    }
+}
+
+/// Both histories in one chronological list, with the direction of each entry. On equal times
+/// the sent entry comes first, as it usually caused the received one.
+pub fn merge<'a>(
+    sent: &'a [HistoryEntry],
+    received: &'a [HistoryEntry],
+) -> Vec<(Stream, &'a HistoryEntry)> {
+    let mut merged = Vec::with_capacity(sent.len() + received.len());
+    let (mut sent, mut received) = (sent.iter().peekable(), received.iter().peekable());
+    loop {
+        let stream = match (sent.peek(), received.peek()) {
+            (Some(s), Some(r)) if s.time <= r.time => Stream::Sent,
+            (_, Some(_)) => Stream::Received,
+            (Some(_), None) => Stream::Sent,
+            (None, None) => return merged,
+        };
+        let entry = match stream {
+            Stream::Sent => sent.next(),
+            Stream::Received => received.next(),
+        };
+        merged.extend(entry.map(|entry| (stream, entry)));
+    }
+}
+
+/// The lengths of the histories when the stream view was paused. Messages keep being stored while
+/// paused, but only the ones from before the pause are shown.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Pause {
+    sent: usize,
+    received: usize,
+}
+
+impl Pause {
+    pub fn new(sent: &[HistoryEntry], received: &[HistoryEntry]) -> Pause {
+        Pause {
+            sent: sent.len(),
+            received: received.len(),
+        }
+    }
+
+    /// The sent messages shown while paused.
+    pub fn sent<'a>(&self, sent: &'a [HistoryEntry]) -> &'a [HistoryEntry] {
+        &sent[..self.sent.min(sent.len())]
+    }
+
+    /// The received messages shown while paused.
+    pub fn received<'a>(&self, received: &'a [HistoryEntry]) -> &'a [HistoryEntry] {
+        &received[..self.received.min(received.len())]
+    }
+
+    /// How many messages were stored since pausing.
+    pub fn new_messages(&self, sent: &[HistoryEntry], received: &[HistoryEntry]) -> usize {
+        (sent.len() - self.sent(sent).len()) + (received.len() - self.received(received).len())
+    }
+}
+
+/// Which consecutive entries the stream view shows as one row.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Coalesce {
+    Off,
+    /// Entries with the same text
+    Identical,
+    /// Entries of the same kind, e.g. all setting updates
+    SameKind,
+}
+
+impl Coalesce {
+    pub fn next(self) -> Coalesce {
+        match self {
+            Coalesce::Off => Coalesce::Identical,
+            Coalesce::Identical => Coalesce::SameKind,
+            Coalesce::SameKind => Coalesce::Off,
+        }
+    }
+
+    fn same(self, a: &HistoryEntry, b: &HistoryEntry) -> bool {
+        match self {
+            Coalesce::Off => false,
+            Coalesce::Identical => a.text == b.text,
+            Coalesce::SameKind => stats::kind(&a.message) == stats::kind(&b.message),
+        }
+    }
+}
+
+/// Groups runs of consecutive entries that are the same according to `mode`, newest first like
+/// `entries`, into the latest entry of the run and how many entries it stands for. Stops after
+/// `limit` rows, so only what is shown is grouped.
+pub fn coalesce<'a>(
+    entries: impl Iterator<Item = &'a HistoryEntry>,
+    mode: Coalesce,
+    limit: usize,
+) -> Vec<(&'a HistoryEntry, usize)> {
+    let mut rows: Vec<(&HistoryEntry, usize)> = Vec::new();
+    for entry in entries {
+        if let Some((latest, count)) = rows.last_mut() {
+            if mode.same(latest, entry) {
+                *count += 1;
+                continue;
+            }
+        }
+        if rows.len() == limit {
+            break;
+        }
+        rows.push((entry, 1));
+    }
+    rows
+}
+
+#[cfg(test)]
+mod test {
+    use rustron_lib::protocol::DeviceClass;
+    use rustron_lib::protocol::DeviceId::Multicast;
+    use rustron_lib::protocol::GlobalSetting::OscSync;
+    use rustron_lib::protocol::NeutronMessage::SetGlobalSetting;
+    use rustron_lib::protocol::ToggleOption::{Off, On};
+
+    use crate::history::{coalesce, merge, Coalesce, HistoryEntry, Message, Pause};
+    use crate::inspect::Stream;
+    use std::time::Duration;
+
+    #[test]
+    fn messages_are_parsed_once() {
+        let bytes = SetGlobalSetting(Multicast, OscSync(On)).as_bytes();
+        let message = Message::parse(&bytes);
+        assert_eq!(
+            message,
+            Message::Parsed(SetGlobalSetting(Multicast, OscSync(On)))
+        );
+        assert_eq!(message.to_bytes(), bytes);
+
+        let message = Message::parse(&[0xf0, 0x01, 0xf7]);
+        assert_eq!(message, Message::Raw(vec![0xf0, 0x01, 0xf7]));
+        assert_eq!(message.to_string(), "f001f7");
+        assert_eq!(
+            HistoryEntry::from_port(message, "Keys").text,
+            "[Keys] f001f7"
+        );
+    }
+
+    #[test]
+    fn entries_are_classified_by_device() {
+        let bytes = SetGlobalSetting(Multicast, OscSync(On)).as_bytes();
+        assert_eq!(
+            HistoryEntry::new(Message::parse(&bytes)).device,
+            DeviceClass::Neutron
+        );
+        // Another Behringer device, never parsed as a Neutron message
+        let entry = HistoryEntry::new(Message::parse(&[0xf0, 0x00, 0x20, 0x32, 0x20, 0x01, 0xf7]));
+        assert_eq!(
+            entry.message,
+            Message::Raw(vec![0xf0, 0x00, 0x20, 0x32, 0x20, 0x01, 0xf7])
+        );
+        assert_eq!(entry.device, DeviceClass::OtherBehringer);
+        assert_eq!(entry.text, "Crave B[ 20 01f7 ]");
+        let crave = [0xf0, 0x00, 0x20, 0x32, 0x20, 0x7f, 0x73, 0xf7];
+        assert_eq!(
+            HistoryEntry::new(Message::parse(&crave)).text,
+            "Crave: SoftwareVersionRequest(Multicast)"
+        );
+        assert_eq!(
+            HistoryEntry::new(Message::parse(&[0x90, 0x3c, 0x40])).device,
+            DeviceClass::NotSysEx
+        );
+    }
+
+    #[test]
+    fn pausing_hides_new_messages() {
+        let entry = || HistoryEntry::new(Message::parse(&[0xf0, 0x01, 0xf7]));
+        let mut received = vec![entry(), entry()];
+        let mut sent = vec![entry()];
+        let pause = Pause::new(&sent, &received);
+        received.push(entry());
+        sent.push(entry());
+        received.push(entry());
+        assert_eq!(pause.received(&received).len(), 2);
+        assert_eq!(pause.sent(&sent).len(), 1);
+        assert_eq!(pause.new_messages(&sent, &received), 3);
+        // A restored session can have fewer messages than when paused
+        assert_eq!(pause.received(&received[..1]).len(), 1);
+    }
+
+    #[test]
+    fn runs_of_entries_are_coalesced() {
+        let entries: Vec<HistoryEntry> = [
+            vec![0xf0, 0x01, 0xf7],
+            SetGlobalSetting(Multicast, OscSync(On)).as_bytes(),
+            SetGlobalSetting(Multicast, OscSync(On)).as_bytes(),
+            SetGlobalSetting(Multicast, OscSync(Off)).as_bytes(),
+        ]
+        .iter()
+        .map(|bytes| HistoryEntry::new(Message::parse(bytes)))
+        .collect();
+        let counts = |mode, limit| -> Vec<usize> {
+            coalesce(entries.iter().rev(), mode, limit)
+                .into_iter()
+                .map(|(_, count)| count)
+                .collect()
+        };
+        assert_eq!(counts(Coalesce::Off, 10), vec![1, 1, 1, 1]);
+        assert_eq!(counts(Coalesce::Identical, 10), vec![1, 2, 1]);
+        assert_eq!(counts(Coalesce::SameKind, 10), vec![3, 1]);
+        assert_eq!(counts(Coalesce::Identical, 2), vec![1, 2]);
+        let rows = coalesce(entries.iter().rev(), Coalesce::SameKind, 1);
+        // The latest entry of a run is shown
+        assert!(std::ptr::eq(rows[0].0, &entries[3]));
+    }
+
+    #[test]
+    fn histories_are_merged_by_time() {
+        let entry = |byte: u8, ms: u64| {
+            let mut entry = HistoryEntry::new(Message::Raw(vec![byte]));
+            entry.time += Duration::from_millis(ms);
+            entry
+        };
+        let sent = vec![entry(1, 0), entry(2, 20)];
+        let received = vec![entry(3, 10), entry(4, 20), entry(5, 30)];
+        let merged: Vec<_> = merge(&sent, &received)
+            .iter()
+            .map(|(stream, entry)| (*stream, entry.text.clone()))
+            .collect();
+        assert_eq!(
+            merged,
+            vec![
+                (Stream::Sent, String::from("01")),
+                (Stream::Received, String::from("03")),
+                (Stream::Sent, String::from("02")),
+                (Stream::Received, String::from("04")),
+                (Stream::Received, String::from("05")),
+            ]
+        );
+    }
+}
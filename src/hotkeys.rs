@@ -0,0 +1,38 @@
+use rustron_lib::setting::SettingId;
+
+/// A setting bound to a number key of the hotkey layer, each press toggles it, see
+/// `AppCore::toggle`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Hotkey {
+    /// The number key, 1 to 9
+    pub key: u8,
+    pub setting: SettingId,
+}
+
+impl Hotkey {
+    /// Binds `setting` to `key`, `None` unless the key is 1 to 9 and the setting has exactly two
+    /// values, see `SettingId::is_two_valued`.
+    pub fn new(key: u8, setting: SettingId) -> Option<Hotkey> {
+        if !(1..=9).contains(&key) || !setting.is_two_valued() {
+            return None;
+        }
+        Some(Hotkey { key, setting })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rustron_lib::setting::SettingId;
+
+    use crate::hotkeys::Hotkey;
+
+    #[test]
+    fn only_settings_with_two_values_are_bound() {
+        assert!(Hotkey::new(0, SettingId::OscSync).is_none());
+        assert!(Hotkey::new(10, SettingId::OscSync).is_none());
+        assert!(Hotkey::new(1, SettingId::LfoDepth).is_none());
+        assert!(Hotkey::new(1, SettingId::KeyRangeReset).is_none());
+        assert!(Hotkey::new(2, SettingId::OscSync).is_some());
+        assert!(Hotkey::new(3, SettingId::LfoBlendMode).is_some());
+    }
+}
@@ -0,0 +1,278 @@
+use std::error;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rustron_lib::parser::{annotate, parse};
+use rustron_lib::preset::diff_state_dumps;
+use rustron_lib::protocol::SYSEX_MESSAGE_START;
+
+use crate::config::Config;
+use crate::history::{HistoryEntry, Message};
+use crate::script;
+
+/// The file the bookmarked messages are exported to, in the configuration directory.
+const BOOKMARKS_FILE_NAME: &str = "bookmarks.txt";
+
+/// Writes `messages` to the bookmarks script, creating the configuration directory if needed,
+/// and returns where it was written.
+pub fn export_bookmarks(messages: &[Vec<u8>]) -> Result<PathBuf, Box<dyn error::Error>> {
+    let directory = Config::directory().ok_or("There is no configuration directory.")?;
+    fs::create_dir_all(&directory)?;
+    let path = directory.join(BOOKMARKS_FILE_NAME);
+    script::export(&path, messages)?;
+    Ok(path)
+}
+
+/// Which of the message histories the inspector shows.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Stream {
+    Received,
+    Sent,
+}
+
+impl Display for Stream {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Stream::Received => write!(f, "MIDI Sysex Input"),
+            Stream::Sent => write!(f, "Command History"),
+        }
+    }
+}
+
+impl Stream {
+    /// Marks the direction of a message in the merged stream.
+    pub fn arrow(self) -> &'static str {
+        match self {
+            Stream::Received => "←",
+            Stream::Sent => "→",
+        }
+    }
+}
+
+/// Picks a sent or received message to show byte by byte. Without a selection it follows the
+/// latest message.
+pub struct Inspector {
+    pub stream: Stream,
+    pub selection: Option<usize>,
+}
+
+impl Default for Inspector {
+    fn default() -> Inspector {
+        Inspector::new()
+    }
+}
+
+impl Inspector {
+    pub fn new() -> Inspector {
+        Inspector {
+            stream: Stream::Received,
+            selection: None,
+        }
+    }
+
+    /// Switches between the received and sent messages, following the latest one.
+    pub fn toggle_stream(&mut self) {
+        self.stream = match self.stream {
+            Stream::Received => Stream::Sent,
+            Stream::Sent => Stream::Received,
+        };
+        self.selection = None;
+    }
+
+    /// The index of the inspected message out of `count` messages.
+    pub fn selected(&self, count: usize) -> Option<usize> {
+        match self.selection {
+            Some(selection) => Some(selection.min(count.checked_sub(1)?)),
+            None => count.checked_sub(1),
+        }
+    }
+
+    pub fn select_previous(&mut self, count: usize) {
+        self.selection = self.selected(count).map(|s| s.saturating_sub(1));
+    }
+
+    /// Moves to the next message, following new messages again after the last one.
+    pub fn select_next(&mut self, count: usize) {
+        self.selection = match self.selected(count) {
+            Some(selected) if selected + 2 < count => Some(selected + 1),
+            _ => None,
+        };
+    }
+
+    pub fn follow(&mut self) {
+        self.selection = None;
+    }
+
+    /// Selects the next bookmarked entry after the selected one, or the previous one before it.
+    /// Stays put if there is none.
+    pub fn select_bookmark(&mut self, entries: &[HistoryEntry], forward: bool) {
+        let selected = match self.selected(entries.len()) {
+            Some(selected) => selected,
+            None => return,
+        };
+        let bookmark = if forward {
+            (selected + 1..entries.len()).find(|i| entries[*i].bookmarked)
+        } else {
+            (0..selected).rev().find(|i| entries[*i].bookmarked)
+        };
+        if let Some(bookmark) = bookmark {
+            self.selection = Some(bookmark);
+        }
+    }
+}
+
+/// One row per byte: the offset, the byte in hex and what it means.
+pub fn hexdump(message: &[u8]) -> Vec<String> {
+    annotate(message)
+        .into_iter()
+        .enumerate()
+        .map(|(offset, (byte, label))| format!("{:3}  {:02x}  {}", offset, byte, label))
+        .collect()
+}
+
+/// What the inspector shows for a message: why it couldn't be parsed, if it couldn't, followed by
+/// its bytes.
+pub fn details(message: &Message) -> Vec<String> {
+    match message {
+        Message::Parsed(msg) => hexdump(&msg.as_bytes()),
+        Message::Raw(bytes) => {
+            let mut rows = hexdump(bytes);
+            if let Err(error) = parse(bytes) {
+                rows.insert(0, error.to_string());
+            }
+            rows
+        }
+    }
+}
+
+/// Reads a message from a file, either raw SysEx like a `.syx` file or hex bytes separated by
+/// whitespace with `#` comment lines, like the captures in `tests/fixtures`.
+pub fn read_message(path: &Path) -> Result<Vec<u8>, Box<dyn error::Error>> {
+    let contents = fs::read(path)?;
+    if contents.first() == Some(&SYSEX_MESSAGE_START) {
+        return Ok(contents);
+    }
+    let text = String::from_utf8(contents)?;
+    let hex: String = text
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .flat_map(str::split_whitespace)
+        .collect();
+    Ok(hex::decode(hex)?)
+}
+
+/// One line per byte that differs between two state dumps.
+pub fn diff_dumps(before: &[u8], after: &[u8]) -> Vec<String> {
+    let differences = diff_state_dumps(before, after);
+    if differences.is_empty() {
+        return vec![String::from("The dumps are the same")];
+    }
+    differences.iter().map(ToString::to_string).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use rustron_lib::protocol::DeviceId::Multicast;
+    use rustron_lib::protocol::GlobalSetting::LfoShapePhase;
+    use rustron_lib::protocol::LfoIndex::Three;
+    use rustron_lib::protocol::LfoPhaseOffset::Ninety;
+    use rustron_lib::protocol::NeutronMessage::GlobalSettingUpdate;
+
+    use crate::history::{HistoryEntry, Message};
+    use crate::inspect::{details, diff_dumps, hexdump, read_message, Inspector, Stream};
+
+    #[test]
+    fn selection_follows_latest_until_moved() {
+        let mut inspector = Inspector::new();
+        assert_eq!(inspector.selected(0), None);
+        assert_eq!(inspector.selected(3), Some(2));
+        inspector.select_previous(3);
+        assert_eq!(inspector.selected(5), Some(1));
+        inspector.select_next(5);
+        inspector.select_next(5);
+        assert_eq!(inspector.selected(5), Some(3));
+        inspector.select_next(5);
+        assert_eq!(inspector.selection, None);
+        assert_eq!(inspector.selected(6), Some(5));
+
+        inspector.select_previous(6);
+        inspector.toggle_stream();
+        assert_eq!(inspector.stream, Stream::Sent);
+        assert_eq!(inspector.selection, None);
+    }
+
+    #[test]
+    fn bookmarks_are_jumped_between() {
+        let mut entries: Vec<HistoryEntry> = (0..5)
+            .map(|_| HistoryEntry::new(Message::parse(&[0xf0, 0x01, 0xf7])))
+            .collect();
+        entries[1].bookmarked = true;
+        entries[3].bookmarked = true;
+        let mut inspector = Inspector::new();
+        inspector.select_bookmark(&entries, true);
+        assert_eq!(inspector.selection, None);
+        inspector.select_bookmark(&entries, false);
+        assert_eq!(inspector.selection, Some(3));
+        inspector.select_bookmark(&entries, false);
+        assert_eq!(inspector.selection, Some(1));
+        inspector.select_bookmark(&entries, false);
+        assert_eq!(inspector.selection, Some(1));
+        inspector.select_bookmark(&entries, true);
+        assert_eq!(inspector.selection, Some(3));
+    }
+
+    #[test]
+    fn dumps_are_read_and_diffed() {
+        let directory = std::env::temp_dir().join("rustron-inspect-test");
+        std::fs::create_dir_all(&directory).unwrap();
+        let hex = directory.join("before.hex");
+        std::fs::write(&hex, "# Captured\nF0 00 20 32 28 00 06 01\n6B 02 F7\n").unwrap();
+        let syx = directory.join("after.syx");
+        std::fs::write(
+            &syx,
+            [
+                0xf0, 0x00, 0x20, 0x32, 0x28, 0x00, 0x06, 0x01, 0x7b, 0x02, 0xf7,
+            ],
+        )
+        .unwrap();
+        let before = read_message(&hex).unwrap();
+        let after = read_message(&syx).unwrap();
+        assert_eq!(
+            diff_dumps(&before, &after),
+            ["byte 8 (state byte 0): 6b -> 7b, bits 00010000: osc.sync"]
+        );
+        assert_eq!(diff_dumps(&before, &before), ["The dumps are the same"]);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn bytes_are_annotated() {
+        let bytes = GlobalSettingUpdate(Multicast, LfoShapePhase(Three, Ninety)).as_bytes();
+        assert_eq!(
+            hexdump(&bytes)[5..],
+            [
+                "  5  7f  device id: all",
+                "  6  5a  command: setting update",
+                "  7  01  protocol version",
+                "  8  3a  setting: lfo.phase.3",
+                "  9  02  LFO index: 3",
+                " 10  02  value: 90",
+                " 11  f7  SysEx end",
+            ]
+        );
+        assert_eq!(
+            hexdump(&[0xf0, 0x01, 0xf7]),
+            ["  0  f0  SysEx start", "  1  01  ?", "  2  f7  SysEx end"]
+        );
+    }
+
+    #[test]
+    fn unparsed_messages_explain_why() {
+        let mut bytes = GlobalSettingUpdate(Multicast, LfoShapePhase(Three, Ninety)).as_bytes();
+        bytes[6] = 0x42;
+        let rows = details(&Message::parse(&bytes));
+        assert_eq!(rows[0], "parse failed at byte 6: expected opcode");
+        assert_eq!(rows[7], "  6  42  expected opcode");
+    }
+}
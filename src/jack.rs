@@ -0,0 +1,276 @@
+//! The JACK MIDI transport, for patching rustron into a JACK graph instead of talking to the ALSA
+//! ports directly. rustron registers a `neutron_in` and a `neutron_out` port and connects them to
+//! the Neutron's ports, after that they can be repatched in any JACK patchbay.
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use jack::{
+    AsyncClient, Client, ClientOptions, Control, MidiIn, MidiOut, Port, PortFlags, PortSpec,
+    ProcessHandler, ProcessScope, RawMidi, RingBuffer, RingBufferReader, RingBufferWriter,
+};
+use log::{info, warn};
+
+use crate::midi::{InputQueue, Source, CLIENT_NAME};
+
+// The ports of the JACK client are `rustron:neutron_in` and `rustron:neutron_out`
+const IN_PORT: &str = "neutron_in";
+const OUT_PORT: &str = "neutron_out";
+
+/// The bytes the messages waiting for the next process cycle, or received and not passed on
+/// yet, may take.
+const BUFFER_SIZE: usize = 1 << 16;
+
+/// How often received messages are passed on to the input queue.
+const RECEIVE_INTERVAL: Duration = Duration::from_millis(1);
+
+/// The longest message that can be sent, firmware blocks included.
+const MAX_MESSAGE: usize = 4096;
+
+/// Messages wait in the ring buffer prefixed with their length.
+const HEADER: usize = 2;
+
+/// A MIDI port of the JACK graph with its aliases, like the ALSA name a2jmidid gives it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct JackPort {
+    /// The full name, `client:port`
+    pub name: String,
+    pub aliases: Vec<String>,
+}
+
+impl JackPort {
+    /// Whether the full name, the name without the client or an alias starts with `name`.
+    pub fn matches(&self, name: &str) -> bool {
+        std::iter::once(&self.name)
+            .chain(&self.aliases)
+            .any(|port_name| {
+                port_name.starts_with(name)
+                    || port_name
+                        .split_once(':')
+                        .is_some_and(|(_, short)| short.starts_with(name))
+            })
+    }
+}
+
+impl Display for JackPort {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.aliases.is_empty() {
+            write!(f, "{}", self.name)
+        } else {
+            write!(f, "{} ({})", self.name, self.aliases.join(", "))
+        }
+    }
+}
+
+/// The MIDI ports of other clients, `PortFlags::IS_OUTPUT` for those sending.
+fn midi_ports(client: &Client, flags: PortFlags) -> Vec<JackPort> {
+    let own = format!("{}:", client.name());
+    client
+        .ports(None, Some(MidiIn.jack_port_type()), flags)
+        .into_iter()
+        .filter(|name| !name.starts_with(&own))
+        .map(|name| JackPort {
+            aliases: client
+                .port_by_name(&name)
+                .and_then(|port| port.aliases().ok())
+                .unwrap_or_default(),
+            name,
+        })
+        .collect()
+}
+
+/// Moves the messages of a process cycle: received ones to a ring buffer for `forward`, and the
+/// ones waiting in the other ring buffer to the output port. Being called in JACK's real-time
+/// thread, it neither allocates nor locks.
+struct Process {
+    input: Port<MidiIn>,
+    output: Port<MidiOut>,
+    received: RingBufferWriter,
+    sent: RingBufferReader,
+    /// A message with its header, allocated once so the process cycle doesn't allocate for it
+    scratch: Vec<u8>,
+}
+
+impl ProcessHandler for Process {
+    fn process(&mut self, _: &Client, scope: &ProcessScope) -> Control {
+        for event in self.input.iter(scope) {
+            let len = event.bytes.len();
+            // What doesn't fit is dropped, as a full input queue drops messages
+            if len <= usize::from(u16::MAX) && self.received.space() >= HEADER + len {
+                self.received.write_buffer(&(len as u16).to_le_bytes());
+                self.received.write_buffer(event.bytes);
+            }
+        }
+        let mut writer = self.output.writer(scope);
+        let mut header = [0; HEADER];
+        while self.sent.peek(&mut header) == HEADER {
+            let framed = &mut self.scratch[..HEADER + usize::from(u16::from_le_bytes(header))];
+            self.sent.peek(framed);
+            let message = RawMidi {
+                time: 0,
+                bytes: &framed[HEADER..],
+            };
+            // A full port buffer keeps the rest for the next cycle
+            if writer.write(&message).is_err() {
+                break;
+            }
+            self.sent.advance(framed.len());
+        }
+        Control::Continue
+    }
+}
+
+/// Passes the messages the process cycle received on to `queue`, until `running` is cleared.
+fn forward(mut received: RingBufferReader, queue: InputQueue, running: Arc<AtomicBool>) {
+    let mut header = [0; HEADER];
+    while running.load(Ordering::Relaxed) {
+        while received.peek(&mut header) == HEADER {
+            let len = usize::from(u16::from_le_bytes(header));
+            // The message follows its header in a separate write
+            if received.space() < HEADER + len {
+                break;
+            }
+            received.advance(HEADER);
+            let mut message = vec![0; len];
+            received.read_buffer(&mut message);
+            queue.push(Source::Neutron, message);
+        }
+        thread::sleep(RECEIVE_INTERVAL);
+    }
+}
+
+/// The active JACK client sending to and receiving from the Neutron.
+pub struct JackConnection {
+    // Deactivated when dropped
+    _client: AsyncClient<(), Process>,
+    sent: RingBufferWriter,
+    /// A message with its header, reused for every message sent
+    framed: Vec<u8>,
+    /// Keeps the thread of `forward` running
+    running: Arc<AtomicBool>,
+}
+
+impl JackConnection {
+    /// Opens the client, without starting a JACK server, and connects its ports to the Neutron's,
+    /// the first whose name or alias starts with `neutron_port`. Ports that aren't found are
+    /// left to be patched in by hand, which is why the Neutron counts as connected as soon as
+    /// the client is active.
+    pub fn open(
+        neutron_port: &str,
+        queue: InputQueue,
+    ) -> Result<JackConnection, Box<dyn error::Error>> {
+        let (client, _) = Client::new(CLIENT_NAME, ClientOptions::NO_START_SERVER)?;
+        let input = client.register_port(IN_PORT, MidiIn)?;
+        let output = client.register_port(OUT_PORT, MidiOut)?;
+        let (in_name, out_name) = (input.name()?, output.name()?);
+        let (reader, sent) = RingBuffer::new(BUFFER_SIZE)?.into_reader_writer();
+        let (received, writer) = RingBuffer::new(BUFFER_SIZE)?.into_reader_writer();
+        let process = Process {
+            input,
+            output,
+            received: writer,
+            sent: reader,
+            scratch: vec![0; HEADER + MAX_MESSAGE],
+        };
+        let client = client.activate_async((), process)?;
+
+        let mut found = true;
+        let sending = midi_ports(client.as_client(), PortFlags::IS_OUTPUT);
+        match sending.iter().find(|port| port.matches(neutron_port)) {
+            Some(port) => {
+                client
+                    .as_client()
+                    .connect_ports_by_name(&port.name, &in_name)?;
+                info!("Receiving from JACK port {}", port);
+            }
+            None => found = false,
+        }
+        let receiving = midi_ports(client.as_client(), PortFlags::IS_INPUT);
+        match receiving.iter().find(|port| port.matches(neutron_port)) {
+            Some(port) => {
+                client
+                    .as_client()
+                    .connect_ports_by_name(&out_name, &port.name)?;
+                info!("Sending to JACK port {}", port);
+            }
+            None => found = false,
+        }
+        if !found {
+            warn!(
+                "Could not find the JACK ports of {}, patch {} in by hand. MIDI ports: {}",
+                neutron_port,
+                CLIENT_NAME,
+                sending
+                    .iter()
+                    .chain(&receiving)
+                    .map(JackPort::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        let running = Arc::new(AtomicBool::new(true));
+        let forwarding = Arc::clone(&running);
+        thread::spawn(move || forward(received, queue, forwarding));
+        Ok(JackConnection {
+            _client: client,
+            sent,
+            framed: Vec::with_capacity(HEADER + MAX_MESSAGE),
+            running,
+        })
+    }
+
+    /// Hands `message` to the next process cycle.
+    pub fn send(&mut self, message: &[u8]) -> Result<(), Box<dyn error::Error>> {
+        if message.len() > MAX_MESSAGE {
+            return Err(
+                format!("{} bytes are too long to send through JACK", message.len()).into(),
+            );
+        }
+        self.framed.clear();
+        self.framed
+            .extend_from_slice(&(message.len() as u16).to_le_bytes());
+        self.framed.extend_from_slice(message);
+        // Written at once, so the process cycle never sees half a message
+        if self.sent.space() < self.framed.len() {
+            return Err("The JACK send buffer is full".into());
+        }
+        self.sent.write_buffer(&self.framed);
+        Ok(())
+    }
+}
+
+impl Drop for JackConnection {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::jack::JackPort;
+
+    #[test]
+    fn ports_are_matched_by_name_or_alias() {
+        let port = JackPort {
+            name: String::from("a2j:Neutron [24] (capture): Neutron MIDI 1"),
+            aliases: vec![String::from("alsa_pcm:Neutron/midi_capture_1")],
+        };
+        assert!(port.matches("a2j:Neutron"));
+        assert!(port.matches("Neutron [24]"));
+        assert!(port.matches("Neutron/midi_capture"));
+        assert!(!port.matches("Behringer"));
+        assert_eq!(
+            port.to_string(),
+            "a2j:Neutron [24] (capture): Neutron MIDI 1 (alsa_pcm:Neutron/midi_capture_1)"
+        );
+        let system = JackPort {
+            name: String::from("system:midi_playback_1"),
+            aliases: Vec::new(),
+        };
+        assert!(!system.matches("Neutron"));
+        assert_eq!(system.to_string(), "system:midi_playback_1");
+    }
+}
@@ -0,0 +1,115 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use rustron_lib::protocol::NeutronMessage;
+use rustron_lib::protocol::NeutronMessage::{
+    SoftwareVersionRequest, SoftwareVersionResponse, StateDump, StateRequest,
+};
+
+/// How many round trips the average and jitter are calculated over.
+pub const LATENCY_SAMPLES: usize = 20;
+
+/// Measures the time between requests and the Neutron's responses to them.
+pub struct LatencyProbe {
+    version_sent: Option<Instant>,
+    state_sent: Option<Instant>,
+    samples: VecDeque<Duration>,
+}
+
+impl Default for LatencyProbe {
+    fn default() -> LatencyProbe {
+        LatencyProbe::new()
+    }
+}
+
+impl LatencyProbe {
+    pub fn new() -> LatencyProbe {
+        LatencyProbe {
+            version_sent: None,
+            state_sent: None,
+            samples: VecDeque::with_capacity(LATENCY_SAMPLES),
+        }
+    }
+
+    /// Notes the time a request is sent, other messages are ignored.
+    pub fn sent(&mut self, message: &NeutronMessage, now: Instant) {
+        match message {
+            SoftwareVersionRequest(_) => self.version_sent = Some(now),
+            StateRequest(_) => self.state_sent = Some(now),
+            _ => {}
+        }
+    }
+
+    /// Takes a sample if `message` responds to a request, other messages are ignored.
+    pub fn received(&mut self, message: &NeutronMessage, now: Instant) {
+        let sent = match message {
+            SoftwareVersionResponse(..) => self.version_sent.take(),
+            StateDump(..) => self.state_sent.take(),
+            _ => None,
+        };
+        if let Some(sent) = sent {
+            if self.samples.len() == LATENCY_SAMPLES {
+                self.samples.pop_front();
+            }
+            self.samples.push_back(now.saturating_duration_since(sent));
+        }
+    }
+
+    pub fn average(&self) -> Option<Duration> {
+        let count = self.samples.len() as u32;
+        if count == 0 {
+            return None;
+        }
+        Some(self.samples.iter().sum::<Duration>() / count)
+    }
+
+    /// The mean deviation from the average.
+    pub fn jitter(&self) -> Option<Duration> {
+        let average = self.average()?;
+        let deviation: Duration = self
+            .samples
+            .iter()
+            .map(|sample| sample.abs_diff(average))
+            .sum();
+        Some(deviation / self.samples.len() as u32)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use rustron_lib::protocol::DeviceId::Multicast;
+    use rustron_lib::protocol::NeutronMessage::{
+        SoftwareVersionRequest, SoftwareVersionResponse, StateDump, StateRequest,
+    };
+
+    use crate::latency::{LatencyProbe, LATENCY_SAMPLES};
+
+    #[test]
+    fn round_trips_are_averaged() {
+        let mut probe = LatencyProbe::new();
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+        assert_eq!(probe.average(), None);
+
+        probe.sent(&SoftwareVersionRequest(Multicast), start);
+        probe.sent(&StateRequest(Multicast), start + ms(5));
+        probe.received(&StateDump(Multicast, vec![]), start + ms(15));
+        probe.received(
+            &SoftwareVersionResponse(Multicast, String::from("1")),
+            start + ms(20),
+        );
+        // Unrequested responses are not measured
+        probe.received(&StateDump(Multicast, vec![]), start + ms(100));
+        assert_eq!(probe.average(), Some(ms(15)));
+        assert_eq!(probe.jitter(), Some(ms(5)));
+
+        for _ in 0..LATENCY_SAMPLES {
+            probe.sent(&StateRequest(Multicast), start);
+            probe.received(&StateDump(Multicast, vec![]), start + ms(30));
+        }
+        assert_eq!(probe.average(), Some(ms(30)));
+        assert_eq!(probe.jitter(), Some(ms(0)));
+    }
+}
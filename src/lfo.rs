@@ -0,0 +1,150 @@
+use std::f32::consts::PI;
+
+use rustron_lib::protocol::GlobalSetting::{LfoShapeOrder, LfoShapePhase};
+use rustron_lib::protocol::{GlobalSetting, LfoIndex, LfoPhaseOffset, LfoShape};
+
+const INDICES: [LfoIndex; 5] = [
+    LfoIndex::One,
+    LfoIndex::Two,
+    LfoIndex::Three,
+    LfoIndex::Four,
+    LfoIndex::Five,
+];
+
+const SHAPES: [LfoShape; 5] = [
+    LfoShape::Sine,
+    LfoShape::Triangle,
+    LfoShape::FallingSaw,
+    LfoShape::Square,
+    LfoShape::RisingSaw,
+];
+
+const PHASES: [LfoPhaseOffset; 8] = [
+    LfoPhaseOffset::Zero,
+    LfoPhaseOffset::FourtyFive,
+    LfoPhaseOffset::Ninety,
+    LfoPhaseOffset::HundredThirtyFive,
+    LfoPhaseOffset::HundredEighty,
+    LfoPhaseOffset::TwoHundredTwentyFive,
+    LfoPhaseOffset::TwoHundredSeventy,
+    LfoPhaseOffset::ThreeHundredFifteen,
+];
+
+/// Value of one cycle of `shape` at `t` (0.0 - 1.0), in the range -1.0 - 1.0.
+pub fn sample(shape: LfoShape, phase: LfoPhaseOffset, t: f32) -> f32 {
+    let t = (t + phase.as_degrees() as f32 / 360.0).fract();
+    match shape {
+        LfoShape::Sine => (2.0 * PI * t).sin(),
+        LfoShape::Triangle => 1.0 - 4.0 * ((t + 0.25).fract() - 0.5).abs(),
+        LfoShape::FallingSaw => 1.0 - 2.0 * t,
+        LfoShape::Square => {
+            if t < 0.5 {
+                1.0
+            } else {
+                -1.0
+            }
+        }
+        LfoShape::RisingSaw => 2.0 * t - 1.0,
+    }
+}
+
+/// Draws one cycle of each `(shape, phase)` after each other, `width` columns and `height` rows.
+pub fn render(slots: &[(LfoShape, LfoPhaseOffset)], width: usize, height: usize) -> Vec<String> {
+    let mut rows = vec![vec![' '; width]; height];
+    if slots.is_empty() || height == 0 {
+        return Vec::new();
+    }
+    for (column, cells) in (0..width).map(|c| (c, c as f32 / width as f32 * slots.len() as f32)) {
+        let (shape, phase) = slots[(cells as usize).min(slots.len() - 1)];
+        let value = sample(shape, phase, cells.fract());
+        let row = ((1.0 - value) / 2.0 * (height - 1) as f32).round() as usize;
+        rows[row.min(height - 1)][column] = '*';
+    }
+    rows.into_iter()
+        .map(|row| row.into_iter().collect())
+        .collect()
+}
+
+fn step<T: Copy + PartialEq>(values: &[T], current: T, forward: bool) -> T {
+    let index = values.iter().position(|v| *v == current).unwrap_or(0);
+    if forward {
+        values[(index + 1) % values.len()]
+    } else {
+        values[(index + values.len() - 1) % values.len()]
+    }
+}
+
+/// Edits the shape order and phase offsets of the five LFO shape slots.
+pub struct LfoEditor {
+    pub slots: [(LfoShape, LfoPhaseOffset); 5],
+    pub selection: usize,
+}
+
+impl Default for LfoEditor {
+    fn default() -> LfoEditor {
+        LfoEditor::new()
+    }
+}
+
+impl LfoEditor {
+    pub fn new() -> LfoEditor {
+        let mut slots = [(LfoShape::Sine, LfoPhaseOffset::Zero); 5];
+        for (slot, shape) in slots.iter_mut().zip(SHAPES.iter()) {
+            slot.0 = *shape;
+        }
+        LfoEditor {
+            slots,
+            selection: 0,
+        }
+    }
+
+    pub fn select_next(&mut self) {
+        self.selection = (self.selection + 1) % self.slots.len();
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selection = (self.selection + self.slots.len() - 1) % self.slots.len();
+    }
+
+    pub fn step_shape(&mut self, forward: bool) {
+        let slot = &mut self.slots[self.selection];
+        slot.0 = step(&SHAPES, slot.0, forward);
+    }
+
+    pub fn step_phase(&mut self, forward: bool) {
+        let slot = &mut self.slots[self.selection];
+        slot.1 = step(&PHASES, slot.1, forward);
+    }
+
+    /// The settings to send for the selected slot.
+    pub fn selected_settings(&self) -> [GlobalSetting; 2] {
+        let index = INDICES[self.selection];
+        let (shape, phase) = self.slots[self.selection];
+        [LfoShapeOrder(index, shape), LfoShapePhase(index, phase)]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rustron_lib::protocol::LfoPhaseOffset::{HundredEighty, Ninety, Zero};
+    use rustron_lib::protocol::LfoShape::{FallingSaw, Sine, Square, Triangle};
+
+    use crate::lfo::{render, sample};
+
+    #[test]
+    fn shapes_are_sampled_with_phase() {
+        assert!((sample(Sine, Ninety, 0.0) - 1.0).abs() < 1e-6);
+        assert_eq!(sample(Triangle, Zero, 0.25), 1.0);
+        assert_eq!(sample(Triangle, Zero, 0.75), -1.0);
+        assert_eq!(sample(Square, HundredEighty, 0.0), -1.0);
+        assert_eq!(sample(FallingSaw, Zero, 0.0), 1.0);
+    }
+
+    #[test]
+    fn square_is_rendered() {
+        assert_eq!(
+            render(&[(Square, Zero)], 4, 3),
+            vec!["**  ", "    ", "  **"]
+        );
+    }
+}
@@ -0,0 +1,65 @@
+//! The Neutron editor behind the terminal frontend, usable by other frontends: `app::App` holds
+//! the whole editor state and is driven by dispatching `app::AppEvent`s, `core::AppCore` is the
+//! part without any user interface, and `midi` talks to the ports.
+//!
+//! ```no_run
+//! use rustron::app::{App, AppEvent};
+//! use rustron::options::Options;
+//!
+//! let mut app = App::new(&Options::default());
+//! app.dispatch(AppEvent::Tick);
+//! ```
+
+pub mod acks;
+pub mod app;
+pub mod autoglide;
+pub mod automation;
+#[cfg(feature = "ble")]
+pub mod ble;
+pub mod calibration;
+pub mod channel;
+pub mod compare;
+pub mod config;
+pub mod core;
+pub mod dips;
+pub mod emulator;
+pub mod events;
+pub mod flash;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod handshake;
+pub mod history;
+pub mod hotkeys;
+pub mod inspect;
+#[cfg(feature = "jack")]
+pub mod jack;
+pub mod latency;
+pub mod lfo;
+pub mod librarian;
+pub mod library;
+pub mod locale;
+pub mod macros;
+pub mod mapping;
+pub mod midi;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+pub mod options;
+pub mod poll;
+pub mod queue;
+pub mod randomize;
+pub mod recall;
+pub mod redraw;
+pub mod remote;
+pub mod report;
+pub mod risk;
+pub mod scenario;
+pub mod script;
+pub mod session;
+pub mod setup;
+pub mod smf;
+pub mod stats;
+pub mod synccheck;
+pub mod syxfiles;
+pub mod tempo;
+pub mod through;
+pub mod workspace;
@@ -0,0 +1,186 @@
+use std::error;
+use std::path::PathBuf;
+
+use rustron_lib::protocol::{SYSEX_EOX, SYSEX_MESSAGE_START};
+
+use crate::syxfiles;
+
+/// One or more SysEx messages of any manufacturer, as received or read from a `.syx` file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Dump {
+    pub name: String,
+    pub messages: Vec<Vec<u8>>,
+}
+
+impl Dump {
+    /// The size of the dump in bytes.
+    pub fn size(&self) -> usize {
+        self.messages.iter().map(Vec::len).sum()
+    }
+}
+
+/// Splits the contents of a `.syx` file into its messages, dropping any bytes between them.
+pub fn split_syx(bytes: &[u8]) -> Vec<Vec<u8>> {
+    let mut messages = Vec::new();
+    let mut current: Option<Vec<u8>> = None;
+    for byte in bytes {
+        if *byte == SYSEX_MESSAGE_START {
+            current = Some(Vec::new());
+        }
+        if let Some(message) = &mut current {
+            message.push(*byte);
+            if *byte == SYSEX_EOX {
+                messages.extend(current.take());
+            }
+        }
+    }
+    messages
+}
+
+/// Receives, stores and resends SysEx dumps without parsing them, kept as `.syx` files in a
+/// directory. A dump is everything received between starting and stopping to receive, so dumps
+/// spanning many messages are kept together.
+pub struct Librarian {
+    pub directory: PathBuf,
+    pub dumps: Vec<Dump>,
+    pub selection: usize,
+    /// The dump being received, until receiving is stopped
+    pub receiving: Option<Dump>,
+    /// The last received dump, until it is saved
+    pub received: Option<Dump>,
+}
+
+impl Librarian {
+    pub fn new(directory: PathBuf) -> Librarian {
+        let mut librarian = Librarian {
+            directory,
+            dumps: Vec::new(),
+            selection: 0,
+            receiving: None,
+            received: None,
+        };
+        librarian.refresh();
+        librarian
+    }
+
+    /// Re-reads the dumps from the directory.
+    pub fn refresh(&mut self) {
+        self.dumps = syxfiles::names(&self.directory)
+            .into_iter()
+            .filter_map(|name| {
+                Some(Dump {
+                    messages: split_syx(&syxfiles::read(&self.directory, &name).ok()?),
+                    name,
+                })
+            })
+            .collect();
+        self.selection = self.selection.min(self.dumps.len().saturating_sub(1));
+    }
+
+    pub fn selected(&self) -> Option<&Dump> {
+        self.dumps.get(self.selection)
+    }
+
+    pub fn select_next(&mut self) {
+        self.selection = syxfiles::step(self.selection, self.dumps.len(), true);
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selection = syxfiles::step(self.selection, self.dumps.len(), false);
+    }
+
+    /// Starts collecting received SysEx messages into a new dump.
+    pub fn start_receiving(&mut self) {
+        self.receiving = Some(Dump::default());
+    }
+
+    /// Adds `bytes` to the dump being received, if it is a SysEx message.
+    pub fn receive(&mut self, bytes: &[u8]) {
+        if let Some(dump) = &mut self.receiving {
+            if bytes.first() == Some(&SYSEX_MESSAGE_START) {
+                dump.messages.push(bytes.to_vec());
+            }
+        }
+    }
+
+    /// Stops receiving, keeping the dump as the received one if anything was received. Returns
+    /// how many messages were received.
+    pub fn stop_receiving(&mut self) -> usize {
+        match self.receiving.take() {
+            Some(dump) if !dump.messages.is_empty() => {
+                let count = dump.messages.len();
+                self.received = Some(dump);
+                count
+            }
+            _ => 0,
+        }
+    }
+
+    /// Saves the received dump as `name`.
+    pub fn save_received(&mut self, name: &str) -> Result<(), Box<dyn error::Error>> {
+        let dump = self.received.as_ref().ok_or("No dump received.")?;
+        syxfiles::write(&self.directory, name, &dump.messages.concat())?;
+        self.received = None;
+        self.refresh();
+        if let Some(index) = self.dumps.iter().position(|dump| dump.name == name) {
+            self.selection = index;
+        }
+        Ok(())
+    }
+
+    pub fn delete(&mut self, name: &str) -> Result<(), Box<dyn error::Error>> {
+        syxfiles::delete(&self.directory, name)?;
+        self.refresh();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use crate::librarian::{split_syx, Dump, Librarian};
+
+    #[test]
+    fn syx_files_are_split_into_messages() {
+        let bytes = [0xf0, 0x41, 0x01, 0xf7, 0x00, 0xf0, 0x43, 0xf7, 0xf0, 0x01];
+        assert_eq!(
+            split_syx(&bytes),
+            vec![vec![0xf0, 0x41, 0x01, 0xf7], vec![0xf0, 0x43, 0xf7]]
+        );
+    }
+
+    #[test]
+    fn received_dumps_are_saved_and_deleted() {
+        let directory = std::env::temp_dir().join("rustron-librarian-test");
+        let _ = fs::remove_dir_all(&directory);
+        let mut librarian = Librarian::new(directory.clone());
+        assert_eq!(librarian.selected(), None);
+        assert!(librarian.save_received("nothing").is_err());
+        librarian.received = Some(Dump::default());
+        assert!(librarian.save_received("../escaped").is_err());
+        librarian.received = None;
+
+        librarian.receive(&[0xf0, 0x41, 0xf7]);
+        librarian.start_receiving();
+        librarian.receive(&[0xf0, 0x41, 0x01, 0xf7]);
+        librarian.receive(&[0x90, 0x3c, 0x40]);
+        librarian.receive(&[0xf0, 0x41, 0x02, 0xf7]);
+        assert_eq!(librarian.stop_receiving(), 2);
+        assert_eq!(librarian.stop_receiving(), 0);
+
+        librarian.save_received("roland.d50").unwrap();
+        assert_eq!(librarian.received, None);
+        let dump = librarian.selected().unwrap();
+        assert_eq!(dump.name, "roland.d50");
+        assert_eq!(
+            dump.messages,
+            vec![vec![0xf0, 0x41, 0x01, 0xf7], vec![0xf0, 0x41, 0x02, 0xf7]]
+        );
+        assert_eq!(dump.size(), 8);
+
+        librarian.delete("roland.d50").unwrap();
+        assert!(librarian.dumps.is_empty());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
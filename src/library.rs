@@ -0,0 +1,162 @@
+use std::error;
+use std::fs;
+use std::path::PathBuf;
+
+use rustron_lib::preset;
+use rustron_lib::preset::Preset;
+
+use crate::syxfiles;
+
+const TEMPLATE_PREFIX: &str = "template: ";
+
+/// The preset files in a directory, stored as `.syx` files, followed by the built in templates.
+pub struct PresetLibrary {
+    pub directory: PathBuf,
+    pub names: Vec<String>,
+    pub templates: Vec<(&'static str, Preset)>,
+    /// Index into the files followed by the templates
+    pub selection: usize,
+    /// The selected preset, loaded for previewing
+    pub preview: Option<Preset>,
+}
+
+impl PresetLibrary {
+    pub fn new(directory: PathBuf) -> PresetLibrary {
+        let mut library = PresetLibrary {
+            directory,
+            names: Vec::new(),
+            templates: preset::templates(),
+            selection: 0,
+            preview: None,
+        };
+        library.refresh();
+        library
+    }
+
+    /// Re-reads the preset names from the directory.
+    pub fn refresh(&mut self) {
+        self.names = syxfiles::names(&self.directory);
+        self.selection = self.selection.min(self.len().saturating_sub(1));
+        self.update_preview();
+    }
+
+    fn len(&self) -> usize {
+        self.names.len() + self.templates.len()
+    }
+
+    /// The files and templates as listed, templates marked as such.
+    pub fn titles(&self) -> Vec<String> {
+        self.names
+            .iter()
+            .cloned()
+            .chain(
+                self.templates
+                    .iter()
+                    .map(|(name, _)| format!("{}{}", TEMPLATE_PREFIX, name)),
+            )
+            .collect()
+    }
+
+    fn update_preview(&mut self) {
+        self.preview = match self.selection.checked_sub(self.names.len()) {
+            Some(template) => self.templates.get(template).map(|(_, p)| p.clone()),
+            None => self.selected().and_then(|name| self.load(name).ok()),
+        };
+    }
+
+    /// The name of the selected preset file, `None` if a template is selected.
+    pub fn selected(&self) -> Option<&str> {
+        self.names.get(self.selection).map(String::as_str)
+    }
+
+    /// The title of the selected file or template.
+    pub fn selected_title(&self) -> Option<String> {
+        self.titles().get(self.selection).cloned()
+    }
+
+    pub fn select_next(&mut self) {
+        self.selection = syxfiles::step(self.selection, self.len(), true);
+        self.update_preview();
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selection = syxfiles::step(self.selection, self.len(), false);
+        self.update_preview();
+    }
+
+    /// Selects the file or template with the given title, if it exists.
+    pub fn select(&mut self, title: &str) {
+        if let Some(index) = self.titles().iter().position(|t| t == title) {
+            self.selection = index;
+            self.update_preview();
+        }
+    }
+
+    pub fn load(&self, name: &str) -> Result<Preset, Box<dyn error::Error>> {
+        Ok(preset::from_app_dump(&syxfiles::read(
+            &self.directory,
+            name,
+        )?)?)
+    }
+
+    pub fn save(&mut self, name: &str, preset: &Preset) -> Result<(), Box<dyn error::Error>> {
+        syxfiles::write(&self.directory, name, &preset::to_syx(preset))?;
+        self.refresh();
+        Ok(())
+    }
+
+    pub fn rename(&mut self, name: &str, new_name: &str) -> Result<(), Box<dyn error::Error>> {
+        let new_path = syxfiles::path(&self.directory, new_name)?;
+        if new_path.exists() {
+            return Err(format!("A preset named {} already exists.", new_name).into());
+        }
+        fs::rename(syxfiles::path(&self.directory, name)?, new_path)?;
+        self.refresh();
+        Ok(())
+    }
+
+    pub fn delete(&mut self, name: &str) -> Result<(), Box<dyn error::Error>> {
+        syxfiles::delete(&self.directory, name)?;
+        self.refresh();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use rustron_lib::preset::Preset;
+    use rustron_lib::protocol::GlobalSetting::ParaphonicMode;
+    use rustron_lib::protocol::ToggleOption::On;
+
+    use crate::library::PresetLibrary;
+
+    #[test]
+    fn presets_are_saved_renamed_and_deleted() {
+        let directory = std::env::temp_dir().join("rustron-library-test");
+        let _ = fs::remove_dir_all(&directory);
+        let mut library = PresetLibrary::new(directory.clone());
+        assert_eq!(library.selected(), None);
+
+        let mut preset = Preset::new();
+        preset.set(ParaphonicMode(On));
+        library.save("lead", &preset).unwrap();
+        library.save("bass", &preset).unwrap();
+        assert_eq!(library.names, vec!["bass", "lead"]);
+        assert_eq!(library.load("lead").unwrap(), preset);
+        assert_eq!(library.preview, Some(preset));
+
+        assert!(library.rename("bass", "lead").is_err());
+        assert!(library.rename("bass", "../bass").is_err());
+        library.rename("bass", "sub.v2").unwrap();
+        library.delete("lead").unwrap();
+        assert_eq!(library.names, vec!["sub.v2"]);
+
+        library.select("template: mono bass");
+        assert_eq!(library.selected(), None);
+        assert_eq!(library.preview, Some(library.templates[2].1.clone()));
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,422 @@
+use std::env;
+use std::fmt::Display;
+
+/// A language the interface is shown in.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Locale {
+    English,
+    German,
+}
+
+/// The interface strings by key, in English and German. Log messages stay in English, they are
+/// meant for bug reports. Each `{}` is filled in by `Locale::format`.
+const CATALOG: [(&str, &str, &str); 128] = [
+    ("tab.app", "app", "app"),
+    ("tab.state", "state", "zustand"),
+    ("tab.presets", "presets", "presets"),
+    ("tab.lfo", "lfo", "lfo"),
+    ("tab.glide", "glide", "glide"),
+    ("tab.inspect", "inspect", "inspektor"),
+    ("tab.stats", "stats", "statistik"),
+    ("tab.devices", "devices", "geräte"),
+    ("tab.librarian", "librarian", "bibliothek"),
+    ("tab.calibrate", "calibrate", "kalibrieren"),
+    ("tab.logs", "logs", "protokoll"),
+    ("title.command_history", "Command History", "Befehlsverlauf"),
+    ("title.help", "Help (?)", "Hilfe (?)"),
+    ("title.midi_stream", "MIDI Stream", "MIDI-Datenstrom"),
+    ("title.neutron_state", "Neutron State", "Neutron-Zustand"),
+    ("title.presets", "Presets", "Presets"),
+    (
+        "title.changes",
+        "Changes when loaded",
+        "Änderungen beim Laden",
+    ),
+    ("title.dumps", "SysEx dumps", "SysEx-Dumps"),
+    ("title.dump", "Dump", "Dump"),
+    ("title.calibration", "Calibration", "Kalibrierung"),
+    (
+        "title.lfo",
+        "LFO shape order (Left/Right: shape, [/]: phase, Enter: send)",
+        "LFO-Formenfolge (Links/Rechts: Form, [/]: Phase, Enter: senden)",
+    ),
+    ("title.preview", "Preview", "Vorschau"),
+    (
+        "title.autoglide",
+        "Autoglide (Left/Right: step, Enter: send)",
+        "Autoglide (Links/Rechts: Schritt, Enter: senden)",
+    ),
+    ("title.bytes", "Bytes", "Bytes"),
+    ("title.statistics", "Statistics", "Statistik"),
+    ("title.setup", "Setup", "Einrichtung"),
+    ("title.midi_outputs", "MIDI outputs", "MIDI-Ausgänge"),
+    ("title.logs", "Logs", "Protokoll"),
+    ("title.hotkeys", "Hotkeys (T)", "Schnelltasten (T)"),
+    ("title.confirm", "Confirm", "Bestätigen"),
+    (
+        "keys.presets",
+        "Enter: load, S: save as, R: rename, D: delete, n: note, J: patch cable",
+        "Enter: laden, S: speichern unter, R: umbenennen, D: löschen, n: Notiz, J: Patchkabel",
+    ),
+    (
+        "keys.librarian",
+        "Enter: send, Q: request dump, R: receive, S: save received, D: delete, \
+         F: inspect firmware, U: send firmware",
+        "Enter: senden, Q: Dump anfordern, R: empfangen, S: Empfangenes speichern, D: löschen, \
+         F: Firmware prüfen, U: Firmware senden",
+    ),
+    (
+        "keys.calibration",
+        "Enter: start/confirm step, A: abort",
+        "Enter: starten/Schritt bestätigen, A: abbrechen",
+    ),
+    (
+        "keys.inspector",
+        "Left/Right: switch, h/H: copy hex/text, R: resend, *: bookmark, [/]: previous/next \
+         bookmark, E: export bookmarks, D: diff with previous dump",
+        "Links/Rechts: wechseln, h/H: Hex/Text kopieren, R: erneut senden, *: Lesezeichen, \
+         [/]: vorheriges/nächstes Lesezeichen, E: Lesezeichen exportieren, D: mit vorigem Dump \
+         vergleichen",
+    ),
+    (
+        "keys.outputs",
+        "Enter: enable/disable",
+        "Enter: ein-/ausschalten",
+    ),
+    ("keys.offline_queue", "C: clear", "C: leeren"),
+    (
+        "text.waiting",
+        "Waiting for the Neutron to respond...",
+        "Warte auf Antwort des Neutron...",
+    ),
+    (
+        "text.same_state",
+        "Same as the current state",
+        "Gleich dem aktuellen Zustand",
+    ),
+    ("text.patch_sheet", "Patch sheet", "Patchblatt"),
+    (
+        "text.no_hotkeys",
+        "No settings bound to the number keys, see [hotkeys] in the configuration",
+        "Keine Einstellungen auf den Zifferntasten, siehe [hotkeys] in der Konfiguration",
+    ),
+    (
+        "text.keep_connected",
+        "don't disconnect the Neutron",
+        "den Neutron nicht trennen",
+    ),
+    ("text.sends_sysex", "sends SysEx", "sendet SysEx"),
+    ("text.not_started", "Not started", "Nicht gestartet"),
+    (
+        "text.calibration_step",
+        "Do the highlighted step, then confirm it",
+        "Den markierten Schritt ausführen, dann bestätigen",
+    ),
+    (
+        "text.calibration_finished",
+        "Calibration finished",
+        "Kalibrierung abgeschlossen",
+    ),
+    ("text.following", "following", "folgt"),
+    (
+        "text.setup_welcome",
+        "Welcome! Pick the MIDI port of the Neutron and press Enter, or Esc to skip and keep \
+         the defaults.",
+        "Willkommen! Den MIDI-Port des Neutron wählen und Enter drücken, oder Esc, um zu \
+         überspringen und die Voreinstellungen zu behalten.",
+    ),
+    (
+        "text.setup_answered",
+        "The Neutron answered. Press Enter to save the configuration.",
+        "Der Neutron hat geantwortet. Enter speichert die Konfiguration.",
+    ),
+    (
+        "text.setup_unanswered",
+        "No answer from the Neutron. Press Enter to save the configuration anyway.",
+        "Keine Antwort vom Neutron. Enter speichert die Konfiguration trotzdem.",
+    ),
+    (
+        "label.software_version",
+        "Software version",
+        "Softwareversion",
+    ),
+    (
+        "label.unconfirmed",
+        "Unconfirmed settings",
+        "Unbestätigte Einstellungen",
+    ),
+    ("label.polling", "Polling", "Abfrage"),
+    (
+        "label.settings",
+        "Settings (*: not acknowledged, !: differs from the loaded preset)",
+        "Einstellungen (*: unbestätigt, !: weicht vom geladenen Preset ab)",
+    ),
+    ("label.every", "every", "alle"),
+    ("label.drift", "Drift", "Abweichung"),
+    ("label.dip_switches", "DIP switches", "DIP-Schalter"),
+    (
+        "label.midi_channel",
+        "Effective MIDI channel",
+        "Wirksamer MIDI-Kanal",
+    ),
+    ("label.channel", "channel", "Kanal"),
+    ("label.state_dump", "State dump", "Zustandsdump"),
+    ("label.cable", "Cable", "Kabel"),
+    ("label.target", "Target", "Ziel"),
+    ("label.pending_acks", "Pending acks", "Offene Bestätigungen"),
+    ("label.timed_out", "Timed out", "Zeitüberschreitungen"),
+    ("label.send_queue", "Send queue", "Sendewarteschlange"),
+    ("label.latency", "Latency", "Latenz"),
+    (
+        "label.firmware_upload",
+        "Sending firmware block",
+        "Sende Firmware-Block",
+    ),
+    (
+        "label.calibration_aborted",
+        "Calibration aborted",
+        "Kalibrierung abgebrochen",
+    ),
+    ("label.semitones", "semitones", "Halbtöne"),
+    ("label.sent", "Sent", "Gesendet"),
+    ("label.received", "Received", "Empfangen"),
+    ("label.messages", "messages", "Nachrichten"),
+    ("label.bytes", "bytes", "Bytes"),
+    ("label.unparsed", "unparsed", "nicht erkannt"),
+    ("label.input_queue", "Input queue", "Eingangswarteschlange"),
+    (
+        "label.output_queue",
+        "Output queue",
+        "Ausgangswarteschlange",
+    ),
+    ("label.dropped", "dropped", "verworfen"),
+    (
+        "label.error_injection",
+        "Error injection",
+        "Fehlerinjektion",
+    ),
+    ("label.corrupted", "corrupted", "verfälscht"),
+    ("label.waiting", "waiting", "wartend"),
+    ("label.coalesced", "coalesced", "zusammengefasst"),
+    ("label.ms_apart", "ms apart", "ms Abstand"),
+    ("label.through", "Through", "Durchleitung"),
+    ("label.rate_last", "bytes/s, last", "Bytes/s, letzte"),
+    ("label.outputs", "Outputs", "Ausgänge"),
+    (
+        "label.profile_outputs",
+        "Outputs of profile",
+        "Ausgänge des Profils",
+    ),
+    (
+        "label.offline_queue",
+        "Queued until connected",
+        "Wartend bis zur Verbindung",
+    ),
+    ("label.port", "Port", "Port"),
+    ("label.firmware", "Firmware", "Firmware"),
+    ("label.state", "State", "Zustand"),
+    (
+        "text.dump",
+        "{}: {} messages, {} bytes",
+        "{}: {} Nachrichten, {} Bytes",
+    ),
+    (
+        "text.receiving_dump",
+        "Receiving: {} messages, {} bytes (R: stop)",
+        "Empfange: {} Nachrichten, {} Bytes (R: stoppen)",
+    ),
+    (
+        "text.received_dump",
+        "Received {} messages, {} bytes, not saved (S: save)",
+        "{} Nachrichten, {} Bytes empfangen, nicht gespeichert (S: speichern)",
+    ),
+    (
+        "text.firmware",
+        "Firmware {}: {} version {}, {} blocks, CRC-32 {}",
+        "Firmware {}: {} Version {}, {} Blöcke, CRC-32 {}",
+    ),
+    (
+        "text.random_preset",
+        "Unsaved random preset ({} settings)",
+        "Ungespeichertes Zufallspreset ({} Einstellungen)",
+    ),
+    (
+        "text.verifying_channel",
+        "verifying channel {}",
+        "prüfe Kanal {}",
+    ),
+    (
+        "prompt.save_preset",
+        "Save preset as",
+        "Preset speichern unter",
+    ),
+    (
+        "prompt.rename_preset",
+        "Rename preset to",
+        "Preset umbenennen in",
+    ),
+    (
+        "prompt.note",
+        "Note (-: clear the patch sheet)",
+        "Notiz (-: Patchblatt leeren)",
+    ),
+    (
+        "prompt.cable",
+        "Patch cable (from -> to)",
+        "Patchkabel (von -> nach)",
+    ),
+    ("prompt.save_dump", "Save dump as", "Dump speichern unter"),
+    (
+        "prompt.dump_request",
+        "Dump request (hex)",
+        "Dump-Anforderung (hex)",
+    ),
+    ("prompt.firmware_file", "Firmware file", "Firmware-Datei"),
+    (
+        "prompt.send_firmware",
+        "Type the CRC-32 {} to send the firmware, Esc to cancel",
+        "CRC-32 {} eingeben, um die Firmware zu senden, Esc bricht ab",
+    ),
+    (
+        "prompt.confirm",
+        "Send {} ({})? y to confirm",
+        "{} ({}) senden? y bestätigt",
+    ),
+    (
+        "prompt.profile",
+        "Profile (- for none)",
+        "Profil (- für keins)",
+    ),
+    (
+        "prompt.midi_channel",
+        "MIDI channel (1-16)",
+        "MIDI-Kanal (1-16)",
+    ),
+    (
+        "prompt.dip_switches",
+        "DIP switches 1-4 (e.g. 0100)",
+        "DIP-Schalter 1-4 (z. B. 0100)",
+    ),
+    (
+        "prompt.export_report",
+        "Export settings report to (-: clipboard)",
+        "Einstellungsbericht exportieren nach (-: Zwischenablage)",
+    ),
+    (
+        "prompt.add_workspace",
+        "Add workspace for MIDI channel (1-16, all)",
+        "Arbeitsbereich für MIDI-Kanal hinzufügen (1-16, all)",
+    ),
+    (
+        "prompt.set_setting",
+        "Set (e.g. osc1.range 16, Ctrl-p: previous)",
+        "Setzen (z. B. osc1.range 16, Strg-p: vorheriger)",
+    ),
+    ("prompt.send", "Send {}?", "{} senden?"),
+    ("button.save", "Save", "Speichern"),
+    ("button.send", "Send", "Senden"),
+    ("button.cancel", "Cancel", "Abbrechen"),
+    ("value.on", "On", "Ein"),
+    ("value.off", "Off", "Aus"),
+    ("value.unknown", "unknown", "unbekannt"),
+    (
+        "value.unknown_device",
+        "unknown device",
+        "unbekanntes Gerät",
+    ),
+    ("value.none", "none", "keine"),
+    ("value.all", "all", "alle"),
+    ("value.connected", "connected", "verbunden"),
+    ("value.not_connected", "not connected", "nicht verbunden"),
+    ("value.connecting", "connecting", "verbindet"),
+    ("value.waiting", "waiting", "wartet"),
+    ("value.received", "received", "empfangen"),
+    (
+        "status.unresponsive",
+        "NEUTRON UNRESPONSIVE",
+        "NEUTRON ANTWORTET NICHT",
+    ),
+    ("status.read_only", "READ-ONLY", "NUR LESEN"),
+    ("status.measure", "l: measure", "l: messen"),
+];
+
+impl Locale {
+    /// The locale of a language code like `de` or a POSIX locale like `de_DE.UTF-8`.
+    pub fn from_name(name: &str) -> Option<Locale> {
+        let language = name.split(['_', '.', '-']).next()?.to_ascii_lowercase();
+        match language.as_str() {
+            "en" | "c" | "posix" => Some(Locale::English),
+            "de" => Some(Locale::German),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Locale::English => "en",
+            Locale::German => "de",
+        }
+    }
+
+    /// The locale to use: `RUSTRON_LANG` if set, then `configured`, then the usual locale
+    /// variables, falling back to English.
+    pub fn select(configured: Option<Locale>) -> Locale {
+        let from_env = |name| {
+            env::var(name)
+                .ok()
+                .and_then(|value| Locale::from_name(&value))
+        };
+        from_env("RUSTRON_LANG")
+            .or(configured)
+            .or_else(|| from_env("LC_ALL"))
+            .or_else(|| from_env("LC_MESSAGES"))
+            .or_else(|| from_env("LANG"))
+            .unwrap_or(Locale::English)
+    }
+
+    /// The string for `key` in this locale, the key itself if there is none.
+    pub fn text(self, key: &str) -> &str {
+        CATALOG
+            .iter()
+            .find(|(k, _, _)| *k == key)
+            .map_or(key, |(_, english, german)| match self {
+                Locale::English => english,
+                Locale::German => german,
+            })
+    }
+
+    /// The string for `key` with each `{}` replaced by the next of `values`.
+    pub fn format(self, key: &str, values: &[&dyn Display]) -> String {
+        values
+            .iter()
+            .fold(self.text(key).to_string(), |text, value| {
+                text.replacen("{}", &value.to_string(), 1)
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::locale::{Locale, CATALOG};
+
+    #[test]
+    fn strings_are_looked_up_by_key() {
+        assert_eq!(Locale::from_name("de_DE.UTF-8"), Some(Locale::German));
+        assert_eq!(Locale::from_name("C"), Some(Locale::English));
+        assert_eq!(Locale::from_name("sv_SE"), None);
+        assert_eq!(Locale::English.text("tab.devices"), "devices");
+        assert_eq!(Locale::German.text("tab.devices"), "geräte");
+        assert_eq!(Locale::German.text("no.such.key"), "no.such.key");
+        assert_eq!(
+            Locale::German.format("text.dump", &[&"bass", &2, &24]),
+            "bass: 2 Nachrichten, 24 Bytes"
+        );
+        for (index, (key, _, _)) in CATALOG.iter().enumerate() {
+            assert!(
+                CATALOG[..index].iter().all(|(other, _, _)| other != key),
+                "{} is in the catalog twice",
+                key
+            );
+        }
+    }
+}
@@ -0,0 +1,337 @@
+use std::collections::VecDeque;
+use std::error;
+use std::fmt::Display;
+use std::time::{Duration, Instant};
+
+use crate::smf::TimedMessage;
+
+/// Time to wait between two messages when playing back a macro, to not flood the Neutron.
+pub const DEFAULT_PACING: Duration = Duration::from_millis(50);
+
+/// The longest wait a step can have.
+pub const MAX_WAIT: Duration = Duration::from_secs(60 * 60);
+
+/// One step of a macro or script: a raw message, or a pause before the next message.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Step {
+    Message(Vec<u8>),
+    /// Waits this long on top of the pacing, for messages the Neutron needs time after
+    Wait(Duration),
+}
+
+impl Step {
+    /// Parses a hex encoded message or a wait like `wait 500ms` or `wait 2s`.
+    pub fn parse(line: &str) -> Result<Step, Box<dyn error::Error>> {
+        let wait = match line.trim().strip_prefix("wait") {
+            Some(wait) => wait.trim(),
+            None => return Ok(Step::Message(hex::decode(line.trim())?)),
+        };
+        let duration = match (wait.strip_suffix("ms"), wait.strip_suffix('s')) {
+            (Some(millis), _) => Duration::from_millis(millis.trim().parse()?),
+            // Negative, infinite and NaN waits are refused rather than panicking
+            (None, Some(seconds)) => Duration::try_from_secs_f64(seconds.trim().parse()?)?,
+            (None, None) => return Err(format!("expected ms or s after {}", wait).into()),
+        };
+        if duration > MAX_WAIT {
+            return Err(format!(
+                "wait {} is longer than the maximum of {}s",
+                wait,
+                MAX_WAIT.as_secs()
+            )
+            .into());
+        }
+        Ok(Step::Wait(duration))
+    }
+}
+
+impl Display for Step {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Step::Message(message) => write!(f, "{}", hex::encode(message)),
+            Step::Wait(duration) => write!(f, "wait {}ms", duration.as_millis()),
+        }
+    }
+}
+
+/// A named sequence of steps, optionally bound to a function key (F1-F12).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Macro {
+    pub name: String,
+    pub key: Option<u8>,
+    pub steps: Vec<Step>,
+}
+
+impl Macro {
+    pub fn new(name: &str) -> Macro {
+        Macro {
+            name: name.to_string(),
+            key: None,
+            steps: Vec::new(),
+        }
+    }
+}
+
+struct Playback {
+    /// Messages with their time relative to `start`, in order
+    messages: VecDeque<TimedMessage>,
+    start: Instant,
+}
+
+/// Records sent commands into macros and plays them back with pacing.
+pub struct Macros {
+    pub macros: Vec<Macro>,
+    recording: Option<Vec<Vec<u8>>>,
+    playback: Option<Playback>,
+    pacing: Duration,
+}
+
+impl Macros {
+    pub fn new(macros: Vec<Macro>) -> Macros {
+        Macros {
+            macros,
+            recording: None,
+            playback: None,
+            pacing: DEFAULT_PACING,
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playback.is_some()
+    }
+
+    pub fn start_recording(&mut self) {
+        self.recording = Some(Vec::new());
+    }
+
+    /// Stops the current recording and stores it as a new macro bound to the first free function
+    /// key. Empty recordings are discarded.
+    pub fn stop_recording(&mut self) -> Option<&Macro> {
+        let messages = self.recording.take()?;
+        if messages.is_empty() {
+            return None;
+        }
+        let key = (1..=12).find(|k| self.macros.iter().all(|m| m.key != Some(*k)));
+        let mut name_index = self.macros.len() + 1;
+        while self
+            .macros
+            .iter()
+            .any(|m| m.name == format!("macro{}", name_index))
+        {
+            name_index += 1;
+        }
+        self.macros.push(Macro {
+            name: format!("macro{}", name_index),
+            key,
+            steps: messages.into_iter().map(Step::Message).collect(),
+        });
+        self.macros.last()
+    }
+
+    pub fn record(&mut self, message: &[u8]) {
+        if let Some(recording) = &mut self.recording {
+            recording.push(message.to_vec());
+        }
+    }
+
+    /// Starts playing back the macro bound to function key `key`, returns `false` if there is none.
+    pub fn play_bound(&mut self, key: u8) -> Result<bool, Box<dyn error::Error>> {
+        match self.macros.iter().find(|m| m.key == Some(key)) {
+            Some(m) => {
+                let steps = m.steps.clone();
+                self.play_steps(steps)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    pub fn stop_playing(&mut self) {
+        self.playback = None;
+    }
+
+    /// Starts playing back `messages`, replacing any running playback.
+    pub fn play(&mut self, messages: Vec<Vec<u8>>) {
+        let pacing = self.pacing;
+        self.play_timed(
+            messages
+                .into_iter()
+                .enumerate()
+                .map(|(i, message)| (pacing * i as u32, message))
+                .collect(),
+        );
+    }
+
+    /// Starts playing back `steps`, the messages paced and the waits added in between, replacing
+    /// any running playback. Fails without playing anything if the steps take too long to be
+    /// scheduled.
+    pub fn play_steps(&mut self, steps: Vec<Step>) -> Result<(), Box<dyn error::Error>> {
+        let mut time = Duration::from_millis(0);
+        let mut messages = Vec::new();
+        for step in steps {
+            let duration = match step {
+                Step::Message(message) => {
+                    messages.push((time, message));
+                    self.pacing
+                }
+                Step::Wait(duration) => duration,
+            };
+            time = time
+                .checked_add(duration)
+                .ok_or("the steps take too long to be played back")?;
+        }
+        self.play_timed(messages);
+        Ok(())
+    }
+
+    /// Starts playing back `messages` at their times from now on, replacing any running playback.
+    pub fn play_timed(&mut self, mut messages: Vec<TimedMessage>) {
+        if messages.is_empty() {
+            return;
+        }
+        messages.sort_by_key(|(time, _)| *time);
+        self.playback = Some(Playback {
+            messages: messages.into(),
+            start: Instant::now(),
+        });
+    }
+
+    /// Returns the next message of the running playback if it is due at `now`.
+    pub fn next_message(&mut self, now: Instant) -> Option<Vec<u8>> {
+        let playback = self.playback.as_mut()?;
+        let (time, _) = playback.messages.front()?;
+        // A time too far in the future to be told is never due
+        if playback
+            .start
+            .checked_add(*time)
+            .is_none_or(|due| now < due)
+        {
+            return None;
+        }
+        let message = playback.messages.pop_front().map(|(_, message)| message);
+        if playback.messages.is_empty() {
+            self.playback = None;
+        }
+        message
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use crate::macros::{Macros, Step, DEFAULT_PACING, MAX_WAIT};
+
+    #[test]
+    fn recorded_macro_is_played_back_with_pacing() {
+        let mut macros = Macros::new(Vec::new());
+        macros.record(&[0x01]);
+        macros.start_recording();
+        macros.record(&[0x02]);
+        macros.record(&[0x03]);
+        let recorded = macros.stop_recording().unwrap();
+        assert_eq!(recorded.key, Some(1));
+        assert_eq!(
+            recorded.steps,
+            vec![Step::Message(vec![0x02]), Step::Message(vec![0x03])]
+        );
+
+        assert!(macros.play_bound(1).unwrap());
+        let start = Instant::now();
+        assert_eq!(macros.next_message(start), Some(vec![0x02]));
+        assert_eq!(macros.next_message(start), None);
+        assert_eq!(
+            macros.next_message(start + DEFAULT_PACING),
+            Some(vec![0x03])
+        );
+        assert!(!macros.is_playing());
+    }
+
+    #[test]
+    fn timed_messages_are_played_in_order() {
+        let mut macros = Macros::new(Vec::new());
+        macros.play_timed(vec![
+            (Duration::from_millis(500), vec![0x02]),
+            (Duration::from_millis(0), vec![0x01]),
+            (Duration::from_millis(500), vec![0x03]),
+        ]);
+        let start = Instant::now();
+        assert_eq!(macros.next_message(start), Some(vec![0x01]));
+        assert_eq!(macros.next_message(start), None);
+        let later = start + Duration::from_secs(1);
+        assert_eq!(macros.next_message(later), Some(vec![0x02]));
+        assert_eq!(macros.next_message(later), Some(vec![0x03]));
+        assert!(!macros.is_playing());
+    }
+
+    #[test]
+    fn waits_pause_between_steps() {
+        assert_eq!(
+            Step::parse("wait 500ms").unwrap(),
+            Step::Wait(Duration::from_millis(500))
+        );
+        assert_eq!(
+            Step::parse("wait 1.5s").unwrap(),
+            Step::Wait(Duration::from_millis(1500))
+        );
+        assert_eq!(
+            Step::parse("f0f7").unwrap(),
+            Step::Message(vec![0xf0, 0xf7])
+        );
+        assert!(Step::parse("wait 500").is_err());
+        for wait in [
+            "wait -1s",
+            "wait nans",
+            "wait infs",
+            "wait 1e30s",
+            "wait 1e19s",
+            "wait 18446744073709551615ms",
+            "wait 3601s",
+        ] {
+            assert!(Step::parse(wait).is_err(), "{}", wait);
+        }
+        assert_eq!(
+            Step::Wait(Duration::from_secs(2)).to_string(),
+            "wait 2000ms"
+        );
+
+        assert_eq!(Step::parse("wait 3600s").unwrap(), Step::Wait(MAX_WAIT));
+
+        let mut macros = Macros::new(Vec::new());
+        macros
+            .play_steps(vec![
+                Step::Message(vec![0x01]),
+                Step::Wait(Duration::from_millis(500)),
+                Step::Message(vec![0x02]),
+            ])
+            .unwrap();
+        let start = Instant::now();
+        assert_eq!(macros.next_message(start), Some(vec![0x01]));
+        assert_eq!(macros.next_message(start + DEFAULT_PACING), None);
+        assert_eq!(
+            macros.next_message(start + DEFAULT_PACING + Duration::from_millis(500)),
+            Some(vec![0x02])
+        );
+    }
+
+    #[test]
+    fn steps_too_long_to_schedule_are_refused() {
+        let mut macros = Macros::new(Vec::new());
+        let steps = vec![
+            Step::Wait(Duration::MAX),
+            Step::Wait(Duration::MAX),
+            Step::Message(vec![0x01]),
+        ];
+        assert!(macros.play_steps(steps).is_err());
+        assert!(!macros.is_playing());
+
+        // Never due rather than panicking when past what an instant can hold
+        macros
+            .play_steps(vec![Step::Wait(Duration::MAX), Step::Message(vec![0x01])])
+            .unwrap();
+        assert_eq!(macros.next_message(Instant::now()), None);
+    }
+}
@@ -4,16 +4,19 @@ use termion::raw::IntoRawMode;
 use tui::backend::{Backend, TermionBackend};
 use tui::layout::{Constraint, Direction, Layout, Rect};
 use tui::style::{Color, Style};
-use tui::widgets::{Block, Borders, List, SelectableList, Tabs, Text, Widget};
+use tui::widgets::{
+    Block, Borders, List, Paragraph, SelectableList, Sparkline, Tabs, Text, Widget,
+};
 use tui::{Frame, Terminal};
 
-use rustron_lib::parser::neutron_message;
+use rustron_lib::protocol::DeviceId;
 
-use crate::app::App;
-
-mod app;
-mod events;
-mod midi;
+use rustron::app::App;
+use rustron::calibration::{CalibrationState, STEPS};
+use rustron::history::{Coalesce, Message};
+use rustron::options::Options;
+use rustron::setup::{SetupStep, SetupWizard};
+use rustron::{history, inspect, lfo, stats};
 
 // Used for primitive scrolling logic
 fn bottom_slice<T>(array: &[T], max_size: usize) -> &[T] {
@@ -30,15 +33,20 @@ fn render_command_history<B>(frame: &mut Frame<B>, rectangle: Rect, app: &App)
 where
     B: Backend,
 {
-    let command_history = bottom_slice(app.command_history.as_slice(), rectangle.height as usize)
-        .iter()
-        .map(|event| Text::raw(event.to_string()));
+    let command_history = bottom_slice(
+        app.core.command_history.as_slice(),
+        rectangle.height as usize,
+    )
+    .iter()
+    .map(|entry| Text::raw(entry.text.as_str()));
+    let mut title = app.locale.text("title.command_history").to_string();
+    if app.core.macros.is_recording() {
+        title.push_str(" [REC]");
+    } else if app.core.macros.is_playing() {
+        title.push_str(" [PLAY]");
+    }
     List::new(command_history)
-        .block(
-            Block::default()
-                .title("Command History")
-                .borders(Borders::ALL),
-        )
+        .block(Block::default().title(&title).borders(Borders::ALL))
         .render(frame, rectangle);
 }
 
@@ -59,6 +67,24 @@ where
         .highlight_symbol(">>")
         .render(frame, chunks[0]);
 
+    if app.show_help {
+        let setting = app.selected_menu_setting();
+        let id = setting.id();
+        Paragraph::new(
+            [
+                Text::styled(format!("{}\n\n", id.id()), Style::default().fg(Color::Cyan)),
+                Text::raw(id.description()),
+            ]
+            .iter(),
+        )
+        .block(
+            Block::default()
+                .title(app.locale.text("title.help"))
+                .borders(Borders::ALL),
+        )
+        .wrap(true)
+        .render(frame, chunks[1]);
+    }
     // Prototype new menu
     //TODO
 }
@@ -67,55 +93,1025 @@ fn render_midi_stream<B>(frame: &mut Frame<B>, rectangle: Rect, app: &App)
 where
     B: Backend,
 {
-    let midi_messages = bottom_slice(app.midi_in_messages.as_slice(), rectangle.height as usize)
+    let received = app.core.midi_in_messages.as_slice();
+    let shown = app
+        .paused
+        .map_or(received, |pause| pause.received(received));
+    let entries = shown.iter().rev().filter(|entry| {
+        app.stream_filter
+            .is_none_or(|device| entry.device == device)
+    });
+    let mut midi_messages: Vec<Text> =
+        history::coalesce(entries, app.coalesce, rectangle.height as usize)
+            .into_iter()
+            .map(|(entry, count)| match count {
+                1 => Text::raw(entry.text.as_str()),
+                _ => Text::raw(format!("{} (x{})", entry.text, count)),
+            })
+            .collect();
+    midi_messages.reverse();
+    let mut title = match app.stream_filter {
+        Some(device) => format!("MIDI Sysex Input from {} devices (v: filter", device),
+        None => String::from("MIDI Sysex Input (v: filter"),
+    };
+    title.push_str(match app.coalesce {
+        Coalesce::Off => ", g: coalesce)",
+        Coalesce::Identical => ", g: coalescing identical)",
+        Coalesce::SameKind => ", g: coalescing same kind)",
+    });
+    if app.paused.is_some() {
+        title.push_str(&format!(
+            " PAUSED, {} new messages (f: resume)",
+            received.len() - shown.len()
+        ));
+    }
+    List::new(midi_messages.into_iter())
+        .block(Block::default().title(&title).borders(Borders::ALL))
+        .render(frame, rectangle);
+}
+
+/// Colors messages in the merged stream by their kind.
+fn kind_color(message: &Message) -> Color {
+    match stats::kind(message) {
+        "set setting" | "restore settings" => Color::Cyan,
+        "setting update" => Color::Green,
+        "state request" | "state dump" => Color::Magenta,
+        "version request" | "version response" => Color::Yellow,
+        "calibration mode" => Color::LightRed,
+        _ => Color::Red,
+    }
+}
+
+fn render_merged_stream<B>(frame: &mut Frame<B>, rectangle: Rect, app: &App)
+where
+    B: Backend,
+{
+    let (sent, received) = (&app.core.command_history, &app.core.midi_in_messages);
+    let merged = match app.paused {
+        Some(pause) => history::merge(pause.sent(sent), pause.received(received)),
+        None => history::merge(sent, received),
+    };
+    let messages = bottom_slice(merged.as_slice(), rectangle.height as usize)
+        .iter()
+        .map(|(stream, entry)| {
+            Text::styled(
+                format!("{} {}", stream.arrow(), entry.text),
+                Style::default().fg(kind_color(&entry.message)),
+            )
+        });
+    let title = match app.paused {
+        Some(pause) => format!(
+            "MIDI Stream PAUSED, {} new messages (f: resume)",
+            pause.new_messages(sent, received)
+        ),
+        None => app.locale.text("title.midi_stream").to_string(),
+    };
+    List::new(messages)
+        .block(Block::default().title(&title).borders(Borders::ALL))
+        .render(frame, rectangle);
+}
+
+fn on_off(app: &App, value: bool) -> &'static str {
+    app.locale
+        .text(if value { "value.on" } else { "value.off" })
+}
+
+fn render_neutron_state<B>(frame: &mut Frame<B>, rectangle: Rect, app: &App)
+where
+    B: Backend,
+{
+    let text = |key| app.locale.text(key);
+    let block = Block::default()
+        .title(text("title.neutron_state"))
+        .borders(Borders::ALL);
+    if app.core.handshake.is_some() {
+        List::new(vec![Text::raw(text("text.waiting"))].into_iter())
+            .block(block)
+            .render(frame, rectangle);
+        return;
+    }
+    let state = &app.core.neutron_state;
+    let mut lines = vec![Text::raw(format!(
+        "{}: {}",
+        text("label.software_version"),
+        state
+            .software_version
+            .as_deref()
+            .unwrap_or(text("value.unknown"))
+    ))];
+    for ((name, commanded), (_, confirmed)) in state
+        .commanded
+        .fields()
         .iter()
-        .map(|event| match neutron_message(event.as_slice()) {
-            Ok((_, msg)) => Text::raw(msg.to_string()),
-            Err(_) => Text::raw(hex::encode(event)),
+        .zip(state.confirmed.fields().iter())
+    {
+        let line = format!(
+            "{}: {} (sent: {})",
+            name,
+            on_off(app, *confirmed),
+            on_off(app, *commanded)
+        );
+        if state.is_mismatched(name) {
+            lines.push(Text::styled(line, Style::default().fg(Color::Red)));
+        } else {
+            lines.push(Text::raw(line));
+        }
+    }
+    lines.push(Text::raw(format!(
+        "{}: {}",
+        text("label.unconfirmed"),
+        state.unconfirmed.len()
+    )));
+    for global_setting in state.unconfirmed.values() {
+        lines.push(Text::styled(
+            format!("  {:?}", global_setting),
+            Style::default().fg(Color::Red),
+        ));
+    }
+    lines.push(Text::raw(text("label.settings")));
+    for (global_setting, mark) in app.core.marked_settings(app.loaded_settings.as_ref()) {
+        let line = format!(
+            "{:>3} {}: {}",
+            mark,
+            global_setting.id().id(),
+            global_setting.value_text()
+        );
+        lines.push(match mark.as_str() {
+            "" => Text::raw(line),
+            _ => Text::styled(line, Style::default().fg(Color::Yellow)),
         });
-    List::new(midi_messages)
+    }
+    lines.push(Text::raw(if app.core.poller.enabled {
+        format!(
+            "{}: {} {} ms",
+            text("label.polling"),
+            text("label.every"),
+            app.core.poller.interval.as_millis()
+        )
+    } else {
+        format!("{}: {}", text("label.polling"), text("value.off"))
+    }));
+    lines.push(Text::raw(format!(
+        "{}: {}",
+        text("label.drift"),
+        if state.drift.is_empty() {
+            text("value.none").to_string()
+        } else {
+            state.drift.join(", ")
+        }
+    )));
+    let advice = state.midi_advice();
+    lines.push(Text::raw(format!(
+        "{}: {}",
+        text("label.dip_switches"),
+        state.dip_switches.map_or(
+            format!("{} (d: enter)", text("value.unknown")),
+            |dips| format!(
+                "{} ({} {})",
+                dips,
+                text("label.channel"),
+                dips.channel().number()
+            )
+        )
+    )));
+    lines.push(Text::raw(format!(
+        "{}: {} ({})",
+        text("label.midi_channel"),
+        advice
+            .channel
+            .map_or(text("value.unknown").to_string(), |c| c
+                .number()
+                .to_string()),
+        advice.source
+    )));
+    for warning in advice.warnings {
+        lines.push(Text::styled(
+            format!("  {}", warning),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+    lines.push(Text::raw(format!(
+        "{}: {}",
+        text("label.state_dump"),
+        state
+            .state_dump
+            .as_ref()
+            .map_or(text("value.none").to_string(), hex::encode)
+    )));
+    List::new(lines.into_iter())
+        .block(block)
+        .render(frame, rectangle);
+}
+
+fn render_presets<B>(frame: &mut Frame<B>, rectangle: Rect, app: &App)
+where
+    B: Backend,
+{
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(30), Constraint::Percentage(70)].as_ref())
+        .split(rectangle);
+
+    SelectableList::default()
+        .block(
+            Block::default()
+                .title(&format!(
+                    "{} ({})",
+                    app.locale.text("title.presets"),
+                    app.library.directory.display()
+                ))
+                .borders(Borders::ALL),
+        )
+        .items(&app.library.titles())
+        .select(Some(app.library.selection))
+        .highlight_symbol(">>")
+        .render(frame, chunks[0]);
+
+    let mut lines = Vec::new();
+    if let Some(preview) = &app.library.preview {
+        let differences = app.core.neutron_state.preset().diff(preview);
+        if differences.is_empty() {
+            lines.push(Text::raw(app.locale.text("text.same_state")));
+        }
+        for (current, preset) in differences {
+            lines.push(Text::raw(format!(
+                "{} -> {}",
+                current.map_or(String::from("unknown"), |s| format!("{:?}", s)),
+                preset.map_or(String::from("unchanged"), |s| format!("{:?}", s)),
+            )));
+        }
+    }
+    if let Some(sheet) = app.library.preview.as_ref().map(|preset| &preset.sheet) {
+        if !sheet.is_empty() {
+            lines.push(Text::raw(""));
+            lines.push(Text::styled(
+                app.locale.text("text.patch_sheet"),
+                Style::default().fg(Color::Yellow),
+            ));
+        }
+        for note in &sheet.notes {
+            lines.push(Text::raw(note.clone()));
+        }
+        for cable in &sheet.cables {
+            lines.push(Text::raw(format!(
+                "{}: {}",
+                app.locale.text("label.cable"),
+                cable
+            )));
+        }
+    }
+    lines.push(Text::raw(""));
+    lines.push(Text::styled(
+        app.locale.text("keys.presets"),
+        Style::default().fg(Color::Cyan),
+    ));
+    List::new(lines.into_iter())
+        .block(
+            Block::default()
+                .title(app.locale.text("title.changes"))
+                .borders(Borders::ALL),
+        )
+        .render(frame, chunks[1]);
+}
+
+fn render_librarian<B>(frame: &mut Frame<B>, rectangle: Rect, app: &App)
+where
+    B: Backend,
+{
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(30), Constraint::Percentage(70)].as_ref())
+        .split(rectangle);
+
+    let librarian = &app.librarian;
+    let names: Vec<String> = librarian
+        .dumps
+        .iter()
+        .map(|dump| dump.name.clone())
+        .collect();
+    SelectableList::default()
+        .block(
+            Block::default()
+                .title(&format!(
+                    "{} ({})",
+                    app.locale.text("title.dumps"),
+                    librarian.directory.display()
+                ))
+                .borders(Borders::ALL),
+        )
+        .items(&names)
+        .select(Some(librarian.selection))
+        .highlight_symbol(">>")
+        .render(frame, chunks[0]);
+
+    let mut lines = Vec::new();
+    if let Some(dump) = &librarian.receiving {
+        lines.push(Text::styled(
+            app.locale
+                .format("text.receiving_dump", &[&dump.messages.len(), &dump.size()]),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+    if let Some(dump) = &librarian.received {
+        lines.push(Text::raw(app.locale.format(
+            "text.received_dump",
+            &[&dump.messages.len(), &dump.size()],
+        )));
+    }
+    if let Some(dump) = librarian.selected() {
+        lines.push(Text::raw(app.locale.format(
+            "text.dump",
+            &[&dump.name, &dump.messages.len(), &dump.size()],
+        )));
+        for message in &dump.messages {
+            lines.push(Text::raw(hex::encode(message)));
+        }
+    }
+    if let Some((path, firmware)) = &app.firmware {
+        lines.push(Text::raw(""));
+        lines.push(Text::raw(
+            app.locale.format(
+                "text.firmware",
+                &[
+                    &path.display(),
+                    &firmware
+                        .device_name()
+                        .unwrap_or(app.locale.text("value.unknown_device")),
+                    &firmware
+                        .version
+                        .as_deref()
+                        .unwrap_or(app.locale.text("value.unknown")),
+                    &firmware.blocks.len(),
+                    &format!("{:08x}", firmware.checksum()),
+                ],
+            ),
+        ));
+    }
+    if let Some(upload) = &app.core.upload {
+        lines.push(Text::styled(
+            format!(
+                "{}: {}/{}, {}",
+                app.locale.text("label.firmware_upload"),
+                upload.next,
+                upload.len(),
+                app.locale.text("text.keep_connected")
+            ),
+            Style::default().fg(Color::LightRed),
+        ));
+    }
+    lines.push(Text::raw(""));
+    lines.push(Text::styled(
+        app.locale.text("keys.librarian"),
+        Style::default().fg(Color::Cyan),
+    ));
+    List::new(lines.into_iter())
+        .block(
+            Block::default()
+                .title(app.locale.text("title.dump"))
+                .borders(Borders::ALL),
+        )
+        .render(frame, chunks[1]);
+}
+
+fn render_calibration<B>(frame: &mut Frame<B>, rectangle: Rect, app: &App)
+where
+    B: Backend,
+{
+    let text = |key| app.locale.text(key);
+    let state = app.calibration.as_ref().map(|wizard| &wizard.state);
+    let mut lines: Vec<Text> = STEPS
+        .iter()
+        .enumerate()
+        .map(|(index, step)| {
+            let marker = match state {
+                Some(CalibrationState::Step(current)) if index < *current => "[x]",
+                Some(CalibrationState::Step(current)) if index == *current => "[>]",
+                Some(CalibrationState::Done) => "[x]",
+                _ => "[ ]",
+            };
+            let mut line = format!("{} {}. {}", marker, index + 1, step.instruction);
+            if step.message.is_some() {
+                line = format!("{} ({})", line, text("text.sends_sysex"));
+            }
+            match state {
+                Some(CalibrationState::Step(current)) if index == *current => {
+                    Text::styled(line, Style::default().fg(Color::Yellow))
+                }
+                _ => Text::raw(line),
+            }
+        })
+        .collect();
+    lines.push(Text::raw(""));
+    lines.push(match state {
+        None => Text::raw(text("text.not_started")),
+        Some(CalibrationState::Step(_)) => Text::raw(text("text.calibration_step")),
+        Some(CalibrationState::Done) => Text::styled(
+            text("text.calibration_finished"),
+            Style::default().fg(Color::Green),
+        ),
+        Some(CalibrationState::Aborted(reason)) => Text::styled(
+            format!("{}: {}", text("label.calibration_aborted"), reason),
+            Style::default().fg(Color::LightRed),
+        ),
+    });
+    lines.push(Text::raw(""));
+    lines.push(Text::styled(
+        text("keys.calibration"),
+        Style::default().fg(Color::Cyan),
+    ));
+    List::new(lines.into_iter())
         .block(
             Block::default()
-                .title("MIDI Sysex Input")
+                .title(text("title.calibration"))
                 .borders(Borders::ALL),
         )
         .render(frame, rectangle);
 }
 
+fn render_lfo_editor<B>(frame: &mut Frame<B>, rectangle: Rect, app: &App)
+where
+    B: Backend,
+{
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(9), Constraint::Min(0)].as_ref())
+        .split(rectangle);
+
+    let slots: Vec<String> = app
+        .lfo_editor
+        .slots
+        .iter()
+        .enumerate()
+        .map(|(i, (shape, phase))| format!("{}: {:?} {}°", i + 1, shape, phase.as_degrees()))
+        .collect();
+    SelectableList::default()
+        .block(
+            Block::default()
+                .title(app.locale.text("title.lfo"))
+                .borders(Borders::ALL),
+        )
+        .items(&slots)
+        .select(Some(app.lfo_editor.selection))
+        .highlight_symbol(">>")
+        .render(frame, chunks[0]);
+
+    let preview = lfo::render(
+        &app.lfo_editor.slots,
+        chunks[1].width.saturating_sub(2) as usize,
+        chunks[1].height.saturating_sub(2) as usize,
+    );
+    List::new(preview.into_iter().map(Text::raw))
+        .block(
+            Block::default()
+                .title(app.locale.text("title.preview"))
+                .borders(Borders::ALL),
+        )
+        .render(frame, chunks[1]);
+}
+
+fn render_autoglide_editor<B>(frame: &mut Frame<B>, rectangle: Rect, app: &App)
+where
+    B: Backend,
+{
+    let items: Vec<String> = app
+        .autoglide_editor
+        .values
+        .iter()
+        .enumerate()
+        .map(|(i, value)| {
+            format!(
+                "OSC {} autoglide: {} ({:+} {})",
+                i + 1,
+                value,
+                value.semitones(),
+                app.locale.text("label.semitones")
+            )
+        })
+        .collect();
+    SelectableList::default()
+        .block(
+            Block::default()
+                .title(app.locale.text("title.autoglide"))
+                .borders(Borders::ALL),
+        )
+        .items(&items)
+        .select(Some(app.autoglide_editor.selection))
+        .highlight_symbol(">>")
+        .render(frame, rectangle);
+}
+
+fn render_inspector<B>(frame: &mut Frame<B>, rectangle: Rect, app: &App)
+where
+    B: Backend,
+{
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(40), Constraint::Percentage(60)].as_ref())
+        .split(rectangle);
+
+    let messages = app.inspected_stream();
+    let selected = app.inspector.selected(messages.len());
+    // Only the rows that fit are handed to the list, keeping the selection in view
+    let height = chunks[0].height.saturating_sub(2).max(1) as usize;
+    let start = selected.map_or(0, |s| (s + 1).saturating_sub(height));
+    let visible: Vec<String> = messages[start..messages.len().min(start + height)]
+        .iter()
+        .map(|entry| match entry.bookmarked {
+            true => format!("* {}", entry.text),
+            false => entry.text.clone(),
+        })
+        .collect();
+    SelectableList::default()
+        .block(
+            Block::default()
+                .title(&match app.inspector.selection {
+                    Some(_) => format!(
+                        "{} ({})",
+                        app.inspector.stream,
+                        app.locale.text("keys.inspector")
+                    ),
+                    None => format!(
+                        "{} [{}] ({})",
+                        app.inspector.stream,
+                        app.locale.text("text.following"),
+                        app.locale.text("keys.inspector")
+                    ),
+                })
+                .borders(Borders::ALL),
+        )
+        .items(&visible)
+        .select(selected.map(|s| s - start))
+        .highlight_symbol(">>")
+        .render(frame, chunks[0]);
+
+    let rows = selected.map_or_else(Vec::new, |s| inspect::details(&messages[s].message));
+    List::new(rows.into_iter().map(Text::raw))
+        .block(
+            Block::default()
+                .title(app.locale.text("title.bytes"))
+                .borders(Borders::ALL),
+        )
+        .render(frame, chunks[1]);
+}
+
+fn render_stats<B>(frame: &mut Frame<B>, rectangle: Rect, app: &App)
+where
+    B: Backend,
+{
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Min(0),
+                Constraint::Length(5),
+                Constraint::Length(5),
+            ]
+            .as_ref(),
+        )
+        .split(rectangle);
+
+    let text = |key| app.locale.text(key);
+    let stats = &app.core.stats;
+    let streams = [
+        (text("label.sent"), &stats.sent),
+        (text("label.received"), &stats.received),
+    ];
+    let mut lines = Vec::new();
+    for (name, stream) in streams.iter() {
+        lines.push(Text::styled(
+            format!(
+                "{}: {} {}, {} {}, {} B/s, {:.1}% {}",
+                name,
+                stream.messages,
+                text("label.messages"),
+                stream.bytes,
+                text("label.bytes"),
+                stream.bytes_per_second(),
+                stream.unparsed_rate() * 100.0,
+                text("label.unparsed")
+            ),
+            Style::default().fg(Color::Cyan),
+        ));
+        for (kind, count) in stream.by_kind.iter() {
+            lines.push(Text::raw(format!("  {:<18}{:>8}", kind, count)));
+        }
+    }
+    lines.push(Text::raw(format!(
+        "{}: {} {}",
+        text("label.input_queue"),
+        app.core.dropped_midi(),
+        text("label.dropped")
+    )));
+    if let Some(corruptor) = &app.core.corruptor {
+        lines.push(Text::styled(
+            format!(
+                "{}: {} {} ({:.0} %)",
+                text("label.error_injection"),
+                corruptor.corrupted,
+                text("label.corrupted"),
+                corruptor.fraction * 100.0
+            ),
+            Style::default().fg(Color::Red),
+        ));
+    }
+    lines.push(Text::raw(format!(
+        "{}: {} {}, {} {}, {} {}",
+        text("label.output_queue"),
+        app.core.limiter.pending(),
+        text("label.waiting"),
+        app.core.limiter.coalesced,
+        text("label.coalesced"),
+        app.core.limiter.interval.as_millis(),
+        text("label.ms_apart")
+    )));
+    let through = &app.core.through;
+    lines.push(Text::raw(format!(
+        "{}: {} ({}), {} {}",
+        text("label.through"),
+        on_off(app, through.enabled),
+        through.filter_names().collect::<Vec<_>>().join(", "),
+        through.dropped,
+        text("label.dropped")
+    )));
+    List::new(lines.into_iter())
+        .block(
+            Block::default()
+                .title(text("title.statistics"))
+                .borders(Borders::ALL),
+        )
+        .render(frame, chunks[0]);
+
+    for (chunk, (name, stream)) in chunks[1..].iter().zip(streams.iter()) {
+        let (front, back) = stream.rate.as_slices();
+        let rate = [front, back].concat();
+        Sparkline::default()
+            .block(
+                Block::default()
+                    .title(&format!(
+                        "{} {} {} s",
+                        name,
+                        text("label.rate_last"),
+                        rate.len()
+                    ))
+                    .borders(Borders::ALL),
+            )
+            .data(&rate)
+            .style(Style::default().fg(Color::Yellow))
+            .render(frame, *chunk);
+    }
+}
+
+fn render_devices<B>(frame: &mut Frame<B>, rectangle: Rect, app: &App)
+where
+    B: Backend,
+{
+    let text = |key| app.locale.text(key);
+    let outputs: Vec<String> = app
+        .core
+        .outputs()
+        .iter()
+        .map(|output| {
+            let mark = if output.enabled { "x" } else { " " };
+            if output.is_connected() {
+                format!("[{}] {} ({})", mark, output.name, text("value.connected"))
+            } else if output.is_connecting() {
+                format!("[{}] {} ({})", mark, output.name, text("value.connecting"))
+            } else {
+                format!("[{}] {}", mark, output.name)
+            }
+        })
+        .collect();
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+        .split(rectangle);
+    SelectableList::default()
+        .block(
+            Block::default()
+                .title(&match &app.core.config.profile {
+                    Some(profile) => format!(
+                        "{} {} ({})",
+                        text("label.profile_outputs"),
+                        profile,
+                        text("keys.outputs")
+                    ),
+                    None => format!("{} ({})", text("label.outputs"), text("keys.outputs")),
+                })
+                .borders(Borders::ALL),
+        )
+        .items(&outputs)
+        .select(Some(app.device_selection))
+        .highlight_symbol(">>")
+        .render(frame, chunks[0]);
+
+    let now = std::time::Instant::now();
+    let queue = &app.core.offline;
+    let queued = queue.commands().map(|command| {
+        Text::raw(format!(
+            "{:>5.1}s {}",
+            now.saturating_duration_since(command.queued).as_secs_f64(),
+            Message::parse(&command.bytes)
+        ))
+    });
+    List::new(queued)
+        .block(
+            Block::default()
+                .title(&format!(
+                    "{}: {}/{}, {} {} ({})",
+                    text("label.offline_queue"),
+                    queue.len(),
+                    queue.capacity,
+                    queue.dropped,
+                    text("label.dropped"),
+                    text("keys.offline_queue")
+                ))
+                .borders(Borders::ALL),
+        )
+        .render(frame, chunks[1]);
+}
+
+fn render_setup<B>(frame: &mut Frame<B>, rectangle: Rect, app: &App, setup: &SetupWizard)
+where
+    B: Backend,
+{
+    let text = |key| app.locale.text(key);
+    let block = Block::default()
+        .title(text("title.setup"))
+        .borders(Borders::ALL);
+    match setup.step {
+        SetupStep::SelectPort => {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(4), Constraint::Min(0)].as_ref())
+                .split(rectangle);
+            Paragraph::new([Text::raw(text("text.setup_welcome"))].iter())
+                .block(block)
+                .wrap(true)
+                .render(frame, chunks[0]);
+            SelectableList::default()
+                .block(
+                    Block::default()
+                        .title(text("title.midi_outputs"))
+                        .borders(Borders::ALL),
+                )
+                .items(&setup.ports)
+                .select(setup.selected_port().map(|_| setup.selection))
+                .highlight_symbol(">>")
+                .render(frame, chunks[1]);
+        }
+        SetupStep::Querying(_) | SetupStep::Done => {
+            let state = &app.core.neutron_state;
+            let mut lines = vec![
+                Text::raw(format!(
+                    "{}: {}\n",
+                    text("label.port"),
+                    app.core.config.device.port
+                )),
+                Text::raw(format!(
+                    "{}: {}\n",
+                    text("label.firmware"),
+                    state
+                        .software_version
+                        .as_deref()
+                        .unwrap_or(text("value.waiting"))
+                )),
+                Text::raw(format!(
+                    "{}: {}\n\n",
+                    text("label.state"),
+                    text(if state.state_dump.is_some() {
+                        "value.received"
+                    } else {
+                        "value.waiting"
+                    })
+                )),
+            ];
+            if setup.step == SetupStep::Done {
+                lines.push(Text::styled(
+                    text(if setup.answered {
+                        "text.setup_answered"
+                    } else {
+                        "text.setup_unanswered"
+                    }),
+                    Style::default().fg(Color::Cyan),
+                ));
+            }
+            Paragraph::new(lines.iter())
+                .block(block)
+                .wrap(true)
+                .render(frame, rectangle);
+        }
+    }
+}
+
+/// The title of the header, listing the workspaces once there is more than one.
+fn header_title(app: &App) -> String {
+    let workspaces = &app.core.workspaces;
+    if workspaces.len() < 2 {
+        return String::from("Rustron");
+    }
+    let names: Vec<String> = workspaces
+        .names(app.core.target)
+        .iter()
+        .enumerate()
+        .map(|(index, name)| {
+            if index == workspaces.active() {
+                format!("[{}: {}]", index + 1, name)
+            } else {
+                format!("{}: {}", index + 1, name)
+            }
+        })
+        .collect();
+    format!("Rustron | {}", names.join(" "))
+}
+
+/// The tab titles in the language of the interface.
+fn tab_titles(app: &App) -> Vec<String> {
+    app.tabs
+        .titles
+        .iter()
+        .map(|id| app.locale.text(&format!("tab.{}", id)).to_string())
+        .collect()
+}
+
+/// The settings bound to the number keys and their values, one after the other.
+fn render_hotkeys<B>(frame: &mut Frame<B>, rectangle: Rect, app: &App)
+where
+    B: Backend,
+{
+    let hotkeys = app.hotkeys();
+    let mut texts = Vec::new();
+    if hotkeys.is_empty() {
+        texts.push(Text::raw(app.locale.text("text.no_hotkeys")));
+    }
+    for (hotkey, current) in hotkeys {
+        let (value, color) = match current {
+            Some(setting) => (setting.value_text(), Color::Yellow),
+            None => (app.locale.text("value.unknown").to_string(), Color::Gray),
+        };
+        texts.push(Text::raw(format!(
+            " {} {}: ",
+            hotkey.key,
+            hotkey.setting.id()
+        )));
+        texts.push(Text::styled(value, Style::default().fg(color)));
+        texts.push(Text::raw("  "));
+    }
+    Paragraph::new(texts.iter())
+        .block(
+            Block::default()
+                .title(app.locale.text("title.hotkeys"))
+                .borders(Borders::ALL),
+        )
+        .render(frame, rectangle);
+}
+
+fn render_status_bar<B>(frame: &mut Frame<B>, rectangle: Rect, app: &App)
+where
+    B: Backend,
+{
+    let text = |key| app.locale.text(key);
+    let status = match &app.prompt {
+        Some(prompt) => format!(" {}: {}_", prompt.label, prompt.text),
+        None => format!(
+            " {}{}: {} | {}: {} | {}: {} | {}: {} | {}: {} | A/B: {}{}",
+            if app.core.unresponsive {
+                format!("{} | ", text("status.unresponsive"))
+            } else {
+                String::new()
+            },
+            text("label.target"),
+            match (&app.core.channel_wizard, app.core.target) {
+                (Some(wizard), _) => app
+                    .locale
+                    .format("text.verifying_channel", &[&wizard.channel.number()]),
+                (None, DeviceId::Channel(channel)) => {
+                    format!("{} {}", text("label.channel"), channel.number())
+                }
+                (None, DeviceId::Multicast) => text("value.all").to_string(),
+            },
+            text("label.pending_acks"),
+            app.core.acks.pending(),
+            text("label.timed_out"),
+            app.core.acks.timed_out,
+            text("label.send_queue"),
+            app.core.send_queue_depth(),
+            text("label.latency"),
+            match (app.core.latency.average(), app.core.latency.jitter()) {
+                (Some(average), Some(jitter)) => format!(
+                    "{:.1} ms ±{:.1}",
+                    average.as_secs_f64() * 1000.0,
+                    jitter.as_secs_f64() * 1000.0
+                ),
+                _ => format!("{} ({})", text("value.unknown"), text("status.measure")),
+            },
+            app.compare.active,
+            match &app.randomized {
+                Some(preset) => format!(
+                    " | {}",
+                    app.locale
+                        .format("text.random_preset", &[&preset.settings.len()])
+                ),
+                None => String::new(),
+            }
+        ),
+    };
+    let mut texts = Vec::new();
+    if app.core.options.read_only {
+        texts.push(Text::styled(
+            format!(" {} |", text("status.read_only")),
+            Style::default().fg(Color::LightRed),
+        ));
+    }
+    texts.push(Text::raw(status));
+    Paragraph::new(texts.iter()).render(frame, rectangle);
+}
+
 fn main() -> Result<(), Box<dyn error::Error>> {
+    let options = Options::parse(std::env::args().skip(1))?;
+    if let Some((before, after)) = &options.diff {
+        let lines = inspect::diff_dumps(
+            &inspect::read_message(before)?,
+            &inspect::read_message(after)?,
+        );
+        for line in lines {
+            println!("{}", line);
+        }
+        return Ok(());
+    }
     let stdout = io::stdout().into_raw_mode()?;
     let backend = TermionBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
     terminal.hide_cursor()?;
     terminal.clear()?;
 
-    let app = &mut App::new();
+    let app = &mut App::new(&options);
 
     while !app.should_quit {
+        if !app.redraw.take(app.visible_panes()) {
+            app.tick();
+            continue;
+        }
         terminal.draw(|mut frame| {
             let size = frame.size();
 
             let header_body = Layout::default()
                 .direction(Direction::Vertical)
-                .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
+                .constraints(
+                    [
+                        Constraint::Length(3),
+                        Constraint::Min(0),
+                        Constraint::Length(1),
+                    ]
+                    .as_ref(),
+                )
                 .split(size);
 
             Tabs::default()
-                .block(Block::default().borders(Borders::ALL).title("Rustron"))
-                .titles(&app.tabs.titles)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title(&header_title(app)),
+                )
+                .titles(&tab_titles(app))
                 .select(app.tabs.index)
                 .style(Style::default().fg(Color::Cyan))
                 .highlight_style(Style::default().fg(Color::Yellow))
                 .render(&mut frame, header_body[0]);
 
-            match app.tabs.index {
-                0 => {
+            if let Some(setup) = &app.setup {
+                render_setup(&mut frame, header_body[1], app, setup);
+                render_status_bar(&mut frame, header_body[2], app);
+                return;
+            }
+
+            let body = if app.hotkey_layer {
+                let chunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Min(0), Constraint::Length(3)].as_ref())
+                    .split(header_body[1]);
+                render_hotkeys(&mut frame, chunks[1], app);
+                chunks[0]
+            } else {
+                header_body[1]
+            };
+            match app.tabs.current() {
+                "app" if app.merged_stream => {
                     let vertical_split = Layout::default()
                         .direction(Direction::Horizontal)
                         .constraints(
                             [Constraint::Percentage(50), Constraint::Percentage(50)].as_ref(),
                         )
-                        .split(header_body[1]);
+                        .split(body);
+                    render_options_menu(&mut frame, vertical_split[0], app);
+                    render_merged_stream(&mut frame, vertical_split[1], app);
+                }
+                "app" => {
+                    let vertical_split = Layout::default()
+                        .direction(Direction::Horizontal)
+                        .constraints(
+                            [Constraint::Percentage(50), Constraint::Percentage(50)].as_ref(),
+                        )
+                        .split(body);
                     {
                         // Left half
                         let chunks = Layout::default()
@@ -131,16 +1127,37 @@ fn main() -> Result<(), Box<dyn error::Error>> {
 
                     render_midi_stream(&mut frame, vertical_split[1], app);
                 }
-                1 => {
-                    List::new(app.log.iter().map(|event| Text::raw(event.to_string())))
-                        .block(Block::default().title("Logs").borders(Borders::ALL))
-                        .render(&mut frame, header_body[1]);
+                "state" => render_neutron_state(&mut frame, body, app),
+                "presets" => render_presets(&mut frame, body, app),
+                "lfo" => render_lfo_editor(&mut frame, body, app),
+                "glide" => render_autoglide_editor(&mut frame, body, app),
+                "inspect" => render_inspector(&mut frame, body, app),
+                "stats" => render_stats(&mut frame, body, app),
+                "devices" => render_devices(&mut frame, body, app),
+                "librarian" => render_librarian(&mut frame, body, app),
+                "calibrate" => render_calibration(&mut frame, body, app),
+                "logs" => {
+                    List::new(
+                        app.core
+                            .log
+                            .iter()
+                            .map(|event| Text::raw(event.to_string())),
+                    )
+                    .block(
+                        Block::default()
+                            .title(app.locale.text("title.logs"))
+                            .borders(Borders::ALL),
+                    )
+                    .render(&mut frame, body);
                 }
                 _ => {}
             }
+
+            render_status_bar(&mut frame, header_body[2], app);
         })?;
 
         app.tick();
     }
+    app.save_session();
     Ok(())
 }
@@ -0,0 +1,384 @@
+use std::error;
+use std::fmt::Display;
+use std::fs;
+use std::path::PathBuf;
+
+use rustron_lib::protocol::{Channel, GlobalSetting};
+use rustron_lib::setting::SettingId;
+
+use crate::config::Config;
+
+/// A controller on another input driving a setting: `value` 0-127 of the controller is scaled
+/// onto the range of the setting.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CcMapping {
+    pub channel: Channel,
+    pub controller: u8,
+    pub setting: SettingId,
+    /// Soft takeover: the controller is ignored until it reaches the value of the setting, so
+    /// a knob in another position than the setting doesn't make it jump
+    pub takeover: bool,
+    pub curve: Curve,
+}
+
+/// How the value of a controller is shaped before it is scaled onto the setting.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Curve {
+    Linear,
+    /// Finer control of the low values
+    Exponential,
+    /// The setting goes down as the controller goes up
+    Inverted,
+    /// Only the given number of evenly spaced values, at least two
+    Stepped(u8),
+}
+
+impl Curve {
+    /// Parses `linear`, `exponential`, `inverted` or `stepped:<steps>`.
+    pub fn parse(text: &str) -> Option<Curve> {
+        match text {
+            "linear" => Some(Curve::Linear),
+            "exponential" => Some(Curve::Exponential),
+            "inverted" => Some(Curve::Inverted),
+            _ => {
+                let steps: u8 = text.strip_prefix("stepped:")?.parse().ok()?;
+                Some(Curve::Stepped(steps)).filter(|_| (2..=128).contains(&steps))
+            }
+        }
+    }
+
+    /// Shapes a controller value, both 0-127.
+    pub fn apply(self, value: u8) -> u8 {
+        let value = u32::from(value.min(127));
+        let shaped = match self {
+            Curve::Linear => value,
+            Curve::Exponential => value * value / 127,
+            Curve::Inverted => 127 - value,
+            Curve::Stepped(steps) => {
+                let last = u32::from(steps) - 1;
+                let step = (value * last + 63) / 127;
+                (step * 127 + last / 2) / last
+            }
+        };
+        shaped as u8
+    }
+}
+
+impl Display for Curve {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Curve::Linear => write!(f, "linear"),
+            Curve::Exponential => write!(f, "exponential"),
+            Curve::Inverted => write!(f, "inverted"),
+            Curve::Stepped(steps) => write!(f, "stepped:{}", steps),
+        }
+    }
+}
+
+/// Where a controller with soft takeover is, compared to its setting.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Pickup {
+    channel: Channel,
+    controller: u8,
+    /// The value of the controller's last message, scaled onto the setting
+    last: Option<i32>,
+    /// The value last sent for the controller, the setting follows the controller as long as
+    /// it still has this value
+    sent: Option<i32>,
+}
+
+/// Maps control changes from the other inputs to settings, stored in the configuration directory
+/// in the file named by `[mappings] file`, as one `<channel> <controller> = <setting>` line per
+/// mapping, optionally followed by a `Curve` and by `takeover` for soft takeover.
+///
+/// ```text
+/// 1 74 = vcf.mod_depth
+/// 1 71 = lfo.depth exponential takeover
+/// 1 72 = osc1.range stepped:4
+/// ```
+///
+/// In MIDI learn mode the next control change is mapped to the setting being learned instead.
+#[derive(Debug, Default, PartialEq)]
+pub struct CcMap {
+    pub mappings: Vec<CcMapping>,
+    /// The setting waiting for a controller to be moved
+    pub learning: Option<SettingId>,
+    /// Whether learned mappings use soft takeover
+    pub takeover: bool,
+    pickups: Vec<Pickup>,
+}
+
+/// Splits a control change message into its channel, controller and value.
+fn control_change(bytes: &[u8]) -> Option<(Channel, u8, u8)> {
+    match *bytes {
+        [status, controller, value] if status & 0xf0 == 0xb0 && controller < 0x80 => Some((
+            Channel::from_number((status & 0x0f) + 1)?,
+            controller,
+            value,
+        )),
+        _ => None,
+    }
+}
+
+impl CcMap {
+    pub fn path(file: &str) -> Option<PathBuf> {
+        Some(Config::directory()?.join(file))
+    }
+
+    /// Loads the mapping file, empty if there is none yet.
+    pub fn load(file: &str) -> Result<CcMap, Box<dyn error::Error>> {
+        match CcMap::path(file) {
+            Some(path) if path.exists() => CcMap::parse(&fs::read_to_string(path)?),
+            _ => Ok(Default::default()),
+        }
+    }
+
+    pub fn save(&self, file: &str) -> Result<(), Box<dyn error::Error>> {
+        let path = CcMap::path(file).ok_or("Could not determine configuration directory.")?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.to_string())?;
+        Ok(())
+    }
+
+    pub fn parse(input: &str) -> Result<CcMap, Box<dyn error::Error>> {
+        let mut map = CcMap::default();
+        for (number, line) in input.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mapping =
+                parse_mapping(line).map_err(|error| format!("line {}: {}", number + 1, error))?;
+            map.mappings.push(mapping);
+        }
+        Ok(map)
+    }
+
+    /// Maps the next control change to `setting`.
+    pub fn learn(&mut self, setting: SettingId) {
+        self.learning = Some(setting);
+    }
+
+    /// Maps the controller of `bytes` to the setting being learned, replacing what the
+    /// controller was mapped to before. Returns the new mapping, `None` if not learning or
+    /// `bytes` isn't a control change.
+    pub fn learn_from(&mut self, bytes: &[u8]) -> Option<CcMapping> {
+        let setting = self.learning?;
+        let (channel, controller, _) = control_change(bytes)?;
+        self.learning = None;
+        self.mappings
+            .retain(|m| (m.channel, m.controller) != (channel, controller));
+        let mapping = CcMapping {
+            channel,
+            controller,
+            setting,
+            takeover: self.takeover,
+            curve: Curve::Linear,
+        };
+        self.mappings.push(mapping);
+        Some(mapping)
+    }
+
+    /// The setting value `bytes` maps to, if it is a control change of a mapped controller.
+    /// `current` gives the raw value a setting has, for soft takeover: until the controller
+    /// reaches or crosses it, or whenever the setting was changed by anything else since,
+    /// nothing is returned.
+    pub fn translate<F>(&mut self, bytes: &[u8], current: F) -> Option<GlobalSetting>
+    where
+        F: Fn(SettingId) -> Option<i32>,
+    {
+        let (channel, controller, value) = control_change(bytes)?;
+        let mapping = *self
+            .mappings
+            .iter()
+            .find(|m| (m.channel, m.controller) == (channel, controller))?;
+        let range = mapping.setting.range()?;
+        let value = u32::from(mapping.curve.apply(value));
+        let value = i32::from(range.scale(value, 127));
+        if mapping.takeover {
+            let pickup = self.pickup(channel, controller);
+            let last = pickup.last.replace(value);
+            match current(mapping.setting) {
+                Some(current) if pickup.sent != Some(current) => {
+                    let crossed = last.is_some_and(|last| (last < current) != (value < current));
+                    if value != current && !crossed {
+                        return None;
+                    }
+                }
+                _ => {}
+            }
+            pickup.sent = Some(value);
+        }
+        mapping.setting.with_raw_value(value)
+    }
+
+    fn pickup(&mut self, channel: Channel, controller: u8) -> &mut Pickup {
+        let index = match self
+            .pickups
+            .iter()
+            .position(|p| (p.channel, p.controller) == (channel, controller))
+        {
+            Some(index) => index,
+            None => {
+                self.pickups.push(Pickup {
+                    channel,
+                    controller,
+                    last: None,
+                    sent: None,
+                });
+                self.pickups.len() - 1
+            }
+        };
+        &mut self.pickups[index]
+    }
+}
+
+fn parse_mapping(line: &str) -> Result<CcMapping, Box<dyn error::Error>> {
+    let (source, setting) = line
+        .split_once('=')
+        .ok_or_else(|| format!("invalid entry {}", line))?;
+    let mut target = setting.split_whitespace();
+    let setting = target
+        .next()
+        .ok_or_else(|| format!("missing setting {}", line))?;
+    let (mut takeover, mut curve) = (false, Curve::Linear);
+    for option in target {
+        match option {
+            "takeover" => takeover = true,
+            _ => curve = Curve::parse(option).ok_or_else(|| format!("invalid curve {}", option))?,
+        }
+    }
+    let mut source = source.split_whitespace();
+    let (channel, controller) = match (source.next(), source.next(), source.next()) {
+        (Some(channel), Some(controller), None) => (channel, controller),
+        _ => return Err(format!("invalid controller {}", line).into()),
+    };
+    Ok(CcMapping {
+        channel: Channel::from_number(channel.parse()?)
+            .ok_or_else(|| format!("invalid channel {}, expected 1-16", channel))?,
+        controller: Some(controller.parse::<u8>()?)
+            .filter(|controller| *controller < 0x80)
+            .ok_or_else(|| format!("invalid controller {}, expected 0-127", controller))?,
+        setting: SettingId::from_id(setting)
+            .ok_or_else(|| format!("unknown setting {}", setting))?,
+        takeover,
+        curve,
+    })
+}
+
+impl Display for CcMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for mapping in &self.mappings {
+            write!(
+                f,
+                "{} {} = {}",
+                mapping.channel.number(),
+                mapping.controller,
+                mapping.setting.id()
+            )?;
+            if mapping.curve != Curve::Linear {
+                write!(f, " {}", mapping.curve)?;
+            }
+            writeln!(f, "{}", if mapping.takeover { " takeover" } else { "" })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rustron_lib::protocol::GlobalSetting::LfoDepth;
+    use rustron_lib::protocol::{Channel, GlobalSetting, Percent};
+    use rustron_lib::setting::SettingId;
+
+    use crate::mapping::{CcMap, CcMapping, Curve};
+
+    #[test]
+    fn learned_controllers_drive_their_setting() {
+        let mut map = CcMap::default();
+        assert_eq!(map.learn_from(&[0xb0, 0x47, 0x00]), None);
+
+        map.learn(SettingId::LfoDepth);
+        // Notes are ignored while learning
+        assert_eq!(map.learn_from(&[0x90, 0x3c, 0x40]), None);
+        assert_eq!(
+            map.learn_from(&[0xb1, 0x47, 0x10]),
+            Some(CcMapping {
+                channel: Channel::Two,
+                controller: 0x47,
+                setting: SettingId::LfoDepth,
+                takeover: false,
+                curve: Curve::Linear,
+            })
+        );
+        assert_eq!(map.learning, None);
+        assert_eq!(
+            map.translate(&[0xb1, 0x47, 0x7f], |_| Some(0)),
+            Some(LfoDepth(Percent::from_byte(63)))
+        );
+        assert_eq!(map.translate(&[0xb0, 0x47, 0x7f], |_| Some(0)), None);
+
+        // Learning the controller again replaces its mapping
+        map.learn(SettingId::VcfModDepth);
+        map.learn_from(&[0xb1, 0x47, 0x10]);
+        assert_eq!(map.mappings.len(), 1);
+
+        assert_eq!(CcMap::parse(&map.to_string()).unwrap(), map);
+    }
+
+    #[test]
+    fn soft_takeover_waits_for_the_controller_to_reach_the_setting() {
+        let mut map = CcMap::parse("1 71 = lfo.depth takeover\n").unwrap();
+        assert!(map.mappings[0].takeover);
+        assert_eq!(CcMap::parse(&map.to_string()).unwrap(), map);
+        let depth = |value: u8| [0xb0, 71, value];
+        let translated = |setting: Option<GlobalSetting>| setting.and_then(|s| s.raw_value());
+        let current = LfoDepth(Percent::from_byte(32)).raw_value();
+
+        // Below the setting, then crossing it
+        assert_eq!(map.translate(&depth(10), |_| current), None);
+        assert_eq!(map.translate(&depth(40), |_| current), None);
+        let value = translated(map.translate(&depth(80), |_| current));
+        assert!(value > current);
+        // Followed while the setting keeps the sent value
+        assert!(translated(map.translate(&depth(20), |_| value)).is_some());
+        // Changed elsewhere, so picked up again first
+        assert_eq!(map.translate(&depth(30), |_| Some(100)), None);
+        // Unknown values can't be jumped away from
+        assert!(map.translate(&depth(30), |_| None).is_some());
+    }
+
+    #[test]
+    fn curves_shape_the_controller() {
+        assert_eq!(Curve::Exponential.apply(64), 32);
+        assert_eq!(Curve::Exponential.apply(127), 127);
+        assert_eq!(Curve::Inverted.apply(0), 127);
+        let stepped: Vec<u8> = [0, 20, 22, 64, 110, 127]
+            .iter()
+            .map(|value| Curve::Stepped(4).apply(*value))
+            .collect();
+        assert_eq!(stepped, [0, 0, 42, 85, 127, 127]);
+
+        let mut map =
+            CcMap::parse("1 71 = lfo.depth inverted\n2 72 = lfo.depth stepped:4 takeover\n")
+                .unwrap();
+        assert_eq!(map.mappings[1].curve, Curve::Stepped(4));
+        assert_eq!(CcMap::parse(&map.to_string()).unwrap(), map);
+        assert_eq!(
+            map.translate(&[0xb0, 71, 0x7f], |_| None),
+            Some(LfoDepth(Percent::from_byte(0)))
+        );
+    }
+
+    #[test]
+    fn invalid_entries_are_rejected() {
+        assert!(CcMap::parse("17 74 = lfo.depth\n").is_err());
+        assert!(CcMap::parse("1 128 = lfo.depth\n").is_err());
+        assert!(CcMap::parse("1 74 = lfo.width\n").is_err());
+        assert!(CcMap::parse("1 = lfo.depth\n").is_err());
+        assert!(CcMap::parse("1 74 = lfo.depth stepped:1\n").is_err());
+        assert!(CcMap::parse("1 74 = lfo.depth wobbly\n").is_err());
+    }
+}
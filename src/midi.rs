@@ -1,70 +1,934 @@
+use std::collections::VecDeque;
 use std::error;
-use std::sync::mpsc::Sender;
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError, TrySendError};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
 
+#[cfg(unix)]
+use midir::os::unix::VirtualOutput;
 use midir::{
     MidiInput, MidiInputConnection, MidiOutput, MidiOutputConnection, PortInfoError, SendError,
 };
 
+use rustron_lib::parser::neutron_message;
+use rustron_lib::protocol::NeutronMessage;
+use rustron_lib::protocol::NeutronMessage::SetGlobalSetting;
+
+#[cfg(feature = "ble")]
+use crate::ble::BleConnection;
+use crate::emulator::Emulator;
+#[cfg(feature = "jack")]
+use crate::jack::JackConnection;
+
+/// The name of rustron's ALSA sequencer clients, and of their ports, as `aconnect -l` and
+/// patchbays like qjackctl show them.
+pub const CLIENT_NAME: &str = "rustron";
+
+/// The port mirroring the messages exchanged with the Neutron, see `MidiConnection::open_monitor`.
+pub const MONITOR_PORT: &str = "rustron monitor";
+
+/// How many received messages may wait to be handled before the oldest are dropped.
+pub const INPUT_QUEUE_CAPACITY: usize = 1024;
+
+/// The longest SysEx message put back together from chunks by default, see `InputQueue`.
+pub const DEFAULT_MAX_SYSEX: usize = 65536;
+
+const SYSEX_START: u8 = 0xf0;
+const SYSEX_END: u8 = 0xf7;
+
+/// How many queued messages the rate limiter sends at once when a tick came late.
+const MAX_BURST: u32 = 8;
+
+/// How many messages may wait to be sent to an output before new ones are dropped.
+pub const SEND_QUEUE_CAPACITY: usize = 256;
+
+/// How long to wait before connecting again to an output that couldn't be connected, doubling
+/// with each failed attempt up to `MAX_RECONNECT_BACKOFF`.
+const RECONNECT_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_RECONNECT_BACKOFF: Duration = Duration::from_secs(30);
+
+/// The input port a message was received on.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Source {
+    Neutron,
+    /// Any other port listened on, by name
+    Port(Arc<str>),
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::Neutron => write!(f, "Neutron"),
+            Source::Port(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+struct InputBuffer {
+    messages: VecDeque<(Source, Vec<u8>)>,
+    dropped: usize,
+    /// The SysEx messages whose end hasn't been received yet, by port
+    partial: Vec<(Source, Vec<u8>)>,
+    /// The ports whose SysEx message was dropped as too long before its end, whose following
+    /// chunks are discarded up to the end
+    discarding: Vec<Source>,
+}
+
+impl InputBuffer {
+    /// Joins the chunks of a SysEx message, returning the message once complete. Other messages
+    /// are returned as they are, real-time ones even in the middle of a SysEx message. A SysEx
+    /// message interrupted by another message or longer than `max_sysex` is dropped, along with the
+    /// rest of its chunks.
+    fn assemble(
+        &mut self,
+        source: Source,
+        bytes: Vec<u8>,
+        max_sysex: usize,
+    ) -> Option<(Source, Vec<u8>)> {
+        let status = bytes.first().copied().unwrap_or(0);
+        if status >= 0xf8 {
+            return Some((source, bytes));
+        }
+        if let Some(index) = self.discarding.iter().position(|port| *port == source) {
+            if status < 0x80 || status == SYSEX_END {
+                if bytes.last() == Some(&SYSEX_END) {
+                    self.discarding.swap_remove(index);
+                }
+                return None;
+            }
+            self.discarding.swap_remove(index);
+        }
+        let mut message = bytes;
+        if let Some(index) = self.partial.iter().position(|(port, _)| *port == source) {
+            let (_, mut partial) = self.partial.swap_remove(index);
+            if status < 0x80 || status == SYSEX_END {
+                partial.append(&mut message);
+                message = partial;
+            } else {
+                self.dropped += 1;
+            }
+        }
+        if message.len() > max_sysex {
+            self.dropped += 1;
+            if message.first() == Some(&SYSEX_START) && message.last() != Some(&SYSEX_END) {
+                self.discarding.push(source);
+            }
+            return None;
+        }
+        if message.first() == Some(&SYSEX_START) && message.last() != Some(&SYSEX_END) {
+            self.partial.push((source, message));
+            return None;
+        }
+        Some((source, message))
+    }
+}
+
+/// Messages received by the MIDI input callback, waiting to be handled. Bounded so a stalled
+/// frontend can't make it grow without limit: when full the oldest message is dropped and
+/// counted. Pushing never panics, so the callback thread of the MIDI backend stays alive.
+///
+/// Some backends deliver a long SysEx message in chunks, like midir's WinMM backend does with
+/// messages longer than its fixed 1024 byte buffers, so state dumps would arrive cut up. The
+/// chunks are put back together here, whatever the backend, up to `max_sysex` bytes.
+#[derive(Clone)]
+pub struct InputQueue {
+    buffer: Arc<Mutex<InputBuffer>>,
+    capacity: usize,
+    max_sysex: usize,
+}
+
+impl InputQueue {
+    pub fn new(capacity: usize) -> InputQueue {
+        InputQueue::with_max_sysex(capacity, DEFAULT_MAX_SYSEX)
+    }
+
+    pub fn with_max_sysex(capacity: usize, max_sysex: usize) -> InputQueue {
+        InputQueue {
+            buffer: Arc::new(Mutex::new(InputBuffer {
+                messages: VecDeque::new(),
+                dropped: 0,
+                partial: Vec::new(),
+                discarding: Vec::new(),
+            })),
+            capacity,
+            max_sysex,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, InputBuffer> {
+        // The buffer stays consistent even if a holder of the lock panicked
+        self.buffer.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn push(&self, source: Source, message: Vec<u8>) {
+        let mut buffer = self.lock();
+        let (source, message) = match buffer.assemble(source, message, self.max_sysex) {
+            Some(complete) => complete,
+            None => return,
+        };
+        while buffer.messages.len() >= self.capacity.max(1) {
+            buffer.messages.pop_front();
+            buffer.dropped += 1;
+        }
+        buffer.messages.push_back((source, message));
+    }
+
+    pub fn pop(&self) -> Option<(Source, Vec<u8>)> {
+        self.lock().messages.pop_front()
+    }
+
+    /// How many messages were dropped because the queue was full, or as incomplete or too long
+    /// SysEx messages.
+    pub fn dropped(&self) -> usize {
+        self.lock().dropped
+    }
+}
+
+/// Progress of connecting to an output, reported by `MidiConnection::poll_connections`.
+#[derive(Debug, PartialEq)]
+pub enum ConnectionEvent {
+    Connecting(String),
+    Connected(String),
+    /// The port and why it couldn't be connected
+    Failed(String, String),
+    /// The port and why a message couldn't be sent to it
+    SendFailed(String, String),
+}
+
+impl Display for ConnectionEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConnectionEvent::Connecting(port) => write!(f, "Connecting to {}", port),
+            ConnectionEvent::Connected(port) => write!(f, "Connected to {}", port),
+            ConnectionEvent::Failed(port, reason) => {
+                write!(f, "Could not connect to {}: {}", port, reason)
+            }
+            ConnectionEvent::SendFailed(port, reason) => {
+                write!(f, "Could not send to {}: {}", port, reason)
+            }
+        }
+    }
+}
+
+/// The outcome of sending a message on a port's thread, see `MidiConnection::send_to_neutron`.
+pub type Delivery = Receiver<Result<(), String>>;
+
+/// A message waiting for a `SendWorker`.
+struct Queued {
+    message: Vec<u8>,
+    /// The worker's generation when it was queued, it is dropped once the worker was flushed
+    generation: usize,
+    /// Where to report the outcome if anything waits for it
+    delivered: Option<mpsc::Sender<Result<(), String>>>,
+}
+
+/// Sends messages to a port on its own thread, so a MIDI driver blocking on a send doesn't stall
+/// the frontend. The queue is bounded: when the port can't keep up, new messages are refused
+/// instead of piling up.
+struct SendWorker {
+    sender: SyncSender<Queued>,
+    /// Messages queued and not sent yet
+    depth: Arc<AtomicUsize>,
+    /// Counts the flushes, see `flush`
+    generation: Arc<AtomicUsize>,
+    errors: Receiver<String>,
+    /// The buffers of sent messages, handed back to be reused for the next ones
+    recycled: Receiver<Vec<u8>>,
+    /// Cleared once the port is gone, for transports that notice, see `linked`
+    link: Arc<AtomicBool>,
+}
+
+impl SendWorker {
+    fn spawn<F>(capacity: usize, mut send: F) -> SendWorker
+    where
+        F: FnMut(&[u8]) -> Result<(), String> + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::sync_channel::<Queued>(capacity);
+        let (error_sender, errors) = mpsc::channel();
+        let (recycler, recycled) = mpsc::sync_channel(capacity);
+        let depth = Arc::new(AtomicUsize::new(0));
+        let worker_depth = Arc::clone(&depth);
+        let generation = Arc::new(AtomicUsize::new(0));
+        let worker_generation = Arc::clone(&generation);
+        thread::spawn(move || {
+            // Ends once the destination, holding the sender, is gone
+            for queued in receiver {
+                let result = if queued.generation == worker_generation.load(Ordering::SeqCst) {
+                    let result = send(&queued.message);
+                    // Reported before the message stops counting, so once the queue is empty
+                    // all of its errors can be polled
+                    if let Err(error) = &result {
+                        let _ = error_sender.send(error.clone());
+                    }
+                    result
+                } else {
+                    Err(String::from("Flushed from the send queue."))
+                };
+                if let Some(delivered) = queued.delivered {
+                    let _ = delivered.send(result);
+                }
+                // Buffers beyond what the queue can hold are dropped
+                let _ = recycler.try_send(queued.message);
+                worker_depth.fetch_sub(1, Ordering::SeqCst);
+            }
+        });
+        SendWorker {
+            sender,
+            depth,
+            generation,
+            errors,
+            recycled,
+            link: Arc::new(AtomicBool::new(true)),
+        }
+    }
+
+    /// Follows `link` to tell whether the port is still there.
+    #[cfg(feature = "ble")]
+    fn linked(self, link: Arc<AtomicBool>) -> SendWorker {
+        SendWorker { link, ..self }
+    }
+
+    fn is_linked(&self) -> bool {
+        self.link.load(Ordering::SeqCst)
+    }
+
+    fn send(&self, message: &[u8]) -> Result<(), Box<dyn error::Error>> {
+        self.queue(message, None)
+    }
+
+    /// Queues `message`, the outcome of sending it is reported to `delivered`.
+    fn send_confirmed(&self, message: &[u8]) -> Result<Delivery, Box<dyn error::Error>> {
+        let (delivered, delivery) = mpsc::channel();
+        self.queue(message, Some(delivered))?;
+        Ok(delivery)
+    }
+
+    fn queue(
+        &self,
+        message: &[u8],
+        delivered: Option<mpsc::Sender<Result<(), String>>>,
+    ) -> Result<(), Box<dyn error::Error>> {
+        // Counted before queueing, so the worker never takes away more than was added
+        self.depth.fetch_add(1, Ordering::SeqCst);
+        let mut buffer = self.recycled.try_recv().unwrap_or_default();
+        buffer.clear();
+        buffer.extend_from_slice(message);
+        let queued = Queued {
+            message: buffer,
+            generation: self.generation.load(Ordering::SeqCst),
+            delivered,
+        };
+        self.sender.try_send(queued).map_err(|error| {
+            self.depth.fetch_sub(1, Ordering::SeqCst);
+            match error {
+                TrySendError::Full(_) => "Send queue is full, message dropped.".into(),
+                TrySendError::Disconnected(_) => "Sending stopped.".into(),
+            }
+        })
+    }
+
+    fn depth(&self) -> usize {
+        self.depth.load(Ordering::SeqCst)
+    }
+
+    /// Drops the queued messages instead of sending them, the one being sent still goes out.
+    fn flush(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// The errors of the sends done since the last call.
+    fn errors(&self) -> Vec<String> {
+        self.errors.try_iter().collect()
+    }
+}
+
+/// Connects to the port with the given name on a destination's connecting thread.
+type Connector = Arc<dyn Fn(&str) -> Result<SendWorker, String> + Send + Sync>;
+
+/// An output port commands are sent to. Connecting happens on a separate thread since it can
+/// take a while, messages sent meanwhile wait for it.
+pub struct Destination {
+    /// The beginning of the port name
+    pub name: String,
+    pub enabled: bool,
+    connection: Option<Output>,
+    /// How to connect, to an ALSA port unless another transport is used
+    connector: Connector,
+    /// The result of the connection attempt in progress
+    connecting: Option<Receiver<Result<SendWorker, String>>>,
+    /// Messages sent while connecting
+    waiting: Vec<Vec<u8>>,
+    /// When to connect again after the connection failed or went away
+    retry_at: Option<Instant>,
+    /// How long to wait after the next failed attempt
+    backoff: Duration,
+}
+
+/// Where a destination's messages go.
+enum Output {
+    Port(SendWorker),
+    /// The emulated Neutron when simulating
+    Emulator(Emulator),
+    /// The Neutron through JACK, see `MidiConnection::jack`
+    #[cfg(feature = "jack")]
+    Jack(JackConnection),
+}
+
+impl Destination {
+    fn new(name: &str) -> Destination {
+        Destination {
+            name: name.to_string(),
+            enabled: true,
+            connection: None,
+            connector: Arc::new(connect_port),
+            connecting: None,
+            waiting: Vec::new(),
+            retry_at: None,
+            backoff: RECONNECT_BACKOFF,
+        }
+    }
+
+    fn emulated(emulator: Emulator) -> Destination {
+        Destination {
+            connection: Some(Output::Emulator(emulator)),
+            ..Destination::new(SIMULATED_PORT)
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connection.is_some()
+    }
+
+    pub fn is_connecting(&self) -> bool {
+        self.connecting.is_some()
+    }
+
+    /// How many messages wait to be sent, including those waiting for the connection.
+    pub fn queued(&self) -> usize {
+        match &self.connection {
+            Some(Output::Port(worker)) => worker.depth(),
+            _ => self.waiting.len(),
+        }
+    }
+
+    /// Drops the messages waiting to be sent, returning how many were queued including one the
+    /// port may be sending.
+    fn flush(&mut self) -> usize {
+        let queued = self.queued();
+        if let Some(Output::Port(worker)) = &self.connection {
+            worker.flush();
+        }
+        self.waiting.clear();
+        queued
+    }
+
+    /// Whether connecting is due at `now`: the output is enabled and neither connected, nor
+    /// connecting nor waiting to retry.
+    fn connect_due(&self, now: Instant) -> bool {
+        self.enabled
+            && !self.is_connected()
+            && !self.is_connecting()
+            && self.retry_at.is_none_or(|retry_at| now >= retry_at)
+    }
+
+    /// Waits before connecting again, longer after each failed attempt.
+    fn retry_later(&mut self) {
+        self.retry_at = Some(Instant::now() + self.backoff);
+        self.backoff = (self.backoff * 2).min(MAX_RECONNECT_BACKOFF);
+    }
+
+    fn start_connecting(&mut self) {
+        let (sender, receiver) = mpsc::channel();
+        let (name, connector) = (self.name.clone(), Arc::clone(&self.connector));
+        thread::spawn(move || {
+            // The destination may be gone by the time the port is connected
+            let _ = sender.send(connector(&name));
+        });
+        self.connecting = Some(receiver);
+    }
+
+    /// Reports failed sends, a port that is gone, and the connection attempt in progress if it is
+    /// done.
+    fn poll(&mut self) -> Vec<ConnectionEvent> {
+        let mut events: Vec<ConnectionEvent> = match &self.connection {
+            Some(Output::Port(worker)) => worker
+                .errors()
+                .into_iter()
+                .map(|error| ConnectionEvent::SendFailed(self.name.clone(), error))
+                .collect(),
+            _ => Vec::new(),
+        };
+        if matches!(&self.connection, Some(Output::Port(worker)) if !worker.is_linked()) {
+            self.connection = None;
+            self.retry_later();
+            events.push(ConnectionEvent::Failed(
+                self.name.clone(),
+                String::from("disconnected"),
+            ));
+        }
+        events.extend(self.finish_connecting());
+        events
+    }
+
+    /// Finishes the connection attempt in progress if it is done, sending the waiting messages.
+    fn finish_connecting(&mut self) -> Option<ConnectionEvent> {
+        let result = match self.connecting.as_ref()?.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => Err(String::from("connecting stopped")),
+        };
+        self.connecting = None;
+        let waiting = std::mem::take(&mut self.waiting);
+        match result {
+            Ok(worker) => {
+                self.connection = Some(Output::Port(worker));
+                self.retry_at = None;
+                self.backoff = RECONNECT_BACKOFF;
+                for message in waiting {
+                    if let Err(error) = self.send(&message) {
+                        return Some(ConnectionEvent::Failed(
+                            self.name.clone(),
+                            error.to_string(),
+                        ));
+                    }
+                }
+                Some(ConnectionEvent::Connected(self.name.clone()))
+            }
+            Err(reason) => {
+                self.retry_later();
+                Some(ConnectionEvent::Failed(self.name.clone(), reason))
+            }
+        }
+    }
+
+    fn send(&mut self, message: &[u8]) -> Result<(), Box<dyn error::Error>> {
+        match &mut self.connection {
+            Some(Output::Port(worker)) => worker.send(message),
+            Some(Output::Emulator(emulator)) => {
+                emulator.receive(message);
+                Ok(())
+            }
+            #[cfg(feature = "jack")]
+            Some(Output::Jack(jack)) => jack.send(message),
+            None if self.connecting.is_some() => {
+                self.waiting.push(message.to_vec());
+                Ok(())
+            }
+            None => Err(Box::new(SendError::Other("No connection established."))),
+        }
+    }
+
+    /// Sends `message` like `send`, with the outcome reported once the port's thread sent it.
+    /// Nothing waits for a connection, the message fails right away without one.
+    fn send_confirmed(&mut self, message: &[u8]) -> Result<Delivery, Box<dyn error::Error>> {
+        if let Some(Output::Port(worker)) = &self.connection {
+            return worker.send_confirmed(message);
+        }
+        if self.connection.is_none() {
+            return Err(Box::new(SendError::Other("No connection established.")));
+        }
+        // The other outputs are done with the message once they return
+        self.send(message)?;
+        let (delivered, delivery) = mpsc::channel();
+        let _ = delivered.send(Ok(()));
+        Ok(delivery)
+    }
+}
+
+fn connect_output(name: &str) -> Result<MidiOutputConnection, Box<dyn error::Error>> {
+    let output = MidiOutput::new(CLIENT_NAME)?;
+    let port_number =
+        find_port(&output, name).ok_or_else(|| format!("Could not find MIDI output {}.", name))?;
+    Ok(output.connect(port_number, CLIENT_NAME)?)
+}
+
+/// Connects to the ALSA port `name`, the `Connector` of destinations by default.
+fn connect_port(name: &str) -> Result<SendWorker, String> {
+    let mut connection = connect_output(name).map_err(|error| error.to_string())?;
+    Ok(SendWorker::spawn(SEND_QUEUE_CAPACITY, move |message| {
+        connection.send(message).map_err(|error| error.to_string())
+    }))
+}
+
+/// Name of the emulated Neutron's port when simulating.
+const SIMULATED_PORT: &str = "Neutron (simulated)";
+
 pub struct MidiConnection {
     // TODO what about closing connections?
-    midi_out: Option<MidiOutputConnection>,
-    midi_in: Option<MidiInputConnection<()>>,
+    /// The Neutron followed by any other destinations
+    outputs: Vec<Destination>,
+    /// The Neutron input followed by any other ports listened on
+    midi_in: Vec<MidiInputConnection<()>>,
+    /// The beginning of the Neutron's port names
+    neutron_port: String,
+    /// Reused when encoding messages, to not allocate for every message sent
+    scratch: Vec<u8>,
+    /// Connection attempts started since the last `poll_connections`
+    events: Vec<ConnectionEvent>,
+    /// The monitor port, see `open_monitor`
+    monitor: Option<SendWorker>,
 }
 
 impl MidiConnection {
-    pub fn new() -> MidiConnection {
+    /// A connection to the Neutron on the ports whose names start with `neutron_port`.
+    pub fn new(neutron_port: &str) -> MidiConnection {
         MidiConnection {
-            midi_out: None,
-            midi_in: None,
+            outputs: vec![Destination::new(neutron_port)],
+            midi_in: Vec::new(),
+            neutron_port: neutron_port.to_string(),
+            scratch: Vec::with_capacity(NeutronMessage::MAX_LEN),
+            events: Vec::new(),
+            monitor: None,
         }
     }
 
-    fn connect_midi_out(&mut self) -> Result<(), Box<dyn error::Error>> {
-        match MidiOutput::new("Neutron") {
-            Ok(output) => {
-                let out_port = get_neutron_port(&output);
-                out_port.and_then(|port_number| {
-                    self.midi_out = output.connect(port_number, "neutron").ok();
-                    Ok(())
-                })
+    /// A connection to an emulated Neutron instead of any MIDI port.
+    pub fn simulated(emulator: Emulator) -> MidiConnection {
+        let mut connection = MidiConnection::new(SIMULATED_PORT);
+        connection.outputs = vec![Destination::emulated(emulator)];
+        connection
+    }
+
+    /// A connection to the Neutron through the ports of a JACK client instead of the ALSA ports,
+    /// receiving into `queue`, see `JackConnection::open`.
+    #[cfg(feature = "jack")]
+    pub fn jack(
+        neutron_port: &str,
+        queue: InputQueue,
+    ) -> Result<MidiConnection, Box<dyn error::Error>> {
+        let jack = JackConnection::open(neutron_port, queue)?;
+        let mut connection = MidiConnection::new(neutron_port);
+        connection.outputs[0].connection = Some(Output::Jack(jack));
+        Ok(connection)
+    }
+
+    /// A connection to the Neutron through a BLE MIDI adapter whose name starts with `name`,
+    /// receiving into `queue`, see `BleConnection::open`. It connects like any destination, in
+    /// the background and again once the adapter disconnected.
+    #[cfg(feature = "ble")]
+    pub fn ble(name: &str, queue: InputQueue) -> MidiConnection {
+        let mut connection = MidiConnection::new(name);
+        connection.outputs[0].connector = Arc::new(move |name: &str| {
+            let mut ble = BleConnection::open(name, queue.clone()).map_err(|e| e.to_string())?;
+            let link = ble.link();
+            Ok(
+                SendWorker::spawn(SEND_QUEUE_CAPACITY, move |message| ble.send(message))
+                    .linked(link),
+            )
+        });
+        connection
+    }
+
+    pub fn outputs(&self) -> &[Destination] {
+        &self.outputs
+    }
+
+    /// Also sends every command to the first port whose name starts with `name`.
+    pub fn add_output(&mut self, name: &str) {
+        self.outputs.push(Destination::new(name));
+    }
+
+    /// Starts connecting to the enabled outputs that aren't connected, without waiting for it.
+    /// Outputs that failed to connect are retried once their backoff passed at `now`.
+    pub fn connect_outputs(&mut self, now: Instant) {
+        for output in &mut self.outputs {
+            if output.connect_due(now) {
+                self.events
+                    .push(ConnectionEvent::Connecting(output.name.clone()));
+                output.start_connecting();
             }
-            Err(error) => Err(Box::new(error)),
         }
     }
 
-    pub fn register_midi_in_channel(
+    /// What happened to the connection attempts since the last call.
+    pub fn poll_connections(&mut self) -> Vec<ConnectionEvent> {
+        let mut events = std::mem::take(&mut self.events);
+        events.extend(self.outputs.iter_mut().flat_map(Destination::poll));
+        events
+    }
+
+    /// Drops what waits to be sent to the outputs, so what is sent next goes out right away.
+    /// Returns about how many messages were dropped, see `Destination::flush`.
+    pub fn flush(&mut self) -> usize {
+        self.outputs.iter_mut().map(Destination::flush).sum()
+    }
+
+    /// Enables or disables sending to the output at `index`, returns its new state.
+    pub fn toggle_output(&mut self, index: usize) -> Option<bool> {
+        let output = self.outputs.get_mut(index)?;
+        output.enabled = !output.enabled;
+        Some(output.enabled)
+    }
+
+    pub fn register_midi_in(&mut self, queue: InputQueue) -> Result<(), Box<dyn error::Error>> {
+        let input = MidiInput::new(CLIENT_NAME)?;
+        let port_number = find_port(&input, &self.neutron_port)
+            .ok_or_else(|| format!("Could not find {}.", self.neutron_port))?;
+        let connection = input.connect(
+            port_number,
+            CLIENT_NAME,
+            move |_, msg, _| queue.push(Source::Neutron, msg.to_vec()),
+            (),
+        )?;
+        self.midi_in.push(connection);
+        Ok(())
+    }
+
+    /// Also receives from the first port whose name starts with `name`, other than the Neutron
+    /// and rustron's own ports. Returns the full name of the port.
+    pub fn listen(
         &mut self,
-        message_sender_channel: Sender<Vec<u8>>,
-    ) -> Result<(), Box<dyn error::Error>> {
-        let input = MidiInput::new("Neutron").unwrap();
-        let in_port = get_neutron_port(&input);
-
-        in_port.and_then(|port_number| {
-            self.midi_in = input
-                .connect(
-                    port_number,
-                    "neutron",
-                    move |_, msg, _| {
-                        // TODO panic on Err for now
-                        message_sender_channel.send(msg.to_vec()).unwrap();
-                    },
-                    (),
-                )
-                .ok();
+        name: &str,
+        queue: InputQueue,
+    ) -> Result<String, Box<dyn error::Error>> {
+        let input = MidiInput::new(CLIENT_NAME)?;
+        let port = (0..input.port_count())
+            .filter_map(|i| input.port_name(i).ok().map(|port_name| (i, port_name)))
+            .find(|(_, port_name)| {
+                port_name.starts_with(name)
+                    && !port_name.starts_with(&self.neutron_port)
+                    && !port_name.starts_with(CLIENT_NAME)
+            });
+        let (port_number, port_name) =
+            port.ok_or_else(|| format!("Could not find MIDI input {}.", name))?;
+        let source = Source::Port(Arc::from(port_name.as_str()));
+        let connection = input.connect(
+            port_number,
+            CLIENT_NAME,
+            move |_, msg, _| queue.push(source.clone(), msg.to_vec()),
+            (),
+        )?;
+        self.midi_in.push(connection);
+        Ok(port_name)
+    }
+
+    /// Opens the `rustron monitor` port, which other applications can subscribe to to watch what
+    /// is sent to and received from the Neutron. Being a virtual output it is readable and
+    /// subscribable, unlike the ports connected to the Neutron. Virtual ports only exist on Unix.
+    #[cfg(unix)]
+    pub fn open_monitor(&mut self) -> Result<(), Box<dyn error::Error>> {
+        let mut port = MidiOutput::new(CLIENT_NAME)?.create_virtual(MONITOR_PORT)?;
+        self.monitor = Some(SendWorker::spawn(SEND_QUEUE_CAPACITY, move |message| {
+            port.send(message).map_err(|error| error.to_string())
+        }));
+        Ok(())
+    }
+
+    /// Mirrors `message` to the monitor port if it is open. Nothing waits for the monitor, what
+    /// it can't keep up with is left out.
+    pub fn monitor(&self, message: &[u8]) {
+        if let Some(monitor) = &self.monitor {
+            let _ = monitor.send(message);
+        }
+    }
+
+    /// Encodes and sends `message` without allocating a new buffer for it.
+    pub fn send(&mut self, message: &NeutronMessage) -> Result<(), Box<dyn error::Error>> {
+        let mut scratch = std::mem::take(&mut self.scratch);
+        scratch.clear();
+        message.write_to(&mut scratch);
+        let result = self.send_message(&scratch);
+        self.scratch = scratch;
+        result
+    }
+
+    /// Sends `message` to the Neutron alone, for what no other output should get, like firmware.
+    /// The returned `Delivery` reports when the message was actually sent.
+    pub fn send_to_neutron(&mut self, message: &[u8]) -> Result<Delivery, Box<dyn error::Error>> {
+        let neutron = &mut self.outputs[0];
+        if !neutron.enabled {
+            return Err(format!("Sending to {} is disabled.", neutron.name).into());
+        }
+        let delivery = neutron.send_confirmed(message)?;
+        self.monitor(message);
+        Ok(delivery)
+    }
+
+    /// Sends `message` to every enabled output, reporting the outputs it couldn't be sent to.
+    /// Outputs still connecting get the message once connected.
+    pub fn send_message(&mut self, message: &[u8]) -> Result<(), Box<dyn error::Error>> {
+        self.monitor(message);
+        let errors: Vec<String> = self
+            .outputs
+            .iter_mut()
+            .filter(|output| output.enabled)
+            .filter_map(|output| output.send(message).err())
+            .map(|error| error.to_string())
+            .collect();
+        if errors.is_empty() {
             Ok(())
+        } else {
+            Err(errors.join(" ").into())
+        }
+    }
+}
+
+/// All Sound Off followed by All Notes Off on every MIDI channel, silencing whatever channel the
+/// Neutron listens on.
+pub fn panic_messages() -> Vec<Vec<u8>> {
+    const ALL_SOUND_OFF: u8 = 120;
+    const ALL_NOTES_OFF: u8 = 123;
+    (0..16)
+        .flat_map(|channel| {
+            [ALL_SOUND_OFF, ALL_NOTES_OFF]
+                .iter()
+                .map(move |controller| vec![0xb0 | channel, *controller, 0])
         })
+        .collect()
+}
+
+/// Decides when to request the state from the Neutron, like the official app does about once
+/// per second.
+pub struct StatePoller {
+    pub enabled: bool,
+    pub interval: Duration,
+    last_poll: Option<Instant>,
+    /// The first poll not answered yet
+    unanswered_since: Option<Instant>,
+}
+
+impl StatePoller {
+    pub fn new(enabled: bool, interval: Duration) -> StatePoller {
+        StatePoller {
+            enabled,
+            interval,
+            last_poll: None,
+            unanswered_since: None,
+        }
     }
 
-    pub fn send_message(&mut self, message: &[u8]) -> Result<(), Box<dyn error::Error>> {
-        if self.midi_out.is_none() {
-            if let Err(error) = self.connect_midi_out() {
-                return Err(error);
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        self.last_poll = None;
+        self.unanswered_since = None;
+    }
+
+    /// Records that the Neutron answered a poll.
+    pub fn answered(&mut self) {
+        self.unanswered_since = None;
+    }
+
+    /// How long polls have gone unanswered at `now`, `None` if the last one was answered.
+    pub fn unanswered_for(&self, now: Instant) -> Option<Duration> {
+        Some(now.saturating_duration_since(self.unanswered_since?))
+    }
+
+    /// Starts counting from `now` again, e.g. after reconnecting.
+    pub fn restart_count(&mut self, now: Instant) {
+        if self.unanswered_since.is_some() {
+            self.unanswered_since = Some(now);
+        }
+    }
+
+    /// Returns `true` if a state request should be sent at `now`.
+    pub fn poll(&mut self, now: Instant) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        match self.last_poll {
+            Some(last) if now.duration_since(last) < self.interval => false,
+            _ => {
+                self.last_poll = Some(now);
+                self.unanswered_since.get_or_insert(now);
+                true
             }
         }
-        match &mut self.midi_out {
-            Some(out) => out.send(message).map_err(|e| Box::new(e).into()),
-            None => Err(Box::new(SendError::Other("No connection established."))),
+    }
+}
+
+/// Spaces outgoing messages at least `interval` apart so continuous edits don't flood the Neutron.
+/// While messages wait, a newer value for a waiting setting replaces the older one in place.
+pub struct RateLimiter {
+    pub interval: Duration,
+    /// Holds back everything while set, e.g. while the Neutron doesn't respond
+    pub paused: bool,
+    /// Settings replaced by a newer value before they were sent
+    pub coalesced: usize,
+    queue: VecDeque<Vec<u8>>,
+    /// When the next message may be sent, `None` when it may be sent right away
+    next_send: Option<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(interval: Duration) -> RateLimiter {
+        RateLimiter {
+            interval,
+            paused: false,
+            coalesced: 0,
+            queue: VecDeque::new(),
+            next_send: None,
+        }
+    }
+
+    pub fn pending(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Drops the queued messages, returns how many there were.
+    pub fn clear(&mut self) -> usize {
+        let count = self.queue.len();
+        self.queue.clear();
+        count
+    }
+
+    fn is_due(&self, now: Instant) -> bool {
+        self.next_send.is_none_or(|next| now >= next)
+    }
+
+    /// When the next queued message is due, `None` if there is none or the limiter is paused.
+    pub fn next_due(&self) -> Option<Instant> {
+        if self.paused || self.queue.is_empty() {
+            return None;
         }
+        Some(self.next_send.unwrap_or_else(Instant::now))
+    }
+
+    /// Returns whether `message` can be sent right away at `now`, otherwise queues a copy of it.
+    pub fn submit(&mut self, message: &[u8], now: Instant) -> bool {
+        if !self.paused && self.queue.is_empty() && self.is_due(now) {
+            self.next_send = Some(now + self.interval);
+            return true;
+        }
+        if let Ok((_, SetGlobalSetting(device, setting))) = neutron_message(message) {
+            let waiting = self.queue.iter_mut().find(|queued| {
+                matches!(
+                    neutron_message(queued),
+                    Ok((_, SetGlobalSetting(d, s))) if d == device && s.id() == setting.id()
+                )
+            });
+            if let Some(waiting) = waiting {
+                waiting.clear();
+                waiting.extend_from_slice(message);
+                self.coalesced += 1;
+                return false;
+            }
+        }
+        self.queue.push_back(message.to_vec());
+        false
+    }
+
+    /// Returns the next queued message if it is due at `now`. Called until it returns `None`, it
+    /// returns every message that fell due since the last call, so a tick slower than the
+    /// interval doesn't slow sending down. After a stall no more than `MAX_BURST` go at once.
+    pub fn poll(&mut self, now: Instant) -> Option<Vec<u8>> {
+        if self.paused {
+            // Sending starts over once resumed
+            self.next_send = None;
+            return None;
+        }
+        if self.queue.is_empty() || !self.is_due(now) {
+            return None;
+        }
+        let due = self.next_send.unwrap_or(now);
+        let due = match now.checked_sub(self.interval * MAX_BURST) {
+            Some(earliest) => due.max(earliest),
+            None => due,
+        };
+        self.next_send = Some(due + self.interval);
+        self.queue.pop_front()
     }
 }
 
@@ -93,16 +957,376 @@ impl Neutron for MidiInput {
     }
 }
 
-fn get_neutron_port(midi_output: &dyn Neutron) -> Result<usize, Box<dyn error::Error>> {
-    let mut out_port: Option<usize> = None;
-    for i in 0..midi_output.port_count() {
-        if midi_output.port_name(i).unwrap().starts_with("Neutron") {
-            out_port = Some(i);
-            break;
+/// The first port whose name starts with `name`.
+fn find_port(ports: &dyn Neutron, name: &str) -> Option<usize> {
+    (0..ports.port_count()).find(|i| {
+        ports
+            .port_name(*i)
+            .is_ok_and(|port_name| port_name.starts_with(name))
+    })
+}
+
+/// The names of all MIDI outputs, empty if MIDI isn't available.
+pub fn output_port_names() -> Vec<String> {
+    MidiOutput::new(CLIENT_NAME)
+        .map(|output| {
+            (0..output.port_count())
+                .filter_map(|i| output.port_name(i).ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use rustron_lib::protocol::DeviceId::Multicast;
+    use rustron_lib::protocol::GlobalSetting::{LfoDepth, VcfModDepth};
+    use rustron_lib::protocol::NeutronMessage::{SetGlobalSetting, StateRequest};
+    use rustron_lib::protocol::{Channel, DeviceId, Percent};
+
+    use crate::emulator::Emulator;
+    use crate::midi::{
+        panic_messages, ConnectionEvent, Destination, InputQueue, MidiConnection, Output,
+        RateLimiter, SendWorker, Source, StatePoller, MAX_RECONNECT_BACKOFF, RECONNECT_BACKOFF,
+    };
+
+    #[test]
+    fn messages_wait_for_the_connection() {
+        let mut destination = Destination::new("Neutron");
+        assert!(destination.send(&[0xf0, 0xf7]).is_err());
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        destination.connecting = Some(receiver);
+        destination.send(&[0xf0, 0xf7]).unwrap();
+        assert_eq!(destination.waiting, vec![vec![0xf0, 0xf7]]);
+        assert_eq!(destination.queued(), 1);
+        assert_eq!(destination.poll(), vec![]);
+
+        sender.send(Err(String::from("no such port"))).unwrap();
+        assert_eq!(
+            destination.poll(),
+            vec![ConnectionEvent::Failed(
+                String::from("Neutron"),
+                String::from("no such port")
+            )]
+        );
+        assert!(!destination.is_connecting());
+        assert!(destination.waiting.is_empty());
+    }
+
+    #[test]
+    fn sysex_chunks_are_put_back_together() {
+        let queue = InputQueue::with_max_sysex(8, 6);
+        let port = Source::Port(Arc::from("Keystep"));
+        queue.push(Source::Neutron, vec![0xf0, 0x01]);
+        queue.push(port.clone(), vec![0xf0, 0x05]);
+        // Real-time messages pass a SysEx message being received
+        queue.push(Source::Neutron, vec![0xf8]);
+        queue.push(Source::Neutron, vec![0x02, 0x03]);
+        queue.push(port.clone(), vec![0x06, 0xf7]);
+        queue.push(Source::Neutron, vec![0xf7]);
+        assert_eq!(queue.pop(), Some((Source::Neutron, vec![0xf8])));
+        assert_eq!(
+            queue.pop(),
+            Some((port.clone(), vec![0xf0, 0x05, 0x06, 0xf7]))
+        );
+        assert_eq!(
+            queue.pop(),
+            Some((Source::Neutron, vec![0xf0, 0x01, 0x02, 0x03, 0xf7]))
+        );
+
+        // Interrupted and too long messages are dropped
+        queue.push(port.clone(), vec![0xf0, 0x01]);
+        queue.push(port.clone(), vec![0x90, 0x40, 0x7f]);
+        queue.push(Source::Neutron, vec![0xf0, 0x01, 0x02, 0x03]);
+        queue.push(Source::Neutron, vec![0x04, 0x05, 0xf7]);
+        assert_eq!(queue.pop(), Some((port.clone(), vec![0x90, 0x40, 0x7f])));
+        assert_eq!(queue.pop(), None);
+        assert_eq!(queue.dropped(), 2);
+
+        // The chunks after a too long message was dropped are discarded up to its end
+        queue.push(Source::Neutron, vec![0xf0, 0x01, 0x02, 0x03]);
+        queue.push(Source::Neutron, vec![0x04, 0x05, 0x06]);
+        queue.push(Source::Neutron, vec![0x07, 0x08]);
+        queue.push(Source::Neutron, vec![0x09, 0xf7]);
+        queue.push(Source::Neutron, vec![0xf0, 0x0a, 0xf7]);
+        assert_eq!(queue.pop(), Some((Source::Neutron, vec![0xf0, 0x0a, 0xf7])));
+        // or up to the next message
+        queue.push(port.clone(), vec![0xf0, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06]);
+        queue.push(port.clone(), vec![0x07]);
+        queue.push(port.clone(), vec![0x90, 0x40, 0x7f]);
+        queue.push(port.clone(), vec![0xf0, 0x0b, 0xf7]);
+        assert_eq!(queue.pop(), Some((port.clone(), vec![0x90, 0x40, 0x7f])));
+        assert_eq!(queue.pop(), Some((port, vec![0xf0, 0x0b, 0xf7])));
+        assert_eq!(queue.pop(), None);
+        assert_eq!(queue.dropped(), 4);
+    }
+
+    #[test]
+    fn sent_messages_are_mirrored_to_the_monitor() {
+        let mut connection = MidiConnection::simulated(Emulator::new(InputQueue::new(8)));
+        let (sender, monitored) = std::sync::mpsc::channel();
+        connection.monitor = Some(SendWorker::spawn(8, move |message| {
+            sender
+                .send(message.to_vec())
+                .map_err(|error| error.to_string())
+        }));
+        connection.send_message(&[0xf0, 0x01, 0xf7]).unwrap();
+        connection.monitor(&[0xf0, 0x02, 0xf7]);
+        assert_eq!(monitored.recv().unwrap(), [0xf0, 0x01, 0xf7]);
+        assert_eq!(monitored.recv().unwrap(), [0xf0, 0x02, 0xf7]);
+    }
+
+    #[test]
+    fn a_blocked_port_fills_the_send_queue() {
+        let (unblock, blocked) = std::sync::mpsc::channel::<()>();
+        let (started, sending) = std::sync::mpsc::channel::<()>();
+        let worker = SendWorker::spawn(2, move |message| {
+            started.send(()).unwrap();
+            blocked.recv().unwrap();
+            match message {
+                [0xf0, 0x02, 0xf7] => Err(String::from("driver error")),
+                _ => Ok(()),
+            }
+        });
+        let mut destination = Destination::new("Neutron");
+        destination.connection = Some(Output::Port(worker));
+        // One message being sent by the blocked worker, two queued, the rest refused
+        destination.send(&[0xf0, 0x01, 0xf7]).unwrap();
+        sending.recv().unwrap();
+        destination.send(&[0xf0, 0x02, 0xf7]).unwrap();
+        destination.send(&[0xf0, 0x03, 0xf7]).unwrap();
+        assert!(destination.send(&[0xf0, 0x04, 0xf7]).is_err());
+        assert_eq!(destination.queued(), 3);
+
+        for _ in 0..3 {
+            unblock.send(()).unwrap();
+        }
+        while destination.queued() != 0 {
+            std::thread::yield_now();
         }
+        assert_eq!(
+            destination.poll(),
+            vec![ConnectionEvent::SendFailed(
+                String::from("Neutron"),
+                String::from("driver error")
+            )]
+        );
+    }
+
+    #[test]
+    fn a_port_that_is_gone_is_dropped() {
+        let worker = SendWorker::spawn(2, |_| Ok(()));
+        let link = Arc::clone(&worker.link);
+        let mut destination = Destination::new("WIDI");
+        destination.connection = Some(Output::Port(worker));
+        assert_eq!(destination.poll(), vec![]);
+        link.store(false, Ordering::SeqCst);
+        assert_eq!(
+            destination.poll(),
+            vec![ConnectionEvent::Failed(
+                String::from("WIDI"),
+                String::from("disconnected")
+            )]
+        );
+        assert!(!destination.is_connected());
+    }
+
+    #[test]
+    fn failed_connections_are_retried_after_a_backoff() {
+        let mut destination = Destination::new("Neutron");
+        destination.connector = Arc::new(|_: &str| Err(String::from("not found")));
+        let start = Instant::now();
+        assert!(destination.connect_due(start));
+        destination.start_connecting();
+        assert!(!destination.connect_due(start));
+        while destination.is_connecting() {
+            destination.poll();
+        }
+        let retry_at = destination.retry_at.unwrap();
+        assert!(retry_at >= start + RECONNECT_BACKOFF);
+        assert!(!destination.connect_due(start));
+        assert!(destination.connect_due(retry_at));
+        assert_eq!(destination.backoff, RECONNECT_BACKOFF * 2);
+        for _ in 0..10 {
+            destination.retry_later();
+        }
+        assert_eq!(destination.backoff, MAX_RECONNECT_BACKOFF);
+    }
+
+    #[test]
+    fn flushed_messages_are_not_sent() {
+        let (unblock, blocked) = std::sync::mpsc::channel::<()>();
+        let (started, sending) = std::sync::mpsc::channel::<()>();
+        let (sender, sent) = std::sync::mpsc::channel();
+        let worker = SendWorker::spawn(4, move |message| {
+            started.send(()).unwrap();
+            blocked.recv().unwrap();
+            sender.send(message.to_vec()).unwrap();
+            Ok(())
+        });
+        let mut destination = Destination::new("Neutron");
+        destination.connection = Some(Output::Port(worker));
+        // The message being sent still goes out, the queued one is dropped
+        destination.send(&[0xf0, 0x01, 0xf7]).unwrap();
+        sending.recv().unwrap();
+        destination.send(&[0xf0, 0x02, 0xf7]).unwrap();
+        assert_eq!(destination.flush(), 2);
+        destination.send(&[0xf0, 0x03, 0xf7]).unwrap();
+        for _ in 0..2 {
+            unblock.send(()).unwrap();
+        }
+        assert_eq!(sent.recv().unwrap(), [0xf0, 0x01, 0xf7]);
+        assert_eq!(sent.recv().unwrap(), [0xf0, 0x03, 0xf7]);
+        while destination.queued() != 0 {
+            std::thread::yield_now();
+        }
+        assert_eq!(destination.poll(), vec![]);
+    }
+
+    #[test]
+    fn neutron_only_messages_report_delivery() {
+        let mut connection = MidiConnection::new("Neutron");
+        connection.add_output("Keystep");
+        let (sender, sent) = std::sync::mpsc::channel();
+        for (i, output) in connection.outputs.iter_mut().enumerate() {
+            let sender = sender.clone();
+            output.connection = Some(Output::Port(SendWorker::spawn(8, move |message| {
+                sender.send((i, message.to_vec())).unwrap();
+                match message {
+                    [0xf0, 0x02, 0xf7] => Err(String::from("driver error")),
+                    _ => Ok(()),
+                }
+            })));
+        }
+        let delivery = connection.send_to_neutron(&[0xf0, 0x01, 0xf7]).unwrap();
+        assert_eq!(delivery.recv().unwrap(), Ok(()));
+        assert_eq!(sent.try_recv().unwrap(), (0, vec![0xf0, 0x01, 0xf7]));
+        let delivery = connection.send_to_neutron(&[0xf0, 0x02, 0xf7]).unwrap();
+        assert_eq!(delivery.recv().unwrap(), Err(String::from("driver error")));
+        assert_eq!(sent.try_recv().unwrap(), (0, vec![0xf0, 0x02, 0xf7]));
+        assert!(sent.try_recv().is_err());
+
+        connection.toggle_output(0);
+        assert!(connection.send_to_neutron(&[0xf0, 0x03, 0xf7]).is_err());
     }
-    match out_port {
-        Some(i) => Ok(i),
-        None => Err(Box::from("Could not find Neutron.")),
+
+    #[test]
+    fn panic_silences_every_channel() {
+        let messages = panic_messages();
+        assert_eq!(messages.len(), 32);
+        assert_eq!(messages[0], vec![0xb0, 120, 0]);
+        assert_eq!(messages[31], vec![0xbf, 123, 0]);
+    }
+
+    #[test]
+    fn poller_respects_interval() {
+        let mut poller = StatePoller::new(false, Duration::from_millis(1000));
+        let start = Instant::now();
+        assert!(!poller.poll(start));
+        poller.toggle();
+        assert!(poller.poll(start));
+        assert!(!poller.poll(start + Duration::from_millis(500)));
+        assert!(poller.poll(start + Duration::from_millis(1000)));
+    }
+
+    #[test]
+    fn poller_tracks_unanswered_polls() {
+        let mut poller = StatePoller::new(true, Duration::from_millis(1000));
+        let start = Instant::now();
+        assert_eq!(poller.unanswered_for(start), None);
+        assert!(poller.poll(start));
+        assert!(poller.poll(start + Duration::from_millis(1000)));
+        assert_eq!(
+            poller.unanswered_for(start + Duration::from_millis(1500)),
+            Some(Duration::from_millis(1500))
+        );
+        poller.answered();
+        assert_eq!(
+            poller.unanswered_for(start + Duration::from_millis(1500)),
+            None
+        );
+    }
+
+    #[test]
+    fn paused_limiter_holds_messages() {
+        let mut limiter = RateLimiter::new(Duration::from_millis(0));
+        let request = StateRequest(Multicast).as_bytes();
+        let start = Instant::now();
+        limiter.paused = true;
+        assert!(!limiter.submit(&request, start));
+        assert_eq!(limiter.poll(start), None);
+        limiter.paused = false;
+        assert_eq!(limiter.poll(start), Some(request));
+    }
+
+    #[test]
+    fn limiter_coalesces_waiting_settings() {
+        let depth =
+            |value| SetGlobalSetting(Multicast, LfoDepth(Percent::from_byte(value))).as_bytes();
+        let interval = Duration::from_millis(20);
+        let mut limiter = RateLimiter::new(interval);
+        let start = Instant::now();
+        assert!(limiter.submit(&depth(1), start));
+        assert!(!limiter.submit(&depth(2), start));
+        let cutoff = SetGlobalSetting(Multicast, VcfModDepth(Percent::from_byte(5))).as_bytes();
+        assert!(!limiter.submit(&cutoff, start));
+        let other_device = SetGlobalSetting(
+            DeviceId::Channel(Channel::Two),
+            LfoDepth(Percent::from_byte(9)),
+        )
+        .as_bytes();
+        assert!(!limiter.submit(&other_device, start));
+        assert!(!limiter.submit(&depth(3), start));
+        let request = StateRequest(Multicast).as_bytes();
+        assert!(!limiter.submit(&request, start));
+        assert_eq!(limiter.pending(), 4);
+        assert_eq!(limiter.coalesced, 1);
+
+        assert_eq!(limiter.poll(start), None);
+        assert_eq!(limiter.poll(start + interval), Some(depth(3)));
+        assert_eq!(limiter.poll(start + interval), None);
+        assert_eq!(limiter.poll(start + interval * 2), Some(cutoff));
+        assert_eq!(limiter.poll(start + interval * 3), Some(other_device));
+        assert_eq!(limiter.poll(start + interval * 4), Some(request));
+        assert_eq!(limiter.poll(start + interval * 5), None);
+        assert!(limiter.submit(&depth(4), start + interval * 5));
+    }
+
+    #[test]
+    fn a_slow_tick_sends_everything_due() {
+        let interval = Duration::from_millis(10);
+        let mut limiter = RateLimiter::new(interval);
+        let start = Instant::now();
+        let request = StateRequest(Multicast).as_bytes();
+        for _ in 0..20 {
+            limiter.submit(&request, start);
+        }
+        assert_eq!(limiter.pending(), 19);
+        assert_eq!(limiter.next_due(), Some(start + interval));
+        // A 50 ms tick sends the five messages due by then
+        let now = start + interval * 5;
+        assert_eq!(std::iter::from_fn(|| limiter.poll(now)).count(), 5);
+        assert_eq!(limiter.next_due(), Some(start + interval * 6));
+        // A stall doesn't send the whole backlog at once
+        let now = start + interval * 100;
+        assert_eq!(std::iter::from_fn(|| limiter.poll(now)).count(), 9);
+    }
+
+    #[test]
+    fn full_input_queue_drops_oldest() {
+        let queue = InputQueue::new(2);
+        let callback = queue.clone();
+        for byte in 0..5 {
+            callback.push(Source::Neutron, vec![byte]);
+        }
+        assert_eq!(queue.dropped(), 3);
+        assert_eq!(queue.pop(), Some((Source::Neutron, vec![3])));
+        assert_eq!(queue.pop(), Some((Source::Neutron, vec![4])));
+        assert_eq!(queue.pop(), None);
     }
 }
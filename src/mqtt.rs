@@ -0,0 +1,146 @@
+//! Bridges the settings to an MQTT broker for home automation like Node-RED or Home Assistant.
+//! Every known value and every change is published retained to a topic per setting, the setting
+//! id with `/` for `.` under the configured topic, e.g. `neutron/osc1/range`. Values published to
+//! that topic followed by `/set` are sent to the Neutron.
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Duration;
+
+use log::{error, info, warn};
+use rumqttc::{Client, Event, MqttOptions, Packet, QoS};
+
+use rustron_lib::protocol::GlobalSetting;
+use rustron_lib::setting::SettingId;
+
+use crate::config::MqttConfig;
+use crate::core::CommandStatus;
+use crate::remote::{ask, Request};
+
+const CLIENT_ID: &str = "rustron";
+
+/// How long to wait before reconnecting after the connection to the broker failed.
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The topic the value of the setting is published to.
+pub fn setting_topic(topic: &str, id: SettingId) -> String {
+    format!("{}/{}", topic, id.id().replace('.', "/"))
+}
+
+/// The id of the setting a command topic is for, `None` for other topics.
+pub fn command_setting(topic: &str, command: &str) -> Option<String> {
+    let path = command
+        .strip_prefix(topic)?
+        .strip_prefix('/')?
+        .strip_suffix("/set")?;
+    Some(path.replace('/', "."))
+}
+
+fn publish(client: &Client, topic: &str, global_setting: &GlobalSetting) {
+    let topic = setting_topic(topic, global_setting.id());
+    if let Err(error) = client.publish(topic, QoS::AtLeastOnce, true, global_setting.value_text()) {
+        warn!("Could not publish to MQTT: {}", error);
+    }
+}
+
+/// Connects to the broker of `config` and bridges it with the core through `requests`, see
+/// `AppCore::remote`, on threads of their own.
+pub fn start(config: &MqttConfig, requests: Sender<Request>) {
+    let (host, port) = match &config.broker {
+        Some(broker) => broker.clone(),
+        None => return,
+    };
+    let mut options = MqttOptions::new(CLIENT_ID, host.as_str(), port);
+    options.set_keep_alive(Duration::from_secs(30));
+    let (client, mut connection) = Client::new(options, 64);
+
+    // Publishes the known values, then every change
+    let publisher = client.clone();
+    let topic = config.topic.clone();
+    let changes = requests.clone();
+    thread::spawn(move || {
+        let (settings, _) = match ask(&changes, |reply| Request::Dump { reply }) {
+            Ok(dump) => dump,
+            Err(error) => return error!("Could not start publishing to MQTT: {}", error),
+        };
+        for (global_setting, _) in &settings {
+            publish(&publisher, &topic, global_setting);
+        }
+        let changes = match ask(&changes, |reply| Request::Subscribe { reply }) {
+            Ok(changes) => changes,
+            Err(error) => return error!("Could not start publishing to MQTT: {}", error),
+        };
+        for change in changes {
+            publish(&publisher, &topic, &change.setting);
+        }
+    });
+
+    // Takes the commands, subscribing again after each reconnect
+    let topic = config.topic.clone();
+    thread::spawn(move || {
+        for event in connection.iter() {
+            match event {
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    info!("Connected to the MQTT broker at {}:{}", host, port);
+                    if let Err(error) = client.subscribe(format!("{}/#", topic), QoS::AtLeastOnce) {
+                        warn!("Could not subscribe to MQTT commands: {}", error);
+                    }
+                }
+                Ok(Event::Incoming(Packet::Publish(message))) => {
+                    let setting = match command_setting(&topic, &message.topic) {
+                        Some(setting) => setting,
+                        None => continue,
+                    };
+                    let value = String::from_utf8_lossy(&message.payload).to_string();
+                    let answer = ask(&requests, |reply| Request::Set {
+                        setting,
+                        value,
+                        reply,
+                    });
+                    match answer {
+                        Ok(Ok(CommandStatus::Sent)) => {}
+                        Ok(Ok(CommandStatus::Blocked)) => {
+                            warn!("MQTT command {} was not sent", message.topic)
+                        }
+                        Ok(Ok(CommandStatus::Held)) => {
+                            info!("MQTT command {} needs to be confirmed", message.topic)
+                        }
+                        Ok(Err(error)) => {
+                            warn!("Invalid MQTT command {}: {}", message.topic, error)
+                        }
+                        Err(error) => return error!("Stopped taking MQTT commands: {}", error),
+                    }
+                }
+                Ok(_) => {}
+                Err(error) => {
+                    warn!("MQTT connection failed: {}", error);
+                    thread::sleep(RETRY_INTERVAL);
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use rustron_lib::setting::SettingId;
+
+    use crate::mqtt::{command_setting, setting_topic};
+
+    #[test]
+    fn settings_have_a_topic_each() {
+        assert_eq!(
+            setting_topic("neutron", SettingId::Osc1Range),
+            "neutron/osc1/range"
+        );
+        assert_eq!(
+            command_setting("neutron", "neutron/osc1/range/set"),
+            Some(String::from("osc1.range"))
+        );
+        assert_eq!(
+            command_setting("studio/neutron", "studio/neutron/paraphonic/set"),
+            Some(String::from("paraphonic"))
+        );
+        assert_eq!(command_setting("neutron", "neutron/osc1/range"), None);
+        assert_eq!(command_setting("neutron", "neutrons/osc1/range/set"), None);
+    }
+}
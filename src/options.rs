@@ -0,0 +1,198 @@
+#[cfg(feature = "grpc")]
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use rustron_lib::parser::neutron_message;
+use rustron_lib::protocol::NeutronMessage::{SoftwareVersionRequest, StateRequest};
+
+/// Command line options.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Options {
+    /// Talk to an emulated Neutron instead of MIDI ports
+    pub simulate: bool,
+    /// How the emulated Neutron behaves, see `Scenario`
+    pub scenario: Option<PathBuf>,
+    /// Fraction of received messages to corrupt before parsing, see `Corruptor`
+    pub corrupt: f64,
+    /// Block everything sent except version and state requests, to watch without changing the
+    /// Neutron
+    pub read_only: bool,
+    /// The configuration profile to start with, see `Config::use_profile`
+    pub profile: Option<String>,
+    /// Print the differences between two state dump files and exit, see `inspect::diff_dumps`
+    pub diff: Option<(PathBuf, PathBuf)>,
+    /// Log how each received message that can't be parsed failed, see `parser::trace`
+    #[cfg(feature = "debug-parser")]
+    pub debug_parser: bool,
+    /// Where the gRPC server listens, not started when `None`, see `grpc::serve`
+    #[cfg(feature = "grpc")]
+    pub grpc: Option<SocketAddr>,
+    /// Talk to the Neutron through JACK instead of the ALSA ports, see `MidiConnection::jack`
+    #[cfg(feature = "jack")]
+    pub jack: bool,
+    /// Talk to the Neutron through a BLE MIDI adapter, see `MidiConnection::ble`
+    #[cfg(feature = "ble")]
+    pub ble: bool,
+}
+
+const USAGE: &str =
+    "Usage: rustron [--simulate] [--scenario <file>] [--corrupt <fraction>] [--profile <name>] \
+     [--read-only] [--debug-parser] [--grpc <address>] [--jack] [--ble]\n       \
+     rustron diff <state dump file> <state dump file>";
+
+impl Options {
+    /// Parses the arguments after the program name.
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Options, String> {
+        let mut options = Options::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--simulate" => options.simulate = true,
+                // A scenario only makes sense for the emulator
+                "--scenario" => {
+                    let path = args
+                        .next()
+                        .ok_or(format!("--scenario needs a file\n{}", USAGE))?;
+                    options.scenario = Some(PathBuf::from(path));
+                    options.simulate = true;
+                }
+                "--corrupt" => {
+                    options.corrupt = args
+                        .next()
+                        .and_then(|fraction| fraction.parse().ok())
+                        .filter(|fraction| (0.0..=1.0).contains(fraction))
+                        .ok_or(format!("--corrupt needs a fraction from 0 to 1\n{}", USAGE))?;
+                }
+                "--read-only" => options.read_only = true,
+                "--profile" => {
+                    let name = args
+                        .next()
+                        .ok_or(format!("--profile needs a name\n{}", USAGE))?;
+                    options.profile = Some(name);
+                }
+                "diff" => match (args.next(), args.next()) {
+                    (Some(before), Some(after)) => {
+                        options.diff = Some((PathBuf::from(before), PathBuf::from(after)))
+                    }
+                    _ => return Err(format!("diff needs two files\n{}", USAGE)),
+                },
+                #[cfg(feature = "debug-parser")]
+                "--debug-parser" => options.debug_parser = true,
+                #[cfg(not(feature = "debug-parser"))]
+                "--debug-parser" => {
+                    return Err(String::from(
+                        "--debug-parser needs rustron built with the debug-parser feature",
+                    ))
+                }
+                #[cfg(feature = "grpc")]
+                "--grpc" => {
+                    let address = args.next().and_then(|address| address.parse().ok());
+                    options.grpc = Some(address.ok_or(format!(
+                        "--grpc needs an address like 127.0.0.1:50051\n{}",
+                        USAGE
+                    ))?);
+                }
+                #[cfg(not(feature = "grpc"))]
+                "--grpc" => {
+                    return Err(String::from(
+                        "--grpc needs rustron built with the grpc feature",
+                    ))
+                }
+                #[cfg(feature = "jack")]
+                "--jack" => options.jack = true,
+                #[cfg(not(feature = "jack"))]
+                "--jack" => {
+                    return Err(String::from(
+                        "--jack needs rustron built with the jack feature",
+                    ))
+                }
+                #[cfg(feature = "ble")]
+                "--ble" => options.ble = true,
+                #[cfg(not(feature = "ble"))]
+                "--ble" => {
+                    return Err(String::from(
+                        "--ble needs rustron built with the ble feature",
+                    ))
+                }
+                _ => return Err(format!("Unknown argument {}\n{}", arg, USAGE)),
+            }
+        }
+        // Never corrupt what a real Neutron sends
+        if options.corrupt > 0.0 && !options.simulate {
+            return Err(format!("--corrupt only works with --simulate\n{}", USAGE));
+        }
+        Ok(options)
+    }
+
+    /// Whether `bytes` may be sent, in read-only mode only version and state requests may.
+    pub fn allows(&self, bytes: &[u8]) -> bool {
+        !self.read_only
+            || matches!(
+                neutron_message(bytes),
+                Ok((_, SoftwareVersionRequest(_))) | Ok((_, StateRequest(_)))
+            )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use rustron_lib::protocol::DeviceId::Multicast;
+    use rustron_lib::protocol::GlobalSetting::OscSync;
+    use rustron_lib::protocol::NeutronMessage::{
+        SetGlobalSetting, SoftwareVersionRequest, StateRequest,
+    };
+    use rustron_lib::protocol::ToggleOption::On;
+
+    use crate::options::Options;
+
+    #[test]
+    fn options_are_parsed() {
+        let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        assert_eq!(Options::parse(args(&[])), Ok(Options::default()));
+        assert_eq!(
+            Options::parse(args(&["--simulate"])),
+            Ok(Options {
+                simulate: true,
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            Options::parse(args(&["--scenario", "slow.txt"])),
+            Ok(Options {
+                simulate: true,
+                scenario: Some(PathBuf::from("slow.txt")),
+                ..Default::default()
+            })
+        );
+        assert!(Options::parse(args(&["--scenario"])).is_err());
+        assert_eq!(
+            Options::parse(args(&["--profile", "laptop"])).map(|o| o.profile),
+            Ok(Some(String::from("laptop")))
+        );
+        assert!(Options::parse(args(&["--profile"])).is_err());
+        assert_eq!(
+            Options::parse(args(&["--simulate", "--corrupt", "0.1"])).map(|o| o.corrupt),
+            Ok(0.1)
+        );
+        assert!(Options::parse(args(&["--corrupt", "0.1"])).is_err());
+        assert!(Options::parse(args(&["--simulate", "--corrupt", "2"])).is_err());
+        assert!(Options::parse(args(&["--frobnicate"])).is_err());
+        assert_eq!(
+            Options::parse(args(&["diff", "a.hex", "b.syx"])).map(|o| o.diff),
+            Ok(Some((PathBuf::from("a.hex"), PathBuf::from("b.syx"))))
+        );
+        assert!(Options::parse(args(&["diff", "a.hex"])).is_err());
+    }
+
+    #[test]
+    fn read_only_only_allows_requests() {
+        let options = Options::parse(vec![String::from("--read-only")]).unwrap();
+        assert!(options.allows(&StateRequest(Multicast).as_bytes()));
+        assert!(options.allows(&SoftwareVersionRequest(Multicast).as_bytes()));
+        assert!(!options.allows(&SetGlobalSetting(Multicast, OscSync(On)).as_bytes()));
+        assert!(!options.allows(&[0xb0, 123, 0]));
+        assert!(Options::default().allows(&[0xb0, 123, 0]));
+    }
+}
@@ -0,0 +1,160 @@
+use std::time::{Duration, Instant};
+
+use rustron_lib::protocol::{COMMS_PROTOCOL_V1, NEUTRON_MESSAGE_HEADER};
+
+/// The opcode of the messages that follow a state dump, thought to carry tuner values.
+const FOLLOW_UP_OPCODE: u8 = 0x72;
+
+/// How many messages follow the state dump in the answer to a state request.
+pub const FOLLOW_UPS: usize = 24;
+
+/// How long after the state dump its follow-up messages are expected.
+pub const FOLLOW_UP_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The answer to one state request: the state dump and the messages that follow it.
+#[derive(Debug, PartialEq)]
+pub struct PollResult {
+    pub requested: Instant,
+    /// When the state dump was received
+    pub answered: Instant,
+    pub state: Vec<u8>,
+    pub follow_ups: Vec<Vec<u8>>,
+    /// Where the state dump is in the received stream
+    pub entry: usize,
+    /// How the state dump is shown in the received stream on its own
+    text: String,
+}
+
+impl PollResult {
+    pub fn is_complete(&self) -> bool {
+        self.follow_ups.len() >= FOLLOW_UPS
+    }
+
+    /// How the whole poll is shown in the received stream, in place of the state dump.
+    pub fn summary(&self) -> String {
+        format!(
+            "{} [+{}/{} follow-ups]",
+            self.text,
+            self.follow_ups.len(),
+            FOLLOW_UPS
+        )
+    }
+}
+
+/// Whether `bytes` is one of the messages following a state dump.
+pub fn is_follow_up(bytes: &[u8]) -> bool {
+    let header = NEUTRON_MESSAGE_HEADER.len();
+    bytes.starts_with(&NEUTRON_MESSAGE_HEADER)
+        && bytes.get(header + 1..header + 3) == Some(&[FOLLOW_UP_OPCODE, COMMS_PROTOCOL_V1][..])
+}
+
+/// Correlates each state request with the state dump and follow-up messages answering it, so they
+/// can be shown as one poll rather than 25 unrelated messages.
+pub struct PollTracker {
+    requested: Option<Instant>,
+    pub current: Option<PollResult>,
+}
+
+impl Default for PollTracker {
+    fn default() -> PollTracker {
+        PollTracker::new()
+    }
+}
+
+impl PollTracker {
+    pub fn new() -> PollTracker {
+        PollTracker {
+            requested: None,
+            current: None,
+        }
+    }
+
+    /// Notes that a state request was sent at `now`, which closes the previous poll.
+    pub fn requested(&mut self, now: Instant) {
+        self.requested = Some(now);
+        self.current = None;
+    }
+
+    /// Starts a poll result if the state dump shown as `text` at `entry`, received at `now`,
+    /// answers a request.
+    pub fn state(&mut self, payload: &[u8], entry: usize, text: &str, now: Instant) {
+        if let Some(requested) = self.requested.take() {
+            self.current = Some(PollResult {
+                requested,
+                answered: now,
+                state: payload.to_vec(),
+                follow_ups: Vec::new(),
+                entry,
+                text: text.to_string(),
+            });
+        }
+    }
+
+    /// Adds `bytes`, received at `now`, to the current poll if it is one of its follow-up
+    /// messages, returning the poll it was added to. A poll is closed once complete, or
+    /// `FOLLOW_UP_TIMEOUT` after its state dump.
+    pub fn follow_up(&mut self, bytes: &[u8], now: Instant) -> Option<&PollResult> {
+        let poll = self.current.as_mut().filter(|poll| {
+            !poll.is_complete() && now.saturating_duration_since(poll.answered) <= FOLLOW_UP_TIMEOUT
+        })?;
+        if !is_follow_up(bytes) {
+            return None;
+        }
+        poll.follow_ups.push(bytes.to_vec());
+        Some(poll)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Instant;
+
+    use crate::poll::{is_follow_up, PollTracker, FOLLOW_UPS, FOLLOW_UP_TIMEOUT};
+
+    const FOLLOW_UP: [u8; 11] = [
+        0xf0, 0x00, 0x20, 0x32, 0x28, 0x00, 0x72, 0x01, 0x12, 0x34, 0xf7,
+    ];
+
+    #[test]
+    fn follow_ups_are_grouped_with_the_answered_poll() {
+        assert!(is_follow_up(&FOLLOW_UP));
+        assert!(!is_follow_up(&[0xf0, 0x01, 0xf7]));
+
+        let now = Instant::now();
+        let mut polls = PollTracker::new();
+        // Nothing was requested, so the dump and what follows it stay on their own
+        polls.state(&[0x6b], 3, "state dump", now);
+        assert_eq!(polls.follow_up(&FOLLOW_UP, now), None);
+
+        polls.requested(now);
+        polls.state(&[0x6b], 5, "state dump", now);
+        assert_eq!(polls.follow_up(&[0xf0, 0x01, 0xf7], now), None);
+        for _ in 1..FOLLOW_UPS {
+            polls.follow_up(&FOLLOW_UP, now);
+        }
+        let poll = polls.follow_up(&FOLLOW_UP, now).unwrap();
+        assert_eq!(poll.entry, 5);
+        assert!(poll.is_complete());
+        assert_eq!(poll.summary(), "state dump [+24/24 follow-ups]");
+        assert_eq!(polls.follow_up(&FOLLOW_UP, now), None);
+    }
+
+    #[test]
+    fn polls_close_on_the_next_request_or_timeout() {
+        let now = Instant::now();
+        let mut polls = PollTracker::new();
+        polls.requested(now);
+        polls.state(&[0x6b], 0, "state dump", now);
+        assert!(polls.follow_up(&FOLLOW_UP, now).is_some());
+        assert_eq!(
+            polls.follow_up(&FOLLOW_UP, now + FOLLOW_UP_TIMEOUT * 2),
+            None
+        );
+
+        polls.requested(now);
+        polls.state(&[0x6b], 1, "state dump", now);
+        polls.requested(now);
+        assert_eq!(polls.current, None);
+        assert_eq!(polls.follow_up(&FOLLOW_UP, now), None);
+    }
+}
@@ -0,0 +1,107 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// A command waiting for the Neutron to be connected.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QueuedCommand {
+    pub bytes: Vec<u8>,
+    pub queued: Instant,
+}
+
+/// Commands sent while no Neutron is connected, sent once it is. Bounded: when full the oldest
+/// command is dropped, and commands older than `ttl` are dropped as stale.
+pub struct OfflineQueue {
+    commands: VecDeque<QueuedCommand>,
+    pub capacity: usize,
+    pub ttl: Duration,
+    /// How many commands were dropped since the queue was last empty
+    pub dropped: usize,
+}
+
+impl OfflineQueue {
+    pub fn new(capacity: usize, ttl: Duration) -> OfflineQueue {
+        OfflineQueue {
+            commands: VecDeque::new(),
+            capacity,
+            ttl,
+            dropped: 0,
+        }
+    }
+
+    pub fn commands(&self) -> impl Iterator<Item = &QueuedCommand> {
+        self.commands.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    pub fn push(&mut self, bytes: Vec<u8>, now: Instant) {
+        while self.commands.len() >= self.capacity.max(1) {
+            self.commands.pop_front();
+            self.dropped += 1;
+        }
+        self.commands
+            .push_back(QueuedCommand { bytes, queued: now });
+    }
+
+    /// Drops the commands that waited longer than `ttl` at `now`, returning how many.
+    pub fn expire(&mut self, now: Instant) -> usize {
+        let before = self.commands.len();
+        let ttl = self.ttl;
+        self.commands
+            .retain(|command| now.saturating_duration_since(command.queued) < ttl);
+        let expired = before - self.commands.len();
+        self.dropped += expired;
+        expired
+    }
+
+    /// Takes the commands that are still fresh at `now`, oldest first.
+    pub fn drain(&mut self, now: Instant) -> Vec<Vec<u8>> {
+        self.expire(now);
+        self.dropped = 0;
+        self.commands
+            .drain(..)
+            .map(|command| command.bytes)
+            .collect()
+    }
+
+    /// Drops all waiting commands, returning how many.
+    pub fn clear(&mut self) -> usize {
+        let cleared = self.commands.len();
+        self.commands.clear();
+        self.dropped = 0;
+        cleared
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use crate::queue::OfflineQueue;
+
+    #[test]
+    fn queue_is_bounded_and_drops_stale_commands() {
+        let mut queue = OfflineQueue::new(2, Duration::from_secs(10));
+        let start = Instant::now();
+        queue.push(vec![1], start);
+        queue.push(vec![2], start + Duration::from_secs(5));
+        queue.push(vec![3], start + Duration::from_secs(6));
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.dropped, 1);
+
+        assert_eq!(queue.expire(start + Duration::from_secs(15)), 1);
+        assert_eq!(queue.drain(start + Duration::from_secs(15)), vec![vec![3]]);
+        assert_eq!(queue.len(), 0);
+        assert_eq!(queue.dropped, 0);
+
+        queue.push(vec![4], start);
+        assert_eq!(queue.clear(), 1);
+        assert!(queue.drain(start).is_empty());
+    }
+}
@@ -0,0 +1,99 @@
+use rustron_lib::protocol::GlobalSetting;
+use rustron_lib::setting::SettingId;
+
+use crate::emulator::Rng;
+
+/// Settings that configure the MIDI setup rather than the sound, never randomized by default.
+const SETUP_SETTINGS: [SettingId; 4] = [
+    SettingId::MidiChannel,
+    SettingId::DisableMidiDips,
+    SettingId::PolyChainMode,
+    SettingId::KeyRangeMute,
+];
+
+/// The settings shaping the sound, the default selection to randomize. Actions have no value and
+/// are left out.
+pub fn sound_settings() -> Vec<SettingId> {
+    SettingId::all()
+        .into_iter()
+        .filter(|id| id.range().is_some() && !SETUP_SETTINGS.contains(id))
+        .collect()
+}
+
+/// Generates random valid values for a selection of settings, for exploring sounds.
+pub struct Randomizer {
+    /// The settings given a random value
+    pub settings: Vec<SettingId>,
+    /// Settings kept at their current value even if selected
+    pub locked: Vec<SettingId>,
+    rng: Rng,
+}
+
+impl Randomizer {
+    pub fn new(settings: Vec<SettingId>, locked: Vec<SettingId>, seed: u64) -> Randomizer {
+        Randomizer {
+            settings,
+            locked,
+            rng: Rng::new(seed),
+        }
+    }
+
+    /// Locks `id` if it is unlocked and the other way around, returning whether it is now locked.
+    pub fn toggle_lock(&mut self, id: SettingId) -> bool {
+        match self.locked.iter().position(|locked| *locked == id) {
+            Some(index) => {
+                self.locked.remove(index);
+                false
+            }
+            None => {
+                self.locked.push(id);
+                true
+            }
+        }
+    }
+
+    /// A random value for every selected setting that isn't locked.
+    pub fn randomize(&mut self) -> Vec<GlobalSetting> {
+        let settings: Vec<SettingId> = self
+            .settings
+            .iter()
+            .filter(|id| !self.locked.contains(id))
+            .copied()
+            .collect();
+        settings
+            .into_iter()
+            .filter_map(|id| {
+                let range = id.range()?;
+                let span = (range.max - range.min) as usize + 1;
+                id.with_raw_value(i32::from(range.min) + self.rng.below(span) as i32)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rustron_lib::setting::SettingId;
+
+    use crate::randomize::{sound_settings, Randomizer};
+
+    #[test]
+    fn only_unlocked_settings_are_randomized() {
+        let settings = sound_settings();
+        assert!(!settings.contains(&SettingId::MidiChannel));
+        assert!(!settings.contains(&SettingId::LfoResetOrder));
+
+        let mut randomizer = Randomizer::new(settings.clone(), vec![SettingId::LfoDepth], 42);
+        let values = randomizer.randomize();
+        assert_eq!(values.len(), settings.len() - 1);
+        assert!(values.iter().all(|value| value.id() != SettingId::LfoDepth));
+
+        assert!(!randomizer.toggle_lock(SettingId::LfoDepth));
+        assert!(randomizer.toggle_lock(SettingId::OscSync));
+        let values = randomizer.randomize();
+        assert!(values.iter().any(|value| value.id() == SettingId::LfoDepth));
+        assert!(values.iter().all(|value| value.id() != SettingId::OscSync));
+        // Different draws, with over 30 settings a repeat is practically impossible
+        assert_ne!(randomizer.randomize(), randomizer.randomize());
+    }
+}
@@ -0,0 +1,139 @@
+use std::error;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config::Config;
+
+const HISTORY_FILE_NAME: &str = "prompt-history.txt";
+
+/// How many commands are kept, the oldest are dropped first.
+pub const MAX_COMMANDS: usize = 500;
+
+/// The commands entered in the set prompt, recalled with Ctrl-p and Ctrl-n like in a shell. A
+/// command entered again moves to the end instead of being kept twice. Stored one command per
+/// line, oldest first, in the data directory.
+pub struct PromptHistory {
+    commands: Vec<String>,
+    /// The recalled command, counted from the newest
+    position: Option<usize>,
+    /// Where the history is saved, not saved when `None`
+    path: Option<PathBuf>,
+}
+
+impl PromptHistory {
+    pub fn path() -> Option<PathBuf> {
+        Some(Config::data_directory()?.join(HISTORY_FILE_NAME))
+    }
+
+    /// Loads the history from `path`, empty if there is no such file.
+    pub fn load(path: Option<PathBuf>) -> PromptHistory {
+        let commands = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|text| {
+                text.lines()
+                    .filter(|line| !line.trim().is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        PromptHistory {
+            commands,
+            position: None,
+            path,
+        }
+    }
+
+    pub fn commands(&self) -> &[String] {
+        &self.commands
+    }
+
+    /// Adds an entered command and saves the history.
+    pub fn add(&mut self, command: &str) -> Result<(), Box<dyn error::Error>> {
+        self.position = None;
+        self.commands.retain(|existing| existing != command);
+        self.commands.push(command.to_string());
+        if self.commands.len() > MAX_COMMANDS {
+            self.commands.drain(..self.commands.len() - MAX_COMMANDS);
+        }
+        match &self.path {
+            Some(path) => {
+                if let Some(dir) = path.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                let mut text = self.commands.join("\n");
+                text.push('\n');
+                fs::write(path, text)?;
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// The command before the recalled one, starting from the newest.
+    pub fn older(&mut self) -> Option<&str> {
+        let position = self.position.map_or(0, |position| position + 1);
+        if position >= self.commands.len() {
+            return self.recalled();
+        }
+        self.position = Some(position);
+        self.recalled()
+    }
+
+    /// The command after the recalled one, `None` when going past the newest.
+    pub fn newer(&mut self) -> Option<&str> {
+        self.position = self.position.and_then(|position| position.checked_sub(1));
+        self.recalled()
+    }
+
+    /// Stops recalling, the next `older` starts from the newest again.
+    pub fn reset(&mut self) {
+        self.position = None;
+    }
+
+    fn recalled(&self) -> Option<&str> {
+        let position = self.position?;
+        self.commands
+            .get(self.commands.len() - 1 - position)
+            .map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use crate::recall::PromptHistory;
+
+    #[test]
+    fn commands_are_recalled_newest_first() {
+        let mut history = PromptHistory::load(None);
+        assert_eq!(history.older(), None);
+        history.add("osc1.range 16").unwrap();
+        history.add("lfo.rate 10").unwrap();
+        history.add("osc1.range 16").unwrap();
+        assert_eq!(history.commands(), ["lfo.rate 10", "osc1.range 16"]);
+
+        assert_eq!(history.older(), Some("osc1.range 16"));
+        assert_eq!(history.older(), Some("lfo.rate 10"));
+        assert_eq!(history.older(), Some("lfo.rate 10"));
+        assert_eq!(history.newer(), Some("osc1.range 16"));
+        assert_eq!(history.newer(), None);
+        history.older();
+        history.reset();
+        assert_eq!(history.older(), Some("osc1.range 16"));
+    }
+
+    #[test]
+    fn history_is_kept_across_sessions() {
+        let path = std::env::temp_dir().join("rustron-prompt-history-test.txt");
+        let _ = fs::remove_file(&path);
+        let mut history = PromptHistory::load(Some(path.clone()));
+        history.add("osc1.range 16").unwrap();
+        history.add("lfo.rate 10").unwrap();
+
+        let mut history = PromptHistory::load(Some(path.clone()));
+        assert_eq!(history.older(), Some("lfo.rate 10"));
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,98 @@
+use std::time::{Duration, Instant};
+
+/// How often everything is redrawn even when nothing was marked, for what changes with time like
+/// timeouts, rates and the latency in the status bar.
+pub const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A part of the screen, by the data it is drawn from.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Pane {
+    /// The sent and received messages
+    Streams,
+    /// The settings of the Neutron
+    State,
+    Logs,
+    Stats,
+    Status,
+    /// Menus and editors, only changed by keys
+    Other,
+}
+
+impl Pane {
+    pub const ALL: [Pane; 6] = [
+        Pane::Streams,
+        Pane::State,
+        Pane::Logs,
+        Pane::Stats,
+        Pane::Status,
+        Pane::Other,
+    ];
+}
+
+/// Tracks which panes changed since the last frame, so a frame is only drawn when something
+/// shown changed instead of on every tick.
+pub struct Redraw {
+    dirty: Vec<Pane>,
+    last_refresh: Instant,
+}
+
+impl Redraw {
+    pub fn new(now: Instant) -> Redraw {
+        Redraw {
+            dirty: Pane::ALL.to_vec(),
+            last_refresh: now,
+        }
+    }
+
+    pub fn mark(&mut self, pane: Pane) {
+        if !self.dirty.contains(&pane) {
+            self.dirty.push(pane);
+        }
+    }
+
+    pub fn mark_all(&mut self) {
+        self.dirty = Pane::ALL.to_vec();
+    }
+
+    /// Marks everything once every `REFRESH_INTERVAL`.
+    pub fn tick(&mut self, now: Instant) {
+        if now.duration_since(self.last_refresh) >= REFRESH_INTERVAL {
+            self.last_refresh = now;
+            self.mark_all();
+        }
+    }
+
+    /// Whether any of the `visible` panes changed since the last frame. The marks are cleared
+    /// when it did, as the frame is then drawn as a whole.
+    pub fn take(&mut self, visible: &[Pane]) -> bool {
+        let changed = self.dirty.iter().any(|pane| visible.contains(pane));
+        if changed {
+            self.dirty.clear();
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use crate::redraw::{Pane, Redraw, REFRESH_INTERVAL};
+
+    #[test]
+    fn only_changes_to_visible_panes_are_drawn() {
+        let start = Instant::now();
+        let mut redraw = Redraw::new(start);
+        assert!(redraw.take(&[Pane::Status]));
+        assert!(!redraw.take(&Pane::ALL));
+
+        redraw.mark(Pane::Logs);
+        assert!(!redraw.take(&[Pane::Streams, Pane::Status]));
+        assert!(redraw.take(&[Pane::Logs, Pane::Status]));
+
+        redraw.tick(start + Duration::from_millis(500));
+        assert!(!redraw.take(&[Pane::Status]));
+        redraw.tick(start + REFRESH_INTERVAL);
+        assert!(redraw.take(&[Pane::Status]));
+    }
+}
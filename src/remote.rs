@@ -0,0 +1,84 @@
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::time::Duration;
+
+use rustron_lib::protocol::GlobalSetting;
+
+use crate::core::state::SettingChange;
+use crate::core::CommandStatus;
+
+/// How long a remote control surface waits for the core to answer, it answers on its next tick.
+pub const REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A setting with whether the Neutron reported that value.
+pub type Value = (GlobalSetting, bool);
+
+/// A request of a remote control surface like the gRPC server, answered by the core on its next
+/// tick, see `AppCore::answer`. Each carries the sender for its reply.
+pub enum Request {
+    /// Sends a setting by id and textual value, see `GlobalSetting::from_name_value`. Replies
+    /// whether it was sent, blocked or held back for confirmation.
+    Set {
+        setting: String,
+        value: String,
+        reply: Sender<Result<CommandStatus, String>>,
+    },
+    /// The expected value of a setting, `None` if it isn't known
+    Get {
+        setting: String,
+        reply: Sender<Result<Option<Value>, String>>,
+    },
+    /// Every change of a setting from now on, see `NeutronState::subscribe`
+    Subscribe {
+        reply: Sender<Receiver<SettingChange>>,
+    },
+    /// All known settings and the last state dump
+    Dump {
+        reply: Sender<(Vec<Value>, Option<Vec<u8>>)>,
+    },
+}
+
+/// Sends the request built by `request` to the core and waits for the reply.
+pub fn ask<T>(
+    requests: &Sender<Request>,
+    request: impl FnOnce(Sender<T>) -> Request,
+) -> Result<T, String> {
+    let (reply, answer) = std::sync::mpsc::channel();
+    requests
+        .send(request(reply))
+        .map_err(|_| String::from("rustron has quit"))?;
+    answer
+        .recv_timeout(REPLY_TIMEOUT)
+        .map_err(|error| match error {
+            RecvTimeoutError::Timeout => String::from("rustron did not answer in time"),
+            RecvTimeoutError::Disconnected => String::from("rustron dropped the request"),
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::mpsc;
+    use std::thread;
+
+    use crate::remote::{ask, Request};
+
+    #[test]
+    fn requests_are_answered_through_their_reply() {
+        let (requests, core) = mpsc::channel();
+        let answering = thread::spawn(move || {
+            if let Ok(Request::Set { setting, reply, .. }) = core.recv() {
+                reply
+                    .send(Err(format!("unknown setting {}", setting)))
+                    .unwrap();
+            }
+        });
+        let answer = ask(&requests, |reply| Request::Set {
+            setting: String::from("osc3.range"),
+            value: String::from("16"),
+            reply,
+        });
+        assert_eq!(answer, Ok(Err(String::from("unknown setting osc3.range"))));
+        answering.join().unwrap();
+        // Nobody answers once the core is gone
+        assert!(ask(&requests, |reply| Request::Dump { reply }).is_err());
+    }
+}
@@ -0,0 +1,82 @@
+use rustron_lib::preset::PatchSheet;
+
+use crate::core::state::NeutronState;
+
+/// Default file name used when exporting a report.
+pub const DEFAULT_REPORT_FILE: &str = "rustron-report.md";
+
+/// Describes the expected settings of the Neutron in Markdown, one table row per setting, followed
+/// by the patch sheet of the preset, with room for the front panel positions that the Neutron
+/// doesn't report.
+pub fn render(state: &NeutronState, preset: Option<&str>, sheet: Option<&PatchSheet>) -> String {
+    let mut report = String::from("# Neutron settings\n\n");
+    if let Some(preset) = preset {
+        report.push_str(&format!("Preset: {}  \n", preset));
+    }
+    report.push_str(&format!(
+        "Software version: {}  \nDIP switches: {}\n\n",
+        state.software_version.as_deref().unwrap_or("unknown"),
+        state
+            .dip_switches
+            .map_or(String::from("unknown"), |dips| dips.to_string())
+    ));
+    report.push_str("| Setting | Value | Confirmed |\n|---|---|---|\n");
+    for global_setting in state.expected().settings.values() {
+        let confirmed = state.confirmed.settings.get(&global_setting.key()) == Some(global_setting);
+        report.push_str(&format!(
+            "| {} | {} | {} |\n",
+            global_setting.id().id(),
+            global_setting.value_text(),
+            if confirmed { "yes" } else { "no" }
+        ));
+    }
+    report.push_str("\n## Front panel\n\n");
+    if let Some(sheet) = sheet {
+        for note in &sheet.notes {
+            report.push_str(&format!("- {}\n", note));
+        }
+        if !sheet.cables.is_empty() {
+            report.push_str("\n## Patch cables\n\n");
+        }
+        for cable in &sheet.cables {
+            report.push_str(&format!("- {}\n", cable));
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod test {
+    use rustron_lib::protocol::DeviceId::Multicast;
+    use rustron_lib::protocol::GlobalSetting::{OscSync, ParaphonicMode};
+    use rustron_lib::protocol::NeutronMessage::{GlobalSettingUpdate, SetGlobalSetting};
+    use rustron_lib::protocol::ToggleOption::On;
+
+    use rustron_lib::preset::{Cable, PatchSheet};
+
+    use crate::core::state::NeutronState;
+    use crate::report::render;
+
+    #[test]
+    fn settings_are_reported_with_their_values() {
+        let mut state = NeutronState::new();
+        state.update(GlobalSettingUpdate(Multicast, OscSync(On)));
+        state.update(SetGlobalSetting(Multicast, ParaphonicMode(On)));
+        let sheet = PatchSheet {
+            notes: vec![String::from("Resonance at 3 o'clock")],
+            cables: vec![Cable::parse("LFO -> VCF").unwrap()],
+        };
+        let report = render(&state, Some("bass"), Some(&sheet));
+        assert!(report.starts_with("# Neutron settings\n\nPreset: bass  \n"));
+        assert!(report.contains(&format!(
+            "| {} | {} | yes |\n",
+            OscSync(On).id().id(),
+            OscSync(On).value_text()
+        )));
+        assert!(report.contains(&format!("| {} | ", ParaphonicMode(On).id().id())));
+        assert!(report.contains(" | no |\n"));
+        assert!(report.ends_with(
+            "## Front panel\n\n- Resonance at 3 o'clock\n\n## Patch cables\n\n- LFO -> VCF\n"
+        ));
+    }
+}
@@ -0,0 +1,91 @@
+use rustron_lib::parser::neutron_message;
+use rustron_lib::protocol::{is_behringer_packet, NeutronMessage, SYSEX_MESSAGE_START};
+
+/// How much harm sending a message can do, in increasing order.
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+pub enum Risk {
+    /// Requests and channel messages, nothing is stored on the device
+    Safe,
+    /// Changes a setting, easily changed back
+    ModifiesSettings,
+    /// Restores the defaults, calibrates or writes firmware, hard or impossible to undo
+    Destructive,
+}
+
+const RISKS: [(&str, Risk); 3] = [
+    ("safe", Risk::Safe),
+    ("settings", Risk::ModifiesSettings),
+    ("destructive", Risk::Destructive),
+];
+
+impl Risk {
+    pub fn from_name(name: &str) -> Option<Risk> {
+        RISKS
+            .iter()
+            .find(|(n, _)| *n == name.trim())
+            .map(|(_, risk)| *risk)
+    }
+
+    pub fn name(self) -> &'static str {
+        // Every risk has a name
+        RISKS.iter().find(|(_, r)| *r == self).unwrap().0
+    }
+
+    /// The risk of sending `bytes`. SysEx messages to a Behringer device that aren't understood
+    /// are taken as destructive, they might as well be firmware.
+    pub fn of(bytes: &[u8]) -> Risk {
+        match neutron_message(bytes) {
+            Ok((_, message)) => match message {
+                NeutronMessage::RestoreGlobalSetting(_)
+                | NeutronMessage::CalibrationModeCommand(_) => Risk::Destructive,
+                NeutronMessage::SetGlobalSetting(..) => Risk::ModifiesSettings,
+                NeutronMessage::SoftwareVersionRequest(_)
+                | NeutronMessage::StateRequest(_)
+                | NeutronMessage::SoftwareVersionResponse(..)
+                | NeutronMessage::GlobalSettingUpdate(..)
+                | NeutronMessage::StateDump(..) => Risk::Safe,
+            },
+            Err(_) if is_behringer_packet(bytes) => Risk::Destructive,
+            Err(_) if bytes.first() == Some(&SYSEX_MESSAGE_START) => Risk::ModifiesSettings,
+            Err(_) => Risk::Safe,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rustron_lib::protocol::DeviceId::Multicast;
+    use rustron_lib::protocol::GlobalSetting::OscSync;
+    use rustron_lib::protocol::NeutronMessage::{
+        CalibrationModeCommand, RestoreGlobalSetting, SetGlobalSetting, StateRequest,
+    };
+    use rustron_lib::protocol::ToggleOption::On;
+
+    use crate::risk::Risk;
+
+    #[test]
+    fn messages_are_classified() {
+        assert_eq!(Risk::of(&StateRequest(Multicast).as_bytes()), Risk::Safe);
+        assert_eq!(Risk::of(&[0xb0, 123, 0]), Risk::Safe);
+        assert_eq!(
+            Risk::of(&SetGlobalSetting(Multicast, OscSync(On)).as_bytes()),
+            Risk::ModifiesSettings
+        );
+        assert_eq!(Risk::of(&[0xf0, 0x7d, 0x01, 0xf7]), Risk::ModifiesSettings);
+        assert_eq!(
+            Risk::of(&RestoreGlobalSetting(Multicast).as_bytes()),
+            Risk::Destructive
+        );
+        assert_eq!(
+            Risk::of(&CalibrationModeCommand(Multicast).as_bytes()),
+            Risk::Destructive
+        );
+        assert_eq!(
+            Risk::of(&[0xf0, 0x00, 0x20, 0x32, 0x28, 0x7f, 0x42, 0x01, 0xf7]),
+            Risk::Destructive
+        );
+        assert!(Risk::ModifiesSettings < Risk::Destructive);
+        assert_eq!(Risk::from_name("settings"), Some(Risk::ModifiesSettings));
+        assert_eq!(Risk::Destructive.name(), "destructive");
+    }
+}
@@ -0,0 +1,162 @@
+use std::error;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use rustron_lib::protocol::{Channel, GlobalSetting};
+use rustron_lib::setting::SettingId;
+
+/// How the emulated Neutron behaves, loaded from a scenario file to test how the client copes
+/// with a slow or unreliable device.
+///
+/// ```text
+/// version = 1.2.3
+/// channel = 2
+/// latency = 50
+/// drop_acks = 0.25
+/// garble = 0.05
+/// chunk = 64
+/// seed = 42
+///
+/// [settings]
+/// paraphonic = on
+/// osc1.range = 16
+/// ```
+///
+/// `latency` is in milliseconds, `drop_acks` and `garble` are the fractions of setting updates
+/// left out and of answers with a corrupted byte. `chunk` cuts answers into pieces of that many
+/// bytes, as WinMM delivers SysEx longer than its buffers. Settings use the ids of the setting
+/// registry.
+#[derive(Debug, PartialEq)]
+pub struct Scenario {
+    pub version: Option<String>,
+    pub channel: Option<Channel>,
+    pub latency: Duration,
+    pub drop_acks: f64,
+    pub garble: f64,
+    /// The size of the pieces answers arrive in, whole when `None`
+    pub chunk: Option<usize>,
+    /// Seeds the faults, so a scenario plays out the same every time
+    pub seed: u64,
+    pub settings: Vec<GlobalSetting>,
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Scenario {
+            version: None,
+            channel: None,
+            latency: Duration::from_millis(0),
+            drop_acks: 0.0,
+            garble: 0.0,
+            chunk: None,
+            seed: 1,
+            settings: Vec::new(),
+        }
+    }
+}
+
+fn parse_fraction(value: &str) -> Result<f64, Box<dyn error::Error>> {
+    match value.parse()? {
+        fraction if (0.0..=1.0).contains(&fraction) => Ok(fraction),
+        fraction => Err(format!("{} is not between 0 and 1", fraction).into()),
+    }
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Scenario, Box<dyn error::Error>> {
+        Scenario::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(input: &str) -> Result<Scenario, Box<dyn error::Error>> {
+        let mut scenario = Scenario::default();
+        let mut in_settings = false;
+        for (number, line) in input.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            scenario
+                .parse_line(&mut in_settings, line)
+                .map_err(|error| format!("line {}: {}", number + 1, error))?;
+        }
+        Ok(scenario)
+    }
+
+    fn parse_line(
+        &mut self,
+        in_settings: &mut bool,
+        line: &str,
+    ) -> Result<(), Box<dyn error::Error>> {
+        if line == "[settings]" {
+            *in_settings = true;
+            return Ok(());
+        }
+        let (key, value) = line
+            .split_once('=')
+            .map(|(key, value)| (key.trim(), value.trim()))
+            .ok_or_else(|| format!("invalid entry {}", line))?;
+        if *in_settings {
+            let id = SettingId::from_id(key).ok_or_else(|| format!("unknown setting {}", key))?;
+            self.settings.push(id.parse_value(value)?);
+            return Ok(());
+        }
+        match key {
+            "version" => self.version = Some(value.to_string()),
+            "channel" => {
+                self.channel = Some(
+                    Channel::from_number(value.parse()?)
+                        .ok_or_else(|| format!("invalid channel {}", value))?,
+                )
+            }
+            "latency" => self.latency = Duration::from_millis(value.parse()?),
+            "drop_acks" => self.drop_acks = parse_fraction(value)?,
+            "garble" => self.garble = parse_fraction(value)?,
+            "chunk" => {
+                self.chunk = Some(value.parse()?).filter(|size| *size > 0);
+                if self.chunk.is_none() {
+                    return Err("chunk must be at least 1".into());
+                }
+            }
+            "seed" => self.seed = value.parse()?,
+            _ => return Err(format!("unknown key {}", key).into()),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use rustron_lib::protocol::Channel::Two;
+    use rustron_lib::protocol::GlobalSetting::{Osc1Range, ParaphonicMode};
+    use rustron_lib::protocol::OscRange::Sixteen;
+    use rustron_lib::protocol::ToggleOption::On;
+
+    use crate::scenario::Scenario;
+
+    #[test]
+    fn scenario_is_parsed() {
+        let scenario = Scenario::parse(
+            "version = 1.2.3\nchannel = 2\nlatency = 50\ndrop_acks = 0.25\nchunk = 64\n\n\
+             [settings]\nparaphonic = on\nosc1.range = 16\n",
+        )
+        .unwrap();
+        assert_eq!(
+            scenario,
+            Scenario {
+                version: Some(String::from("1.2.3")),
+                channel: Some(Two),
+                latency: Duration::from_millis(50),
+                drop_acks: 0.25,
+                chunk: Some(64),
+                settings: vec![ParaphonicMode(On), Osc1Range(Sixteen)],
+                ..Default::default()
+            }
+        );
+        assert!(Scenario::parse("garble = 2\n").is_err());
+        assert!(Scenario::parse("chunk = 0\n").is_err());
+        assert!(Scenario::parse("[settings]\nfoo = on\n").is_err());
+    }
+}
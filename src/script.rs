@@ -0,0 +1,82 @@
+use std::error;
+use std::fs;
+use std::path::Path;
+
+use rustron_lib::parser::neutron_message;
+
+use crate::macros::Step;
+
+/// Default file name used when exporting or importing a script.
+pub const DEFAULT_SCRIPT_FILE: &str = "rustron-script.txt";
+
+/// Formats messages as a replayable script: one hex encoded message per line, preceded by a
+/// comment with the decoded message when it could be parsed.
+pub fn to_script(messages: &[Vec<u8>]) -> String {
+    let mut script = String::new();
+    for message in messages {
+        if let Ok((_, msg)) = neutron_message(message.as_slice()) {
+            script.push_str(&format!("# {}\n", msg));
+        }
+        script.push_str(&hex::encode(message));
+        script.push('\n');
+    }
+    script
+}
+
+/// Parses a script, ignoring empty lines and comments starting with `#`. Besides messages,
+/// lines like `wait 500ms` pause the script, see `Step`.
+pub fn parse_script(input: &str) -> Result<Vec<Step>, Box<dyn error::Error>> {
+    input
+        .lines()
+        .map(str::trim)
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| {
+            Step::parse(line).map_err(|error| format!("line {}: {}", number + 1, error).into())
+        })
+        .collect()
+}
+
+pub fn export(path: &Path, messages: &[Vec<u8>]) -> Result<(), Box<dyn error::Error>> {
+    fs::write(path, to_script(messages))?;
+    Ok(())
+}
+
+pub fn import(path: &Path) -> Result<Vec<Step>, Box<dyn error::Error>> {
+    parse_script(&fs::read_to_string(path)?)
+}
+
+#[cfg(test)]
+mod test {
+    use rustron_lib::protocol::DeviceId::Multicast;
+    use rustron_lib::protocol::GlobalSetting::ParaphonicMode;
+    use rustron_lib::protocol::NeutronMessage::SetGlobalSetting;
+    use rustron_lib::protocol::ToggleOption::On;
+
+    use std::time::Duration;
+
+    use crate::macros::Step;
+    use crate::script::{parse_script, to_script};
+
+    #[test]
+    fn script_round_trip() {
+        let messages = vec![
+            SetGlobalSetting(Multicast, ParaphonicMode(On)).as_bytes(),
+            vec![0xf0, 0x01, 0xf7],
+        ];
+        let script = to_script(&messages);
+        assert!(script.starts_with("# SetGlobalSetting"));
+        assert_eq!(
+            parse_script(&script).unwrap(),
+            messages.into_iter().map(Step::Message).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            parse_script("f0f7\nwait 100ms\n").unwrap(),
+            vec![
+                Step::Message(vec![0xf0, 0xf7]),
+                Step::Wait(Duration::from_millis(100))
+            ]
+        );
+        assert!(parse_script("f0zz").is_err());
+    }
+}
@@ -0,0 +1,237 @@
+use std::error;
+use std::fmt::Display;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use rustron_lib::protocol::{Channel, DeviceId};
+
+use crate::config::Config;
+
+const SESSION_FILE_NAME: &str = "session.txt";
+
+/// A message of the history with when it was sent or received, unknown for sessions saved
+/// without times.
+pub type LoggedMessage = (Option<SystemTime>, Vec<u8>);
+
+/// What is kept of a session between runs when `[session] restore` is enabled, stored next to
+/// the configuration in the same style. Messages are prefixed with when they were sent or
+/// received, in milliseconds since the Unix epoch.
+///
+/// ```text
+/// target = 3
+/// tab = inspect
+/// preset = bass
+///
+/// [sent]
+/// 1700000000000 f0002032287f0a0f01f7
+///
+/// [received]
+/// 1700000000042 f0002032287f5a010f01f7
+/// ```
+#[derive(Debug, PartialEq)]
+pub struct Session {
+    pub target: DeviceId,
+    /// Title of the selected tab
+    pub tab: String,
+    /// The last preset loaded from the library
+    pub preset: Option<String>,
+    pub sent: Vec<LoggedMessage>,
+    pub received: Vec<LoggedMessage>,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Session {
+            target: DeviceId::Multicast,
+            tab: String::new(),
+            preset: None,
+            sent: Vec::new(),
+            received: Vec::new(),
+        }
+    }
+}
+
+enum Section {
+    None,
+    Sent,
+    Received,
+}
+
+impl Session {
+    pub fn path() -> Option<PathBuf> {
+        Some(Config::data_directory()?.join(SESSION_FILE_NAME))
+    }
+
+    /// Loads the last session, `None` if there is none.
+    pub fn load() -> Result<Option<Session>, Box<dyn error::Error>> {
+        match Session::path() {
+            Some(path) if path.exists() => Ok(Some(Session::parse(&fs::read_to_string(path)?)?)),
+            _ => Ok(None),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn error::Error>> {
+        let path = Session::path().ok_or("Could not determine data directory.")?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.to_string())?;
+        Ok(())
+    }
+
+    pub fn parse(input: &str) -> Result<Session, Box<dyn error::Error>> {
+        let mut session = Session::default();
+        let mut section = Section::None;
+        for (number, line) in input.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            session
+                .parse_line(&mut section, line)
+                .map_err(|error| format!("line {}: {}", number + 1, error))?;
+        }
+        Ok(session)
+    }
+
+    fn parse_line(
+        &mut self,
+        section: &mut Section,
+        line: &str,
+    ) -> Result<(), Box<dyn error::Error>> {
+        match line {
+            "[sent]" => *section = Section::Sent,
+            "[received]" => *section = Section::Received,
+            _ => match (section, line.split_once('=')) {
+                (Section::None, Some((key, value))) => match (key.trim(), value.trim()) {
+                    ("target", "all") => self.target = DeviceId::Multicast,
+                    ("target", number) => {
+                        self.target = DeviceId::Channel(
+                            Channel::from_number(number.parse()?)
+                                .ok_or_else(|| format!("invalid target {}", number))?,
+                        )
+                    }
+                    ("tab", tab) => self.tab = tab.to_string(),
+                    ("preset", preset) => self.preset = Some(preset.to_string()),
+                    (key, _) => return Err(format!("unknown key {}", key).into()),
+                },
+                (Section::Sent, None) => self.sent.push(parse_message(line)?),
+                (Section::Received, None) => self.received.push(parse_message(line)?),
+                _ => return Err(format!("invalid entry {}", line).into()),
+            },
+        }
+        Ok(())
+    }
+}
+
+fn parse_message(line: &str) -> Result<LoggedMessage, Box<dyn error::Error>> {
+    match line.split_once(' ') {
+        Some((millis, bytes)) => Ok((
+            Some(UNIX_EPOCH + Duration::from_millis(millis.parse()?)),
+            hex::decode(bytes.trim())?,
+        )),
+        None => Ok((None, hex::decode(line)?)),
+    }
+}
+
+fn write_message(
+    f: &mut std::fmt::Formatter<'_>,
+    (time, bytes): &LoggedMessage,
+) -> std::fmt::Result {
+    match time.and_then(|time| time.duration_since(UNIX_EPOCH).ok()) {
+        Some(since_epoch) => writeln!(f, "{} {}", since_epoch.as_millis(), hex::encode(bytes)),
+        None => writeln!(f, "{}", hex::encode(bytes)),
+    }
+}
+
+/// The wall clock time of `instant`, given the current time on both clocks.
+pub fn wall_time(instant: Instant, now: (Instant, SystemTime)) -> SystemTime {
+    now.1 - now.0.saturating_duration_since(instant)
+}
+
+/// The instant of the wall clock time `time`, given the current time on both clocks. Times the
+/// monotonic clock can't go back to are taken as `now`.
+pub fn instant(time: SystemTime, now: (Instant, SystemTime)) -> Instant {
+    now.1
+        .duration_since(time)
+        .ok()
+        .and_then(|age| now.0.checked_sub(age))
+        .unwrap_or(now.0)
+}
+
+impl Display for Session {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.target {
+            DeviceId::Multicast => writeln!(f, "target = all")?,
+            DeviceId::Channel(channel) => writeln!(f, "target = {}", channel.number())?,
+        }
+        writeln!(f, "tab = {}", self.tab)?;
+        if let Some(preset) = &self.preset {
+            writeln!(f, "preset = {}", preset)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "[sent]")?;
+        for message in &self.sent {
+            write_message(f, message)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "[received]")?;
+        for message in &self.received {
+            write_message(f, message)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+    use rustron_lib::protocol::{Channel, DeviceId};
+
+    use crate::session::{instant, wall_time, Session};
+
+    #[test]
+    fn session_round_trip() {
+        let at = |millis| Some(UNIX_EPOCH + Duration::from_millis(millis));
+        let session = Session {
+            target: DeviceId::Channel(Channel::Three),
+            tab: String::from("inspect"),
+            preset: Some(String::from("bass")),
+            sent: vec![
+                (at(1_700_000_000_000), vec![0xf0, 0x01, 0xf7]),
+                (at(1_700_000_000_250), vec![0xf0, 0x02, 0xf7]),
+            ],
+            received: vec![(None, vec![0x90, 0x3c, 0x40])],
+        };
+        assert_eq!(Session::parse(&session.to_string()).unwrap(), session);
+        assert_eq!(
+            Session::parse(&Session::default().to_string()).unwrap(),
+            Session::default()
+        );
+    }
+
+    #[test]
+    fn times_are_kept_across_clocks() {
+        let now = (Instant::now(), SystemTime::now());
+        let earlier = now.0 - Duration::from_millis(1500);
+        let time = wall_time(earlier, now);
+        assert_eq!(
+            now.1.duration_since(time).unwrap(),
+            Duration::from_millis(1500)
+        );
+        assert_eq!(instant(time, now), earlier);
+        // From the future, as with a clock set back since
+        assert_eq!(instant(now.1 + Duration::from_secs(5), now), now.0);
+    }
+
+    #[test]
+    fn invalid_entries_are_rejected() {
+        assert!(Session::parse("target = 17\n").is_err());
+        assert!(Session::parse("f0f7\n").is_err());
+        assert!(Session::parse("[sent]\nzz\n").is_err());
+        assert!(Session::parse("[sent]\nsoon f0f7\n").is_err());
+        assert!(Session::parse("[received]\nkey = value\n").is_err());
+    }
+}
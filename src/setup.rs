@@ -0,0 +1,97 @@
+use std::time::{Duration, Instant};
+
+/// Where the first run setup is.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SetupStep {
+    /// Picking which of the MIDI ports is the Neutron
+    SelectPort,
+    /// Waiting for the Neutron to answer the version and state requests, since the instant
+    Querying(Instant),
+    /// Showing the result, the configuration is written when leaving
+    Done,
+}
+
+/// Walks through connecting to the Neutron on the first run, before any configuration has been
+/// written.
+pub struct SetupWizard {
+    pub step: SetupStep,
+    /// Names of the MIDI outputs found
+    pub ports: Vec<String>,
+    pub selection: usize,
+    /// Whether the Neutron answered, known once done
+    pub answered: bool,
+}
+
+impl SetupWizard {
+    pub fn new(ports: Vec<String>) -> SetupWizard {
+        // Most likely the Neutron is the one called so
+        let selection = ports
+            .iter()
+            .position(|port| port.starts_with("Neutron"))
+            .unwrap_or(0);
+        SetupWizard {
+            step: SetupStep::SelectPort,
+            ports,
+            selection,
+            answered: false,
+        }
+    }
+
+    pub fn select_next(&mut self) {
+        if !self.ports.is_empty() {
+            self.selection = (self.selection + 1) % self.ports.len();
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        if !self.ports.is_empty() {
+            self.selection = (self.selection + self.ports.len() - 1) % self.ports.len();
+        }
+    }
+
+    pub fn selected_port(&self) -> Option<&str> {
+        self.ports.get(self.selection).map(String::as_str)
+    }
+
+    /// Moves on to waiting for the Neutron on the selected port.
+    pub fn start_query(&mut self, now: Instant) {
+        self.step = SetupStep::Querying(now);
+    }
+
+    /// Finishes the query once the Neutron `answered` both requests or `timeout` has passed.
+    pub fn update(&mut self, answered: bool, timeout: Duration, now: Instant) {
+        if let SetupStep::Querying(started) = self.step {
+            if answered || now.duration_since(started) > timeout {
+                self.answered = answered;
+                self.step = SetupStep::Done;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use crate::setup::{SetupStep, SetupWizard};
+
+    #[test]
+    fn wizard_waits_for_the_neutron() {
+        let mut wizard = SetupWizard::new(vec![
+            String::from("Midi Through"),
+            String::from("Neutron(1)"),
+        ]);
+        assert_eq!(wizard.selected_port(), Some("Neutron(1)"));
+        wizard.select_next();
+        assert_eq!(wizard.selected_port(), Some("Midi Through"));
+
+        let start = Instant::now();
+        let timeout = Duration::from_secs(2);
+        wizard.start_query(start);
+        wizard.update(false, timeout, start + Duration::from_secs(1));
+        assert_eq!(wizard.step, SetupStep::Querying(start));
+        wizard.update(false, timeout, start + Duration::from_secs(3));
+        assert_eq!(wizard.step, SetupStep::Done);
+        assert!(!wizard.answered);
+    }
+}
@@ -0,0 +1,419 @@
+use std::error;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+/// Default file name used when recording a session.
+pub const DEFAULT_SMF_FILE: &str = "rustron-session.mid";
+
+/// Ticks per quarter note, with the default tempo of 120 bpm one tick is a millisecond.
+const TICKS_PER_QUARTER: u16 = 500;
+const MICROSECONDS_PER_QUARTER: u32 = 500_000;
+
+/// A message and when it was sent or received, relative to the start of the session.
+pub type TimedMessage = (Duration, Vec<u8>);
+
+/// Writes a variable length quantity, as used for delta times and event lengths.
+fn write_var_len(bytes: &mut Vec<u8>, mut value: u32) {
+    let mut buffer = [0u8; 5];
+    let mut length = 0;
+    loop {
+        buffer[length] = (value & 0x7f) as u8;
+        length += 1;
+        value >>= 7;
+        if value == 0 {
+            break;
+        }
+    }
+    for (i, byte) in buffer[..length].iter().rev().enumerate() {
+        bytes.push(if i + 1 < length { byte | 0x80 } else { *byte });
+    }
+}
+
+fn write_meta(track: &mut Vec<u8>, kind: u8, data: &[u8]) {
+    track.extend_from_slice(&[0x00, 0xff, kind]);
+    write_var_len(track, data.len() as u32);
+    track.extend_from_slice(data);
+}
+
+fn write_chunk(smf: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    smf.extend_from_slice(kind);
+    smf.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    smf.extend_from_slice(data);
+}
+
+/// A track with a name and one SysEx event per message. Anything not starting with `0xf0` is
+/// stored as an escape event so it's still written as is.
+fn message_track(name: &str, messages: &[TimedMessage]) -> Vec<u8> {
+    let mut track = Vec::new();
+    write_meta(&mut track, 0x03, name.as_bytes());
+    let mut sorted: Vec<&TimedMessage> = messages.iter().filter(|(_, m)| !m.is_empty()).collect();
+    sorted.sort_by_key(|(time, _)| *time);
+    let mut previous = 0;
+    for (time, message) in sorted {
+        let tick = time.as_millis().min(u128::from(u32::MAX >> 4)) as u32;
+        write_var_len(&mut track, tick - previous);
+        previous = tick;
+        match message.split_first() {
+            Some((0xf0, data)) => {
+                track.push(0xf0);
+                write_var_len(&mut track, data.len() as u32);
+                track.extend_from_slice(data);
+            }
+            _ => {
+                track.push(0xf7);
+                write_var_len(&mut track, message.len() as u32);
+                track.extend_from_slice(message);
+            }
+        }
+    }
+    track.extend_from_slice(&[0x00, 0xff, 0x2f, 0x00]);
+    track
+}
+
+/// Formats a session as a format 1 Standard MIDI File: a tempo track followed by a track of sent
+/// and a track of received messages.
+pub fn to_smf(sent: &[TimedMessage], received: &[TimedMessage]) -> Vec<u8> {
+    let mut smf = Vec::new();
+    let mut header = vec![0x00, 0x01, 0x00, 0x03];
+    header.extend_from_slice(&TICKS_PER_QUARTER.to_be_bytes());
+    write_chunk(&mut smf, b"MThd", &header);
+
+    let mut tempo = Vec::new();
+    write_meta(&mut tempo, 0x03, b"rustron");
+    write_meta(
+        &mut tempo,
+        0x51,
+        &MICROSECONDS_PER_QUARTER.to_be_bytes()[1..],
+    );
+    tempo.extend_from_slice(&[0x00, 0xff, 0x2f, 0x00]);
+    write_chunk(&mut smf, b"MTrk", &tempo);
+
+    write_chunk(&mut smf, b"MTrk", &message_track("Sent", sent));
+    write_chunk(&mut smf, b"MTrk", &message_track("Received", received));
+    smf
+}
+
+pub fn export(
+    path: &Path,
+    sent: &[TimedMessage],
+    received: &[TimedMessage],
+) -> Result<(), Box<dyn error::Error>> {
+    fs::write(path, to_smf(sent, received))?;
+    Ok(())
+}
+
+/// Reads the chunks and events of a file, keeping track of the position for error messages.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], Box<dyn error::Error>> {
+        if self.bytes.len() - self.position < length {
+            return Err(format!("unexpected end of file at byte {}", self.position).into());
+        }
+        let taken = &self.bytes[self.position..self.position + length];
+        self.position += length;
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> Result<u8, Box<dyn error::Error>> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Box<dyn error::Error>> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, Box<dyn error::Error>> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn var_len(&mut self) -> Result<u32, Box<dyn error::Error>> {
+        let mut value = 0u32;
+        for _ in 0..4 {
+            let byte = self.byte()?;
+            value = (value << 7) | u32::from(byte & 0x7f);
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(format!("variable length value too long at byte {}", self.position).into())
+    }
+
+    fn chunk(&mut self) -> Result<(&'a [u8], Reader<'a>), Box<dyn error::Error>> {
+        let kind = self.take(4)?;
+        let length = self.u32()? as usize;
+        let data = self.take(length)?;
+        Ok((
+            kind,
+            Reader {
+                bytes: data,
+                position: 0,
+            },
+        ))
+    }
+}
+
+/// What a track event means for import, everything else is skipped.
+enum Event {
+    SysEx(Vec<u8>),
+    /// Microseconds per quarter note from here on
+    Tempo(u32),
+}
+
+/// Reads the events of a track with their absolute tick.
+fn track_events(mut track: Reader) -> Result<Vec<(u64, Event)>, Box<dyn error::Error>> {
+    let mut events = Vec::new();
+    let mut tick = 0u64;
+    let mut running_status = None;
+    while track.position < track.bytes.len() {
+        tick += u64::from(track.var_len()?);
+        let status = track.byte()?;
+        match status {
+            0xf0 => {
+                let length = track.var_len()? as usize;
+                let mut message = vec![0xf0];
+                message.extend_from_slice(track.take(length)?);
+                events.push((tick, Event::SysEx(message)));
+                running_status = None;
+            }
+            0xf7 => {
+                // Escaped bytes, only complete messages are kept
+                let length = track.var_len()? as usize;
+                let data = track.take(length)?;
+                if data.first() == Some(&0xf0) {
+                    events.push((tick, Event::SysEx(data.to_vec())));
+                }
+                running_status = None;
+            }
+            0xff => {
+                let kind = track.byte()?;
+                let length = track.var_len()? as usize;
+                let data = track.take(length)?;
+                match (kind, data) {
+                    (0x2f, _) => break,
+                    (0x51, [a, b, c]) => {
+                        events.push((tick, Event::Tempo(u32::from_be_bytes([0, *a, *b, *c]))))
+                    }
+                    _ => {}
+                }
+            }
+            _ => {
+                let (status, data_read) = if status & 0x80 != 0 {
+                    running_status = Some(status);
+                    (status, 0)
+                } else {
+                    // Running status, this byte was the first data byte
+                    let running = running_status.ok_or_else(|| {
+                        format!("data byte without status at byte {}", track.position)
+                    })?;
+                    (running, 1)
+                };
+                let data_length = match status & 0xf0 {
+                    0xc0 | 0xd0 => 1,
+                    0x80..=0xe0 => 2,
+                    _ => return Err(format!("unsupported status {:#04x}", status).into()),
+                };
+                track.take(data_length - data_read)?;
+            }
+        }
+    }
+    Ok(events)
+}
+
+/// Extracts the SysEx messages of a Standard MIDI File with their time from the start, in order.
+pub fn from_smf(bytes: &[u8]) -> Result<Vec<TimedMessage>, Box<dyn error::Error>> {
+    let mut reader = Reader { bytes, position: 0 };
+    let (kind, mut header) = reader.chunk()?;
+    if kind != b"MThd" {
+        return Err("not a MIDI file".into());
+    }
+    let _format = header.u16()?;
+    let track_count = header.u16()?;
+    let division = header.u16()?;
+
+    let mut events = Vec::new();
+    let mut tracks = 0;
+    while tracks < track_count && reader.position < bytes.len() {
+        let (kind, track) = reader.chunk()?;
+        // Unknown chunks are to be skipped
+        if kind == b"MTrk" {
+            events.extend(track_events(track)?);
+            tracks += 1;
+        }
+    }
+    events.sort_by_key(|(tick, _)| *tick);
+
+    let mut messages = Vec::new();
+    let overflow = || "event time out of range";
+    if division & 0x8000 != 0 {
+        // SMPTE: frames per second as a negative number and ticks per frame
+        let frames = ((division >> 8) as u8 as i8)
+            .checked_neg()
+            .ok_or("invalid time division")?;
+        let ticks_per_second = u64::from(frames as u8) * u64::from(division & 0xff);
+        if ticks_per_second == 0 {
+            return Err("invalid time division".into());
+        }
+        for (tick, event) in events {
+            if let Event::SysEx(message) = event {
+                let micros = tick.checked_mul(1_000_000).ok_or_else(overflow)? / ticks_per_second;
+                messages.push((Duration::from_micros(micros), message));
+            }
+        }
+    } else {
+        let ticks_per_quarter = u64::from(division);
+        if ticks_per_quarter == 0 {
+            return Err("invalid time division".into());
+        }
+        let mut tempo = u64::from(MICROSECONDS_PER_QUARTER);
+        let mut tempo_tick = 0;
+        let mut tempo_micros = 0u64;
+        for (tick, event) in events {
+            let micros = (tick - tempo_tick)
+                .checked_mul(tempo)
+                .and_then(|elapsed| tempo_micros.checked_add(elapsed / ticks_per_quarter))
+                .ok_or_else(overflow)?;
+            match event {
+                Event::SysEx(message) => messages.push((Duration::from_micros(micros), message)),
+                Event::Tempo(new_tempo) => {
+                    tempo = u64::from(new_tempo);
+                    tempo_tick = tick;
+                    tempo_micros = micros;
+                }
+            }
+        }
+    }
+    Ok(messages)
+}
+
+pub fn import(path: &Path) -> Result<Vec<TimedMessage>, Box<dyn error::Error>> {
+    from_smf(&fs::read(path)?)
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::smf::{from_smf, to_smf, write_var_len};
+
+    #[test]
+    fn var_len() {
+        for (value, expected) in [
+            (0, vec![0x00]),
+            (0x7f, vec![0x7f]),
+            (0x80, vec![0x81, 0x00]),
+            (0x3fff, vec![0xff, 0x7f]),
+            (0x0fff_ffff, vec![0xff, 0xff, 0xff, 0x7f]),
+        ]
+        .iter()
+        {
+            let mut bytes = Vec::new();
+            write_var_len(&mut bytes, *value);
+            assert_eq!(&bytes, expected);
+        }
+    }
+
+    #[test]
+    fn session_to_smf() {
+        let sent = vec![(Duration::from_millis(200), vec![0xf0, 0x01, 0x02, 0xf7])];
+        let received = vec![
+            (Duration::from_millis(300), vec![0x90, 0x3c, 0x40]),
+            (Duration::from_millis(100), vec![0xf0, 0x03, 0xf7]),
+        ];
+        let smf = to_smf(&sent, &received);
+        assert_eq!(
+            &smf[..14],
+            &[b'M', b'T', b'h', b'd', 0, 0, 0, 6, 0, 1, 0, 3, 0x01, 0xf4]
+        );
+
+        let sent_track = [
+            0x00, 0xff, 0x03, 0x04, b'S', b'e', b'n', b't', // name
+            0x81, 0x48, 0xf0, 0x03, 0x01, 0x02, 0xf7, // SysEx at 200 ms
+            0x00, 0xff, 0x2f, 0x00,
+        ];
+        let received_track = [
+            0x00, 0xff, 0x03, 0x08, b'R', b'e', b'c', b'e', b'i', b'v', b'e', b'd', // name
+            0x64, 0xf0, 0x02, 0x03, 0xf7, // SysEx at 100 ms
+            0x81, 0x48, 0xf7, 0x03, 0x90, 0x3c, 0x40, // escaped at 300 ms
+            0x00, 0xff, 0x2f, 0x00,
+        ];
+        let tracks = &smf[14 + 8 + 22..];
+        assert_eq!(&tracks[..4], b"MTrk");
+        assert_eq!(&tracks[8..8 + sent_track.len()], &sent_track[..]);
+        let tracks = &tracks[8 + sent_track.len()..];
+        assert_eq!(&tracks[8..], &received_track[..]);
+    }
+
+    #[test]
+    fn session_round_trip() {
+        let sent = vec![
+            (Duration::from_millis(0), vec![0xf0, 0x01, 0xf7]),
+            (Duration::from_millis(1500), vec![0xf0, 0x02, 0xf7]),
+        ];
+        let received = vec![(Duration::from_millis(700), vec![0xf0, 0x03, 0xf7])];
+        let smf = to_smf(&sent, &received);
+        assert_eq!(
+            from_smf(&smf).unwrap(),
+            vec![
+                (Duration::from_millis(0), vec![0xf0, 0x01, 0xf7]),
+                (Duration::from_millis(700), vec![0xf0, 0x03, 0xf7]),
+                (Duration::from_millis(1500), vec![0xf0, 0x02, 0xf7]),
+            ]
+        );
+    }
+
+    #[test]
+    fn tempo_changes_and_channel_messages() {
+        let track = [
+            0x00, 0x90, 0x3c, 0x40, // note on
+            0x60, 0x3c, 0x00, // running status note off after a quarter
+            0x00, 0xff, 0x51, 0x03, 0x0f, 0x42, 0x40, // 1 s per quarter
+            0x00, 0xc0, 0x05, // program change
+            0x60, 0xf0, 0x02, 0x01, 0xf7, // SysEx after another quarter
+            0x00, 0xff, 0x2f, 0x00,
+        ];
+        let mut smf = vec![
+            b'M', b'T', b'h', b'd', 0, 0, 0, 6, 0, 0, 0, 1, 0, 0x60, b'M', b'T', b'r', b'k', 0, 0,
+            0,
+        ];
+        smf.push(track.len() as u8);
+        smf.extend_from_slice(&track);
+        assert_eq!(
+            from_smf(&smf).unwrap(),
+            vec![(Duration::from_millis(1500), vec![0xf0, 0x01, 0xf7])]
+        );
+        assert!(from_smf(&smf[..smf.len() - 3]).is_err());
+        assert!(from_smf(b"RIFF").is_err());
+    }
+
+    #[test]
+    fn times_out_of_range_are_errors() {
+        let smf = |division: [u8; 2], track: &[u8]| {
+            let mut smf = vec![b'M', b'T', b'h', b'd', 0, 0, 0, 6, 0, 0, 0, 1];
+            smf.extend_from_slice(&division);
+            smf.extend_from_slice(b"MTrk");
+            smf.extend_from_slice(&(track.len() as u32).to_be_bytes());
+            smf.extend_from_slice(track);
+            smf
+        };
+        let sysex = [0x00, 0xf0, 0x02, 0x01, 0xf7];
+        // -128 frames per second has no positive counterpart
+        assert!(from_smf(&smf([0x80, 0x04], &sysex)).is_err());
+        assert_eq!(
+            from_smf(&smf([0xe7, 0x28], &sysex)).unwrap(),
+            vec![(Duration::from_millis(0), vec![0xf0, 0x01, 0xf7])]
+        );
+        // The slowest tempo at one tick per quarter, for longer than a u64 of microseconds
+        let mut track = vec![0x00, 0xff, 0x51, 0x03, 0xff, 0xff, 0xff];
+        for _ in 0..5000 {
+            track.extend_from_slice(&[0xff, 0xff, 0xff, 0x7f, 0xf0, 0x02, 0x01, 0xf7]);
+        }
+        assert!(from_smf(&smf([0x00, 0x01], &track)).is_err());
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use rustron_lib::protocol::NeutronMessage;
+
+use crate::history::Message;
+
+/// How many seconds of byte counts are kept for the rate history.
+pub const RATE_HISTORY: usize = 60;
+
+/// A short name for the kind of message, to count messages by.
+pub fn kind(message: &Message) -> &'static str {
+    match message {
+        Message::Parsed(msg) => match msg {
+            NeutronMessage::SetGlobalSetting(..) => "set setting",
+            NeutronMessage::RestoreGlobalSetting(..) => "restore settings",
+            NeutronMessage::CalibrationModeCommand(..) => "calibration mode",
+            NeutronMessage::SoftwareVersionRequest(..) => "version request",
+            NeutronMessage::SoftwareVersionResponse(..) => "version response",
+            NeutronMessage::GlobalSettingUpdate(..) => "setting update",
+            NeutronMessage::StateRequest(..) => "state request",
+            NeutronMessage::StateDump(..) => "state dump",
+        },
+        Message::Raw(_) => "unparsed",
+    }
+}
+
+/// Message and byte counts of one direction.
+pub struct StreamStats {
+    pub messages: u64,
+    pub bytes: u64,
+    pub by_kind: BTreeMap<&'static str, u64>,
+    /// Bytes per second, the last entry is the current second
+    pub rate: VecDeque<u64>,
+    second_start: Instant,
+}
+
+impl StreamStats {
+    pub fn new(now: Instant) -> StreamStats {
+        StreamStats {
+            messages: 0,
+            bytes: 0,
+            by_kind: BTreeMap::new(),
+            rate: vec![0].into(),
+            second_start: now,
+        }
+    }
+
+    pub fn record(&mut self, message: &Message, now: Instant) {
+        self.advance(now);
+        let length = message.to_bytes().len() as u64;
+        self.messages += 1;
+        self.bytes += length;
+        *self.by_kind.entry(kind(message)).or_insert(0) += 1;
+        if let Some(current) = self.rate.back_mut() {
+            *current += length;
+        }
+    }
+
+    /// Starts new seconds in the rate history up to `now`.
+    pub fn advance(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.second_start).as_secs();
+        if elapsed == 0 {
+            return;
+        }
+        for _ in 0..elapsed.min(RATE_HISTORY as u64) {
+            self.rate.push_back(0);
+        }
+        while self.rate.len() > RATE_HISTORY {
+            self.rate.pop_front();
+        }
+        self.second_start += Duration::from_secs(elapsed);
+    }
+
+    /// Bytes during the last complete second.
+    pub fn bytes_per_second(&self) -> u64 {
+        self.rate.iter().rev().nth(1).copied().unwrap_or(0)
+    }
+
+    /// Share of the messages that couldn't be parsed, 0-1.
+    pub fn unparsed_rate(&self) -> f64 {
+        match self.messages {
+            0 => 0.0,
+            messages => *self.by_kind.get("unparsed").unwrap_or(&0) as f64 / messages as f64,
+        }
+    }
+}
+
+/// Counts of sent and received messages, for spotting periodic traffic and flooding.
+pub struct Stats {
+    pub sent: StreamStats,
+    pub received: StreamStats,
+}
+
+impl Stats {
+    pub fn new(now: Instant) -> Stats {
+        Stats {
+            sent: StreamStats::new(now),
+            received: StreamStats::new(now),
+        }
+    }
+
+    pub fn advance(&mut self, now: Instant) {
+        self.sent.advance(now);
+        self.received.advance(now);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use rustron_lib::protocol::DeviceId::Multicast;
+    use rustron_lib::protocol::NeutronMessage::StateRequest;
+
+    use crate::history::Message;
+    use crate::stats::{StreamStats, RATE_HISTORY};
+
+    #[test]
+    fn messages_are_counted_by_kind_and_second() {
+        let start = Instant::now();
+        let mut stats = StreamStats::new(start);
+        let request = Message::Parsed(StateRequest(Multicast));
+        stats.record(&request, start);
+        stats.record(&request, start + Duration::from_millis(900));
+        stats.record(&Message::Raw(vec![0xf0, 0xf7]), start);
+        assert_eq!(stats.messages, 3);
+        assert_eq!(stats.bytes, 2 * 8 + 2);
+        assert_eq!(stats.by_kind.get("state request"), Some(&2));
+        assert!((stats.unparsed_rate() - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(stats.bytes_per_second(), 0);
+
+        stats.advance(start + Duration::from_millis(1500));
+        assert_eq!(stats.bytes_per_second(), 18);
+        stats.advance(start + Duration::from_secs(3));
+        assert_eq!(stats.rate, vec![18, 0, 0, 0]);
+        stats.advance(start + Duration::from_secs(1000));
+        assert_eq!(stats.rate.len(), RATE_HISTORY);
+        assert!(stats.rate.iter().all(|bytes| *bytes == 0));
+    }
+}
@@ -0,0 +1,256 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use rustron_lib::protocol::GlobalSetting::LfoMidiSync;
+use rustron_lib::protocol::ToggleOption::{Off, On};
+use rustron_lib::protocol::{GlobalSetting, NeutronMessage, ToggleOption};
+
+use crate::poll;
+
+/// The MIDI clock message, sent 24 times per quarter note.
+pub const CLOCK: u8 = 0xf8;
+const CLOCKS_PER_BEAT: u32 = 24;
+
+/// The tempo of the clock sent by the check unless one was tapped in, see `TapTempo`.
+pub const DEFAULT_BPM: u32 = 120;
+
+/// How long the LFO runs free and then synced.
+pub const PHASE_DURATION: Duration = Duration::from_secs(4);
+
+/// How often the state is requested during the check, answered by the messages that follow the
+/// state dump.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Phase {
+    /// The clock runs with MIDI sync off
+    Free,
+    /// The clock runs with MIDI sync on
+    Synced,
+    Done,
+}
+
+/// Something the check needs sent, see `SyncCheck::due`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Step {
+    Clock,
+    Setting(GlobalSetting),
+    Poll,
+}
+
+/// The outcome of a check.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SyncReport {
+    pub bpm: u32,
+    /// Whether the Neutron acknowledged turning MIDI sync on
+    pub acknowledged: bool,
+    /// Messages following the state dumps, with MIDI sync off and on
+    pub follow_ups: [usize; 2],
+}
+
+impl SyncReport {
+    /// Whether the Neutron took MIDI sync on and kept reporting while clocked. This is not a
+    /// pass: whether the LFO locked to the clock can't be told yet, the follow-up messages aren't
+    /// understood.
+    pub fn responded(&self) -> bool {
+        self.acknowledged && self.follow_ups[1] > 0
+    }
+}
+
+impl fmt::Display for SyncReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "LFO sync check at {} BPM: {}, MIDI sync {}, {} follow-ups free and {} synced",
+            self.bpm,
+            if self.responded() {
+                "responded (lock not verified)"
+            } else {
+                "no response"
+            },
+            if self.acknowledged {
+                "acknowledged"
+            } else {
+                "not acknowledged"
+            },
+            self.follow_ups[0],
+            self.follow_ups[1]
+        )
+    }
+}
+
+/// Sends MIDI clock at a known tempo, first with the LFO running free and then with MIDI sync
+/// on, watching how the Neutron responds. MIDI sync is set back to `restore` at the end.
+pub struct SyncCheck {
+    pub bpm: u32,
+    pub phase: Phase,
+    started: Instant,
+    clocks_sent: u32,
+    /// When the clock last took its tempo and the clocks due by then, see `set_bpm`
+    clock_origin: Instant,
+    clocks_at_origin: u32,
+    last_poll: Option<Instant>,
+    restore: ToggleOption,
+    acknowledged: bool,
+    follow_ups: [usize; 2],
+}
+
+impl SyncCheck {
+    pub fn start(bpm: u32, restore: ToggleOption, now: Instant) -> SyncCheck {
+        SyncCheck {
+            bpm: bpm.max(1),
+            phase: Phase::Free,
+            started: now,
+            clocks_sent: 0,
+            clock_origin: now,
+            clocks_at_origin: 1,
+            last_poll: None,
+            restore,
+            acknowledged: false,
+            follow_ups: [0, 0],
+        }
+    }
+
+    /// What MIDI sync is set back to at the end, also when the check is stopped early.
+    pub fn restore(&self) -> ToggleOption {
+        self.restore
+    }
+
+    /// What is due at `now`: the clocks since the last call, the MIDI sync setting when the
+    /// phase changes and state requests.
+    pub fn due(&mut self, now: Instant) -> Vec<Step> {
+        let mut steps = Vec::new();
+        let elapsed = now.saturating_duration_since(self.started);
+        if self.phase == Phase::Done {
+            return steps;
+        }
+        if self.clocks_sent == 0 {
+            steps.push(Step::Setting(LfoMidiSync(Off)));
+        }
+        if self.phase == Phase::Free && elapsed >= PHASE_DURATION {
+            self.phase = Phase::Synced;
+            steps.push(Step::Setting(LfoMidiSync(On)));
+        }
+        if elapsed >= PHASE_DURATION * 2 {
+            self.phase = Phase::Done;
+            steps.push(Step::Setting(LfoMidiSync(self.restore)));
+            return steps;
+        }
+        let since_origin = now.saturating_duration_since(self.clock_origin);
+        let clocks = self.clocks_at_origin
+            + (since_origin.as_secs_f64() * f64::from(self.bpm * CLOCKS_PER_BEAT) / 60.0) as u32;
+        steps.extend((self.clocks_sent..clocks).map(|_| Step::Clock));
+        self.clocks_sent = clocks;
+        if self
+            .last_poll
+            .is_none_or(|last| now.saturating_duration_since(last) >= POLL_INTERVAL)
+        {
+            self.last_poll = Some(now);
+            steps.push(Step::Poll);
+        }
+        steps
+    }
+
+    /// Follows a new tempo from `now` on, without a jump in the clocks already sent.
+    pub fn set_bpm(&mut self, bpm: u32, now: Instant) {
+        self.bpm = bpm.max(1);
+        self.clock_origin = now;
+        self.clocks_at_origin = self.clocks_sent;
+    }
+
+    /// Watches a message from the Neutron for the acknowledgement of MIDI sync.
+    pub fn receive(&mut self, message: &NeutronMessage) {
+        if let NeutronMessage::GlobalSettingUpdate(_, LfoMidiSync(On)) = message {
+            if self.phase == Phase::Synced {
+                self.acknowledged = true;
+            }
+        }
+    }
+
+    /// Counts `bytes` if it is one of the messages following a state dump.
+    pub fn receive_raw(&mut self, bytes: &[u8]) {
+        if !poll::is_follow_up(bytes) {
+            return;
+        }
+        match self.phase {
+            Phase::Free => self.follow_ups[0] += 1,
+            Phase::Synced => self.follow_ups[1] += 1,
+            Phase::Done => {}
+        }
+    }
+
+    /// The outcome, once done.
+    pub fn report(&self) -> Option<SyncReport> {
+        if self.phase != Phase::Done {
+            return None;
+        }
+        Some(SyncReport {
+            bpm: self.bpm,
+            acknowledged: self.acknowledged,
+            follow_ups: self.follow_ups,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use rustron_lib::protocol::DeviceId::Multicast;
+    use rustron_lib::protocol::GlobalSetting::LfoMidiSync;
+    use rustron_lib::protocol::NeutronMessage::GlobalSettingUpdate;
+    use rustron_lib::protocol::ToggleOption::{Off, On};
+
+    use crate::synccheck::{Phase, Step, SyncCheck, PHASE_DURATION};
+
+    const FOLLOW_UP: [u8; 11] = [
+        0xf0, 0x00, 0x20, 0x32, 0x28, 0x00, 0x72, 0x01, 0x12, 0x34, 0xf7,
+    ];
+
+    #[test]
+    fn clock_is_sent_while_toggling_midi_sync() {
+        let start = Instant::now();
+        let mut check = SyncCheck::start(120, Off, start);
+        let steps = check.due(start);
+        assert_eq!(
+            steps,
+            [Step::Setting(LfoMidiSync(Off)), Step::Clock, Step::Poll]
+        );
+        // 48 clocks per second at 120 BPM
+        let steps = check.due(start + Duration::from_millis(500));
+        assert_eq!(
+            steps.iter().filter(|step| **step == Step::Clock).count(),
+            24
+        );
+        check.receive_raw(&FOLLOW_UP);
+
+        let steps = check.due(start + PHASE_DURATION);
+        assert_eq!(check.phase, Phase::Synced);
+        assert_eq!(steps[0], Step::Setting(LfoMidiSync(On)));
+        assert!(steps.contains(&Step::Poll));
+        check.receive(&GlobalSettingUpdate(Multicast, LfoMidiSync(On)));
+        assert_eq!(check.report(), None);
+
+        let steps = check.due(start + PHASE_DURATION * 2);
+        assert_eq!(steps, [Step::Setting(LfoMidiSync(Off))]);
+        let report = check.report().unwrap();
+        assert!(!report.responded());
+        assert_eq!(report.follow_ups, [1, 0]);
+        assert_eq!(
+            report.to_string(),
+            "LFO sync check at 120 BPM: no response, MIDI sync acknowledged, 1 follow-ups free and 0 synced"
+        );
+    }
+
+    #[test]
+    fn clock_follows_a_new_tempo() {
+        let start = Instant::now();
+        let mut check = SyncCheck::start(120, Off, start);
+        let clocks = |steps: Vec<Step>| steps.iter().filter(|step| **step == Step::Clock).count();
+        assert_eq!(clocks(check.due(start + Duration::from_millis(500))), 25);
+        check.set_bpm(60, start + Duration::from_millis(500));
+        // 24 clocks per second at 60 BPM
+        assert_eq!(clocks(check.due(start + Duration::from_millis(1000))), 12);
+        assert_eq!(check.bpm, 60);
+    }
+}
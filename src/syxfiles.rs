@@ -0,0 +1,88 @@
+use std::error;
+use std::fs;
+use std::path::{self, Path, PathBuf};
+
+const EXTENSION: &str = "syx";
+
+/// The `.syx` file in `directory` for `name`. Names that would point outside the directory, with
+/// a path separator or being `..`, are refused, dots are kept as part of the name.
+pub fn path(directory: &Path, name: &str) -> Result<PathBuf, Box<dyn error::Error>> {
+    if name.is_empty() || name == "." || name == ".." || name.chars().any(path::is_separator) {
+        return Err(format!("{} can't be used as a file name.", name).into());
+    }
+    Ok(directory.join(format!("{}.{}", name, EXTENSION)))
+}
+
+/// The names of the `.syx` files in `directory`, sorted, none if it can't be read.
+pub fn names(directory: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(directory)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|e| e == EXTENSION))
+                .filter_map(|path| Some(path.file_stem()?.to_string_lossy().into_owned()))
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    names
+}
+
+pub fn read(directory: &Path, name: &str) -> Result<Vec<u8>, Box<dyn error::Error>> {
+    Ok(fs::read(path(directory, name)?)?)
+}
+
+/// Writes the file for `name`, creating the directory if needed.
+pub fn write(directory: &Path, name: &str, bytes: &[u8]) -> Result<(), Box<dyn error::Error>> {
+    let path = path(directory, name)?;
+    fs::create_dir_all(directory)?;
+    Ok(fs::write(path, bytes)?)
+}
+
+pub fn delete(directory: &Path, name: &str) -> Result<(), Box<dyn error::Error>> {
+    Ok(fs::remove_file(path(directory, name)?)?)
+}
+
+/// The selection one entry on from `selection`, or back, in a list of `len` entries, wrapping
+/// around at either end.
+pub fn step(selection: usize, len: usize, forward: bool) -> usize {
+    match len {
+        0 => 0,
+        _ if forward => (selection + 1) % len,
+        _ => (selection + len - 1) % len,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::path::Path;
+
+    use crate::syxfiles::{names, path, read, step, write};
+
+    #[test]
+    fn names_stay_in_the_directory() {
+        let directory = Path::new("presets");
+        assert_eq!(
+            path(directory, "lead.v2").unwrap(),
+            directory.join("lead.v2.syx")
+        );
+        for name in ["", ".", "..", "../lead", "sub/lead"] {
+            assert!(path(directory, name).is_err(), "{}", name);
+        }
+
+        let directory = std::env::temp_dir().join("rustron-syxfiles-test");
+        let _ = fs::remove_dir_all(&directory);
+        assert!(names(&directory).is_empty());
+        write(&directory, "lead.v2", &[0xf0, 0xf7]).unwrap();
+        write(&directory, "bass", &[0xf0, 0xf7]).unwrap();
+        assert_eq!(names(&directory), vec!["bass", "lead.v2"]);
+        assert_eq!(read(&directory, "lead.v2").unwrap(), [0xf0, 0xf7]);
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(step(2, 3, true), 0);
+        assert_eq!(step(0, 3, false), 2);
+        assert_eq!(step(0, 0, true), 0);
+    }
+}
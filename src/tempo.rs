@@ -0,0 +1,76 @@
+use std::time::{Duration, Instant};
+
+/// Taps further apart than this start counting the tempo afresh.
+const TAP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How many of the last taps the tempo is averaged over.
+const TAPS: usize = 5;
+
+/// The tempo range the clock generator runs at, in BPM.
+pub const MIN_BPM: u32 = 20;
+pub const MAX_BPM: u32 = 300;
+
+/// The tempo tapped in with a key, the source the clock generator of `SyncCheck` follows.
+#[derive(Clone, Debug, Default)]
+pub struct TapTempo {
+    taps: Vec<Instant>,
+    bpm: Option<u32>,
+}
+
+impl TapTempo {
+    pub fn new() -> TapTempo {
+        TapTempo::default()
+    }
+
+    /// Counts a tap at `now`, returning the tempo of the last taps from the second one on.
+    pub fn tap(&mut self, now: Instant) -> Option<u32> {
+        if self
+            .taps
+            .last()
+            .is_some_and(|last| now.saturating_duration_since(*last) > TAP_TIMEOUT)
+        {
+            self.taps.clear();
+        }
+        self.taps.push(now);
+        if self.taps.len() > TAPS {
+            self.taps.remove(0);
+        }
+        if let [first, .., last] = self.taps.as_slice() {
+            let beat = last.saturating_duration_since(*first) / (self.taps.len() as u32 - 1);
+            if !beat.is_zero() {
+                let bpm = (60.0 / beat.as_secs_f64()).round() as u32;
+                self.bpm = Some(bpm.clamp(MIN_BPM, MAX_BPM));
+            }
+        }
+        self.bpm
+    }
+
+    /// The last tapped tempo, kept after the taps time out.
+    pub fn bpm(&self) -> Option<u32> {
+        self.bpm
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use crate::tempo::{TapTempo, MAX_BPM};
+
+    #[test]
+    fn tempo_follows_the_last_taps() {
+        let start = Instant::now();
+        let mut tempo = TapTempo::new();
+        assert_eq!(tempo.tap(start), None);
+        assert_eq!(tempo.tap(start + Duration::from_millis(500)), Some(120));
+        assert_eq!(tempo.tap(start + Duration::from_millis(1100)), Some(109));
+        // A pause starts over, keeping the tempo until the next pair of taps
+        let later = start + Duration::from_secs(10);
+        assert_eq!(tempo.tap(later), Some(109));
+        assert_eq!(tempo.tap(later + Duration::from_millis(600)), Some(100));
+        let later = start + Duration::from_secs(20);
+        tempo.tap(later);
+        assert_eq!(tempo.tap(later + Duration::from_millis(100)), Some(MAX_BPM));
+        assert_eq!(tempo.bpm(), Some(MAX_BPM));
+    }
+}
@@ -0,0 +1,162 @@
+use std::error;
+
+use rustron_lib::parser::neutron_message;
+use rustron_lib::protocol::{Channel, NeutronMessage, NEUTRON_MESSAGE_HEADER};
+
+/// Transforms a message passing through, `None` drops it.
+pub type Filter = Box<dyn FnMut(Vec<u8>) -> Option<Vec<u8>>>;
+
+/// Forwards messages received on other ports to the outputs, e.g. from a DAW to the Neutron,
+/// after passing them through a chain of named filters.
+pub struct Through {
+    pub enabled: bool,
+    /// Messages dropped by a filter
+    pub dropped: usize,
+    filters: Vec<(String, Filter)>,
+}
+
+/// Builds a `Through` from filters registered in order.
+///
+/// ```ignore
+/// let through = Through::builder()
+///     .filter("drop short", |message| Some(message).filter(|m| m.len() > 3))
+///     .build();
+/// ```
+pub struct ThroughBuilder {
+    filters: Vec<(String, Filter)>,
+}
+
+impl ThroughBuilder {
+    pub fn filter<F>(mut self, name: &str, filter: F) -> ThroughBuilder
+    where
+        F: FnMut(Vec<u8>) -> Option<Vec<u8>> + 'static,
+    {
+        self.filters.push((name.to_string(), Box::new(filter)));
+        self
+    }
+
+    /// Adds a filter by its name as used in the configuration, see `named_filter`.
+    pub fn named(self, spec: &str) -> Result<ThroughBuilder, Box<dyn error::Error>> {
+        let filter = named_filter(spec)?;
+        Ok(self.filter(spec, filter))
+    }
+
+    pub fn build(self) -> Through {
+        Through {
+            enabled: false,
+            dropped: 0,
+            filters: self.filters,
+        }
+    }
+}
+
+impl Through {
+    pub fn builder() -> ThroughBuilder {
+        ThroughBuilder {
+            filters: Vec::new(),
+        }
+    }
+
+    pub fn filter_names(&self) -> impl Iterator<Item = &str> {
+        self.filters.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Runs `message` through the filters, returning what should be forwarded.
+    pub fn process(&mut self, message: Vec<u8>) -> Option<Vec<u8>> {
+        let mut message = Some(message);
+        for (_, filter) in self.filters.iter_mut() {
+            message = message.and_then(filter.as_mut());
+        }
+        if message.is_none() {
+            self.dropped += 1;
+        }
+        message
+    }
+}
+
+/// The filters that can be chosen without writing code:
+///
+/// - `drop_state_requests`: drops state requests, which some software sends periodically
+/// - `drop_unparsed`: drops anything that isn't a known Neutron message
+/// - `device_id <1-16|all>`: sends Neutron messages to another device id
+pub fn named_filter(spec: &str) -> Result<Filter, Box<dyn error::Error>> {
+    let mut parts = spec.split_whitespace();
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some("drop_state_requests"), None, _) => Ok(Box::new(|message: Vec<u8>| {
+            match neutron_message(&message) {
+                Ok((_, NeutronMessage::StateRequest(_))) => None,
+                _ => Some(message),
+            }
+        })),
+        (Some("drop_unparsed"), None, _) => Ok(Box::new(|message: Vec<u8>| {
+            neutron_message(&message).ok()?;
+            Some(message)
+        })),
+        (Some("device_id"), Some(id), None) => {
+            let byte = match id {
+                "all" => 0x7f,
+                number => {
+                    let channel = number.parse().ok().and_then(Channel::from_number);
+                    channel
+                        .ok_or_else(|| format!("invalid device id {}", id))?
+                        .number()
+                        - 1
+                }
+            };
+            Ok(Box::new(move |mut message: Vec<u8>| {
+                let index = NEUTRON_MESSAGE_HEADER.len();
+                if message.starts_with(&NEUTRON_MESSAGE_HEADER) && message.len() > index + 1 {
+                    message[index] = byte;
+                }
+                Some(message)
+            }))
+        }
+        _ => Err(format!("unknown filter {}", spec).into()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rustron_lib::protocol::DeviceId::Multicast;
+    use rustron_lib::protocol::GlobalSetting::OscSync;
+    use rustron_lib::protocol::NeutronMessage::{SetGlobalSetting, StateRequest};
+    use rustron_lib::protocol::ToggleOption::On;
+    use rustron_lib::protocol::{Channel, DeviceId};
+
+    use crate::through::Through;
+
+    #[test]
+    fn filters_are_applied_in_order() {
+        let mut through = Through::builder()
+            .named("drop_state_requests")
+            .unwrap()
+            .named("device_id 3")
+            .unwrap()
+            .filter("drop note on", |message| {
+                Some(message).filter(|m| m.first() != Some(&0x90))
+            })
+            .build();
+        assert_eq!(
+            through.filter_names().collect::<Vec<_>>(),
+            ["drop_state_requests", "device_id 3", "drop note on"]
+        );
+        assert_eq!(through.process(StateRequest(Multicast).as_bytes()), None);
+        assert_eq!(
+            through.process(SetGlobalSetting(Multicast, OscSync(On)).as_bytes()),
+            Some(SetGlobalSetting(DeviceId::Channel(Channel::Three), OscSync(On)).as_bytes())
+        );
+        assert_eq!(through.process(vec![0x90, 0x3c, 0x40]), None);
+        assert_eq!(
+            through.process(vec![0x80, 0x3c, 0x00]),
+            Some(vec![0x80, 0x3c, 0x00])
+        );
+        assert_eq!(through.dropped, 2);
+    }
+
+    #[test]
+    fn unknown_filters_are_rejected() {
+        assert!(Through::builder().named("device_id 17").is_err());
+        assert!(Through::builder().named("drop_everything").is_err());
+        assert!(Through::builder().named("drop_unparsed").is_ok());
+    }
+}
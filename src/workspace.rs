@@ -0,0 +1,160 @@
+use std::mem;
+
+use rustron_lib::protocol::{DeviceId, NeutronMessage};
+
+use crate::core::state::NeutronState;
+use crate::history::HistoryEntry;
+
+/// What is kept per device when several Neutrons are connected, each set to its own MIDI
+/// channel: the state and the sent and received messages.
+pub struct Workspace {
+    pub target: DeviceId,
+    pub neutron_state: NeutronState,
+    pub command_history: Vec<HistoryEntry>,
+    pub midi_in_messages: Vec<HistoryEntry>,
+}
+
+impl Workspace {
+    pub fn new(target: DeviceId) -> Workspace {
+        Workspace {
+            target,
+            neutron_state: NeutronState::new(),
+            command_history: Vec::new(),
+            midi_in_messages: Vec::new(),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self.target {
+            DeviceId::Channel(channel) => format!("channel {}", channel.number()),
+            DeviceId::Multicast => String::from("all"),
+        }
+    }
+
+    /// Stores a message received from the device while the workspace isn't active.
+    pub fn receive(&mut self, message: &NeutronMessage, entry: HistoryEntry) {
+        self.neutron_state.update(message.clone());
+        self.midi_in_messages.push(entry);
+    }
+}
+
+/// The workspaces, one per device. The active one is used through the fields of `AppCore`, its
+/// slot here is empty until another one is activated.
+pub struct Workspaces {
+    list: Vec<Workspace>,
+    active: usize,
+}
+
+impl Workspaces {
+    pub fn new(target: DeviceId) -> Workspaces {
+        Workspaces {
+            list: vec![Workspace::new(target)],
+            active: 0,
+        }
+    }
+
+    pub fn active(&self) -> usize {
+        self.active
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    /// The names of the workspaces, with the active one named after `active_target` since its
+    /// target may have changed while active.
+    pub fn names(&self, active_target: DeviceId) -> Vec<String> {
+        self.list
+            .iter()
+            .enumerate()
+            .map(|(index, workspace)| {
+                if index == self.active {
+                    Workspace::new(active_target).name()
+                } else {
+                    workspace.name()
+                }
+            })
+            .collect()
+    }
+
+    /// Adds a workspace for `target`, returns its index. `None` if there already is one.
+    pub fn add(&mut self, target: DeviceId) -> Option<usize> {
+        if self.list.iter().any(|workspace| workspace.target == target) {
+            return None;
+        }
+        self.list.push(Workspace::new(target));
+        Some(self.list.len() - 1)
+    }
+
+    /// Makes `index` the active workspace: `live` holds the active workspace's data and is
+    /// swapped for the one at `index`. Returns whether the workspace changed.
+    pub fn activate(&mut self, index: usize, live: &mut Workspace) -> bool {
+        if index == self.active || index >= self.list.len() {
+            return false;
+        }
+        mem::swap(&mut self.list[self.active], live);
+        mem::swap(&mut self.list[index], live);
+        self.active = index;
+        true
+    }
+
+    /// The inactive workspace whose device sent `message`, if any.
+    pub fn background_for(&mut self, message: &NeutronMessage) -> Option<&mut Workspace> {
+        let device = message.device_id();
+        let active = self.active;
+        self.list
+            .iter_mut()
+            .enumerate()
+            .find(|(index, workspace)| {
+                *index != active
+                    && workspace.target != DeviceId::Multicast
+                    && workspace.target == device
+            })
+            .map(|(_, workspace)| workspace)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rustron_lib::protocol::DeviceId::{Channel, Multicast};
+    use rustron_lib::protocol::GlobalSetting::OscSync;
+    use rustron_lib::protocol::NeutronMessage::GlobalSettingUpdate;
+    use rustron_lib::protocol::ToggleOption::On;
+
+    use crate::history::{HistoryEntry, Message};
+    use crate::workspace::{Workspace, Workspaces};
+
+    #[test]
+    fn workspaces_keep_their_messages() {
+        let channel =
+            |number| Channel(rustron_lib::protocol::Channel::from_number(number).unwrap());
+        let mut workspaces = Workspaces::new(Multicast);
+        let mut live = Workspace::new(Multicast);
+        live.command_history
+            .push(HistoryEntry::new(Message::Raw(vec![0x01])));
+        assert_eq!(workspaces.add(channel(3)), Some(1));
+        assert_eq!(workspaces.add(channel(3)), None);
+        assert_eq!(workspaces.names(channel(2)), ["channel 2", "channel 3"]);
+
+        let update = GlobalSettingUpdate(channel(3), OscSync(On));
+        let entry = HistoryEntry::new(Message::Parsed(update.clone()));
+        workspaces
+            .background_for(&update)
+            .unwrap()
+            .receive(&update, entry);
+
+        assert!(workspaces.activate(1, &mut live));
+        assert!(!workspaces.activate(1, &mut live));
+        assert_eq!(live.target, channel(3));
+        assert_eq!(live.midi_in_messages.len(), 1);
+        assert!(live.command_history.is_empty());
+        assert!(workspaces.background_for(&update).is_none());
+
+        assert!(workspaces.activate(0, &mut live));
+        assert_eq!(live.command_history.len(), 1);
+    }
+}